@@ -0,0 +1,622 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use anyhow::*;
+use log::*;
+use tokio::sync::broadcast::error::TryRecvError;
+use crate::context::Ctx;
+use crate::controller::*;
+use crate::convert::*;
+use crate::dump::ProgramsDump;
+use crate::event::*;
+use crate::http::*;
+use crate::json::Json;
+use crate::midi::Channel;
+use crate::model::{Config, Control, Format};
+use crate::pcmap::{program_from_str, program_is_valid, program_to_str};
+use crate::persist::*;
+use crate::profile::Profiles;
+use crate::program_id_string;
+
+/// Connections served at the same time, others get a 503 response
+const MAX_CONNECTIONS: usize = 16;
+
+/// HTTP API server settings
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    /// Address to listen on
+    pub address: String,
+    pub port: u16
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        ApiSettings { enabled: false, address: "127.0.0.1".into(), port: 8080 }
+    }
+}
+
+impl ApiSettings {
+    /// Load the API settings from the `[api]` section of the settings file
+    pub fn load() -> Result<Self> {
+        let mut settings = Self::default();
+        let Some(section) = load_section(&Profiles::file_path()?, "api")? else {
+            return Ok(settings);
+        };
+
+        settings.enabled = section.get_bool("enabled").unwrap_or(settings.enabled);
+        settings.address = section.get("address").map(|a| a.to_string()).unwrap_or(settings.address);
+        settings.port = section.get_parsed("port").unwrap_or(settings.port);
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        update_section(&Profiles::file_path()?, "api", |section| {
+            section.set_bool("enabled", self.enabled);
+            section.set("address", &self.address);
+            section.set("port", self.port);
+        })
+    }
+}
+
+/// The parts of the device context that the API works with
+#[derive(Clone)]
+struct ApiDevice {
+    config: &'static Config,
+    controller: Arc<Mutex<Controller>>,
+    dump: Arc<Mutex<ProgramsDump>>,
+    ui_controller: Arc<Mutex<Controller>>
+}
+
+#[derive(Default)]
+struct ApiState {
+    device: Option<ApiDevice>,
+    detected: Option<DeviceDetectedEvent>
+}
+
+/// Device state for the API, kept up to date by the app event loop
+/// independently of the server being started or stopped
+#[derive(Clone, Default)]
+pub struct ApiContext {
+    state: Arc<Mutex<ApiState>>
+}
+
+impl ApiContext {
+    /// Update the device the API works with
+    pub fn set_device(&self, ctx: &Ctx) {
+        let device = ApiDevice {
+            config: ctx.config,
+            controller: ctx.controller.clone(),
+            dump: ctx.dump.clone(),
+            ui_controller: ctx.ui_controller.clone()
+        };
+        self.state.lock().unwrap().device.replace(device);
+    }
+
+    pub fn set_detected(&self, event: &DeviceDetectedEvent) {
+        self.state.lock().unwrap().detected.replace(event.clone());
+    }
+}
+
+struct Shared {
+    context: ApiContext,
+    app_event_tx: EventSender,
+    /// Address the server listens on, allowed as a host name in addition
+    /// to loopback ones
+    address: String,
+    connections: AtomicUsize,
+    stop: AtomicBool
+}
+
+/// Counts an open connection until dropped
+struct ConnectionGuard(Arc<Shared>);
+
+impl ConnectionGuard {
+    fn new(shared: &Arc<Shared>) -> Option<Self> {
+        let n = shared.connections.fetch_add(1, Ordering::SeqCst);
+        let guard = ConnectionGuard(shared.clone());
+        (n < MAX_CONNECTIONS).then_some(guard)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Local HTTP server with a JSON API:
+/// - `GET /api/device`: device model, detected device and state;
+/// - `GET /api/controls`, `GET /api/controls/<name>`: controls with their
+///   type, bounds, value, formatted value and value labels;
+/// - `PUT /api/controls/<name>`: set a control, `{"value": N}` in raw
+///   units or `{"norm": X}` in normalized 0..1 units;
+/// - `GET /api/programs`: programs with names and modified flags;
+/// - `GET /api/program`, `PUT /api/program`: current program,
+///   `{"program": N|"manual"|"tuner"}`;
+/// - `POST /api/load`, `POST /api/store`: load from or store to the
+///   device, `{"buffer": "edit"|"current"|"all"|N}`, edit buffer by default;
+/// - `GET /api/events`: WebSocket feed of app events as JSON.
+///
+/// `PUT` and `POST` requests must have a JSON content type, so that web
+/// pages cannot send them cross-origin without a (failing) preflight.
+/// Requests with a `Host` or `Origin` other than a loopback one or the
+/// listening address are rejected, which covers DNS rebinding and
+/// cross-site WebSocket connections.
+pub struct ApiServer {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>
+}
+
+impl ApiServer {
+    pub fn start(settings: &ApiSettings, context: ApiContext, app_event_tx: EventSender) -> Result<Self> {
+        let addr = format!("{}:{}", settings.address, settings.port);
+        let listener = TcpListener::bind(&addr)
+            .with_context(|| format!("Failed to bind HTTP API server to {}", addr))?;
+        // poll, so that the stop flag is checked
+        listener.set_nonblocking(true)?;
+
+        let shared = Arc::new(Shared {
+            context,
+            app_event_tx,
+            address: settings.address.clone(),
+            connections: AtomicUsize::new(0),
+            stop: AtomicBool::new(false)
+        });
+        let handle = std::thread::spawn({
+            let shared = shared.clone();
+
+            move || {
+                info!("HTTP API server listening on {}", addr);
+                while !shared.stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        std::result::Result::Ok((stream, _)) => {
+                            let Some(guard) = ConnectionGuard::new(&shared) else {
+                                warn!("HTTP API: too many connections");
+                                stream.set_nonblocking(false).unwrap_or_default();
+                                Response::error(503, "Too many connections").write(&stream)
+                                    .unwrap_or_default();
+                                continue;
+                            };
+                            let shared = shared.clone();
+                            std::thread::spawn(move || {
+                                handle_connection(shared, stream)
+                                    .unwrap_or_else(|err| debug!("HTTP API connection: {}", err));
+                                drop(guard);
+                            });
+                        }
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                            std::thread::sleep(Duration::from_millis(100));
+                        }
+                        Err(err) => {
+                            error!("HTTP API server accept error: {}", err);
+                            std::thread::sleep(Duration::from_millis(100));
+                        }
+                    }
+                }
+                info!("HTTP API server stopped");
+            }
+        });
+
+        Ok(ApiServer { shared, handle: Some(handle) })
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.join().unwrap_or_default();
+        }
+    }
+}
+
+fn handle_connection(shared: Arc<Shared>, stream: TcpStream) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let request = Request::read(&stream)?;
+    debug!("HTTP API: {} {}", request.method, request.path);
+
+    if let Err(msg) = check_host_and_origin(&shared, &request) {
+        warn!("HTTP API: {}", msg);
+        return Response::error(403, &msg).write(&stream);
+    }
+    if request.method == "GET" && request.segments() == ["api", "events"] {
+        if !request.is_websocket_upgrade() {
+            return Response::error(400, "WebSocket upgrade expected").write(&stream);
+        }
+        return serve_events(shared, stream, &request);
+    }
+    route(&shared, &request).write(&stream)
+}
+
+fn allowed_host(shared: &Shared, host: &str) -> bool {
+    is_loopback_host(host) || host.eq_ignore_ascii_case(host_name(&shared.address))
+}
+
+/// Only accept requests addressed to this server and, for requests from
+/// web pages, sent by a page served from it
+fn check_host_and_origin(shared: &Shared, request: &Request) -> std::result::Result<(), String> {
+    let Some(host) = request.header("host") else {
+        return Err("Missing Host header".into());
+    };
+    if !allowed_host(shared, host_name(host)) {
+        return Err(format!("Host {:?} not allowed", host));
+    }
+    if let Some(origin) = request.header("origin") {
+        if !allowed_host(shared, host_name(origin)) {
+            return Err(format!("Origin {:?} not allowed", origin));
+        }
+    }
+    std::result::Result::Ok(())
+}
+
+/// Stream app events to a WebSocket client until it closes the connection
+/// or the server stops
+fn serve_events(shared: Arc<Shared>, stream: TcpStream, request: &Request) -> Result<()> {
+    let mut rx = shared.app_event_tx.subscribe();
+    write_websocket_handshake(&stream, request)?;
+    stream.set_read_timeout(None)?;
+
+    let closed = Arc::new(AtomicBool::new(false));
+    let writer = Arc::new(Mutex::new(stream.try_clone()?));
+    let reader = std::thread::spawn({
+        let stream = stream.try_clone()?;
+        let writer = writer.clone();
+        let closed = closed.clone();
+
+        move || {
+            loop {
+                match WsFrame::read(&stream) {
+                    std::result::Result::Ok(WsFrame::Ping(data)) => {
+                        let writer = writer.lock().unwrap();
+                        WsFrame::Pong(data).write(&*writer).unwrap_or_default();
+                    }
+                    std::result::Result::Ok(WsFrame::Close) | Err(_) => break,
+                    std::result::Result::Ok(_) => {}
+                }
+            }
+            closed.store(true, Ordering::Relaxed);
+        }
+    });
+
+    info!("HTTP API: event feed client connected");
+    while !closed.load(Ordering::Relaxed) && !shared.stop.load(Ordering::Relaxed) {
+        let event = match rx.try_recv() {
+            std::result::Result::Ok(event) => event,
+            Err(TryRecvError::Empty) => {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            Err(TryRecvError::Lagged(n)) => {
+                warn!("HTTP API: event feed lagged: {}", n);
+                continue;
+            }
+            Err(TryRecvError::Closed) => break
+        };
+        let Some(json) = event_json(&event) else { continue };
+        let writer = writer.lock().unwrap();
+        if WsFrame::Text(json.to_string()).write(&*writer).is_err() {
+            break;
+        }
+    }
+
+    WsFrame::Close.write(&*writer.lock().unwrap()).unwrap_or_default();
+    stream.shutdown(Shutdown::Both).unwrap_or_default();
+    reader.join().unwrap_or_default();
+    info!("HTTP API: event feed client disconnected");
+    Ok(())
+}
+
+fn program_json(program: &Program) -> Json {
+    match program {
+        Program::Program(p) => Json::from(*p),
+        p => Json::from(program_to_str(p))
+    }
+}
+
+fn program_from_json(json: &Json) -> Option<Program> {
+    match json {
+        Json::Number(v) if *v >= 0.0 => Some(Program::Program(*v as u16)),
+        Json::String(str) => program_from_str(str),
+        _ => None
+    }
+}
+
+fn buffer_json(buffer: &Buffer) -> Json {
+    match buffer {
+        Buffer::EditBuffer => "edit".into(),
+        Buffer::Current => "current".into(),
+        Buffer::Program(p) => (*p).into(),
+        Buffer::All => "all".into()
+    }
+}
+
+fn buffer_from_json(json: &Json, config: &Config) -> Option<Buffer> {
+    match json {
+        Json::Null => Some(Buffer::EditBuffer),
+        Json::String(str) if str == "edit" => Some(Buffer::EditBuffer),
+        Json::String(str) if str == "current" => Some(Buffer::Current),
+        Json::String(str) if str == "all" => Some(Buffer::All),
+        Json::Number(v) if *v >= 0.0 && (*v as usize) < config.program_num => {
+            Some(Buffer::Program(*v as usize))
+        }
+        _ => None
+    }
+}
+
+fn origin_json(origin: StoreOrigin) -> Json {
+    match origin {
+        StoreOrigin::NONE => "none".into(),
+        StoreOrigin::MIDI => "midi".into(),
+        StoreOrigin::UI => "ui".into()
+    }
+}
+
+/// JSON representation of app events streamed on the WebSocket feed.
+/// Returns `None` for events not included in the feed.
+pub fn event_json(event: &AppEvent) -> Option<Json> {
+    let json = match event {
+        AppEvent::ControlChange(e) => Json::object()
+            .with("event", "control")
+            .with("name", e.name.as_str())
+            .with("value", e.value)
+            .with("origin", origin_json(e.origin)),
+        AppEvent::ProgramChange(e) => Json::object()
+            .with("event", "program")
+            .with("program", program_json(&e.program))
+            .with("origin", origin_json(e.origin.into())),
+        AppEvent::Modified(e) => Json::object()
+            .with("event", "modified")
+            .with("buffer", buffer_json(&e.buffer))
+            .with("modified", e.modified),
+        AppEvent::DeviceDetected(e) => Json::object()
+            .with("event", "device")
+            .with("name", e.name.as_str())
+            .with("version", e.version.as_str()),
+        AppEvent::Notification(e) => Json::object()
+            .with("event", "notification")
+            .with("message", e.msg.as_str()),
+        _ => return None
+    };
+    Some(json)
+}
+
+fn control_type(control: &Control) -> &'static str {
+    match control {
+        Control::SwitchControl(_) => "switch",
+        Control::MidiSwitchControl(_) => "midi_switch",
+        Control::RangeControl(_) => "range",
+        Control::AddrRangeControl(_) => "addr_range",
+        Control::VirtualRangeControl(_) => "virtual_range",
+        Control::Select(_) => "select",
+        Control::MidiSelect(_) => "midi_select",
+        Control::VirtualSelect(_) => "virtual_select",
+        Control::Button(_) => "button"
+    }
+}
+
+fn control_json(device: &ApiDevice, labels: &HashMap<String, Vec<String>>, name: &str) -> Option<Json> {
+    let control = device.config.controls.get(name)?;
+    let value = device.controller.get(name);
+    let bounds = control_bounds(device.config, name);
+    let labels = labels.get(name).or(match control {
+        Control::RangeControl(c) => match &c.format { Format::Labels(l) => Some(l), _ => None },
+        _ => None
+    });
+
+    let mut json = Json::object()
+        .with("name", name)
+        .with("type", control_type(control))
+        .with("min", bounds.map(|(from, _)| from))
+        .with("max", bounds.map(|(_, to)| to))
+        .with("value", value)
+        .with("norm", value.and_then(|v| control_value_to_norm(device.config, name, v)))
+        .with("display", value.and_then(|v| format_value(control, labels, v)));
+    if let Some(labels) = labels {
+        json = json.with("labels", labels.clone());
+    }
+    Some(json)
+}
+
+fn device_json(state: &ApiState) -> Json {
+    let detected = state.detected.as_ref().map(|d| Json::object()
+        .with("name", d.name.as_str())
+        .with("version", d.version.as_str()));
+    let Some(device) = &state.device else {
+        return Json::object()
+            .with("name", Json::Null)
+            .with("detected", detected);
+    };
+    let midi_channel = device.ui_controller.get("midi_channel")
+        .map(|c| if c as u8 == Channel::all() { Json::from("omni") } else { Json::from(c + 1) });
+    let program = device.ui_controller.get("program").map(|p| program_json(&p.into()));
+    Json::object()
+        .with("name", device.config.name.as_str())
+        .with("detected", detected)
+        .with("programs", device.config.program_num)
+        .with("program", program)
+        .with("midi_channel", midi_channel)
+        .with("offline", device.ui_controller.get("offline").unwrap_or(0) != 0)
+}
+
+fn programs_json(device: &ApiDevice) -> Json {
+    let dump = device.dump.lock().unwrap();
+    let programs = (0 .. dump.program_num())
+        .map(|p| Json::object()
+            .with("index", p)
+            .with("id", program_id_string(p))
+            .with("name", dump.name(p).map(|n| n.trim().to_string()))
+            .with("modified", dump.modified(p)))
+        .collect::<Vec<_>>();
+    Json::Array(programs)
+}
+
+/// Body value: either the body itself or the `key` field of a body object
+fn body_value<'a>(json: &'a Json, key: &str) -> Option<&'a Json> {
+    match json {
+        Json::Object(_) => json.get(key),
+        Json::Null => None,
+        json => Some(json)
+    }
+}
+
+fn route(shared: &Shared, request: &Request) -> Response {
+    let segments = request.segments();
+    let method = request.method.as_str();
+    let Some(("api", path)) = segments.split_first().map(|(first, rest)| (*first, rest)) else {
+        return Response::error(404, "Not found");
+    };
+
+    let body = if method == "PUT" || method == "POST" {
+        let is_json = request.header("content-type")
+            .map(|t| t.starts_with("application/json"))
+            .unwrap_or(false);
+        if !is_json {
+            return Response::error(415, "Content-Type must be application/json");
+        }
+        match request.json() {
+            std::result::Result::Ok(json) => json,
+            Err(err) => return Response::error(400, &format!("Invalid JSON: {}", err))
+        }
+    } else {
+        Json::Null
+    };
+
+    let state = shared.context.state.lock().unwrap();
+    if let (&"GET", ["device"]) = (&method, path) {
+        return Response::json(200, &device_json(&state));
+    }
+    let Some(device) = state.device.clone() else {
+        return Response::error(503, "No device");
+    };
+    drop(state);
+
+    match (method, path) {
+        ("GET", ["controls"]) => {
+            let labels = select_labels(device.config);
+            let mut names = device.config.controls.keys().collect::<Vec<_>>();
+            names.sort();
+            let controls = names.into_iter()
+                .flat_map(|name| control_json(&device, &labels, name))
+                .collect::<Vec<_>>();
+            Response::json(200, &Json::Array(controls))
+        }
+        ("GET", ["controls", name]) => {
+            match control_json(&device, &select_labels(device.config), name) {
+                Some(json) => Response::json(200, &json),
+                None => Response::error(404, "Unknown control")
+            }
+        }
+        ("PUT" | "POST", ["controls", name]) => {
+            if !device.config.controls.contains_key(*name) {
+                return Response::error(404, "Unknown control");
+            }
+            let norm = body.get("norm").and_then(|v| v.as_f64());
+            let value = match (norm, body_value(&body, "value").and_then(|v| v.as_f64())) {
+                (Some(norm), _) => control_value_from_norm(device.config, name, norm),
+                (None, Some(value)) => Some(control_value_clamp(device.config, name, value)),
+                (None, None) => return Response::error(400, "Value expected")
+            };
+            let Some(value) = value else {
+                return Response::error(400, "Control has no known range for normalized values");
+            };
+            // UI origin, so that the value is sent to the device
+            device.controller.set(name, value, StoreOrigin::UI);
+            let json = control_json(&device, &select_labels(device.config), name);
+            Response::json(200, &json.unwrap_or(Json::Null))
+        }
+        ("GET", ["programs"]) => {
+            Response::json(200, &programs_json(&device))
+        }
+        ("GET", ["program"]) => {
+            let program = device.ui_controller.get("program").map(|p| program_json(&p.into()));
+            Response::json(200, &Json::object().with("program", program))
+        }
+        ("PUT" | "POST", ["program"]) => {
+            let program = body_value(&body, "program").and_then(program_from_json);
+            match program {
+                Some(program) if program_is_valid(device.config, &program) => {
+                    // same as selecting the program in the UI
+                    device.ui_controller.set("program", program.clone().into(), StoreOrigin::UI);
+                    Response::json(200, &Json::object().with("program", program_json(&program)))
+                }
+                _ => Response::error(400, "Invalid program")
+            }
+        }
+        ("POST", ["load" | "store"]) => {
+            let buffer = body_value(&body, "buffer").unwrap_or(&Json::Null);
+            let Some(buffer) = buffer_from_json(buffer, device.config) else {
+                return Response::error(400, "Invalid buffer");
+            };
+            let json = Json::object().with("buffer", buffer_json(&buffer));
+            let e = if path == ["load"] {
+                AppEvent::Load(BufferLoadEvent { buffer, origin: Origin::UI })
+            } else {
+                AppEvent::Store(BufferStoreEvent { buffer, origin: Origin::UI })
+            };
+            shared.app_event_tx.send_or_warn(e);
+            Response::json(202, &json)
+        }
+        (_, ["device" | "controls" | "programs" | "program" | "load" | "store", ..]) => {
+            Response::error(405, "Method not allowed")
+        }
+        _ => Response::error(404, "Not found")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::*;
+
+    #[test]
+    fn events_and_values() {
+        let e = AppEvent::ControlChange(ControlChangeEvent {
+            name: "drive".into(), value: 64, origin: StoreOrigin::UI
+        });
+        assert_eq!(event_json(&e).unwrap().to_string(),
+                   r#"{"event":"control","name":"drive","value":64,"origin":"ui"}"#);
+        let e = AppEvent::ProgramChange(ProgramChangeEvent { program: Program::Tuner, origin: Origin::MIDI });
+        assert_eq!(event_json(&e).unwrap().to_string(),
+                   r#"{"event":"program","program":"tuner","origin":"midi"}"#);
+        assert!(event_json(&AppEvent::Shutdown).is_none());
+
+        assert_eq!(program_from_json(&Json::parse("12").unwrap()), Some(Program::Program(12)));
+        assert_eq!(program_from_json(&Json::parse("\"manual\"").unwrap()), Some(Program::ManualMode));
+        let body = Json::parse(r#"{"program": 3}"#).unwrap();
+        assert_eq!(body_value(&body, "program").and_then(program_from_json), Some(Program::Program(3)));
+    }
+
+    #[test]
+    fn host_and_origin() {
+        let (app_event_tx, _) = tokio::sync::broadcast::channel(1);
+        let shared = Shared {
+            context: ApiContext::default(),
+            app_event_tx,
+            address: "192.168.1.10".into(),
+            connections: AtomicUsize::new(0),
+            stop: AtomicBool::new(false)
+        };
+        let check = |headers: &[(&str, &str)]| {
+            let request = Request {
+                method: "GET".into(),
+                path: "/api/events".into(),
+                headers: headers.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+                body: vec![]
+            };
+            check_host_and_origin(&shared, &request).is_ok()
+        };
+
+        assert!(check(&[("host", "localhost:8080")]));
+        assert!(check(&[("host", "192.168.1.10:8080")]));
+        assert!(check(&[("host", "[::1]:8080"), ("origin", "http://127.0.0.1:8080")]));
+        assert!(!check(&[]));
+        assert!(!check(&[("host", "evil.example.com:8080")]));
+        assert!(!check(&[("host", "localhost:8080"), ("origin", "https://evil.example.com")]));
+        assert!(!check(&[("host", "localhost:8080"), ("origin", "null")]));
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::Path;
+use anyhow::*;
+use log::*;
+use crate::dump::ProgramsDump;
+use crate::edit::EditBuffer;
+use crate::event::Program;
+use crate::generic::num_program;
+use crate::model::{AbstractControl, Config};
+use crate::persist::*;
+use crate::program;
+
+/// A set of programs exported to a file, keyed by program slot
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bank {
+    pub config_name: String,
+    /// Programs as (program index, name, program data)
+    pub programs: Vec<(usize, String, Vec<u8>)>
+}
+
+impl Bank {
+    /// Capture programs `pages` from the programs dump. If the current
+    /// program is one of them and the edit buffer is modified, the edit
+    /// buffer is used in place of that program's data.
+    pub fn capture(config: &Config, program: Program, edit: &EditBuffer,
+                   dump: &ProgramsDump, pages: &[usize]) -> Self {
+        let current = num_program(&program);
+        let programs = pages.iter()
+            .filter(|p| **p < dump.program_num())
+            .map(|p| {
+                if current == Some(*p) && edit.modified() {
+                    (*p, edit.name(), program::store_patch_dump_ctrl(edit))
+                } else {
+                    (*p, dump.name(*p).unwrap_or_default(), program::store_patch_dump(dump, *p))
+                }
+            })
+            .collect();
+
+        Bank { config_name: config.name.clone(), programs }
+    }
+
+    pub fn load(path: &Path, config: &Config) -> Result<Self> {
+        check_bank_file(path)?;
+        let doc = Document::load(path)?;
+        let Some(section) = doc.section("bank") else {
+            bail!("Bank file {:?} has no [bank] section", path);
+        };
+        let config_name = section.get("config").unwrap_or_default().to_string();
+        if config_name != config.name {
+            bail!("Bank file {:?} is for {:?}, not {:?}", path, config_name, config.name);
+        }
+
+        let mut programs = vec![];
+        for (suffix, section) in doc.sections_with_prefix("program") {
+            let Some(p) = suffix.parse::<usize>().ok().filter(|p| *p < config.program_num) else {
+                warn!("Bank: ignoring program {:?}", suffix);
+                continue;
+            };
+            let data = from_hex(section.get("data").unwrap_or_default())?;
+            if data.len() != config.program_size {
+                warn!("Bank: ignoring program {} with data size {}", p, data.len());
+                continue;
+            }
+            let name = section.get("name").unwrap_or_default().to_string();
+            programs.push((p, name, data));
+        }
+
+        Ok(Bank { config_name, programs })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut doc = Document::new();
+        doc.section_mut("bank").set("config", &self.config_name);
+        for (p, name, data) in self.programs.iter() {
+            let section = doc.section_mut(&format!("program {}", p));
+            section.set("name", name);
+            section.set("data", to_hex(data));
+        }
+
+        doc.save(path)
+    }
+}
+
+/// Programs can be copied between devices that store the same controls
+/// at the same program data addresses and share the amp models
+/// (such as the PODxt and PODxt Pro)
+pub fn is_compatible(a: &Config, b: &Config) -> bool {
+    if a == b {
+        return true;
+    }
+    if a.program_size != b.program_size || a.program_name_addr != b.program_name_addr ||
+        a.program_name_length != b.program_name_length {
+        return false;
+    }
+    let same_amps = a.amp_models.len() == b.amp_models.len() &&
+        a.amp_models.iter().zip(b.amp_models.iter()).all(|(x, y)| x.name == y.name);
+    let same_addrs = a.controls.iter()
+        .filter_map(|(name, control)| Some((name, control.get_addr()?)))
+        .all(|(name, addr)| {
+            b.controls.get(name)
+                .and_then(|control| control.get_addr())
+                .map(|b_addr| b_addr == addr)
+                .unwrap_or(true)
+        });
+    same_amps && same_addrs
+}
+
+/// Check that `path` can be a bank file. Bank files are the INI files
+/// written by the program export, other patch formats such as Line 6
+/// Edit (.l6t) or SysEx (.syx) files are not supported.
+pub fn check_bank_file(path: &Path) -> Result<()> {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    match ext.as_deref() {
+        None | Some("ini") => Ok(()),
+        Some(ext) => bail!("Cannot import {:?}: .{} files are not supported, only bank files (.ini) exported by pod-ui",
+                           path, ext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bank::*;
+    use crate::def;
+    use crate::model::{Amp, Control, RangeControl};
+
+    #[test]
+    fn compatible() {
+        let amp = |name: &str| Amp { name: name.into(), ..Default::default() };
+        let control = |addr| -> Control { RangeControl { addr, ..def() }.into() };
+        let a = Config {
+            name: "A".into(), member: 1,
+            program_size: 16,
+            amp_models: vec![amp("Tweed"), amp("Brit")],
+            controls: [("drive".to_string(), control(1)), ("volume".to_string(), control(2))].into(),
+            ..Config::empty()
+        };
+        let b = Config {
+            name: "B".into(), member: 2,
+            controls: [("drive".to_string(), control(1))].into(),
+            ..a.clone()
+        };
+        assert!(is_compatible(&a, &b));
+
+        let c = Config { name: "C".into(), member: 3, program_size: 32, ..a.clone() };
+        assert!(!is_compatible(&a, &c));
+        let d = Config { name: "D".into(), member: 4, amp_models: vec![amp("Tweed"), amp("Bass")], ..a.clone() };
+        assert!(!is_compatible(&a, &d));
+        let e = Config {
+            name: "E".into(), member: 5,
+            controls: [("drive".to_string(), control(3))].into(),
+            ..a.clone()
+        };
+        assert!(!is_compatible(&a, &e));
+    }
+
+    #[test]
+    fn bank_file_extension() {
+        assert!(check_bank_file(Path::new("/tmp/programs.ini")).is_ok());
+        assert!(check_bank_file(Path::new("/tmp/PROGRAMS.INI")).is_ok());
+        assert!(check_bank_file(Path::new("/tmp/programs")).is_ok());
+        assert!(check_bank_file(Path::new("/tmp/tone.l6t")).is_err());
+        assert!(check_bank_file(Path::new("/tmp/bank.syx")).is_err());
+    }
+}
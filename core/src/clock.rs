@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use anyhow::*;
+use log::*;
+use crate::context::Ctx;
+use crate::controller::*;
+use crate::convert::control_value_clamp;
+use crate::persist::*;
+use crate::profile::Profiles;
+
+/// MIDI clock runs at 24 pulses per quarter note
+const TICKS_PER_BEAT: usize = 24;
+/// Clock pulses further apart than this (~10 bpm) mean the clock stopped
+const MAX_TICK_INTERVAL: Duration = Duration::from_millis(250);
+/// Weight of a new tempo measurement in the smoothed tempo
+const SMOOTHING: f64 = 0.1;
+
+const CLOCK: u8 = 0xf8;
+const START: u8 = 0xfa;
+const CONTINUE: u8 = 0xfb;
+const STOP: u8 = 0xfc;
+
+/// MIDI input the clock is taken from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClockSource {
+    /// MIDI input from the device
+    #[default]
+    Device,
+    /// External controller input
+    Controller
+}
+
+/// Tempo sync settings
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClockSettings {
+    pub enabled: bool,
+    pub source: ClockSource,
+    /// Smallest tempo change, in bpm, sent to the device
+    pub min_change: f64
+}
+
+impl Default for ClockSettings {
+    fn default() -> Self {
+        ClockSettings {
+            enabled: false,
+            source: ClockSource::Device,
+            min_change: 1.0
+        }
+    }
+}
+
+impl ClockSettings {
+    /// Load the tempo sync settings from the `[clock]` section of the
+    /// settings file
+    pub fn load() -> Result<Self> {
+        let mut settings = Self::default();
+        let Some(section) = load_section(&Profiles::file_path()?, "clock")? else {
+            return Ok(settings);
+        };
+
+        settings.enabled = section.get_bool("enabled").unwrap_or(settings.enabled);
+        settings.source = match section.get("source") {
+            Some("controller") => ClockSource::Controller,
+            _ => ClockSource::Device
+        };
+        settings.min_change = section.get_parsed::<f64>("min-change")
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .unwrap_or(settings.min_change);
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        update_section(&Profiles::file_path()?, "clock", |section| {
+            section.set_bool("enabled", self.enabled);
+            section.set("source", match self.source {
+                ClockSource::Device => "device",
+                ClockSource::Controller => "controller"
+            });
+            section.set("min-change", self.min_change);
+        })
+    }
+}
+
+/// Tempo tracking from incoming MIDI clock. The tempo is measured over
+/// the last beat worth of clock pulses and smoothed further, so that
+/// MIDI delivery jitter does not make the tempo wobble.
+#[derive(Clone, Debug)]
+pub struct MidiClock {
+    settings: ClockSettings,
+    ticks: VecDeque<Instant>,
+    bpm: Option<f64>,
+    reported: Option<f64>
+}
+
+impl MidiClock {
+    pub fn new(settings: ClockSettings) -> Self {
+        MidiClock { settings, ticks: VecDeque::new(), bpm: None, reported: None }
+    }
+
+    pub fn settings(&self) -> &ClockSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: ClockSettings) {
+        self.settings = settings;
+        self.reset();
+        self.reported = None;
+    }
+
+    /// Forget the clock pulses seen so far
+    pub fn reset(&mut self) {
+        self.ticks.clear();
+        self.bpm = None;
+    }
+
+    /// Current (smoothed) tempo in bpm, if the clock is running
+    pub fn bpm(&self) -> Option<f64> {
+        self.bpm
+    }
+
+    /// Feed a raw MIDI message received at `time`. Returns the new tempo,
+    /// in bpm, if it differs from the one returned last by at least the
+    /// configured minimum change.
+    pub fn input(&mut self, bytes: &[u8], time: Instant) -> Option<f64> {
+        match bytes {
+            [CLOCK] => {}
+            [START] | [CONTINUE] | [STOP] => {
+                self.reset();
+                return None;
+            }
+            _ => return None
+        }
+
+        if let Some(last) = self.ticks.back() {
+            if time.saturating_duration_since(*last) > MAX_TICK_INTERVAL {
+                self.reset();
+            }
+        }
+        self.ticks.push_back(time);
+        if self.ticks.len() > TICKS_PER_BEAT + 1 {
+            self.ticks.pop_front();
+        }
+        if self.ticks.len() < TICKS_PER_BEAT + 1 {
+            // wait for a full beat
+            return None;
+        }
+
+        let span = self.ticks.back()?.saturating_duration_since(*self.ticks.front()?);
+        if span.is_zero() {
+            return None;
+        }
+        let bpm = 60.0 / span.as_secs_f64();
+        let bpm = match self.bpm {
+            Some(smoothed) => smoothed + (bpm - smoothed) * SMOOTHING,
+            None => bpm
+        };
+        self.bpm = Some(bpm);
+
+        let changed = self.reported
+            .map(|reported| (bpm - reported).abs() >= self.settings.min_change.max(0.1))
+            .unwrap_or(true);
+        if !changed {
+            return None;
+        }
+        // the device takes tempo in 0.1 bpm units
+        let bpm = (bpm * 10.0).round() / 10.0;
+        self.reported = Some(bpm);
+        Some(bpm)
+    }
+}
+
+/// Handler for raw MIDI messages from `source`: follow the MIDI clock
+/// with the device's `tempo` control, if tempo sync is enabled and the
+/// device has one.
+pub fn midi_clock_handler(ctx: &Ctx, clock: &mut MidiClock, source: ClockSource, bytes: &[u8]) {
+    if !clock.settings.enabled || clock.settings.source != source {
+        return;
+    }
+    let Some(bpm) = clock.input(bytes, Instant::now()) else {
+        return;
+    };
+    if !ctx.config.controls.contains_key("tempo") {
+        return;
+    }
+
+    let tempo = control_value_clamp(ctx.config, "tempo", bpm * 10.0);
+    if ctx.controller.get("tempo") == Some(tempo) {
+        return;
+    }
+    debug!("MIDI clock tempo: {:.1} bpm", bpm);
+    // UI origin, so that the value is sent to the device
+    ctx.controller.set("tempo", tempo, StoreOrigin::UI);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::*;
+
+    fn feed(clock: &mut MidiClock, time: &mut Instant, bpm: f64, beats: usize) -> Vec<f64> {
+        let interval = Duration::from_secs_f64(60.0 / bpm / TICKS_PER_BEAT as f64);
+        let mut reported = vec![];
+        for _ in 0 .. beats * TICKS_PER_BEAT {
+            *time += interval;
+            reported.extend(clock.input(&[CLOCK], *time));
+        }
+        reported
+    }
+
+    #[test]
+    fn follow_tempo() {
+        let settings = ClockSettings { enabled: true, min_change: 1.0, ..Default::default() };
+        let mut clock = MidiClock::new(settings);
+        let mut time = Instant::now();
+
+        // nothing until a full beat is seen
+        assert!(feed(&mut clock, &mut time, 120.0, 1).is_empty());
+        assert_eq!(feed(&mut clock, &mut time, 120.0, 2), vec![120.0]);
+
+        // changes below the minimum are not reported
+        assert!(feed(&mut clock, &mut time, 120.5, 8).is_empty());
+
+        let reported = feed(&mut clock, &mut time, 90.0, 8);
+        assert!(!reported.is_empty());
+        assert!((reported.last().unwrap() - 90.0).abs() < 1.0);
+
+        // the tempo is measured anew after a stop
+        clock.input(&[STOP], time);
+        assert_eq!(clock.bpm(), None);
+        time += Duration::from_secs(1);
+        assert!(feed(&mut clock, &mut time, 90.0, 1).is_empty());
+        assert_eq!(clock.input(&[0xb0, 1, 2], time), None);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::model::Config;
+use std::sync::Mutex;
+use anyhow::*;
+use once_cell::sync::Lazy;
+use crate::model::{Config, FirmwareVersion};
 
 // TODO: remove the "static mut" hack!
 pub static mut PODS: Vec<Config> = Vec::new();
@@ -15,8 +18,73 @@ pub fn configs() -> &'static Vec<Config> {
     }
 }
 
+/// Index of `config` in `configs()`. Firmware variants of a config get
+/// the index of the registered config.
+pub fn config_index(config: &Config) -> Option<usize> {
+    let config = config_for_id(config.family, config.member).unwrap_or(config);
+    configs().iter().position(|c| std::ptr::eq(c, config))
+}
+
 pub fn config_for_id(family: u16, member: u16) -> Option<&'static Config> {
     configs().iter().find(|config| {
         family == config.family && member == config.member
     })
-}
\ No newline at end of file
+}
+
+/// Device family, member and the indexes of the applied firmware variants
+type FirmwareConfigKey = (u16, u16, Vec<usize>);
+
+/// Registered configs with firmware variants applied
+static FIRMWARE_CONFIGS: Lazy<Mutex<Vec<(FirmwareConfigKey, &'static Config)>>> =
+    Lazy::new(|| Mutex::new(Vec::new()));
+
+/// The config for a device of the same model as `config` running firmware
+/// `version`. This is the registered config if no firmware variants apply.
+/// Variant configs are created once and live as long as the registered ones.
+pub fn config_for_firmware(config: &'static Config, version: FirmwareVersion) -> &'static Config {
+    let base = config_for_id(config.family, config.member).unwrap_or(config);
+    let variants = base.firmware_variants_for(version);
+    if variants.is_empty() {
+        return base;
+    }
+
+    let key = (base.family, base.member, variants);
+    let mut cache = FIRMWARE_CONFIGS.lock().unwrap();
+    if let Some((_, config)) = cache.iter().find(|(k, _)| *k == key) {
+        return config;
+    }
+    // kept for good like the registered configs: at most one per model and
+    // variant set, and `Ctx` needs a `&'static Config`
+    let config: &'static Config = Box::leak(Box::new(base.for_firmware(version)));
+    cache.push((key, config));
+    config
+}
+
+/// Look up a config by its index or (case-insensitive) name
+pub fn config_for_str(config_str: &str) -> Result<&'static Config> {
+    use std::str::FromStr;
+    use regex::Regex;
+
+    let n_re = Regex::new(r"\d+").unwrap();
+
+    let mut found = None;
+    if n_re.is_match(&config_str) {
+        let index = usize::from_str(&config_str)
+            .with_context(|| format!("Unrecognized config index {:?}", config_str))?;
+        let config = configs().get(index)
+            .with_context(|| format!("Config with index {} not found!", index))?;
+        found = Some(config);
+    } else {
+        for c in configs().iter() {
+            if c.name.eq_ignore_ascii_case(&config_str) {
+                found = Some(c);
+                break;
+            }
+        }
+        if found.is_none() {
+            bail!("Config with name {:?} not found!", config_str);
+        }
+    }
+
+    Ok(found.unwrap())
+}
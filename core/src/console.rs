@@ -0,0 +1,192 @@
+use std::path::PathBuf;
+use anyhow::*;
+use log::*;
+use crate::midi::{Channel, MidiMessage};
+use crate::persist::*;
+
+const HISTORY_FILE: &str = "console-history.ini";
+const HISTORY_SIZE: usize = 100;
+
+/// A `MidiMessage` the developer console can build, with an optional
+/// numeric parameter
+pub struct Template {
+    pub name: &'static str,
+    /// Parameter name and its maximum value
+    pub param: Option<(&'static str, u16)>,
+    /// Build the message from the parameter and the device MIDI channel
+    pub build: fn(u16, u8) -> MidiMessage
+}
+
+pub static TEMPLATES: &[Template] = &[
+    Template { name: "UniversalDeviceInquiry", param: Some(("channel", 0x7f)),
+        build: |v, _| MidiMessage::UniversalDeviceInquiry { channel: v as u8 } },
+    Template { name: "ProgramPatchDumpRequest", param: Some(("patch", 0x7f)),
+        build: |v, _| MidiMessage::ProgramPatchDumpRequest { patch: v as u8 } },
+    Template { name: "ProgramEditBufferDumpRequest", param: None,
+        build: |_, _| MidiMessage::ProgramEditBufferDumpRequest },
+    Template { name: "AllProgramsDumpRequest", param: None,
+        build: |_, _| MidiMessage::AllProgramsDumpRequest },
+    Template { name: "XtInstalledPacksRequest", param: None,
+        build: |_, _| MidiMessage::XtInstalledPacksRequest },
+    Template { name: "XtEditBufferDumpRequest", param: None,
+        build: |_, _| MidiMessage::XtEditBufferDumpRequest },
+    // patch is not a program number, but a flat index of the bank and
+    // patch bytes `PodXtPatch::to_midi()` expects, 128 patches per bank
+    Template { name: "XtPatchDumpRequest", param: Some(("patch", 383)),
+        build: |v, _| MidiMessage::XtPatchDumpRequest { patch: ((v / 128) << 8) | (v % 128) } },
+    Template { name: "XtTunerNoteRequest", param: None,
+        build: |_, _| MidiMessage::XtTunerNoteRequest },
+    Template { name: "XtTunerOffsetRequest", param: None,
+        build: |_, _| MidiMessage::XtTunerOffsetRequest },
+    Template { name: "XtProgramNumberRequest", param: None,
+        build: |_, _| MidiMessage::XtProgramNumberRequest },
+    Template { name: "XtProgramEditStateRequest", param: None,
+        build: |_, _| MidiMessage::XtProgramEditStateRequest },
+    Template { name: "ProgramChange", param: Some(("program", 0x7f)),
+        build: |v, channel| MidiMessage::ProgramChange { channel, program: v as u8 } },
+];
+
+impl Template {
+    pub fn by_name(name: &str) -> Option<&'static Template> {
+        TEMPLATES.iter().find(|t| t.name == name)
+    }
+
+    /// Build the message for a device on MIDI `channel`, clamping `param`
+    /// to the parameter range. In omni mode, channel 1 is used.
+    pub fn to_bytes(&self, param: u16, channel: u8) -> Vec<u8> {
+        let param = self.param.map(|(_, max)| param.min(max)).unwrap_or_default();
+        let channel = if channel == Channel::all() { 0 } else { channel };
+        (self.build)(param, channel).to_bytes()
+    }
+}
+
+/// Parse bytes typed into the console. Bytes are hex, optionally
+/// prefixed with "0x" and separated by spaces or commas. Without
+/// separators, the input is read as pairs of hex digits.
+pub fn parse_hex(str: &str) -> Result<Vec<u8>> {
+    let tokens = str.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|t| !t.is_empty())
+        .map(|t| t.trim_start_matches("0x").trim_start_matches("0X"))
+        .collect::<Vec<_>>();
+
+    let bytes = match tokens.as_slice() {
+        [] => bail!("Nothing to send"),
+        [one] if one.len() > 2 => from_hex(one)?,
+        tokens => tokens.iter()
+            .map(|t| u8::from_str_radix(t, 16)
+                .with_context(|| format!("Invalid hex byte {:?}", t)))
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    // data bytes only, `sendmidi`-style, are wrapped into a sysex message
+    if bytes[0] & 0x80 == 0 {
+        let mut msg = vec![0xf0];
+        msg.extend(bytes);
+        msg.push(0xf7);
+        return Ok(msg);
+    }
+    Ok(bytes)
+}
+
+/// Commands sent from the developer console, most recent last. The
+/// history is kept in a state file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConsoleHistory {
+    pub commands: Vec<String>
+}
+
+impl ConsoleHistory {
+    pub fn file_path() -> Result<PathBuf> {
+        state_file(HISTORY_FILE)
+    }
+
+    /// Load the saved history. Failures are logged and result in an
+    /// empty history.
+    pub fn load() -> Self {
+        let mut history = Self::default();
+        let doc = Self::file_path()
+            .and_then(|path| Document::load_if_exists(&path));
+        let doc = match doc {
+            std::result::Result::Ok(Some(doc)) => doc,
+            std::result::Result::Ok(None) => return history,
+            Err(err) => {
+                error!("Failed to load console history: {}", err);
+                return history;
+            }
+        };
+
+        if let Some(section) = doc.section("history") {
+            history.commands = section.entries.iter()
+                .map(|(_, v)| v.clone())
+                .collect();
+        }
+        history
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut doc = Document::new();
+        let section = doc.section_mut("history");
+        for (i, command) in self.commands.iter().enumerate() {
+            section.set(&i.to_string(), command);
+        }
+        doc.save(&Self::file_path()?)
+    }
+
+    /// Add a command, moving it to the end if it is already in the
+    /// history
+    pub fn push(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+        self.commands.retain(|c| c != command);
+        self.commands.push(command.to_string());
+        if self.commands.len() > HISTORY_SIZE {
+            self.commands.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::console::*;
+
+    #[test]
+    fn parse_console_input() {
+        let udi = vec![0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7];
+        assert_eq!(parse_hex("f0 7e 7f 06 01 f7").unwrap(), udi);
+        assert_eq!(parse_hex("F07E7F0601F7").unwrap(), udi);
+        assert_eq!(parse_hex("0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7").unwrap(), udi);
+        assert_eq!(parse_hex("7e 7f 06 01").unwrap(), udi);
+        assert_eq!(parse_hex("b0 07 7f").unwrap(), vec![0xb0, 0x07, 0x7f]);
+        assert!(parse_hex("").is_err());
+        assert!(parse_hex("f0 xx").is_err());
+        assert!(parse_hex("f07").is_err());
+    }
+
+    #[test]
+    fn templates() {
+        let t = Template::by_name("XtPatchDumpRequest").unwrap();
+        assert_eq!(MidiMessage::from_bytes(t.to_bytes(130, 0)).unwrap(),
+                   MidiMessage::XtPatchDumpRequest { patch: 0x0102 });
+        assert_eq!(MidiMessage::from_bytes(t.to_bytes(9999, 0)).unwrap(),
+                   MidiMessage::XtPatchDumpRequest { patch: 0x027f });
+        let t = Template::by_name("ProgramChange").unwrap();
+        assert_eq!(t.to_bytes(5, 3), vec![0xc3, 5]);
+        assert_eq!(t.to_bytes(5, Channel::all()), vec![0xc0, 5]);
+        for t in TEMPLATES {
+            assert_eq!(Template::by_name(t.name).map(|t| t.name), Some(t.name));
+            t.to_bytes(0, 0);
+        }
+    }
+
+    #[test]
+    fn history() {
+        let mut history = ConsoleHistory::default();
+        history.push("f0 01 f7");
+        history.push("  ");
+        history.push("b0 07 7f");
+        history.push("f0 01 f7 ");
+        assert_eq!(history.commands, vec!["b0 07 7f", "f0 01 f7"]);
+    }
+}
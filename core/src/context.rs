@@ -1,6 +1,7 @@
 use std::fmt::{Debug, Formatter};
 use std::sync::{Arc, Mutex};
 use crate::controller::*;
+use crate::dispatch::BufferReroute;
 use crate::dump::ProgramsDump;
 use crate::edit::EditBuffer;
 use crate::event::{EventSender, Origin, Program};
@@ -14,6 +15,7 @@ pub struct Ctx {
     pub controller: Arc<Mutex<Controller>>,
     pub edit: Arc<Mutex<EditBuffer>>,
     pub dump: Arc<Mutex<ProgramsDump>>,
+    pub reroute: BufferReroute,
 
     pub ui_controller: Arc<Mutex<Controller>>,
 
@@ -29,6 +31,16 @@ impl Ctx {
         self.ui_controller.set("midi_channel", midi_channel as u16, StoreOrigin::NONE);
     }
 
+    /// Returns `true` if no MIDI device is connected and all
+    /// the editing happens offline
+    pub fn is_offline(&self) -> bool {
+        self.ui_controller.get("offline").unwrap_or(0) != 0
+    }
+
+    pub fn set_offline(&self, offline: bool) {
+        self.ui_controller.set("offline", offline as u16, StoreOrigin::NONE);
+    }
+
     pub fn program(&self) -> Program {
         self.ui_controller.get("program").unwrap().into()
     }
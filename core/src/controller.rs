@@ -3,16 +3,24 @@ use std::collections::HashMap;
 use tokio::sync::broadcast;
 use log::*;
 use std::sync::{Mutex, Arc};
+use crate::rules::Rules;
 use crate::store::{Origin, StoreBase};
 
 // re-export useful things from store
 pub use crate::store::{Store, StoreSetIm, Signal, Event};
 pub use crate::store::{Origin as StoreOrigin};
 
+/// Rule chains deeper than this are assumed to be a loop and cut short
+const MAX_RULES_DEPTH: usize = 16;
+
 pub struct Controller {
     store: StoreBase<String, u16>,
     pub controls: HashMap<String, Control>,
     values: HashMap<String, (u16, Origin)>,
+    rules: Arc<Rules>,
+    rules_depth: usize,
+    /// Value changes whose rules are held back until `run_deferred_rules()`
+    deferred: Option<Vec<(String, u16, Origin)>>
 }
 
 pub trait ControllerStoreExt {
@@ -27,7 +35,44 @@ impl Controller {
             values.insert(name.clone(), (0, Origin::NONE));
         }
 
-        Controller { store: StoreBase::new(), controls, values }
+        Controller { store: StoreBase::new(), controls, values,
+            rules: Arc::new(Rules::new()), rules_depth: 0, deferred: None }
+    }
+
+    /// Install the parameter rules run on every signalled value change
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = Arc::new(rules);
+    }
+
+    /// Hold back the rules of the values set from now on until
+    /// `run_deferred_rules()`, so that a bulk load does not run rules
+    /// against a half-loaded set of values
+    pub fn defer_rules(&mut self) {
+        self.deferred.get_or_insert_with(Vec::new);
+    }
+
+    /// Run the rules held back since `defer_rules()`, in the order the
+    /// values were set
+    pub fn run_deferred_rules(&mut self) {
+        let Some(deferred) = self.deferred.take() else { return };
+        for (name, value, origin) in deferred {
+            self.run_rules(&name, value, origin);
+        }
+    }
+
+    fn run_rules(&mut self, name: &str, value: u16, origin: Origin) {
+        if let Some(deferred) = self.deferred.as_mut() {
+            deferred.push((name.to_string(), value, origin));
+            return;
+        }
+        if self.rules_depth >= MAX_RULES_DEPTH {
+            warn!("Rules for {:?} not run: rule chain too deep", name);
+            return;
+        }
+        let rules = self.rules.clone();
+        self.rules_depth += 1;
+        rules.run(name, value, origin, self);
+        self.rules_depth -= 1;
     }
 
     pub fn get_origin(&self, name: &str) -> Option<(u16, Origin)> {
@@ -70,20 +115,23 @@ impl Store<&str, u16, String> for Controller {
     fn set_full(&mut self, name: &str, value: u16, origin: Origin, signal: Signal) -> bool {
         info!("set {:?} = {} <{:?}>", name, value, origin);
         let store = &self.store;
-        self.values.get_mut(name).map(|v| {
-            let value_changed = v.0 != value;
-            // need to check "signal == Force" because we're also setting origin here!
-            if value_changed || signal == Signal::Force {
-                v.0 = value;
-                v.1 = origin;
-            }
-
-            store.send_signal(name.to_string(), value, value_changed, origin, signal);
-            value_changed
-        }).unwrap_or_else(|| {
+        let Some(v) = self.values.get_mut(name) else {
             warn!("No control {:?} defined", name);
-            false
-        })
+            return false;
+        };
+        let value_changed = v.0 != value;
+        // need to check "signal == Force" because we're also setting origin here!
+        if value_changed || signal == Signal::Force {
+            v.0 = value;
+            v.1 = origin;
+        }
+
+        let signalled = signal == Signal::Force || (signal == Signal::Change && value_changed);
+        store.send_signal(name.to_string(), value, value_changed, origin, signal);
+        if signalled {
+            self.run_rules(name, value, origin);
+        }
+        value_changed
     }
 
     fn broadcast(&mut self, tx: Option<broadcast::Sender<Event<String, u16>>>) {
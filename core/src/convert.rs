@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use anyhow::*;
+use crate::controller::*;
+use crate::device::device_for_config;
+use crate::edit::EditBuffer;
+use crate::handler::Handler;
+use crate::model::{AbstractControl, Config, Control, Format, RangeConfig};
+use crate::program;
+
+/// What happened to a patch during conversion between two devices.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConversionReport {
+    /// Controls in the source patch that the target device does not have
+    pub dropped: Vec<String>,
+    /// Controls of the target device that the source patch does not have.
+    /// These are left at their initial values.
+    pub defaulted: Vec<String>,
+    /// Select controls whose value has no counterpart on the target
+    /// device, as (control name, source value label). These are left at
+    /// their initial values.
+    pub unmapped: Vec<(String, String)>
+}
+
+impl ConversionReport {
+    /// Returns `true` if everything in the source patch was carried over
+    pub fn is_lossless(&self) -> bool {
+        self.dropped.is_empty() && self.unmapped.is_empty()
+    }
+}
+
+impl Display for ConversionReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !self.dropped.is_empty() {
+            writeln!(f, "Not available on the target device: {}", self.dropped.join(", "))?;
+        }
+        for (name, label) in self.unmapped.iter() {
+            writeln!(f, "No counterpart for {} {:?} on the target device", name, label)?;
+        }
+        if !self.defaulted.is_empty() {
+            writeln!(f, "Left at initial values: {}", self.defaulted.join(", "))?;
+        }
+        std::result::Result::Ok(())
+    }
+}
+
+/// Value labels for select controls, which are defined by lists in the
+/// `Config` rather than in the controls themselves
+pub fn select_labels(config: &Config) -> HashMap<String, Vec<String>> {
+    let mut labels = HashMap::new();
+
+    let amps = config.amp_models.iter().map(|a| a.name.clone()).collect::<Vec<_>>();
+    if !amps.is_empty() {
+        labels.insert("amp_select".to_string(), amps);
+    }
+    if !config.cab_models.is_empty() {
+        labels.insert("cab_select".to_string(), config.cab_models.clone());
+    }
+
+    // effect select raw value is the effect id, which also encodes the
+    // delay on/off state
+    let ids = config.effects.iter()
+        .flat_map(|e| {
+            let clean = e.clean.as_ref().map(|c| (c.id, e.name.clone()));
+            let delay = e.delay.as_ref().map(|d| (d.id, format!("{} + Delay", e.name)));
+            clean.into_iter().chain(delay)
+        })
+        .collect::<Vec<_>>();
+    if let Some(max) = ids.iter().map(|(id, _)| *id as usize).max() {
+        let mut effects = vec![String::new(); max + 1];
+        for (id, name) in ids {
+            effects[id as usize] = name;
+        }
+        labels.insert("effect_select:raw".to_string(), effects);
+    }
+
+    labels
+}
+
+/// Value formatted for display, as in the UI. Select controls use
+/// `labels`, such as the ones from `select_labels()`.
+pub fn format_value(control: &Control, labels: Option<&Vec<String>>, value: u16) -> Option<String> {
+    let (config, format) = match control {
+        Control::RangeControl(c) => (&c.config, &c.format),
+        Control::AddrRangeControl(c) => (&c.config, &c.format),
+        Control::VirtualRangeControl(c) => (&c.config, &c.format),
+        _ => return labels.and_then(|labels| labels.get(value as usize)).cloned()
+    };
+    match format {
+        Format::Callback(f) => Some(f(config, value as f64)),
+        Format::Data(data) => Some(data.format(value as f64)),
+        Format::Interpolate(data) => Some(data.format(value as f64)),
+        Format::Labels(labels) => labels.get(value as usize).cloned(),
+        Format::None => None
+    }
+}
+
+/// Value bounds (inclusive) of a control, as stored in the `Controller`.
+/// Returns `None` for controls with no known value range, such as
+/// virtual selects without labels.
+pub fn control_bounds(config: &Config, name: &str) -> Option<(u16, u16)> {
+    let control = config.controls.get(name)?;
+    if let Some(config) = range_config(control) {
+        let (from, to) = config.bounds();
+        return Some((from as u16, to as u16));
+    }
+    let labels = select_labels(config).get(name)
+        .or_else(|| format_labels(control))
+        .map(|labels| (0, labels.len().saturating_sub(1) as u16));
+    match control {
+        Control::SwitchControl(_) | Control::MidiSwitchControl(_) | Control::Button(_) => Some((0, 1)),
+        Control::Select(_) | Control::MidiSelect(_) => labels.or(Some((0, 127))),
+        _ => labels
+    }
+}
+
+/// Clamp a raw value into the control's bounds, if known
+pub fn control_value_clamp(config: &Config, name: &str, value: f64) -> u16 {
+    let (from, to) = control_bounds(config, name).unwrap_or((0, u16::MAX));
+    value.round().max(from as f64).min(to as f64) as u16
+}
+
+/// Convert a value in normalized 0..1 units into the control's bounds.
+/// Returns `None` if the control has no known value range.
+pub fn control_value_from_norm(config: &Config, name: &str, value: f64) -> Option<u16> {
+    let (from, to) = control_bounds(config, name)?;
+    let value = from as f64 + (to as f64 - from as f64) * value.clamp(0.0, 1.0);
+    Some(value.round() as u16)
+}
+
+/// Convert a control value into normalized 0..1 units.
+/// Returns `None` if the control has no known value range.
+pub fn control_value_to_norm(config: &Config, name: &str, value: u16) -> Option<f64> {
+    let (from, to) = control_bounds(config, name).filter(|(from, to)| to > from)?;
+    let value = (value as f64 - from as f64) / (to as f64 - from as f64);
+    Some(value.clamp(0.0, 1.0))
+}
+
+/// Converts patches between devices with different controls or models by
+/// matching controls by name:
+/// - range controls are scaled if their bounds differ;
+/// - select controls with value labels (amp, cab, effect selects or any
+///   control with `Format::Labels`) are matched by label;
+/// - all other values are copied as-is.
+///
+/// Select controls with labels defined outside of the `Config` (such as
+/// PODxt stomp/mod/delay models) are taken from the registered device,
+/// others can be added using `labels()`.
+pub struct PatchConverter<'a> {
+    from: &'a Config,
+    to: &'a Config,
+    from_labels: HashMap<String, Vec<String>>,
+    to_labels: HashMap<String, Vec<String>>
+}
+
+impl<'a> PatchConverter<'a> {
+    pub fn new(from: &'a Config, to: &'a Config) -> Self {
+        Self {
+            from, to,
+            from_labels: device_select_labels(from),
+            to_labels: device_select_labels(to)
+        }
+    }
+
+    pub fn labels(&mut self, control: &str, from: Vec<String>, to: Vec<String>) -> &mut Self {
+        self.from_labels.insert(control.to_string(), from);
+        self.to_labels.insert(control.to_string(), to);
+        self
+    }
+
+    /// Convert patch `data` for the source device into a patch for the
+    /// target device. If `init` is given, it is used as the base of the
+    /// target patch, otherwise the target patch starts zeroed.
+    pub fn convert(&self, from_handler: &dyn Handler, data: &[u8],
+                   to_handler: &dyn Handler, init: Option<&[u8]>) -> Result<(Vec<u8>, ConversionReport)> {
+        if data.len() != self.from.program_size {
+            bail!("Patch size {} does not match {} program size {}",
+                data.len(), self.from.name, self.from.program_size);
+        }
+
+        let mut src = EditBuffer::new(self.from);
+        program::load_patch_dump_ctrl(&mut src, data, |c, n, b| {
+            from_handler.control_value_from_buffer(c, n, b)
+        });
+
+        let mut dst = EditBuffer::new(self.to);
+        if let Some(init) = init {
+            if init.len() != self.to.program_size {
+                bail!("Init patch size {} does not match {} program size {}",
+                    init.len(), self.to.name, self.to.program_size);
+            }
+            program::load_patch_dump_ctrl(&mut dst, init, |c, n, b| {
+                to_handler.control_value_from_buffer(c, n, b)
+            });
+        }
+
+        let mut report = ConversionReport::default();
+        let src_controller = src.controller();
+        let dst_controller = dst.controller();
+
+        for (name, control) in patch_controls(self.to) {
+            let Some(src_control) = self.from.controls.get(name)
+                .filter(|c| c.get_addr().is_some()) else {
+                report.defaulted.push(name.clone());
+                continue;
+            };
+            let Some(value) = src_controller.get(name) else {
+                continue;
+            };
+            let value = match self.map_value(name, src_control, control, value) {
+                std::result::Result::Ok(v) => v,
+                Err(label) => {
+                    report.unmapped.push((name.clone(), label));
+                    continue;
+                }
+            };
+
+            let mut controller = dst_controller.lock().unwrap();
+            controller.set_full(name, value, StoreOrigin::NONE, Signal::None);
+            let mut raw = dst.raw_locked();
+            to_handler.control_value_to_buffer(&controller, name, &mut raw);
+        }
+
+        for (name, _) in patch_controls(self.from) {
+            let present = self.to.controls.get(name)
+                .filter(|c| c.get_addr().is_some())
+                .is_some();
+            if !present {
+                report.dropped.push(name.clone());
+            }
+        }
+
+        let name = src.name();
+        dst.set_name(&name);
+
+        report.dropped.sort();
+        report.defaulted.sort();
+        report.unmapped.sort();
+
+        Ok((program::store_patch_dump_ctrl(&dst), report))
+    }
+
+    /// Map a control value, returning the source value label as an error
+    /// if there is no counterpart on the target device
+    fn map_value(&self, name: &str, from: &Control, to: &Control, value: u16) -> std::result::Result<u16, String> {
+        let from_labels = self.from_labels.get(name).or_else(|| format_labels(from));
+        let to_labels = self.to_labels.get(name).or_else(|| format_labels(to));
+        if let (Some(from_labels), Some(to_labels)) = (from_labels, to_labels) {
+            if from_labels == to_labels {
+                return std::result::Result::Ok(value);
+            }
+            let Some(label) = from_labels.get(value as usize) else {
+                // value outside the labels, nothing to match against
+                return std::result::Result::Ok(value);
+            };
+            return to_labels.iter()
+                .position(|l| l.eq_ignore_ascii_case(label))
+                .map(|v| v as u16)
+                .ok_or_else(|| label.clone());
+        }
+
+        if let (Some(from), Some(to)) = (range_config(from), range_config(to)) {
+            let (from_a, from_b) = from.bounds();
+            let (to_a, to_b) = to.bounds();
+            if (from_a, from_b) != (to_a, to_b) && from_b > from_a {
+                let v = (value as f64 - from_a) / (from_b - from_a);
+                let v = (v * (to_b - to_a) + to_a).round().max(to_a).min(to_b);
+                return std::result::Result::Ok(v as u16);
+            }
+        }
+
+        std::result::Result::Ok(value)
+    }
+}
+
+/// Convert patch `data` of a `from` device into a patch of a `to` device,
+/// using the handlers of the registered devices. Controls the source
+/// patch does not have are left at their initial values.
+pub fn convert_patch(from: &'static Config, data: &[u8], to: &'static Config) -> Result<(Vec<u8>, ConversionReport)> {
+    let device = |config: &'static Config| device_for_config(config)
+        .with_context(|| format!("No device registered for config {:?}", config.name));
+    let from_handler = device(from)?.handler(from);
+    let to_handler = device(to)?.handler(to);
+    let init = program::init_program(to, to_handler.as_ref());
+
+    PatchConverter::new(from, to)
+        .convert(from_handler.as_ref(), data, to_handler.as_ref(), Some(&init))
+}
+
+/// Select labels of `config` together with the ones its device defines
+fn device_select_labels(config: &Config) -> HashMap<String, Vec<String>> {
+    let mut labels = select_labels(config);
+    if let Some(device) = device_for_config(config) {
+        labels.extend(device.select_labels(config));
+    }
+    labels
+}
+
+/// Controls that are stored in the patch data
+fn patch_controls(config: &Config) -> Vec<(&String, &Control)> {
+    let mut controls = config.controls.iter()
+        .filter(|(_, c)| c.get_addr().is_some())
+        .collect::<Vec<_>>();
+    controls.sort_by_key(|(name, _)| name.as_str());
+    controls
+}
+
+fn range_config(control: &Control) -> Option<&RangeConfig> {
+    match control {
+        Control::RangeControl(c) => Some(&c.config),
+        Control::AddrRangeControl(c) => Some(&c.config),
+        Control::VirtualRangeControl(c) => Some(&c.config),
+        _ => None
+    }
+}
+
+fn format_labels(control: &Control) -> Option<&Vec<String>> {
+    let format = match control {
+        Control::RangeControl(c) => &c.format,
+        Control::AddrRangeControl(c) => &c.format,
+        Control::VirtualRangeControl(c) => &c.format,
+        _ => return None
+    };
+    match format {
+        Format::Labels(labels) => Some(labels),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::controller::*;
+    use crate::convert::*;
+    use crate::handler::Handler;
+    use crate::model::*;
+
+    struct ByteHandler;
+
+    impl Handler for ByteHandler {
+        fn control_value_from_buffer(&self, controller: &mut Controller, name: &str, buffer: &[u8]) {
+            let Some((addr, _)) = controller.get_config(name).and_then(|c| c.get_addr()) else { return };
+            controller.set_full(name, buffer[addr as usize] as u16, StoreOrigin::NONE, Signal::None);
+        }
+
+        fn control_value_to_buffer(&self, controller: &Controller, name: &str, buffer: &mut [u8]) {
+            let Some((addr, _)) = controller.get_config(name).and_then(|c| c.get_addr()) else { return };
+            buffer[addr as usize] = controller.get(name).unwrap() as u8;
+        }
+    }
+
+    fn config(name: &str, amps: &[&str], controls: Vec<(&str, Control)>) -> Config {
+        Config {
+            name: name.into(),
+            program_size: 8,
+            amp_models: amps.iter().map(|a| Amp { name: a.to_string(), ..Default::default() }).collect(),
+            controls: controls.into_iter().map(|(n, c)| (n.to_string(), c)).collect(),
+            program_name_addr: 4,
+            program_name_length: 4,
+            ..Config::empty()
+        }
+    }
+
+    #[test]
+    fn convert_by_control_name() {
+        let from = config("A", &["Clean", "Crunch", "Lead"], vec![
+            ("amp_select", Select { cc: 12, addr: 0 }.into()),
+            ("drive", RangeControl { cc: 13, addr: 1, config: RangeConfig::Short { from: 0, to: 63, edge: false }, ..Default::default() }.into()),
+            ("digiout_show", Select { cc: 14, addr: 2 }.into()),
+        ]);
+        let to = config("B", &["Lead", "Clean"], vec![
+            ("amp_select", Select { cc: 12, addr: 1 }.into()),
+            ("drive", RangeControl { cc: 13, addr: 0, config: RangeConfig::Normal, ..Default::default() }.into()),
+            ("wah_enable", Select { cc: 43, addr: 3 }.into()),
+        ]);
+
+        let h = ByteHandler;
+        let data = [2, 63, 1, 0, b'T', b'e', b's', b't'];
+        let (out, report) = PatchConverter::new(&from, &to)
+            .convert(&h, &data, &h, None).unwrap();
+        assert_eq!(&out, &[127, 0, 0, 0, b'T', b'e', b's', b't']);
+        assert_eq!(report.dropped, vec!["digiout_show".to_string()]);
+        assert_eq!(report.defaulted, vec!["wah_enable".to_string()]);
+        assert_eq!(report.unmapped, vec![]);
+
+        // "Crunch" has no counterpart
+        let data = [1, 0, 0, 0, 0, 0, 0, 0];
+        let (out, report) = PatchConverter::new(&from, &to)
+            .convert(&h, &data, &h, None).unwrap();
+        assert_eq!(out[1], 0);
+        assert_eq!(report.unmapped, vec![("amp_select".to_string(), "Crunch".to_string())]);
+        assert!(!report.is_lossless());
+    }
+}
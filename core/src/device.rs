@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use anyhow::*;
+use log::*;
+use once_cell::sync::Lazy;
+use crate::config::{config_index, configs, register_config};
+use crate::dump::ProgramsDump;
+use crate::edit::EditBuffer;
+use crate::handler::BoxedHandler;
+use crate::model::{AbstractControl, Config};
+use crate::store::{Signal, Store};
+use crate::store::Origin::NONE;
+
+/// A device family supported by a module: the configs of its members
+/// and the handler that talks to them. Toolkit-independent, so that
+/// headless tools can talk to a POD without pulling in a UI toolkit.
+pub trait Device: Send + Sync {
+    fn config(&self) -> Box<[Config]>;
+    fn handler(&self, config: &'static Config) -> BoxedHandler;
+
+    /// Value labels of select controls that are not defined in the
+    /// `Config`, such as effect models, for patch conversion
+    fn select_labels(&self, _config: &Config) -> HashMap<String, Vec<String>> {
+        HashMap::new()
+    }
+}
+
+pub type BoxedDevice = Box<dyn Device>;
+
+/// A registered device with the indexes of its configs in `configs()`
+type DeviceEntry = (Range<usize>, &'static dyn Device);
+
+static DEVICES: Lazy<Mutex<Vec<DeviceEntry>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+fn validate_unique_cc(config: &Config) -> bool {
+    let mut seen_cc = vec![];
+    let mut ok = true;
+    for (_, v) in config.controls.iter() {
+        if let Some(cc) = v.get_cc() {
+            if seen_cc.contains(&cc) {
+                error!("Config {:?} contains multiple controls for CC={}", config.name, cc);
+                ok = false;
+            }
+            seen_cc.push(cc);
+        }
+    }
+
+    ok
+}
+
+/// Register a device and its configs
+pub fn register_device(device: BoxedDevice) -> Result<()> {
+    let start = configs().len();
+    for config in device.config().iter() {
+        if !validate_unique_cc(config) {
+            bail!("Config {:?} failed unique CC validation!", config.name);
+        }
+        register_config(config);
+    }
+
+    // kept for good, like the registered configs
+    let device: &'static dyn Device = Box::leak(device);
+    DEVICES.lock().unwrap().push((start .. configs().len(), device));
+
+    Ok(())
+}
+
+pub fn device_for_config(config: &Config) -> Option<&'static dyn Device> {
+    let index = config_index(config)?;
+    DEVICES.lock().unwrap().iter()
+        .find(|(configs, _)| configs.contains(&index))
+        .map(|(_, device)| *device)
+}
+
+pub struct InitializedDevice {
+    pub handler: BoxedHandler,
+    pub edit_buffer: Arc<Mutex<EditBuffer>>,
+    pub dump: Arc<Mutex<ProgramsDump>>
+}
+
+/// Create the handler, edit buffer (with the device parameter rules
+/// installed) and programs dump for `config`
+pub fn init_device(config: &'static Config) -> Result<InitializedDevice> {
+    let device = device_for_config(config)
+        .with_context(|| format!("No device registered for config {:?}", config.name))?;
+    let handler = device.handler(config);
+
+    let edit_buffer = Arc::new(Mutex::new(EditBuffer::new(config)));
+    edit_buffer.lock().unwrap().controller_locked().set_rules(handler.rules(config));
+    let dump = Arc::new(Mutex::new(ProgramsDump::new(config)));
+
+    Ok(InitializedDevice { handler, edit_buffer, dump })
+}
+
+/// Signal the current values of the config's `init_controls`, so that
+/// the rules and frontends depending on them are brought up to date
+pub fn init_device_controls(config: &Config, edit_buffer: &EditBuffer) -> Result<()> {
+    let mut controller = edit_buffer.controller_locked();
+
+    for name in &config.init_controls {
+        let value = controller.get(name)
+            .with_context(|| format!("Initializing control {:?} value not found!", &name))?;
+        controller.set_full(name, value, NONE, Signal::Force);
+    }
+
+    Ok(())
+}
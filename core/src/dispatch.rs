@@ -1,29 +1,30 @@
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use log::*;
-use once_cell::sync::Lazy;
 use crate::context::Ctx;
 use crate::event::*;
 use crate::midi::{Channel, MidiMessage};
 
-/// DISPATCH_BUFFER_REROUTE is a hash map of Buffer -> Buffer routing,
-/// used when an unmodified program (load from device) is requested into
-/// the edit buffer (possibly, into a different program) from a program
-/// that is not the current program.
-static DISPATCH_BUFFER_REROUTE: Lazy<Mutex<HashMap<Buffer, Buffer>>> = Lazy::new(|| {
-    Mutex::new(HashMap::new())
-});
-
-pub fn dispatch_buffer_set(from: Buffer, to: Buffer) {
-    DISPATCH_BUFFER_REROUTE.lock().unwrap().insert(from, to);
-}
+/// Buffer -> Buffer routing, used when an unmodified program (load from
+/// device) is requested into the edit buffer (possibly, into a different
+/// program) from a program that is not the current program. Every device
+/// context has its own routing, so that concurrent device sessions do not
+/// reroute each other's buffers.
+#[derive(Clone, Default)]
+pub struct BufferReroute(Arc<Mutex<HashMap<Buffer, Buffer>>>);
+
+impl BufferReroute {
+    pub fn set(&self, from: Buffer, to: Buffer) {
+        self.0.lock().unwrap().insert(from, to);
+    }
 
-pub fn dispatch_buffer_get(from: &Buffer) -> Option<Buffer> {
-    DISPATCH_BUFFER_REROUTE.lock().unwrap().remove(from)
-}
+    pub fn get(&self, from: &Buffer) -> Option<Buffer> {
+        self.0.lock().unwrap().remove(from)
+    }
 
-pub fn dispatch_buffer_clear() {
-    DISPATCH_BUFFER_REROUTE.lock().unwrap().clear()
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear()
+    }
 }
 
 // -------------------------------------------------------------
@@ -81,7 +82,7 @@ pub fn midi_pc_out_handler(ctx: &Ctx, midi_message: &MidiMessage) {
 }
 
 pub fn pc_handler(ctx: &Ctx, event: &ProgramChangeEvent) {
-    dispatch_buffer_clear();
+    ctx.reroute.clear();
     ctx.handler.pc_handler(ctx, event);
 }
 
@@ -98,6 +99,16 @@ pub fn midi_out_handler(ctx: &Ctx, midi_message: &MidiMessage) {
 // load & store
 
 pub fn load_handler(ctx: &Ctx, event: &BufferLoadEvent) {
+    if event.origin == Origin::UI && ctx.is_offline() {
+        // There is no device to load from, so drop the request (and the
+        // possible reroute set up for it) instead of waiting for a reply
+        // that never comes
+        ctx.reroute.get(&event.buffer);
+        let msg = "Not connected to a device, nothing to load from".to_string();
+        ctx.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+        return;
+    }
+
     ctx.handler.load_handler(ctx, event)
 }
 
@@ -118,8 +129,12 @@ pub fn copy_handler(ctx: &Ctx, event: &BufferCopyEvent) {
     ctx.handler.copy_handler(ctx, event)
 }
 
+pub fn reorder_handler(ctx: &Ctx, event: &ReorderEvent) {
+    ctx.handler.reorder_handler(ctx, event)
+}
+
 pub fn buffer_handler(ctx: &Ctx, event: &BufferDataEvent) {
-    match dispatch_buffer_get(&event.buffer) {
+    match ctx.reroute.get(&event.buffer) {
         Some(buffer) => {
             // As part of a copy, we must also rewrite the origin so that the
             // data looks like it is coming from the MIDI side, so that the
@@ -134,6 +149,12 @@ pub fn buffer_handler(ctx: &Ctx, event: &BufferDataEvent) {
             event.origin = origin;
             ctx.handler.buffer_handler(ctx, &event, true)
         }
+        None if event.origin == Origin::UI && ctx.is_offline() => {
+            // Sending buffer data to the device while offline. Drop it
+            // without touching the "modified" flag, so that the buffer
+            // can be stored once the device is connected.
+            debug!("Offline, buffer {:?} not sent", event.buffer);
+        }
         None => {
             // process buffer data event as-is
             ctx.handler.buffer_handler(ctx, event, false)
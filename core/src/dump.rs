@@ -1,7 +1,9 @@
+use anyhow::*;
 use tokio::sync::broadcast;
 use crate::event::Origin;
 use crate::model::Config;
 use crate::names::ProgramNames;
+use crate::program_id_string;
 use crate::store::{Event, Store};
 
 pub struct ProgramsDump {
@@ -71,6 +73,113 @@ impl ProgramsDump {
     pub fn set_all_modified(&mut self, modified: bool) {
         self.modified.iter_mut().for_each(|m| *m = modified);
     }
+
+    /// Swap programs `a` and `b`. Returns the programs that changed.
+    pub fn swap(&mut self, a: usize, b: usize) -> Vec<usize> {
+        if a == b || a >= self.program_num || b >= self.program_num {
+            return vec![];
+        }
+        let (lo, hi) = (a.min(b), a.max(b));
+        let size = self.program_size;
+        let (head, tail) = self.data.split_at_mut(hi * size);
+        head[lo * size .. (lo + 1) * size].swap_with_slice(&mut tail[.. size]);
+
+        self.reordered(vec![lo, hi])
+    }
+
+    /// Move program `from` to slot `to`, shifting the programs in between
+    /// by one slot. Returns the programs that changed.
+    pub fn move_program(&mut self, from: usize, to: usize) -> Vec<usize> {
+        if from == to || from >= self.program_num || to >= self.program_num {
+            return vec![];
+        }
+        let size = self.program_size;
+        if from < to {
+            self.data[from * size .. (to + 1) * size].rotate_left(size);
+        } else {
+            self.data[to * size .. (from + 1) * size].rotate_right(size);
+        }
+
+        self.reordered((from.min(to) ..= from.max(to)).collect())
+    }
+
+    /// Insert program `data` at slot `at`, shifting the programs after it
+    /// by one slot. The last program is pushed out, so the insert is
+    /// refused if the last program has changes not stored to the device.
+    /// Returns the programs that changed.
+    pub fn insert(&mut self, at: usize, data: &[u8]) -> Result<Vec<usize>> {
+        if at >= self.program_num || data.len() != self.program_size {
+            return Ok(vec![]);
+        }
+        let last = self.program_num - 1;
+        if self.modified(last) {
+            bail!("Program {} has changes not stored to the device and would be pushed out",
+                  program_id_string(last));
+        }
+        let size = self.program_size;
+        self.data[at * size ..].rotate_right(size);
+        self.data[at * size .. (at + 1) * size].copy_from_slice(data);
+
+        Ok(self.reordered((at .. self.program_num).collect()))
+    }
+
+    /// Copy programs `from` (in the given order) to consecutive slots
+    /// starting at `to`. Programs that do not fit are skipped. Returns
+    /// the programs that changed.
+    pub fn copy_programs(&mut self, from: &[usize], to: usize) -> Vec<usize> {
+        let copies = from.iter()
+            .filter_map(|p| self.data(*p).map(|data| data.to_vec()))
+            .zip(to .. self.program_num)
+            .collect::<Vec<_>>();
+        let mut pages = vec![];
+        for (data, page) in copies {
+            self.data_mut(page).unwrap().copy_from_slice(&data);
+            pages.push(page);
+        }
+
+        self.reordered(pages)
+    }
+
+    /// Overwrite programs `pages` with `data`. Returns the programs
+    /// that changed.
+    pub fn fill(&mut self, pages: &[usize], data: &[u8]) -> Vec<usize> {
+        if data.len() != self.program_size {
+            return vec![];
+        }
+        let pages = pages.iter()
+            .filter(|p| **p < self.program_num)
+            .cloned()
+            .collect::<Vec<_>>();
+        for page in pages.iter() {
+            self.data_mut(*page).unwrap().copy_from_slice(data);
+        }
+
+        self.reordered(pages)
+    }
+
+    /// Overwrite programs with imported (program index, program data).
+    /// Programs out of range or of the wrong size are skipped. Returns
+    /// the programs that changed.
+    pub fn import(&mut self, programs: &[(usize, Vec<u8>)]) -> Vec<usize> {
+        let mut pages = vec![];
+        for (page, data) in programs {
+            if *page >= self.program_num || data.len() != self.program_size {
+                continue;
+            }
+            self.data_mut(*page).unwrap().copy_from_slice(data);
+            pages.push(*page);
+        }
+
+        self.reordered(pages)
+    }
+
+    fn reordered(&mut self, pages: Vec<usize>) -> Vec<usize> {
+        for page in pages.iter() {
+            self.update_name_from_data(*page, Origin::UI);
+            self.set_modified(*page, true);
+        }
+        pages
+    }
 }
 fn nth_chunk(data: &[u8], page: usize, page_size: usize) -> Option<&[u8]> {
     data.chunks(page_size).nth(page)
@@ -79,3 +188,71 @@ fn nth_chunk(data: &[u8], page: usize, page_size: usize) -> Option<&[u8]> {
 fn nth_chunk_mut(data: &mut [u8], page: usize, page_size: usize) -> Option<&mut [u8]> {
     data.chunks_mut(page_size).nth(page)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::dump::ProgramsDump;
+    use crate::event::Origin;
+    use crate::model::Config;
+
+    fn dump() -> ProgramsDump {
+        let config = Config {
+            program_num: 4,
+            program_size: 2,
+            program_name_addr: 0,
+            program_name_length: 2,
+            ..Config::empty()
+        };
+        let mut dump = ProgramsDump::new(&config);
+        for p in 0 .. 4 {
+            dump.data_mut(p).unwrap().copy_from_slice(format!("P{}", p).as_bytes());
+            dump.update_name_from_data(p, Origin::MIDI);
+        }
+        dump
+    }
+
+    fn names(dump: &ProgramsDump) -> Vec<String> {
+        (0 .. dump.program_num()).map(|p| dump.name(p).unwrap_or_default()).collect()
+    }
+
+    #[test]
+    fn reorder_programs() {
+        let mut d = dump();
+        assert_eq!(d.swap(3, 1), vec![1, 3]);
+        assert_eq!(names(&d), vec!["P0", "P3", "P2", "P1"]);
+        assert!(!d.modified(0) && d.modified(1) && !d.modified(2) && d.modified(3));
+
+        let mut d = dump();
+        assert_eq!(d.move_program(0, 2), vec![0, 1, 2]);
+        assert_eq!(names(&d), vec!["P1", "P2", "P0", "P3"]);
+        assert_eq!(d.move_program(3, 1), vec![1, 2, 3]);
+        assert_eq!(names(&d), vec!["P1", "P3", "P2", "P0"]);
+
+        let mut d = dump();
+        assert_eq!(d.insert(1, b"XX").unwrap(), vec![1, 2, 3]);
+        assert_eq!(names(&d), vec!["P0", "XX", "P1", "P2"]);
+        assert_eq!(d.insert(1, b"XXX").unwrap(), Vec::<usize>::new());
+
+        let mut d = dump();
+        assert_eq!(d.copy_programs(&[0, 1], 1), vec![1, 2]);
+        assert_eq!(names(&d), vec!["P0", "P0", "P1", "P3"]);
+        assert_eq!(d.copy_programs(&[0, 1, 2], 3), vec![3]);
+        assert_eq!(d.fill(&[0, 2, 9], b"XX"), vec![0, 2]);
+        assert_eq!(names(&d), vec!["XX", "P0", "XX", "P0"]);
+    }
+
+    #[test]
+    fn insert_last_program() {
+        // an unmodified last program is pushed out
+        let mut d = dump();
+        assert_eq!(d.insert(3, b"XX").unwrap(), vec![3]);
+        assert_eq!(names(&d), vec!["P0", "P1", "P2", "XX"]);
+
+        // a modified one is kept and nothing is inserted
+        let mut d = dump();
+        d.set_modified(3, true);
+        assert!(d.insert(0, b"XX").is_err());
+        assert_eq!(names(&d), vec!["P0", "P1", "P2", "P3"]);
+        assert!(!d.modified(0));
+    }
+}
@@ -47,9 +47,12 @@ impl EditBuffer {
     {
         let mut controller = self.controller.lock().unwrap();
         let raw = self.raw.lock().unwrap();
+        // rules see the whole program, not the controls loaded so far
+        controller.defer_rules();
         for (name, _) in ordered_controls(&controller) {
             control_value_from_buffer(&mut controller, &name, &raw);
         }
+        controller.run_deferred_rules();
         controller.set_full("name_change", 1, Origin::NONE, Signal::Force);
     }
 
@@ -0,0 +1,918 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures_util::future::{join_all, JoinAll};
+use futures_util::FutureExt;
+use log::*;
+use tokio::sync::{broadcast, oneshot};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use crate::api::ApiContext;
+use crate::clock::{midi_clock_handler, ClockSettings, ClockSource, MidiClock};
+use crate::context::Ctx;
+use crate::dispatch::*;
+use crate::event::*;
+use crate::hotplug::{HotplugWatch, LostConnection};
+use crate::learn::{ext_midi_in_handler, MidiLearn};
+use crate::midi::MidiMessage;
+use crate::midi_io::{MidiIn, MidiOut};
+use crate::config::config_for_firmware;
+use crate::model::{Config, FirmwareSupport, FirmwareVersion, MidiQuirks};
+use crate::next_thread_id;
+use crate::offline::{is_all_programs_loaded, OfflineEdits, SyncSlot};
+use crate::osc::{osc_in_handler, osc_out_handler, OscServer};
+use crate::pcmap::{pc_map_handler, PcMap, PcMapInput};
+use crate::profile::Profile;
+use crate::remote::{remote_handler, RemoteCommand};
+use crate::setlist::{setlist_handler, Setlist, SetlistTriggers};
+use crate::thru::{ext_midi_thru_handler, MessageType, MidiThru};
+
+const APP_EVENT_CHANNEL_CAPACITY: usize = 512;
+const ENGINE_EVENT_CHANNEL_CAPACITY: usize = 512;
+const CLOSE_QUIET_DURATION_MS: u64 = 1000;
+
+/// Events the engine reports to its frontend
+#[derive(Clone, Debug)]
+pub enum EngineEvent {
+    DeviceDetected(DeviceDetectedEvent),
+    /// MIDI connection changed: connected, disconnected or a new MIDI channel
+    NewMidiConnection,
+    /// A new device config was installed in `State::config`. The frontend
+    /// is expected to create the device edit buffer, programs dump and
+    /// handler and install them with `Engine::set_ctx`.
+    NewConfig,
+    MidiTx,
+    MidiRx,
+    Modified(usize, bool),
+    Sync(Vec<SyncSlot>),
+    Setlist(Option<usize>),
+    Notification(String, Option<String>),
+    Shutdown
+}
+
+pub type EngineEventSender = broadcast::Sender<EngineEvent>;
+
+/// Device MIDI connection state
+pub struct State {
+    pub midi_in_name: Option<String>,
+    pub midi_in_cancel: Option<oneshot::Sender<()>>,
+    pub midi_in_handle: Option<JoinHandle<()>>,
+
+    pub midi_out_name: Option<String>,
+    pub midi_out_cancel: Option<oneshot::Sender<()>>,
+    pub midi_out_handle: Option<JoinHandle<()>>,
+
+    pub midi_channel_num: u8,
+
+    pub ext_in_name: Option<String>,
+    pub ext_in_cancel: Option<oneshot::Sender<()>>,
+    pub ext_in_handle: Option<JoinHandle<()>>,
+
+    /// Connection to reopen when the device is plugged back in
+    pub lost: Option<LostConnection>,
+
+    pub app_event_tx: EventSender,
+    pub engine_event_tx: EngineEventSender,
+
+    pub config: Option<&'static Config>,
+    pub detected: Option<DeviceDetectedEvent>,
+    /// Device and firmware the user was last told about, so that the
+    /// notification is not repeated on every device inquiry
+    pub firmware_notified: Option<(String, FirmwareVersion)>,
+}
+
+impl State {
+    fn new(app_event_tx: EventSender, engine_event_tx: EngineEventSender) -> Self {
+        State {
+            midi_in_name: None,
+            midi_in_cancel: None,
+            midi_in_handle: None,
+            midi_out_name: None,
+            midi_out_cancel: None,
+            midi_out_handle: None,
+            midi_channel_num: 0,
+            ext_in_name: None,
+            ext_in_cancel: None,
+            ext_in_handle: None,
+            lost: None,
+            app_event_tx,
+            engine_event_tx,
+            config: None,
+            detected: None,
+            firmware_notified: None,
+        }
+    }
+
+    /// The MIDI connection in use, `None` unless both ports are open
+    pub fn connection(&self) -> Option<Profile> {
+        if self.midi_in_name.is_none() || self.midi_out_name.is_none() {
+            return None;
+        }
+        Some(Profile {
+            name: String::new(),
+            midi_in: self.midi_in_name.clone(),
+            midi_out: self.midi_out_name.clone(),
+            midi_channel: Some(self.midi_channel_num),
+            model: self.config.map(|c| c.name.clone()),
+            controller_in: self.ext_in_name.clone()
+        })
+    }
+}
+
+/// State of the optional app features the event loop feeds, shared
+/// with the frontend that configures them. A frontend with several
+/// sessions shares one `Services` between their engines.
+#[derive(Clone)]
+pub struct Services {
+    pub midi_learn: Arc<Mutex<Option<MidiLearn>>>,
+    pub pc_map: Arc<Mutex<Option<PcMap>>>,
+    pub setlist: Arc<Mutex<Option<Setlist>>>,
+    pub setlist_triggers: Arc<Mutex<SetlistTriggers>>,
+    pub midi_thru: Arc<Mutex<MidiThru>>,
+    pub midi_clock: Arc<Mutex<MidiClock>>,
+    pub osc_server: Arc<Mutex<Option<OscServer>>>,
+    pub api_context: ApiContext,
+    pub hotplug: HotplugWatch
+}
+
+impl Services {
+    /// Create the services with their settings loaded from the
+    /// settings file. Settings that fail to load are reset to defaults.
+    pub fn load() -> Self {
+        let setlist_triggers = SetlistTriggers::load()
+            .map_err(|err| error!("Failed to load setlist triggers: {}", err))
+            .unwrap_or_default();
+        let midi_thru = MidiThru::load()
+            .map_err(|err| error!("Failed to load MIDI thru settings: {}", err))
+            .unwrap_or_default();
+        let clock_settings = ClockSettings::load()
+            .map_err(|err| error!("Failed to load MIDI clock settings: {}", err))
+            .unwrap_or_default();
+
+        Services {
+            setlist_triggers: Arc::new(Mutex::new(setlist_triggers)),
+            midi_thru: Arc::new(Mutex::new(midi_thru)),
+            midi_clock: Arc::new(Mutex::new(MidiClock::new(clock_settings))),
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for Services {
+    /// Services with default settings, nothing is read from the
+    /// settings file
+    fn default() -> Self {
+        Services {
+            midi_learn: Arc::new(Mutex::new(None)),
+            pc_map: Arc::new(Mutex::new(None)),
+            setlist: Arc::new(Mutex::new(None)),
+            setlist_triggers: Arc::new(Mutex::new(SetlistTriggers::default())),
+            midi_thru: Arc::new(Mutex::new(MidiThru::default())),
+            midi_clock: Arc::new(Mutex::new(MidiClock::new(ClockSettings::default()))),
+            osc_server: Arc::new(Mutex::new(None)),
+            api_context: ApiContext::default(),
+            hotplug: HotplugWatch::default()
+        }
+    }
+}
+
+/// The application engine: owns the app event bus, the device MIDI
+/// connection and the device context, and runs the app event loop that
+/// dispatches app events to the device handlers. Frontends drive it via
+/// the app event bus and follow it via the engine event stream.
+#[derive(Clone)]
+pub struct Engine {
+    app_event_tx: EventSender,
+    engine_event_tx: EngineEventSender,
+    /// Subscribed on creation, so that no events sent before `start()` are lost
+    app_event_rx: Arc<Mutex<Option<broadcast::Receiver<AppEvent>>>>,
+    state: Arc<Mutex<State>>,
+    ctx_share: Arc<Mutex<Option<Ctx>>>,
+    services: Services
+}
+
+impl Engine {
+    pub fn new(services: Services) -> Self {
+        let (app_event_tx, app_event_rx) = broadcast::channel::<AppEvent>(APP_EVENT_CHANNEL_CAPACITY);
+        let (engine_event_tx, _) = broadcast::channel::<EngineEvent>(ENGINE_EVENT_CHANNEL_CAPACITY);
+        let state = State::new(app_event_tx.clone(), engine_event_tx.clone());
+
+        Engine {
+            app_event_tx,
+            engine_event_tx,
+            app_event_rx: Arc::new(Mutex::new(Some(app_event_rx))),
+            state: Arc::new(Mutex::new(state)),
+            ctx_share: Arc::new(Mutex::new(None)),
+            services
+        }
+    }
+
+    /// The app event bus
+    pub fn sender(&self) -> EventSender {
+        self.app_event_tx.clone()
+    }
+
+    pub fn send(&self, event: AppEvent) {
+        self.app_event_tx.send_or_warn(event);
+    }
+
+    /// Subscribe to the engine events. Subscribe before starting the
+    /// MIDI connection, or early events will be missed.
+    pub fn subscribe(&self) -> broadcast::Receiver<EngineEvent> {
+        self.engine_event_tx.subscribe()
+    }
+
+    pub fn state(&self) -> Arc<Mutex<State>> {
+        self.state.clone()
+    }
+
+    pub fn services(&self) -> &Services {
+        &self.services
+    }
+
+    /// Take the device context handed back by the event loop after
+    /// `EngineEvent::NewConfig`
+    pub fn take_ctx(&self) -> Option<Ctx> {
+        self.ctx_share.lock().unwrap().take()
+    }
+
+    /// Install a new device context into the event loop
+    pub fn set_ctx(&self, ctx: Ctx) {
+        self.ctx_share.lock().unwrap().replace(ctx);
+        self.app_event_tx.send_or_warn(AppEvent::NewCtx);
+    }
+
+    /// Start the app event loop and the MIDI hotplug watch.
+    /// `startup_commands` are executed once the device is ready.
+    /// The event loop can only be started once.
+    pub fn start(&self, startup_commands: Vec<RemoteCommand>) -> JoinHandle<()> {
+        let app_event_rx = self.app_event_rx.lock().unwrap().take()
+            .expect("Engine event loop already started");
+        self.services.hotplug.add(self.state.clone());
+        tokio::spawn(
+            run(self.clone(), app_event_rx, startup_commands)
+        )
+    }
+
+    /// Stop the controller MIDI input, the hotplug watch of the device
+    /// and the device MIDI connection. Returns a future that resolves when
+    /// the MIDI threads have finished. The services are shared with other
+    /// engines, so stopping the OSC server is up to the frontend.
+    pub fn stop(&self) -> JoinAll<JoinHandle<()>> {
+        self.services.hotplug.remove(&self.state);
+        let mut state = self.state.lock().unwrap();
+        set_ext_midi_in(&mut state, None);
+        midi_in_out_stop(&mut state)
+    }
+}
+
+pub fn midi_in_out_stop(state: &mut State) -> JoinAll<JoinHandle<()>> {
+    state.midi_in_cancel.take().map(|cancel| cancel.send(()));
+    state.midi_out_cancel.take().map(|cancel| cancel.send(()));
+
+    let handles = state.midi_in_handle.take().into_iter()
+        .chain(state.midi_out_handle.take());
+    join_all(handles)
+}
+
+pub fn midi_in_out_start(state: &mut State,
+                         midi_in: Option<MidiIn>, midi_out: Option<MidiOut>,
+                         midi_channel: u8, quirks: MidiQuirks,
+                         config_changed: bool) {
+
+    let notify = |state: &mut State| {
+        let e = NewConfigEvent {
+            midi_changed: true,
+            midi_channel: state.midi_channel_num,
+            config_changed,
+            offline: state.midi_in_name.is_none() || state.midi_out_name.is_none()
+        };
+        state.app_event_tx.send_or_warn(AppEvent::NewConfig(e));
+    };
+
+    if midi_in.is_none() || midi_out.is_none() {
+        warn!("Not starting MIDI because in/out is None");
+        state.midi_in_name = None;
+        state.midi_in_cancel = None;
+        state.midi_out_name = None;
+        state.midi_out_cancel = None;
+        state.midi_channel_num = midi_channel;
+        notify(state);
+        return;
+    }
+
+    let mut midi_in = midi_in.unwrap();
+    let mut midi_out = midi_out.unwrap();
+
+    let (in_cancel_tx, in_cancel_rx) = oneshot::channel::<()>();
+    let (out_cancel_tx, out_cancel_rx) = oneshot::channel::<()>();
+
+    state.midi_in_name = Some(midi_in.name.clone());
+    state.midi_in_cancel = Some(in_cancel_tx);
+
+    state.midi_out_name = Some(midi_out.name.clone());
+    state.midi_out_cancel = Some(out_cancel_tx);
+
+    state.midi_channel_num = midi_channel;
+
+    notify(state);
+
+    // midi in
+    let midi_in_handle =
+        tokio::spawn({
+            let app_event_tx = state.app_event_tx.clone();
+            let engine_event_tx = state.engine_event_tx.clone();
+            let mut in_cancel_rx = in_cancel_rx.fuse();
+
+            async move {
+                let id = next_thread_id();
+                let mut close_quiet_duration: Option<Duration> = None;
+
+                info!("MIDI in thread {:?} start", id);
+                loop {
+                    tokio::select! {
+                        msg = midi_in.recv() => {
+                            if let Some(bytes) = msg {
+                                app_event_tx.send_or_warn(AppEvent::MidiIn(bytes));
+                                engine_event_tx.send_or_warn(EngineEvent::MidiRx);
+                            }
+                        }
+                        _ = &mut in_cancel_rx => {
+                            if quirks.contains(MidiQuirks::MIDI_CLOSE_QUIET_TIMEOUT) {
+                                debug!("close_quiet_duration set!");
+                                close_quiet_duration = Some(Duration::from_millis(CLOSE_QUIET_DURATION_MS));
+                            } else {
+                                break;
+                            }
+                        }
+                        _ = async {
+                            if let Some(d) = close_quiet_duration {
+                                sleep(d).await
+                            } else {
+                                std::future::pending::<()>().await
+                            }
+                        } => {
+                            break;
+                        }
+                    }
+                }
+                midi_in.close();
+                info!("MIDI in thread {:?} finish", id);
+            }
+        });
+
+    // midi out
+    let midi_out_handle =
+        tokio::spawn({
+            let engine_event_tx = state.engine_event_tx.clone();
+            let mut app_event_rx = state.app_event_tx.subscribe();
+            let mut out_cancel_rx = out_cancel_rx.fuse();
+
+            async move {
+                let id = next_thread_id();
+                info!("MIDI out thread {:?} start", id);
+                loop {
+                    tokio::select! {
+                        msg = app_event_rx.recv() => {
+                            match msg {
+                                std::result::Result::Ok(AppEvent::MidiOut(bytes)) => {
+                                    midi_out.send(&bytes)
+                                    .unwrap_or_else(|e| error!("MIDI OUT thread tx error: {}", e));
+                                    engine_event_tx.send_or_warn(EngineEvent::MidiTx);
+                                }
+                                Err(err) => {
+                                    error!("MIDI OUT thread rx error: {:?}", err);
+                                }
+                                _ => {}
+                            }
+                        }
+                        _ = &mut out_cancel_rx => {
+                            midi_out.close();
+                            break;
+                        }
+                    }
+                }
+                midi_out.close();
+                info!("MIDI out thread {:?} finish", id);
+            }
+        });
+
+    state.midi_in_handle = Some(midi_in_handle);
+    state.midi_out_handle = Some(midi_out_handle);
+}
+
+/// Install `config` (if it differs from the current one) and start the
+/// device MIDI connection. Returns `true` if the config changed. A lost
+/// connection is forgotten, since a new one was chosen.
+pub fn set_midi_in_out(state: &mut State, midi_in: Option<MidiIn>, midi_out: Option<MidiOut>,
+                       midi_channel: u8, config: Option<&'static Config>) -> bool {
+    if state.midi_in_cancel.is_some() || state.midi_out_cancel.is_some() {
+        error!("Midi still running when entering send_midi_in_out");
+    }
+    state.lost = None;
+
+    let config_changed = match (config, state.config) {
+        (Some(a), Some(b)) => { *a != *b }
+        (Some(_), None) => { true }
+        _ => { false }
+    };
+    if config_changed {
+        // config changed, update config & edit buffer
+        let config = config.unwrap();
+        info!("Installing config {:?}", &config.name);
+
+        state.config.replace(config);
+    }
+
+    let quirks = state.config.map(|c| c.midi_quirks)
+        .unwrap_or_else(MidiQuirks::empty);
+    midi_in_out_start(state, midi_in, midi_out, midi_channel, quirks, config_changed);
+
+    config_changed
+}
+
+/// Start the MIDI input thread for the external controller used for
+/// MIDI learn and MIDI thru, stopping the previous one. `None` just
+/// stops it.
+pub fn set_ext_midi_in(state: &mut State, midi_in: Option<MidiIn>) {
+    state.ext_in_cancel.take().map(|cancel| cancel.send(()));
+    state.ext_in_handle = None;
+    state.ext_in_name = midi_in.as_ref().map(|midi_in| midi_in.name.clone());
+
+    let Some(mut midi_in) = midi_in else { return };
+    let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+    state.ext_in_cancel = Some(cancel_tx);
+
+    let handle =
+        tokio::spawn({
+            let app_event_tx = state.app_event_tx.clone();
+            let mut cancel_rx = cancel_rx.fuse();
+
+            async move {
+                let id = next_thread_id();
+                info!("Controller MIDI in thread {:?} start", id);
+                loop {
+                    tokio::select! {
+                        msg = midi_in.recv() => {
+                            let Some(bytes) = msg else { break };
+                            app_event_tx.send_or_warn(AppEvent::ExtMidiIn(bytes));
+                        }
+                        _ = &mut cancel_rx => {
+                            break;
+                        }
+                    }
+                }
+                midi_in.close();
+                info!("Controller MIDI in thread {:?} finish", id);
+            }
+        });
+    state.ext_in_handle = Some(handle);
+}
+
+fn modified_events(ctx: &Ctx, event: &ModifiedEvent, engine_event_tx: &EngineEventSender) {
+    match event.buffer {
+        Buffer::EditBuffer => { /* don't touch event buffer */ }
+        Buffer::Current => {
+            let program = match ctx.program() {
+                Program::Program(v) => { v as usize }
+                _ => { return; }
+            };
+            engine_event_tx.send_or_warn(EngineEvent::Modified(program, event.modified));
+        }
+        Buffer::Program(program) => {
+            engine_event_tx.send_or_warn(EngineEvent::Modified(program, event.modified));
+        }
+        Buffer::All => {
+            for program in 0 .. ctx.config.program_num {
+                engine_event_tx.send_or_warn(EngineEvent::Modified(program, event.modified));
+            }
+        }
+    }
+}
+
+/// Tell the user if the detected device firmware is older or newer than
+/// any tested, and install the config variant for the firmware
+fn firmware_handler(state: &Arc<Mutex<State>>, ctx: &Ctx, event: &DeviceDetectedEvent) {
+    if event.family != ctx.config.family || event.member != ctx.config.member {
+        // not the configured device
+        return;
+    }
+    let Some(version) = event.version.parse::<FirmwareVersion>().ok() else {
+        return;
+    };
+
+    let msg = match ctx.config.firmware_support(version) {
+        FirmwareSupport::Older(oldest) =>
+            Some(format!("{} firmware {} is older than any tested (oldest tested: {})",
+                         ctx.config.name, version, oldest)),
+        FirmwareSupport::Newer(newest) =>
+            Some(format!("{} firmware {} is newer than any tested (newest tested: {})",
+                         ctx.config.name, version, newest)),
+        _ => None
+    };
+    if let Some(msg) = msg {
+        let notified = Some((ctx.config.name.clone(), version));
+        let mut state = state.lock().unwrap();
+        if state.firmware_notified != notified {
+            warn!("{}", msg);
+            ctx.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+            state.firmware_notified = notified;
+        }
+    }
+
+    let config = config_for_firmware(ctx.config, version);
+    if std::ptr::eq(config, ctx.config) {
+        return;
+    }
+    info!("Installing config {:?} for firmware {}", &config.name, version);
+    let mut state = state.lock().unwrap();
+    state.config.replace(config);
+    let e = NewConfigEvent {
+        midi_changed: false,
+        midi_channel: state.midi_channel_num,
+        config_changed: true,
+        offline: state.midi_in_name.is_none() || state.midi_out_name.is_none()
+    };
+    state.app_event_tx.send_or_warn(AppEvent::NewConfig(e));
+}
+
+async fn run(engine: Engine, mut app_event_rx: broadcast::Receiver<AppEvent>,
+             startup_commands: Vec<RemoteCommand>) {
+    let Engine { app_event_tx, engine_event_tx, ctx_share, services, state, .. } = engine;
+    let Services {
+        midi_learn, pc_map, setlist, setlist_triggers, midi_thru, midi_clock, osc_server, api_context, ..
+    } = services;
+
+    let mut ctx: Option<Ctx> = None;
+    let mut offline = true;
+    let mut offline_edits: Option<OfflineEdits> = None;
+    let mut startup_commands = startup_commands;
+
+    loop {
+        let msg = match app_event_rx.recv().await {
+            std::result::Result::Ok(msg) => { msg }
+            Err(RecvError::Closed) => {
+                info!("App event bus closed");
+                return;
+            }
+            Err(RecvError::Lagged(n)) => {
+                error!("App event bus lagged: {}", n);
+                continue;
+            }
+        };
+        debug!("== {:?}", msg);
+
+        // execute device-specific handlers
+        if let Some(ctx) = &ctx {
+            match &msg {
+                // device inquiry
+                AppEvent::MidiMsgIn(msg @ MidiMessage::UniversalDeviceInquiry { .. }) |
+                AppEvent::MidiMsgIn(msg @ MidiMessage::UniversalDeviceInquiryResponse { .. }) => {
+                    midi_udi_handler(ctx, msg);
+                }
+
+                // control change
+                AppEvent::MidiMsgIn(msg @ MidiMessage::ControlChange { .. }) => {
+                    midi_cc_in_handler(ctx, msg);
+                }
+                AppEvent::MidiMsgOut(msg @ MidiMessage::ControlChange { .. }) => {
+                    midi_cc_out_handler(ctx, msg);
+                }
+                AppEvent::ControlChange(cc) => {
+                    cc_handler(ctx, cc);
+                    if let Some(server) = osc_server.lock().unwrap().as_ref() {
+                        osc_out_handler(ctx, server, &msg);
+                    }
+                }
+
+                // program change
+                AppEvent::MidiMsgIn(msg @ MidiMessage::ProgramChange { .. }) => {
+                    let mapped = pc_map.lock().unwrap().as_ref()
+                        .map(|m| pc_map_handler(ctx, m, PcMapInput::Device, msg))
+                        .unwrap_or(false);
+                    if !mapped {
+                        midi_pc_in_handler(ctx, msg);
+                    }
+                }
+                AppEvent::MidiMsgOut(msg @ MidiMessage::ProgramChange { .. }) => {
+                    midi_pc_out_handler(ctx, msg);
+                }
+                AppEvent::ProgramChange(pc) => {
+                    pc_handler(ctx, pc);
+                    if let Some(server) = osc_server.lock().unwrap().as_ref() {
+                        osc_out_handler(ctx, server, &msg);
+                    }
+                }
+
+                // store & load
+                AppEvent::Load(event) => {
+                    load_handler(ctx, event)
+                }
+                AppEvent::Store(event) => {
+                    store_handler(ctx, event)
+                }
+                AppEvent::Copy(event) => {
+                    copy_handler(ctx, event)
+                }
+                AppEvent::Reorder(event) => {
+                    reorder_handler(ctx, event)
+                }
+                AppEvent::Setlist(event) => {
+                    let position = setlist.lock().unwrap().as_mut()
+                        .and_then(|setlist| setlist_handler(ctx, setlist, event));
+                    if position.is_some() {
+                        engine_event_tx.send_or_warn(EngineEvent::Setlist(position));
+                    }
+                }
+                AppEvent::BufferData(event) => {
+                    buffer_handler(ctx, event)
+                }
+                AppEvent::Modified(event) => {
+                    modified_handler(ctx, event);
+                    modified_events(ctx, event, &engine_event_tx)
+                }
+
+                // other
+                AppEvent::MidiMsgIn(msg) => {
+                    midi_in_handler(ctx, msg);
+                }
+                AppEvent::MidiMsgOut(msg) => {
+                    midi_out_handler(ctx, msg);
+                }
+                AppEvent::Marker(marker) => {
+                    marker_handler(ctx, *marker);
+                }
+
+                // MIDI clock
+                AppEvent::MidiIn(bytes) => {
+                    midi_clock_handler(ctx, &mut midi_clock.lock().unwrap(), ClockSource::Device, bytes);
+                }
+
+                // external controller
+                AppEvent::ExtMidiIn(bytes) => {
+                    midi_clock_handler(ctx, &mut midi_clock.lock().unwrap(), ClockSource::Controller, bytes);
+
+                    // CCs taken by MIDI learn or setlist triggers and mapped PCs
+                    // are not forwarded
+                    let taken = MidiMessage::from_bytes(bytes.clone()).ok()
+                        .map(|msg| {
+                            let learned = midi_learn.lock().unwrap().as_ref()
+                                .map(|l| l.handles(&msg)).unwrap_or(false);
+                            let mapped = pc_map.lock().unwrap().as_ref()
+                                .and_then(|m| m.lookup(PcMapInput::Controller, &msg)).is_some();
+                            let trigger = setlist_triggers.lock().unwrap().matches(&msg);
+                            learned || mapped || trigger
+                        })
+                        .unwrap_or(false);
+                    if !taken {
+                        ext_midi_thru_handler(ctx, &midi_thru.lock().unwrap(), bytes);
+                    }
+                }
+                AppEvent::ExtMidiMsgIn(msg @ MidiMessage::ProgramChange { .. }) => {
+                    if let Some(m) = pc_map.lock().unwrap().as_ref() {
+                        pc_map_handler(ctx, m, PcMapInput::Controller, msg);
+                    }
+                }
+                AppEvent::ExtMidiMsgIn(msg @ MidiMessage::ControlChange { .. })
+                    if setlist_triggers.lock().unwrap().matches(msg) => {
+                    if let Some(e) = setlist_triggers.lock().unwrap().event(msg) {
+                        app_event_tx.send_or_warn(AppEvent::Setlist(e));
+                    }
+                }
+                AppEvent::ExtMidiMsgIn(msg) => {
+                    let mut learn = midi_learn.lock().unwrap();
+                    let learned = learn.as_mut()
+                        .and_then(|learn| ext_midi_in_handler(ctx, learn, msg));
+                    if let Some(binding) = learned {
+                        let msg = format!("MIDI learn: {} bound to CC {}", binding.control, binding.cc);
+                        app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+                    }
+                }
+
+                // OSC
+                AppEvent::OscIn(osc) => {
+                    if let Some(server) = osc_server.lock().unwrap().as_ref() {
+                        osc_in_handler(ctx, server, osc);
+                    }
+                }
+
+                // other pod-ui instances
+                AppEvent::Remote(command) => {
+                    remote_handler(ctx, command);
+                }
+
+                // silently ignoring
+                AppEvent::MidiOut(_)  => { /* handled in MIDI OUT thread */ }
+                e if is_system_app_event(e) => {}
+
+                // error message
+                _ => {
+                    error!("Unhandled app event: {:?}", msg);
+                }
+            }
+        } else if !is_system_app_event(&msg) {
+            warn!("MIDI CC event {:?} without context", msg);
+        }
+
+        // execute system handlers
+        match &msg {
+            // device detected
+            AppEvent::DeviceDetected(event) => {
+                engine_event_tx.send_or_warn(EngineEvent::DeviceDetected(event.clone()));
+                api_context.set_detected(event);
+                if let Some(ctx) = &ctx {
+                    firmware_handler(&state, ctx, event);
+                }
+            }
+            // forward notification messages to the frontend
+            AppEvent::Notification(event) => {
+                engine_event_tx.send_or_warn(EngineEvent::Notification(event.msg.clone(), event.id.clone()));
+            }
+            // new config & shutdown
+            AppEvent::NewConfig(event) => {
+                if event.midi_changed {
+                    let was_offline = offline;
+                    offline = event.offline;
+                    if let Some(ctx) = &ctx {
+                        ctx.set_midi_channel(event.midi_channel);
+                        ctx.set_offline(offline);
+
+                        if was_offline && !offline && !event.config_changed {
+                            // Device connected after offline editing. Remember
+                            // the offline edits and load the programs from the
+                            // device to compare them against.
+                            let edits = OfflineEdits::capture(ctx);
+                            if !edits.is_empty() {
+                                offline_edits.replace(edits);
+                            }
+                            new_device_handler(ctx);
+                        }
+                    }
+                    engine_event_tx.send_or_warn(EngineEvent::NewMidiConnection);
+                }
+                if event.config_changed {
+                    // transfer Ctx ownership to the frontend and
+                    // ask it to initialize a new Ctx
+                    let mut ctx_share = ctx_share.lock().unwrap();
+                    *ctx_share = ctx.take();
+
+                    engine_event_tx.send_or_warn(EngineEvent::NewConfig);
+                }
+            }
+            AppEvent::NewCtx => {
+                trace!("New context installed...");
+                let mut ctx_share = ctx_share.lock().unwrap();
+                ctx.replace(ctx_share.take().unwrap());
+
+                let ctx = ctx.as_ref().unwrap();
+                // a firmware variant of the config installed after the device
+                // got connected keeps the offline edits, the programs are
+                // loaded again below
+                offline_edits = offline_edits.take().filter(|e| e.applies_to(ctx.config));
+                ctx.set_offline(offline);
+                api_context.set_device(ctx);
+
+                let mut learn = midi_learn.lock().unwrap();
+                if learn.as_ref().map(|l| l.config() != ctx.config).unwrap_or(true) {
+                    learn.replace(MidiLearn::load(ctx.config));
+                }
+                drop(learn);
+
+                let mut map = pc_map.lock().unwrap();
+                if map.as_ref().map(|m| m.config() != ctx.config).unwrap_or(true) {
+                    map.replace(PcMap::load(ctx.config));
+                }
+                drop(map);
+
+                if !offline {
+                    new_device_handler(ctx);
+                } else {
+                    // no programs to wait for
+                    for command in startup_commands.drain(..) {
+                        app_event_tx.send_or_warn(AppEvent::Remote(command));
+                    }
+                }
+            }
+            // device programs loaded after offline editing
+            AppEvent::BufferData(event) if offline_edits.is_some() => {
+                let Some(ctx) = &ctx else { continue };
+                if is_all_programs_loaded(ctx.config, event) {
+                    let edits = offline_edits.take().unwrap();
+                    let slots = edits.diff(&ctx.dump.lock().unwrap());
+                    if !slots.is_empty() {
+                        engine_event_tx.send_or_warn(EngineEvent::Sync(slots));
+                    }
+                }
+            }
+            // device programs loaded, so that startup commands do not
+            // get overwritten by the incoming program data
+            AppEvent::BufferData(event) if !startup_commands.is_empty() => {
+                let Some(ctx) = &ctx else { continue };
+                if is_all_programs_loaded(ctx.config, event) {
+                    for command in startup_commands.drain(..) {
+                        app_event_tx.send_or_warn(AppEvent::Remote(command));
+                    }
+                }
+            }
+            AppEvent::Shutdown => {
+                engine_event_tx.send_or_warn(EngineEvent::Shutdown);
+            }
+
+            // message conversion
+            // clock & real-time messages are only used for tempo sync
+            AppEvent::MidiIn(bytes) if MessageType::of(bytes) == Some(MessageType::Realtime) => {}
+            AppEvent::MidiIn(bytes) => {
+                match MidiMessage::from_bytes(bytes.clone()) {
+                    std::result::Result::Ok(msg) => app_event_tx.send_or_warn(AppEvent::MidiMsgIn(msg)),
+                    Err(e) => error!("{}", e)
+                }
+            }
+            AppEvent::ExtMidiIn(bytes) => {
+                // not all messages forwarded by MIDI thru are known
+                if let std::result::Result::Ok(msg) = MidiMessage::from_bytes(bytes.clone()) {
+                    app_event_tx.send_or_warn(AppEvent::ExtMidiMsgIn(msg));
+                }
+            }
+            AppEvent::MidiMsgOut(msg) => {
+                let bytes = MidiMessage::to_bytes(msg);
+                app_event_tx.send_or_warn(AppEvent::MidiOut(bytes));
+            }
+
+            // silently ignore everything else
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::controller::Controller;
+    use crate::dump::ProgramsDump;
+    use crate::edit::EditBuffer;
+    use crate::engine::*;
+    use crate::handler::Handler;
+
+    struct NullHandler;
+    impl Handler for NullHandler {}
+
+    #[tokio::test]
+    async fn system_events() {
+        let engine = Engine::new(Services::default());
+        let mut events = engine.subscribe();
+        // sent before start, still handled
+        engine.send(AppEvent::Notification(NotificationEvent::msg("hello".into())));
+        engine.start(vec![]);
+        engine.send(AppEvent::Shutdown);
+
+        match events.recv().await.unwrap() {
+            EngineEvent::Notification(msg, None) => assert_eq!(msg, "hello"),
+            e => panic!("Unexpected event {:?}", e)
+        }
+        assert!(matches!(events.recv().await.unwrap(), EngineEvent::Shutdown));
+    }
+
+    #[test]
+    fn untested_firmware_notification() {
+        let config: &'static Config = Box::leak(Box::new(Config {
+            name: "Test POD".into(),
+            family: 0x0000,
+            member: 0x0003,
+            tested_firmware: vec![FirmwareVersion::new(2, 0), FirmwareVersion::new(2, 30)],
+            ..Config::empty()
+        }));
+        let engine = Engine::new(Services::default());
+        let mut app_events = engine.sender().subscribe();
+        let edit = EditBuffer::new(config);
+        let ctx = Ctx {
+            config,
+            handler: Box::new(NullHandler),
+            controller: edit.controller(),
+            edit: Arc::new(Mutex::new(edit)),
+            dump: Arc::new(Mutex::new(ProgramsDump::new(config))),
+            reroute: BufferReroute::default(),
+            ui_controller: Arc::new(Mutex::new(Controller::new(Default::default()))),
+            app_event_tx: engine.sender()
+        };
+        let detected = |version: &str, member: u16| DeviceDetectedEvent {
+            name: "POD".into(), version: version.into(), family: 0x0000, member
+        };
+
+        // tested, unparsable and other device versions are not reported
+        for e in [detected("2.30", 0x0003), detected("bad", 0x0003), detected("9.00", 0x0006)] {
+            firmware_handler(&engine.state, &ctx, &e);
+        }
+        assert!(app_events.try_recv().is_err());
+
+        firmware_handler(&engine.state, &ctx, &detected("3.00", 0x0003));
+        match app_events.try_recv() {
+            std::result::Result::Ok(AppEvent::Notification(e)) =>
+                assert_eq!(e.msg, "Test POD firmware 3.00 is newer than any tested (newest tested: 2.30)"),
+            e => panic!("Unexpected event {:?}", e)
+        }
+        // the user is only told once
+        firmware_handler(&engine.state, &ctx, &detected("3.00", 0x0003));
+        assert!(app_events.try_recv().is_err());
+        firmware_handler(&engine.state, &ctx, &detected("1.04", 0x0003));
+        assert!(matches!(app_events.try_recv(), std::result::Result::Ok(AppEvent::Notification(_))));
+    }
+}
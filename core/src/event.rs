@@ -2,6 +2,8 @@ use std::fmt::Debug;
 use log::warn;
 use tokio::sync::broadcast;
 use crate::midi::MidiMessage;
+use crate::osc::OscMessage;
+use crate::remote::RemoteCommand;
 use crate::store::{Origin as StoreOrigin};
 
 #[derive(Clone, Debug, PartialEq)]
@@ -113,10 +115,39 @@ pub struct ModifiedEvent {
     pub modified: bool
 }
 
+/// Reorganize programs in the programs dump. All affected programs
+/// are marked modified, so that they can be stored to the device.
+#[derive(Clone, Debug)]
+pub enum ReorderEvent {
+    /// Swap two programs
+    Swap { a: usize, b: usize },
+    /// Move a program to a different slot, shifting the programs in between
+    Move { from: usize, to: usize },
+    /// Insert the edit buffer at a slot, shifting the programs after it
+    Insert { at: usize },
+    /// Copy programs to consecutive slots starting at `to`
+    Copy { from: Vec<usize>, to: usize },
+    /// Reset programs to the init program
+    Reset { programs: Vec<usize> },
+    /// Overwrite programs with imported (program index, program data)
+    Import { programs: Vec<(usize, Vec<u8>)> }
+}
+
+/// Step through the active setlist
+#[derive(Clone, Debug, PartialEq)]
+pub enum SetlistEvent {
+    Next,
+    Prev,
+    /// Go to the setlist entry with this index
+    Select(usize)
+}
+
 #[derive(Clone, Debug)]
 pub struct DeviceDetectedEvent {
     pub name: String,
-    pub version: String
+    pub version: String,
+    pub family: u16,
+    pub member: u16
 }
 
 #[derive(Clone, Debug)]
@@ -135,7 +166,9 @@ impl NotificationEvent {
 pub struct NewConfigEvent {
     pub midi_changed: bool,
     pub midi_channel: u8,
-    pub config_changed: bool
+    pub config_changed: bool,
+    /// No MIDI in/out connected
+    pub offline: bool
 }
 
 // -------------------------------------------------------------
@@ -147,6 +180,14 @@ pub enum AppEvent {
 
     MidiMsgIn(MidiMessage),
     MidiMsgOut(MidiMessage),
+    /// Raw MIDI data from the external controller input (MIDI thru)
+    ExtMidiIn(Vec<u8>),
+    /// MIDI message from the external controller input (MIDI learn)
+    ExtMidiMsgIn(MidiMessage),
+    /// Message received by the OSC server
+    OscIn(OscMessage),
+    /// Command forwarded from another pod-ui instance
+    Remote(RemoteCommand),
 
     ControlChange(ControlChangeEvent),
     ProgramChange(ProgramChangeEvent),
@@ -155,6 +196,8 @@ pub enum AppEvent {
     Copy(BufferCopyEvent),
     BufferData(BufferDataEvent),
     Modified(ModifiedEvent),
+    Reorder(ReorderEvent),
+    Setlist(SetlistEvent),
 
     DeviceDetected(DeviceDetectedEvent),
     NewConfig(NewConfigEvent),
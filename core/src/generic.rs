@@ -7,7 +7,6 @@ use crate::midi::{Channel, MidiMessage};
 use crate::model::{AbstractControl, DeviceFlags};
 use crate::{config, program};
 use crate::cc_values::*;
-use crate::dispatch::dispatch_buffer_set;
 
 fn update_edit_buffer(ctx: &Ctx, event: &ControlChangeEvent) {
     let controller = &ctx.controller.lock().unwrap();
@@ -356,7 +355,7 @@ pub fn store_handler(ctx: &Ctx, event: &BufferStoreEvent) -> bool {
 }
 
 pub fn copy_handler(ctx: &Ctx, event: &BufferCopyEvent) {
-    dispatch_buffer_set(event.from.clone(), event.to.clone());
+    ctx.reroute.set(event.from.clone(), event.to.clone());
     let e = BufferStoreEvent {
         buffer: event.from.clone(),
         origin: UI
@@ -364,6 +363,67 @@ pub fn copy_handler(ctx: &Ctx, event: &BufferCopyEvent) {
     ctx.app_event_tx.send_or_warn(AppEvent::Store(e));
 }
 
+pub fn reorder_handler(ctx: &Ctx, event: &ReorderEvent) {
+    let current = num_program(&ctx.program());
+    let pages = {
+        let mut edit = ctx.edit.lock().unwrap();
+        let mut dump = ctx.dump.lock().unwrap();
+
+        // bring the edit buffer changes into the current program first,
+        // so that they move along with it
+        let edit_data = program::store_patch_dump_ctrl(&edit);
+        if let Some(page) = current {
+            if edit.modified() {
+                program::load_patch_dump(&mut dump, page, edit_data.as_slice(), UI);
+                dump.set_modified(page, true);
+            }
+        }
+
+        let pages = match event {
+            ReorderEvent::Swap { a, b } => dump.swap(*a, *b),
+            ReorderEvent::Move { from, to } => dump.move_program(*from, *to),
+            ReorderEvent::Insert { at } => {
+                dump.insert(*at, edit_data.as_slice()).unwrap_or_else(|err| {
+                    let msg = format!("Cannot insert: {}", err);
+                    ctx.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+                    vec![]
+                })
+            }
+            ReorderEvent::Copy { from, to } => dump.copy_programs(from, *to),
+            ReorderEvent::Reset { programs } => {
+                dump.fill(programs, program::init_program(ctx.config, ctx.handler.as_ref()).as_slice())
+            }
+            ReorderEvent::Import { programs } => dump.import(programs),
+        };
+
+        // the current program slot now holds a different program, load it
+        // into the edit buffer
+        if let Some(page) = current.filter(|p| pages.contains(p)) {
+            let value_fn = |controller: &mut Controller, name: &str, buffer: &[u8]|
+                ctx.handler.control_value_from_buffer(controller, name, buffer);
+            program::load_patch_dump_ctrl(&mut edit, dump.data(page).unwrap(), value_fn);
+            edit.set_modified(true);
+        }
+
+        pages
+    };
+
+    for page in pages.iter() {
+        let e = ModifiedEvent {
+            buffer: Buffer::Program(*page),
+            origin: UI,
+            modified: true
+        };
+        ctx.app_event_tx.send_or_warn(AppEvent::Modified(e));
+    }
+
+    // make the device play what the edit buffer now holds
+    if current.filter(|p| pages.contains(p)).is_some() {
+        let e = BufferStoreEvent { buffer: Buffer::EditBuffer, origin: UI };
+        ctx.app_event_tx.send_or_warn(AppEvent::Store(e));
+    }
+}
+
 pub fn buffer_handler(ctx: &Ctx, event: &BufferDataEvent) {
     let value_fn = |controller: &mut Controller, name: &str, buffer: &[u8]| {
         ctx.handler.control_value_from_buffer(controller, name, buffer)
@@ -528,7 +588,7 @@ pub fn midi_udi_handler(ctx: &Ctx, midi_message: &MidiMessage) {
                 .map(|c| c.name.clone())
                 .unwrap_or_else(|| format!("Unknown ({:04x}:{:04x})", family, member));
 
-            let e = DeviceDetectedEvent { name, version };
+            let e = DeviceDetectedEvent { name, version, family: *family, member: *member };
             ctx.app_event_tx.send_or_warn(AppEvent::DeviceDetected(e));
         }
         _ => {}
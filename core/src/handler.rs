@@ -4,6 +4,8 @@ use crate::controller::Controller;
 use crate::event::*;
 use crate::generic;
 use crate::midi::MidiMessage;
+use crate::model::Config;
+use crate::rules::Rules;
 
 /// The `Handler` trait is to be implemented by all device modules.
 pub trait Handler {
@@ -58,6 +60,11 @@ pub trait Handler {
         generic::buffer_modified_handler(ctx, event, rerouted);
     }
 
+    /// Handler for program reorder requests (UI)
+    fn reorder_handler(&self, ctx: &Ctx, event: &ReorderEvent) {
+        generic::reorder_handler(ctx, event)
+    }
+
     /// Handler for program "modified" status changes
     fn modified_handler(&self, ctx: &Ctx, event: &ModifiedEvent) {
         generic::modified_handler(ctx, event)
@@ -89,6 +96,11 @@ pub trait Handler {
     /// Handler for custom markers that this handler sent to itself
     fn marker_handler(&self, ctx: &Ctx, marker: u32) {}
 
+    /// Parameter rules to be installed into the edit buffer `Controller`
+    fn rules(&self, config: &'static Config) -> Rules {
+        Rules::new()
+    }
+
     fn control_value_from_buffer(&self, controller: &mut Controller, name: &str, buffer: &[u8]) {}
     fn control_value_to_buffer(&self, controller: &Controller, name: &str, buffer: &mut [u8]) {}
 }
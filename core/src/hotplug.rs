@@ -0,0 +1,197 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures_util::FutureExt;
+use log::*;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::sync::oneshot;
+use tokio::time::sleep;
+use crate::engine::{midi_in_out_start, midi_in_out_stop, set_midi_in_out, State};
+use crate::event::*;
+use crate::midi_io::*;
+use crate::model::MidiQuirks;
+use crate::next_thread_id;
+
+const HOTPLUG_POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// ALSA port address suffix, such as " 24:0"
+static PORT_ADDR_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s+\d+:\d+$").unwrap());
+
+/// The device MIDI connection lost when its ports disappeared, such
+/// as when the USB cable is unplugged
+#[derive(Clone, Debug)]
+pub struct LostConnection {
+    pub midi_in: String,
+    pub midi_out: String,
+    pub midi_channel: u8
+}
+
+/// Port name without the ALSA client:port address, which may change
+/// when the device is plugged back in
+pub fn port_base_name(name: &str) -> &str {
+    match PORT_ADDR_RE.find(name) {
+        Some(m) => &name[.. m.start()],
+        None => name
+    }
+}
+
+/// Find the port that `name` refers to, allowing for a changed address
+fn find_port(ports: &[String], name: &str) -> Option<String> {
+    ports.iter().find(|p| *p == name)
+        .or_else(|| ports.iter().find(|p| port_base_name(p) == port_base_name(name)))
+        .cloned()
+}
+
+fn notify(state: &State, msg: String) {
+    state.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+}
+
+/// Take the device offline if its MIDI ports are gone. Returns `true`
+/// if the device was disconnected.
+async fn check_disconnect(state: &Arc<Mutex<State>>, in_ports: &[String], out_ports: &[String]) -> bool {
+    let (handle, lost) = {
+        let mut state = state.lock().unwrap();
+        // only a running MIDI connection can be lost
+        if state.midi_in_cancel.is_none() || state.midi_out_cancel.is_none() {
+            return false;
+        }
+        let (Some(midi_in), Some(midi_out)) = (state.midi_in_name.clone(), state.midi_out_name.clone()) else {
+            return false;
+        };
+        if in_ports.contains(&midi_in) && out_ports.contains(&midi_out) {
+            return false;
+        }
+
+        warn!("MIDI ports {:?} / {:?} disappeared, device disconnected", midi_in, midi_out);
+        let lost = LostConnection { midi_in, midi_out, midi_channel: state.midi_channel_num };
+        (midi_in_out_stop(&mut state), lost)
+    };
+    handle.await;
+
+    let mut state = state.lock().unwrap();
+    let quirks = state.config.map(|c| c.midi_quirks)
+        .unwrap_or_else(MidiQuirks::empty);
+    midi_in_out_start(&mut state, None, None, lost.midi_channel, quirks, false);
+    notify(&state, "Device disconnected, editing offline until it is back".into());
+    state.lost.replace(lost);
+    true
+}
+
+/// Reopen the lost device MIDI connection once its ports are back
+fn check_reconnect(state: &Arc<Mutex<State>>, in_ports: &[String], out_ports: &[String]) {
+    let mut state = state.lock().unwrap();
+    let Some(lost) = state.lost.clone() else { return };
+    let (Some(midi_in), Some(midi_out)) = (find_port(in_ports, &lost.midi_in), find_port(out_ports, &lost.midi_out)) else {
+        return;
+    };
+
+    let ports = MidiIn::new_for_name(&midi_in)
+        .and_then(|midi_in| Ok((midi_in, MidiOut::new_for_name(&midi_out)?)));
+    let (midi_in, midi_out) = match ports {
+        std::result::Result::Ok(ports) => ports,
+        Err(err) => {
+            // the ports may not be ready yet, try again later
+            warn!("Failed to reopen MIDI ports: {}", err);
+            return;
+        }
+    };
+
+    info!("MIDI ports {:?} / {:?} are back, reconnecting", midi_in.name, midi_out.name);
+    // same config, so that the engine treats this as the device coming
+    // back after offline editing: it reloads the programs and offers to
+    // sync the edits made in the meantime
+    let config = state.config;
+    set_midi_in_out(&mut state, Some(midi_in), Some(midi_out), lost.midi_channel, config);
+    notify(&state, "Device reconnected".into());
+}
+
+/// Watch the device MIDI ports, taking a device offline when its ports
+/// disappear and reconnecting it when they come back. Ports are polled,
+/// since not all MIDI backends announce port changes. The watch is shared
+/// by the engines of all sessions, so that the ports are listed once per
+/// poll for all the devices.
+#[derive(Clone, Default)]
+pub struct HotplugWatch {
+    inner: Arc<Mutex<Watched>>
+}
+
+#[derive(Default)]
+struct Watched {
+    states: Vec<Arc<Mutex<State>>>,
+    cancel: Option<oneshot::Sender<()>>
+}
+
+impl HotplugWatch {
+    /// Watch the MIDI connection of `state`, starting the watch thread
+    /// with the first one
+    pub fn add(&self, state: Arc<Mutex<State>>) {
+        let mut watched = self.inner.lock().unwrap();
+        watched.states.push(state);
+        if watched.cancel.is_none() {
+            watched.cancel = Some(self.start());
+        }
+    }
+
+    /// Stop watching the MIDI connection of `state`, stopping the watch
+    /// thread with the last one
+    pub fn remove(&self, state: &Arc<Mutex<State>>) {
+        let mut watched = self.inner.lock().unwrap();
+        watched.states.retain(|s| !Arc::ptr_eq(s, state));
+        if watched.states.is_empty() {
+            watched.cancel.take().map(|cancel| cancel.send(()));
+        }
+    }
+
+    fn start(&self) -> oneshot::Sender<()> {
+        let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+        let mut cancel_rx = cancel_rx.fuse();
+        let inner = self.inner.clone();
+
+        tokio::spawn(async move {
+            let id = next_thread_id();
+            info!("MIDI hotplug thread {:?} start", id);
+            loop {
+                tokio::select! {
+                    _ = sleep(HOTPLUG_POLL_INTERVAL) => {}
+                    _ = &mut cancel_rx => { break; }
+                }
+
+                let ports = MidiIn::ports()
+                    .and_then(|in_ports| Ok((in_ports, MidiOut::ports()?)));
+                let (in_ports, out_ports) = match ports {
+                    std::result::Result::Ok(ports) => ports,
+                    Err(err) => {
+                        debug!("Failed to list MIDI ports: {}", err);
+                        continue;
+                    }
+                };
+
+                let states = inner.lock().unwrap().states.clone();
+                for state in states.iter() {
+                    if !check_disconnect(state, &in_ports, &out_ports).await {
+                        check_reconnect(state, &in_ports, &out_ports);
+                    }
+                }
+            }
+            info!("MIDI hotplug thread {:?} finish", id);
+        });
+
+        cancel_tx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hotplug::*;
+
+    #[test]
+    fn port_names() {
+        assert_eq!(port_base_name("PODxt:PODxt MIDI 1 24:0"), "PODxt:PODxt MIDI 1");
+        assert_eq!(port_base_name("USB MIDI Interface"), "USB MIDI Interface");
+
+        let ports = vec!["Midi Through:Midi Through Port-0 14:0".to_string(),
+                         "PODxt:PODxt MIDI 1 28:0".to_string()];
+        assert_eq!(find_port(&ports, "PODxt:PODxt MIDI 1 24:0"), Some(ports[1].clone()));
+        assert_eq!(find_port(&ports, "Pocket POD 20:0"), None);
+    }
+}
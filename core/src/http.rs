@@ -0,0 +1,346 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::IpAddr;
+use anyhow::*;
+use crate::json::Json;
+
+/// Largest request body accepted
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// A minimal HTTP/1.1 request: no chunked bodies, no keep-alive
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
+
+impl Request {
+    pub fn read<R: Read>(reader: R) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            bail!("Invalid request line {:?}", line.trim());
+        };
+        let method = method.to_string();
+        // query strings are not used by the API
+        let path = target.split('?').next().unwrap_or_default().to_string();
+
+        let mut headers = vec![];
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                bail!("Unexpected end of request headers");
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                bail!("Invalid header {:?}", line);
+            };
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+
+        let mut request = Request { method, path, headers, body: vec![] };
+        let len = request.header("content-length")
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(0);
+        if len > MAX_BODY_SIZE {
+            bail!("Request body too large: {} bytes", len);
+        }
+        request.body.resize(len, 0);
+        reader.read_exact(&mut request.body)?;
+        Ok(request)
+    }
+
+    /// Header value by (case-insensitive) name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Path segments, without the empty leading one
+    pub fn segments(&self) -> Vec<&str> {
+        self.path.split('/').filter(|s| !s.is_empty()).collect()
+    }
+
+    /// Request body parsed as JSON, `Json::Null` if empty
+    pub fn json(&self) -> Result<Json> {
+        let body = std::str::from_utf8(&self.body)
+            .map_err(|_| anyhow!("Request body is not UTF-8"))?;
+        if body.trim().is_empty() {
+            return Ok(Json::Null);
+        }
+        Json::parse(body)
+    }
+
+    pub fn is_websocket_upgrade(&self) -> bool {
+        self.header("upgrade").map(|v| v.eq_ignore_ascii_case("websocket")).unwrap_or(false) &&
+            self.header("sec-websocket-key").is_some()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>
+}
+
+impl Response {
+    pub fn json(status: u16, json: &Json) -> Self {
+        Response { status, content_type: "application/json", body: json.to_string().into_bytes() }
+    }
+
+    pub fn error(status: u16, msg: &str) -> Self {
+        Self::json(status, &Json::object().with("error", msg))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            202 => "Accepted",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            415 => "Unsupported Media Type",
+            503 => "Service Unavailable",
+            _ => ""
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        write!(writer, "HTTP/1.1 {} {}\r\n", self.status, self.reason())?;
+        write!(writer, "Content-Type: {}\r\n", self.content_type)?;
+        write!(writer, "Content-Length: {}\r\n", self.body.len())?;
+        write!(writer, "Connection: close\r\n\r\n")?;
+        writer.write_all(&self.body)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Host name of a `Host` header value or an `Origin` URL, without the
+/// scheme and port: "http://[::1]:8080" -> "::1"
+pub fn host_name(value: &str) -> &str {
+    let authority = value.split_once("://").map(|(_, a)| a).unwrap_or(value);
+    let authority = authority.split('/').next().unwrap_or_default();
+    if let Some(rest) = authority.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or_default();
+    }
+    authority.split(':').next().unwrap_or_default()
+}
+
+/// Whether `host` (as returned by `host_name`) is "localhost" or a
+/// loopback address
+pub fn is_loopback_host(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost") ||
+        host.parse::<IpAddr>().map(|ip| ip.is_loopback()).unwrap_or(false)
+}
+
+/// Complete a WebSocket upgrade started by `request`
+pub fn write_websocket_handshake<W: Write>(mut writer: W, request: &Request) -> Result<()> {
+    let Some(key) = request.header("sec-websocket-key") else {
+        bail!("Not a WebSocket upgrade request");
+    };
+    write!(writer, "HTTP/1.1 101 Switching Protocols\r\n")?;
+    write!(writer, "Upgrade: websocket\r\nConnection: Upgrade\r\n")?;
+    write!(writer, "Sec-WebSocket-Accept: {}\r\n\r\n", websocket_accept(key))?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn websocket_accept(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WsFrame {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close
+}
+
+impl WsFrame {
+    /// Read a (client, masked) frame. Fragmented messages are not supported.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self> {
+        let mut header = [0u8; 2];
+        reader.read_exact(&mut header)?;
+        let opcode = header[0] & 0x0f;
+        let masked = header[1] & 0x80 != 0;
+        let len = match header[1] & 0x7f {
+            126 => {
+                let mut buf = [0u8; 2];
+                reader.read_exact(&mut buf)?;
+                u16::from_be_bytes(buf) as usize
+            }
+            127 => {
+                let mut buf = [0u8; 8];
+                reader.read_exact(&mut buf)?;
+                u64::from_be_bytes(buf) as usize
+            }
+            len => len as usize
+        };
+        if len > MAX_BODY_SIZE {
+            bail!("WebSocket frame too large: {} bytes", len);
+        }
+        let mut mask = [0u8; 4];
+        if masked {
+            reader.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload)?;
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+
+        let frame = match opcode {
+            0x1 => WsFrame::Text(String::from_utf8_lossy(&payload).to_string()),
+            0x2 => WsFrame::Binary(payload),
+            0x8 => WsFrame::Close,
+            0x9 => WsFrame::Ping(payload),
+            0xa => WsFrame::Pong(payload),
+            op => bail!("Unsupported WebSocket opcode {:#x}", op)
+        };
+        Ok(frame)
+    }
+
+    /// Write an (unmasked, server) frame
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        let (opcode, payload) = match self {
+            WsFrame::Text(text) => (0x1, text.as_bytes()),
+            WsFrame::Binary(data) => (0x2, data.as_slice()),
+            WsFrame::Close => (0x8, &[][..]),
+            WsFrame::Ping(data) => (0x9, data.as_slice()),
+            WsFrame::Pong(data) => (0xa, data.as_slice())
+        };
+        let mut buf = vec![0x80 | opcode];
+        match payload.len() {
+            len if len < 126 => buf.push(len as u8),
+            len if len <= u16::MAX as usize => {
+                buf.push(126);
+                buf.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                buf.push(127);
+                buf.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        buf.extend_from_slice(payload);
+        writer.write_all(&buf)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0 .. 16 {
+            w[i] = u32::from_be_bytes([chunk[i * 4], chunk[i * 4 + 1], chunk[i * 4 + 2], chunk[i * 4 + 3]]);
+        }
+        for i in 16 .. 80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0 ..= 19 => ((b & c) | (!b & d), 0x5a827999),
+                20 ..= 39 => (b ^ c ^ d, 0x6ed9eba1),
+                40 ..= 59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6)
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0u8; 20];
+    for (i, v) in h.iter().enumerate() {
+        out[i * 4 .. i * 4 + 4].copy_from_slice(&v.to_be_bytes());
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0 .. 4 {
+            if i <= chunk.len() {
+                out.push(CHARS[(n >> (18 - i * 6)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::http::*;
+
+    #[test]
+    fn request_and_websocket() {
+        let raw = "PUT /api/controls/drive?x=1 HTTP/1.1\r\nHost: localhost\r\nContent-Length: 13\r\n\r\n{\"value\": 64}";
+        let request = Request::read(Cursor::new(raw)).unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.segments(), vec!["api", "controls", "drive"]);
+        assert_eq!(request.header("Host"), Some("localhost"));
+        assert_eq!(request.json().unwrap().get("value").and_then(|v| v.as_f64()), Some(64.0));
+        assert!(!request.is_websocket_upgrade());
+
+        // RFC 6455 example
+        assert_eq!(websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(base64(b"ab"), "YWI=");
+
+        // masked "Hello" from RFC 6455
+        let frame = [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+        assert_eq!(WsFrame::read(Cursor::new(frame)).unwrap(), WsFrame::Text("Hello".into()));
+        let mut buf = vec![];
+        WsFrame::Text("Hello".into()).write(&mut buf).unwrap();
+        assert_eq!(buf, [0x81, 0x05, b'H', b'e', b'l', b'l', b'o']);
+    }
+
+    #[test]
+    fn hosts() {
+        assert_eq!(host_name("localhost:8080"), "localhost");
+        assert_eq!(host_name("127.0.0.1"), "127.0.0.1");
+        assert_eq!(host_name("http://[::1]:8080"), "::1");
+        assert_eq!(host_name("https://example.com/x"), "example.com");
+        assert!(is_loopback_host("localhost"));
+        assert!(is_loopback_host("127.0.0.2"));
+        assert!(is_loopback_host("::1"));
+        assert!(!is_loopback_host("example.com"));
+        assert!(!is_loopback_host("null"));
+        assert!(!is_loopback_host("0.0.0.0"));
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt::{Display, Formatter, Write};
+use anyhow::*;
+
+/// Maximum array/object nesting depth accepted by `Json::parse`
+const MAX_DEPTH: usize = 64;
+
+/// A JSON value, just enough for the HTTP API. Object keys keep their
+/// insertion order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+impl Json {
+    /// An empty object, to be filled with `with()`
+    pub fn object() -> Self {
+        Json::Object(vec![])
+    }
+
+    /// Add a key to an object
+    pub fn with<V: Into<Json>>(mut self, key: &str, value: V) -> Self {
+        if let Json::Object(entries) = &mut self {
+            entries.push((key.to_string(), value.into()));
+        }
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(v) => Some(*v),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(v) => Some(v.as_str()),
+            _ => None
+        }
+    }
+
+    pub fn parse(str: &str) -> Result<Self> {
+        let mut parser = Parser { bytes: str.as_bytes(), pos: 0, depth: 0 };
+        let value = parser.value()?;
+        parser.skip_ws();
+        if parser.pos != parser.bytes.len() {
+            bail!("Trailing characters at {}", parser.pos);
+        }
+        Ok(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(v: bool) -> Self { Json::Bool(v) }
+}
+
+impl From<f64> for Json {
+    fn from(v: f64) -> Self { Json::Number(v) }
+}
+
+macro_rules! json_from_int {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Json {
+            fn from(v: $t) -> Self { Json::Number(v as f64) }
+        })*
+    }
+}
+json_from_int!(u8, u16, u32, usize, i32);
+
+impl From<&str> for Json {
+    fn from(v: &str) -> Self { Json::String(v.to_string()) }
+}
+
+impl From<String> for Json {
+    fn from(v: String) -> Self { Json::String(v) }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(v: Option<T>) -> Self {
+        v.map(|v| v.into()).unwrap_or(Json::Null)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(v: Vec<T>) -> Self {
+        Json::Array(v.into_iter().map(|v| v.into()).collect())
+    }
+}
+
+fn write_str(f: &mut Formatter<'_>, str: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in str.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?
+        }
+    }
+    f.write_char('"')
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(v) => write!(f, "{}", v),
+            Json::Number(v) if !v.is_finite() => f.write_str("null"),
+            Json::Number(v) => write!(f, "{}", v),
+            Json::String(v) => write_str(f, v),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 { f.write_char(',')?; }
+                    write!(f, "{}", v)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(entries) => {
+                f.write_char('{')?;
+                for (i, (k, v)) in entries.iter().enumerate() {
+                    if i > 0 { f.write_char(',')?; }
+                    write_str(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Current array/object nesting depth
+    depth: usize
+}
+
+impl<'a> Parser<'a> {
+    fn skip_ws(&mut self) {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_ws();
+        self.bytes.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        if self.peek() != Some(c) {
+            bail!("Expected {:?} at {}", c as char, self.pos);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, str: &str, value: Json) -> Result<Json> {
+        if !self.bytes[self.pos ..].starts_with(str.as_bytes()) {
+            bail!("Unexpected token at {}", self.pos);
+        }
+        self.pos += str.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json> {
+        match self.peek() {
+            None => bail!("Unexpected end of input"),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'{') => self.nested(Self::object),
+            Some(_) => self.number()
+        }
+    }
+
+    /// Parse an array or object with `f`, limiting the nesting depth so
+    /// that deeply nested input cannot overflow the stack
+    fn nested(&mut self, f: fn(&mut Self) -> Result<Json>) -> Result<Json> {
+        if self.depth >= MAX_DEPTH {
+            bail!("Nesting deeper than {} at {}", MAX_DEPTH, self.pos);
+        }
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn array(&mut self) -> Result<Json> {
+        self.expect(b'[')?;
+        let mut values = vec![];
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                _ => break
+            }
+        }
+        self.expect(b']')?;
+        Ok(Json::Array(values))
+    }
+
+    fn object(&mut self) -> Result<Json> {
+        self.expect(b'{')?;
+        let mut entries = vec![];
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(entries));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                bail!("Expected object key at {}", self.pos);
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            entries.push((key, self.value()?));
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                _ => break
+            }
+        }
+        self.expect(b'}')?;
+        Ok(Json::Object(entries))
+    }
+
+    fn number(&mut self) -> Result<Json> {
+        let start = self.pos;
+        while self.pos < self.bytes.len() &&
+            matches!(self.bytes[self.pos], b'0' ..= b'9' | b'-' | b'+' | b'.' | b'e' | b'E') {
+            self.pos += 1;
+        }
+        let str = std::str::from_utf8(&self.bytes[start .. self.pos]).unwrap_or_default();
+        str.parse::<f64>()
+            .map(Json::Number)
+            .map_err(|_| anyhow!("Invalid number at {}", start))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut bytes = vec![];
+        loop {
+            let Some(c) = self.bytes.get(self.pos).cloned() else {
+                bail!("Unterminated string");
+            };
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let Some(c) = self.bytes.get(self.pos).cloned() else {
+                        bail!("Unterminated string");
+                    };
+                    self.pos += 1;
+                    let c = match c {
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let hex = self.bytes.get(self.pos .. self.pos + 4)
+                                .and_then(|h| std::str::from_utf8(h).ok())
+                                .and_then(|h| u32::from_str_radix(h, 16).ok());
+                            self.pos += 4;
+                            // surrogate pairs are not supported
+                            hex.and_then(char::from_u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        c => c as char
+                    };
+                    let mut buf = [0u8; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                c => bytes.push(c)
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| anyhow!("Invalid UTF-8 in string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::*;
+
+    #[test]
+    fn round_trip() {
+        let json = Json::object()
+            .with("name", "Drive \"hot\"\n")
+            .with("value", 64u16)
+            .with("norm", 0.5)
+            .with("labels", vec!["a", "b"])
+            .with("none", Option::<u8>::None)
+            .with("on", true);
+        let str = json.to_string();
+        assert_eq!(str, r#"{"name":"Drive \"hot\"\n","value":64,"norm":0.5,"labels":["a","b"],"none":null,"on":true}"#);
+        assert_eq!(Json::parse(&str).unwrap(), json);
+
+        let json = Json::parse(r#" { "value" : -1.5e1, "a": [ ], "s": "é" } "#).unwrap();
+        assert_eq!(json.get("value").and_then(|v| v.as_f64()), Some(-15.0));
+        assert_eq!(json.get("s").and_then(|v| v.as_str()), Some("é"));
+        assert!(Json::parse("{\"a\": 1,}").is_err());
+        assert!(Json::parse("[1] 2").is_err());
+    }
+
+    #[test]
+    fn nesting_depth() {
+        let nested = |n: usize| format!("{}{}", "[".repeat(n), "]".repeat(n));
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Json::parse(&"[".repeat(60000)).is_err());
+        assert!(Json::parse(&"{\"a\":".repeat(60000)).is_err());
+    }
+}
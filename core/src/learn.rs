@@ -0,0 +1,229 @@
+use std::path::PathBuf;
+use anyhow::*;
+use log::*;
+use crate::context::Ctx;
+use crate::controller::*;
+use crate::convert::select_labels;
+use crate::midi::{Channel, MidiMessage};
+use crate::model::{AbstractControl, Config, Control};
+use crate::persist::*;
+
+/// A binding of an external MIDI controller's CC to a control
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binding {
+    pub control: String,
+    /// MIDI channel, `Channel::all()` matches any channel
+    pub channel: u8,
+    pub cc: u8,
+    /// Incoming values 0..127 are scaled into `from` ..= `to` (MIDI
+    /// value range of the control). `from` may be larger than `to`.
+    pub from: u8,
+    pub to: u8,
+    pub invert: bool
+}
+
+impl Binding {
+    pub fn new(config: &Config, control: &str, channel: u8, cc: u8) -> Self {
+        // For selects with a known number of entries, limit the range so
+        // that the whole controller travel maps onto valid values
+        let to = select_labels(config).get(control)
+            .map(|labels| labels.len().saturating_sub(1).min(127) as u8)
+            .unwrap_or(127);
+        Binding { control: control.to_string(), channel, cc, from: 0, to, invert: false }
+    }
+
+    pub fn matches(&self, channel: u8, cc: u8) -> bool {
+        self.cc == cc && (self.channel == Channel::all() || self.channel == channel)
+    }
+
+    /// Scale an incoming MIDI value into the binding's range
+    pub fn scale(&self, value: u8) -> u8 {
+        let value = value.min(127) as f64 / 127.0;
+        let value = if self.invert { 1.0 - value } else { value };
+        let (from, to) = (self.from as f64, self.to as f64);
+        (from + (to - from) * value).round() as u8
+    }
+}
+
+/// MIDI learn bindings for a device model. While learning, the next CC
+/// received from the external controller gets bound to the control
+/// being learned.
+#[derive(Clone, Debug)]
+pub struct MidiLearn {
+    config: &'static Config,
+    pub bindings: Vec<Binding>,
+    learning: Option<String>
+}
+
+impl MidiLearn {
+    pub fn new(config: &'static Config) -> Self {
+        MidiLearn { config, bindings: vec![], learning: None }
+    }
+
+    pub fn file_path(config: &Config) -> Result<PathBuf> {
+        state_file(&format!("learn-{}.ini", slug(&config.name)))
+    }
+
+    /// Load the bindings saved for `config`. Failures are logged and
+    /// result in no bindings.
+    pub fn load(config: &'static Config) -> Self {
+        let mut learn = Self::new(config);
+        let doc = Self::file_path(config)
+            .and_then(|path| Document::load_if_exists(&path));
+        let doc = match doc {
+            std::result::Result::Ok(Some(doc)) => doc,
+            std::result::Result::Ok(None) => return learn,
+            Err(err) => {
+                error!("Failed to load MIDI learn bindings: {}", err);
+                return learn;
+            }
+        };
+
+        for (control, section) in doc.sections_with_prefix("control") {
+            if !config.controls.contains_key(control) {
+                warn!("MIDI learn: ignoring unknown control {:?}", control);
+                continue;
+            }
+            let Some(cc) = section.get_parsed::<u8>("cc") else {
+                warn!("MIDI learn: no CC for control {:?}", control);
+                continue;
+            };
+            learn.bindings.push(Binding {
+                control: control.to_string(),
+                channel: section.get_parsed("channel").unwrap_or(Channel::all()),
+                cc,
+                from: section.get_parsed("from").unwrap_or(0),
+                to: section.get_parsed("to").unwrap_or(127),
+                invert: section.get_bool("invert").unwrap_or(false)
+            });
+        }
+        learn
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut doc = Document::new();
+        for b in self.bindings.iter() {
+            let section = doc.section_mut(&format!("control {}", b.control));
+            section.set("channel", b.channel);
+            section.set("cc", b.cc);
+            section.set("from", b.from);
+            section.set("to", b.to);
+            section.set_bool("invert", b.invert);
+        }
+        doc.save(&Self::file_path(self.config)?)
+    }
+
+    pub fn config(&self) -> &'static Config {
+        self.config
+    }
+
+    /// Start learning a binding for `control`, or stop learning if `None`
+    pub fn learn(&mut self, control: Option<String>) {
+        self.learning = control;
+    }
+
+    pub fn learning(&self) -> Option<&str> {
+        self.learning.as_deref()
+    }
+
+    pub fn binding(&self, control: &str) -> Option<&Binding> {
+        self.bindings.iter().find(|b| b.control == control)
+    }
+
+    /// Add a binding, replacing the existing binding for the same control
+    pub fn bind(&mut self, binding: Binding) {
+        self.unbind(&binding.control);
+        self.bindings.push(binding);
+        self.bindings.sort_by(|a, b| a.control.cmp(&b.control));
+    }
+
+    pub fn unbind(&mut self, control: &str) {
+        self.bindings.retain(|b| b.control != control);
+    }
+
+    /// Returns `true` if `msg` is taken by MIDI learn: a binding is being
+    /// learned or an existing binding matches the message
+    pub fn handles(&self, msg: &MidiMessage) -> bool {
+        let MidiMessage::ControlChange { channel, control: cc, .. } = msg else {
+            return false;
+        };
+        self.learning.is_some() || self.bindings.iter().any(|b| b.matches(*channel, *cc))
+    }
+
+    /// Handle a MIDI message from the external controller: either complete
+    /// learning or return the (control name, MIDI value) pairs to set
+    fn handle(&mut self, msg: &MidiMessage) -> (Option<Binding>, Vec<(String, u8)>) {
+        let MidiMessage::ControlChange { channel, control: cc, value } = msg else {
+            return (None, vec![]);
+        };
+
+        if let Some(control) = self.learning.take() {
+            let binding = Binding::new(self.config, &control, *channel, *cc);
+            self.bind(binding.clone());
+            return (Some(binding), vec![]);
+        }
+
+        let values = self.bindings.iter()
+            .filter(|b| b.matches(*channel, *cc))
+            .map(|b| (b.control.clone(), b.scale(*value)))
+            .collect();
+        (None, values)
+    }
+}
+
+/// Handler for MIDI messages from the external controller. Bound controls
+/// are set with the UI origin, so that the values are sent to the device.
+/// Returns the new binding if learning completed.
+pub fn ext_midi_in_handler(ctx: &Ctx, learn: &mut MidiLearn, msg: &MidiMessage) -> Option<Binding> {
+    if learn.config != ctx.config {
+        // bindings for a different device
+        return None;
+    }
+
+    let (learned, values) = learn.handle(msg);
+    if let Some(binding) = &learned {
+        info!("MIDI learn: {:?} bound to CC {} on channel {}",
+            binding.control, binding.cc, binding.channel + 1);
+        learn.save().unwrap_or_else(|err| error!("Failed to save MIDI learn bindings: {}", err));
+    }
+
+    for (name, value) in values {
+        let Some(control) = ctx.controller.get_config(&name) else {
+            warn!("MIDI learn: control {:?} not found", name);
+            continue;
+        };
+        let value = match control {
+            // virtual controls take their values as-is
+            Control::VirtualSelect(_) => value as u16,
+            c => c.value_from_midi(value)
+        };
+        ctx.controller.set(&name, value, StoreOrigin::UI);
+    }
+
+    learned
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::learn::*;
+
+    #[test]
+    fn binding_scale() {
+        let mut b = Binding {
+            control: "drive".into(), channel: Channel::all(), cc: 7,
+            from: 0, to: 127, invert: false
+        };
+        assert_eq!(b.scale(0), 0);
+        assert_eq!(b.scale(127), 127);
+        assert!(b.matches(3, 7) && !b.matches(3, 8));
+
+        b.from = 10;
+        b.to = 20;
+        b.invert = true;
+        assert_eq!(b.scale(0), 20);
+        assert_eq!(b.scale(127), 10);
+
+        b.channel = 2;
+        assert!(b.matches(2, 7) && !b.matches(3, 7));
+    }
+}
@@ -1,6 +1,6 @@
 pub mod midi;
 mod util;
-pub use util::{def, is_valid_char, program_id_string};
+pub use util::{def, is_valid_char, next_thread_id, program_id_string};
 
 pub mod store;
 pub mod model;
@@ -21,3 +21,25 @@ pub mod context;
 pub mod handler;
 pub mod dispatch;
 pub mod cc_values;
+pub mod persist;
+pub mod session;
+pub mod offline;
+pub mod convert;
+pub mod bank;
+pub mod profile;
+pub mod learn;
+pub mod thru;
+pub mod pcmap;
+pub mod setlist;
+pub mod osc;
+pub mod json;
+pub mod http;
+pub mod api;
+pub mod remote;
+pub mod clock;
+pub mod rules;
+pub mod engine;
+pub mod device;
+pub mod hotplug;
+pub mod monitor;
+pub mod console;
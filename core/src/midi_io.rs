@@ -379,8 +379,8 @@ async fn detect_channel(in_port: &mut MidiIn, out_port: &mut MidiOut) -> Result<
     Ok(channel)
 }
 
-pub async fn autodetect(channel: Option<u8>) -> Result<(MidiIn, MidiOut, u8, &'static Config)> {
-
+/// Open all the MIDI input and output ports that can be opened
+fn open_all_ports() -> Result<(Vec<MidiIn>, Vec<MidiOut>)> {
     let in_port_names = MidiIn::ports()?;
     let mut in_port_errors = vec![];
     let in_ports = in_port_names.iter().enumerate()
@@ -420,94 +420,140 @@ pub async fn autodetect(channel: Option<u8>) -> Result<(MidiIn, MidiOut, u8, &'s
         }
     }
 
+    Ok((in_ports, out_ports))
+}
+
+pub async fn autodetect(channel: Option<u8>) -> Result<(MidiIn, MidiOut, u8, &'static Config)> {
+    let (in_ports, out_ports) = open_all_ports()?;
     autodetect_with_ports(in_ports, out_ports, channel).await
 }
 
+/// Autodetect all the devices connected, for when several devices are
+/// used at once. Returns the devices in the order of their MIDI input
+/// ports. A device whose output port or channel could not be determined
+/// is skipped.
+pub async fn autodetect_all(channel: Option<u8>) -> Result<Vec<(MidiIn, MidiOut, u8, &'static Config)>> {
+    let (mut in_ports, mut out_ports) = open_all_ports()?;
+
+    let (mut rep, error) = detect(in_ports.as_mut_slice(), out_ports.as_mut_slice()).await?;
+    if rep.len() == 0 {
+        if let Some(e) = error {
+            bail!("{}", e);
+        } else {
+            bail!("Received no device response");
+        }
+    }
+
+    let mut devices = vec![];
+    // take the replied inputs out of the list, highest index first,
+    // so that the remaining indexes stay valid
+    rep.sort_by_key(|(i, _)| std::cmp::Reverse(*i));
+    rep.dedup_by_key(|(i, _)| *i);
+    let mut replied = rep.into_iter()
+        .map(|(i, config)| (in_ports.remove(i), config))
+        .collect::<Vec<_>>();
+    replied.reverse();
+
+    for (mut in_port, config) in replied {
+        let res = detect_output_and_channel(&mut in_port, &mut out_ports, config, channel).await;
+        match res {
+            std::result::Result::Ok((out_port, channel)) => {
+                devices.push((in_port, out_port, channel, config));
+            }
+            Err(e) => {
+                warn!("Skipping {} on MIDI in port {:?}: {}", config.name, in_port.name, e);
+            }
+        }
+    }
+    if devices.is_empty() {
+        bail!("Failed to determine the output port or channel of any detected device");
+    }
+
+    Ok(devices)
+}
+
 pub async fn autodetect_with_ports(in_ports: Vec<MidiIn>, out_ports: Vec<MidiOut>,
                                    channel: Option<u8>) -> Result<(MidiIn, MidiOut, u8, &'static Config)> {
-    let config: Option<&Config>;
     let mut in_ports = in_ports.into_iter().collect::<Vec<_>>();
     let mut out_ports = out_ports.into_iter().collect::<Vec<_>>();
 
     // 1. find the input
-    {
-        let (rep, error) = detect(in_ports.as_mut_slice(), out_ports.as_mut_slice()).await?;
-        if rep.len() == 0 {
-            if let Some(e) = error {
-                bail!("{}", e);
-            } else {
-                bail!("Received no device response");
-            }
-        }
-        if rep.len() > 1 {
-            bail!("Received device response on multiple ({}) ports", rep.len());
+    let (rep, error) = detect(in_ports.as_mut_slice(), out_ports.as_mut_slice()).await?;
+    if rep.len() == 0 {
+        if let Some(e) = error {
+            bail!("{}", e);
+        } else {
+            bail!("Received no device response");
         }
-        in_ports = in_ports.into_iter().enumerate()
-            .filter(|(i, _)| *i == rep[0].0).map(|(_,v)| v).collect();
-        config = Some(rep[0].1);
     }
+    if rep.len() > 1 {
+        bail!("Received device response on multiple ({}) ports", rep.len());
+    }
+    let mut in_port = in_ports.remove(rep[0].0);
+    let config = rep[0].1;
+
+    // 2. find the output & 3. find the channel
+    let (out_port, channel) = detect_output_and_channel(&mut in_port, &mut out_ports, config, channel).await?;
 
-    // 2. find the output
+    Ok((in_port, out_port, channel, config))
+}
+
+/// Find the output port and the channel of the device of `config` that
+/// replied on `in_port`. The output port found is taken out of `out_ports`.
+async fn detect_output_and_channel(in_port: &mut MidiIn, out_ports: &mut Vec<MidiOut>,
+                                   config: &Config, channel: Option<u8>) -> Result<(MidiOut, u8)> {
+    // binary search for the output in the range of candidates [lo, hi)
+    let mut lo = 0usize;
+    let mut hi = out_ports.len();
     loop {
-        let slice =  (out_ports.len() as f32 / 2.0).ceil() as usize;
-        let chunks = out_ports.chunks_mut(slice);
-        let mut good = Vec::<usize>::new();
+        let slice = ((hi - lo) as f32 / 2.0).ceil() as usize;
+        let mut good: Option<(usize, usize)> = None;
         let mut error: Option<String> = None;
-        let mut i = 0usize;
-        for chunk in chunks {
-            let (rep, e) = detect(in_ports.as_mut_slice(), chunk).await?;
+        let mut i = lo;
+        for chunk in out_ports[lo .. hi].chunks_mut(slice.max(1)) {
+            let len = chunk.len();
+            let (rep, e) = detect(std::slice::from_mut(in_port), chunk).await?;
             if let Some(e) = e {
                 error.replace(e);
             }
 
-            let rep = rep
-                .into_iter()
-                // make sure we only count the ports that have the same device as in step 1
-                .filter(|(_, c)| config.filter(|c1| *c1 == *c).is_some())
-                .collect::<Vec<_>>();
-            if rep.len() > 0 {
-                for x in i .. i+chunk.len() {
-                    good.push(x);
-                }
+            // make sure we only count the ports that have the same device as found on input
+            if rep.iter().any(|(_, c)| **c == *config) {
                 // binary search: this group is good, let's continue with it!
+                good = Some((i, i + len));
                 break;
             }
-            i += chunk.len();
+            i += len;
         }
-        if good.len() == 0 {
+        let Some((l, h)) = good else {
             if let Some(e) = error {
                 bail!("{}", e);
             } else {
                 bail!("Received no device response (output search)");
             }
-        }
-        if good.len() == out_ports.len() && good.len() > 1 {
-            bail!("Can't determine output port -- stuck at {}", good.len());
-        }
-        out_ports = out_ports.into_iter().enumerate()
-            .filter(|(i, _)| good.contains(i)).map(|(_,v)| v).collect();
-        if out_ports.len() == 1 {
+        };
+        lo = l;
+        hi = h;
+        if hi - lo == 1 {
             break;
         }
     }
+    let mut out_port = out_ports.remove(lo);
 
-    // 3. find the channel
-    let mut in_port = in_ports.remove(0);
-    let mut out_port = out_ports.remove(0);
     let channel = match channel {
         None => {
-            detect_channel(&mut in_port, &mut out_port).await?
+            detect_channel(in_port, &mut out_port).await?
         },
         Some(c) => {
             warn!("MIDI channel {} set manually", c);
             channel
         }
     };
-    if channel.is_none() {
+    let Some(channel) = channel else {
         bail!("Can't determine POD channel");
-    }
+    };
 
-    Ok((in_port, out_port, channel.unwrap(), config.unwrap()))
+    Ok((out_port, channel))
 }
 
 pub async fn test(in_name: &str, out_name: &str, channel: u8, config: &Config) -> Result<(MidiIn, MidiOut, u8)> {
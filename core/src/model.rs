@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
 use bitflags::bitflags;
 use log::warn;
 
@@ -28,6 +29,7 @@ bitflags! {
 }
 
 bitflags! {
+    #[derive(Default)]
     pub struct MidiQuirks: u16 {
         /// To work around buggy PocketPOD drivers for WinMM, we must ensure
         /// there's a quiet time on the MIDI IN line before it is closed,
@@ -63,9 +65,84 @@ pub struct Config {
     pub program_name_addr: usize,
     pub program_name_length: usize,
     pub flags: DeviceFlags,
+    pub midi_quirks: MidiQuirks,
+
+    /// Differences on specific firmware versions, see `for_firmware`
+    pub firmware_variants: Vec<FirmwareVariant>,
+    /// Firmware versions the config is known to work with
+    pub tested_firmware: Vec<FirmwareVersion>
+}
+
+/// Device firmware version as reported by the universal device inquiry
+/// response, shown as "2.14"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    pub major: u8,
+    pub minor: u8
+}
+
+impl FirmwareVersion {
+    pub const fn new(major: u8, minor: u8) -> Self {
+        FirmwareVersion { major, minor }
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}", self.major, self.minor)
+    }
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Invalid firmware version {:?}", s);
+        let (major, minor) = s.split_once('.').ok_or_else(err)?;
+        Ok(FirmwareVersion {
+            major: major.parse().map_err(|_| err())?,
+            minor: minor.parse().map_err(|_| err())?
+        })
+    }
+}
+
+/// Changes to a config for a range of firmware versions
+#[derive(Clone, Debug, Default)]
+pub struct FirmwareVariant {
+    /// First firmware version the variant applies to
+    pub from: Option<FirmwareVersion>,
+    /// Last firmware version the variant applies to
+    pub to: Option<FirmwareVersion>,
+    pub amp_models: Option<Vec<Amp>>,
+    pub cab_models: Option<Vec<String>>,
+    pub effects: Option<Vec<Effect>>,
+    /// Controls that these firmware versions don't have
+    pub missing_controls: Vec<String>,
+    /// Additional MIDI quirks, in effect from the next MIDI connection
     pub midi_quirks: MidiQuirks
 }
 
+impl FirmwareVariant {
+    pub fn matches(&self, version: FirmwareVersion) -> bool {
+        self.from.map(|v| version >= v).unwrap_or(true) &&
+            self.to.map(|v| version <= v).unwrap_or(true)
+    }
+}
+
+/// How a firmware version relates to the tested ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FirmwareSupport {
+    /// No firmware versions were tested with the config
+    Unknown,
+    Tested,
+    /// Within the tested versions, but not tested itself
+    Untested,
+    /// Older than the oldest tested version
+    Older(FirmwareVersion),
+    /// Newer than the newest tested version
+    Newer(FirmwareVersion)
+}
+
 
 #[derive(Clone, Default, Debug)]
 pub struct Amp {
@@ -658,7 +735,9 @@ impl Config {
             program_name_addr: 0,
             program_name_length: 0,
             flags: DeviceFlags::empty(),
-            midi_quirks: MidiQuirks::empty()
+            midi_quirks: MidiQuirks::empty(),
+            firmware_variants: vec![],
+            tested_firmware: vec![]
         }
     }
 
@@ -711,10 +790,113 @@ impl Config {
         controls
     }
 
+    /// Returns `true` if the device has a tuner, engaged by a program
+    /// change or by the `tuner_enable` control
+    pub fn has_tuner(&self) -> bool {
+        self.pc_tuner.is_some() || self.controls.contains_key("tuner_enable")
+    }
+
+    /// Indexes of the firmware variants that apply to `version`
+    pub fn firmware_variants_for(&self, version: FirmwareVersion) -> Vec<usize> {
+        self.firmware_variants.iter().enumerate()
+            .filter(|(_, v)| v.matches(version))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// This config with the variants for firmware `version` applied.
+    /// Variants are applied in order, so later ones take precedence.
+    pub fn for_firmware(&self, version: FirmwareVersion) -> Config {
+        let mut config = self.clone();
+        for i in self.firmware_variants_for(version) {
+            let variant = &self.firmware_variants[i];
+            if let Some(amp_models) = &variant.amp_models {
+                config.amp_models = amp_models.clone();
+            }
+            if let Some(cab_models) = &variant.cab_models {
+                config.cab_models = cab_models.clone();
+            }
+            if let Some(effects) = &variant.effects {
+                config.effects = effects.clone();
+            }
+            for name in &variant.missing_controls {
+                config.controls.remove(name);
+            }
+            config.init_controls.retain(|name| !variant.missing_controls.contains(name));
+            config.midi_quirks |= variant.midi_quirks;
+        }
+        config
+    }
+
+    pub fn firmware_support(&self, version: FirmwareVersion) -> FirmwareSupport {
+        let (Some(oldest), Some(newest)) = (self.tested_firmware.iter().min(), self.tested_firmware.iter().max()) else {
+            return FirmwareSupport::Unknown;
+        };
+        if version < *oldest {
+            FirmwareSupport::Older(*oldest)
+        } else if version > *newest {
+            FirmwareSupport::Newer(*newest)
+        } else if self.tested_firmware.contains(&version) {
+            FirmwareSupport::Tested
+        } else {
+            FirmwareSupport::Untested
+        }
+    }
+
 }
 
 impl PartialEq for Config {
     fn eq(&self, other: &Self) -> bool {
         self.family == other.family && self.member == other.member
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::*;
+
+    #[test]
+    fn firmware_variants() {
+        let v = |s: &str| s.parse::<FirmwareVersion>().unwrap();
+        assert_eq!(v("2.14"), FirmwareVersion::new(2, 14));
+        assert_eq!(v("3.03").to_string(), "3.03");
+        assert!("?.?".parse::<FirmwareVersion>().is_err());
+
+        let amp = |name: &str| Amp { name: name.into(), ..Default::default() };
+        let config = Config {
+            amp_models: vec![amp("Rectified")],
+            init_controls: vec!["drive".into(), "tuner".into()],
+            firmware_variants: vec![
+                FirmwareVariant {
+                    from: Some(v("2.00")),
+                    amp_models: Some(vec![amp("Treadplate")]),
+                    ..Default::default()
+                },
+                FirmwareVariant {
+                    to: Some(v("2.05")),
+                    missing_controls: vec!["tuner".into()],
+                    ..Default::default()
+                }
+            ],
+            tested_firmware: vec![v("2.00"), v("2.14")],
+            ..Config::empty()
+        };
+
+        assert_eq!(config.firmware_variants_for(v("1.10")), vec![1]);
+        assert_eq!(config.firmware_variants_for(v("2.05")), vec![0, 1]);
+        assert_eq!(config.firmware_variants_for(v("2.14")), vec![0]);
+
+        let c = config.for_firmware(v("1.10"));
+        assert_eq!(c.amp_models[0].name, "Rectified");
+        assert_eq!(c.init_controls, vec!["drive"]);
+        let c = config.for_firmware(v("2.14"));
+        assert_eq!(c.amp_models[0].name, "Treadplate");
+        assert_eq!(c.init_controls.len(), 2);
+
+        assert_eq!(config.firmware_support(v("1.10")), FirmwareSupport::Older(v("2.00")));
+        assert_eq!(config.firmware_support(v("2.14")), FirmwareSupport::Tested);
+        assert_eq!(config.firmware_support(v("2.05")), FirmwareSupport::Untested);
+        assert_eq!(config.firmware_support(v("3.00")), FirmwareSupport::Newer(v("2.14")));
+        assert_eq!(Config::empty().firmware_support(v("3.00")), FirmwareSupport::Unknown);
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use anyhow::*;
+use crate::dump::ProgramsDump;
+use crate::midi::MidiMessage;
+use crate::model::Config;
+use crate::program_id_string;
+use crate::thru::MessageType;
+
+/// Messages kept by the monitor, older ones are dropped
+pub const MONITOR_CAPACITY: usize = 5000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out
+}
+
+impl Direction {
+    pub fn arrow(&self) -> &'static str {
+        match self {
+            Direction::In => "<<",
+            Direction::Out => ">>"
+        }
+    }
+}
+
+/// A MIDI message seen by the monitor
+#[derive(Clone, Debug)]
+pub struct MonitorEntry {
+    /// Time since the monitor was started
+    pub time: Duration,
+    pub direction: Direction,
+    pub kind: Option<MessageType>,
+    pub bytes: Vec<u8>,
+    pub description: String
+}
+
+impl MonitorEntry {
+    pub fn time_string(&self) -> String {
+        format!("{:.3}", self.time.as_secs_f64())
+    }
+
+    pub fn hex(&self) -> String {
+        format_hex(&self.bytes)
+    }
+
+    /// The entry as a line of the saved monitor log
+    pub fn to_line(&self) -> String {
+        format!("{:>10} {} {:<40} {}", self.time_string(), self.direction.arrow(),
+                self.description, self.hex())
+    }
+}
+
+/// Which messages the monitor shows
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitorFilter {
+    pub incoming: bool,
+    pub outgoing: bool,
+    pub cc: bool,
+    pub pc: bool,
+    pub sysex: bool,
+    pub realtime: bool,
+    pub other: bool
+}
+
+impl Default for MonitorFilter {
+    fn default() -> Self {
+        // MIDI clock floods everything else, so real-time messages are hidden
+        MonitorFilter {
+            incoming: true,
+            outgoing: true,
+            cc: true,
+            pc: true,
+            sysex: true,
+            realtime: false,
+            other: true
+        }
+    }
+}
+
+impl MonitorFilter {
+    pub fn matches(&self, entry: &MonitorEntry) -> bool {
+        let direction = match entry.direction {
+            Direction::In => self.incoming,
+            Direction::Out => self.outgoing
+        };
+        let kind = match entry.kind {
+            Some(MessageType::ControlChange) => self.cc,
+            Some(MessageType::ProgramChange) => self.pc,
+            Some(MessageType::SysEx) => self.sysex,
+            Some(MessageType::Realtime) => self.realtime,
+            Some(MessageType::Other) | None => self.other
+        };
+        direction && kind
+    }
+}
+
+/// Format MIDI bytes as space-separated hex: "f0 7e 7f 06 01 f7"
+pub fn format_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+fn realtime_name(status: u8) -> &'static str {
+    match status {
+        0xf8 => "Clock",
+        0xfa => "Start",
+        0xfb => "Continue",
+        0xfc => "Stop",
+        0xfe => "Active sensing",
+        0xff => "Reset",
+        _ => "Real-time"
+    }
+}
+
+fn program_change_name(program: u8, config: &Config, dump: Option<&ProgramsDump>) -> String {
+    let p = program as usize;
+    if config.pc_manual_mode == Some(p) {
+        return "Manual".into();
+    }
+    if config.pc_tuner == Some(p) {
+        return "Tuner".into();
+    }
+    let Some(p) = p.checked_sub(config.pc_offset.unwrap_or_default())
+        .filter(|p| *p < config.program_num) else {
+        return "?".into();
+    };
+    let name = dump.and_then(|d| d.name(p)).unwrap_or_default();
+    format!("{} {}", program_id_string(p), name.trim()).trim_end().to_string()
+}
+
+/// Decode a raw MIDI message into a human-readable description. Control
+/// and program names are looked up from the device `config` and `dump`
+/// when given.
+pub fn describe(bytes: &[u8], config: Option<&Config>, dump: Option<&ProgramsDump>) -> String {
+    let kind = MessageType::of(bytes);
+    if kind == Some(MessageType::Realtime) {
+        return realtime_name(bytes[0]).into();
+    }
+
+    let msg = match MidiMessage::from_bytes(bytes.to_vec()) {
+        std::result::Result::Ok(msg) => msg,
+        Err(_) => {
+            return match kind {
+                Some(MessageType::SysEx) => format!("SysEx ({} bytes)", bytes.len()),
+                Some(_) => format!("Status {:02x}", bytes[0]),
+                None => "Unknown".into()
+            }
+        }
+    };
+
+    // data dumps are summarized, the raw hex has the details
+    match msg {
+        MidiMessage::ControlChange { channel, control, value } => {
+            let name = config.and_then(|c| c.cc_to_control(control))
+                .map(|(name, _)| name.as_str())
+                .unwrap_or("?");
+            format!("CC ch{} {} {} = {}", channel + 1, control, name, value)
+        }
+        MidiMessage::ProgramChange { channel, program } => {
+            let name = config.map(|c| program_change_name(program, c, dump))
+                .unwrap_or("?".into());
+            format!("PC ch{} {} {}", channel + 1, program, name)
+        }
+        MidiMessage::ProgramPatchDump { patch, ver, data } =>
+            format!("ProgramPatchDump {{ patch: {}, ver: {}, {} bytes }}", patch, ver, data.len()),
+        MidiMessage::ProgramEditBufferDump { ver, data } =>
+            format!("ProgramEditBufferDump {{ ver: {}, {} bytes }}", ver, data.len()),
+        MidiMessage::AllProgramsDump { ver, data } =>
+            format!("AllProgramsDump {{ ver: {}, {} bytes }}", ver, data.len()),
+        MidiMessage::XtBufferDump { id, data } =>
+            format!("XtBufferDump {{ id: {}, {} bytes }}", id, data.len()),
+        MidiMessage::XtPatchDump { patch, id, data } =>
+            format!("XtPatchDump {{ patch: {}, id: {}, {} bytes }}", patch, id, data.len()),
+        msg => format!("{:?}", msg)
+    }
+}
+
+/// MIDI monitor: a log of the messages to and from the device with
+/// filters and pause. The frontend shows `entries()`.
+pub struct MidiMonitor {
+    start: Instant,
+    entries: VecDeque<MonitorEntry>,
+    pub filter: MonitorFilter,
+    /// No new messages are recorded while paused
+    pub paused: bool
+}
+
+impl Default for MidiMonitor {
+    fn default() -> Self {
+        MidiMonitor {
+            start: Instant::now(),
+            entries: VecDeque::new(),
+            filter: MonitorFilter::default(),
+            paused: false
+        }
+    }
+}
+
+impl MidiMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a message. Returns the recorded entry if it passes the
+    /// filter, so that the frontend can append it to its view.
+    pub fn record(&mut self, direction: Direction, bytes: Vec<u8>,
+                  config: Option<&Config>, dump: Option<&ProgramsDump>) -> Option<&MonitorEntry> {
+        if self.paused {
+            return None;
+        }
+        if self.entries.len() >= MONITOR_CAPACITY {
+            self.entries.pop_front();
+        }
+
+        let entry = MonitorEntry {
+            time: self.start.elapsed(),
+            direction,
+            kind: MessageType::of(&bytes),
+            description: describe(&bytes, config, dump),
+            bytes
+        };
+        self.entries.push_back(entry);
+        self.entries.back().filter(|e| self.filter.matches(e))
+    }
+
+    /// Recorded entries that pass the filter
+    pub fn entries(&self) -> impl Iterator<Item = &MonitorEntry> {
+        self.entries.iter().filter(|e| self.filter.matches(e))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Save the entries that pass the filter as text
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut text = String::new();
+        for entry in self.entries() {
+            text.push_str(&entry.to_line());
+            text.push('\n');
+        }
+        fs::write(path, text)
+            .with_context(|| format!("Failed to save MIDI monitor log {:?}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Config, Control, SwitchControl};
+    use crate::monitor::*;
+
+    #[test]
+    fn describe_messages() {
+        let mut config = Config {
+            program_num: 4,
+            pc_offset: Some(1),
+            pc_tuner: Some(99),
+            ..Config::empty()
+        };
+        config.controls.insert("drive_enable".into(),
+                               Control::SwitchControl(SwitchControl { cc: 25, addr: 0, inverted: false }));
+
+        assert_eq!(describe(&[0xb0, 25, 127], Some(&config), None), "CC ch1 25 drive_enable = 127");
+        assert_eq!(describe(&[0xb2, 26, 0], Some(&config), None), "CC ch3 26 ? = 0");
+        assert_eq!(describe(&[0xc0, 2], Some(&config), None), "PC ch1 2 1B");
+        assert_eq!(describe(&[0xc0, 99], Some(&config), None), "PC ch1 99 Tuner");
+        assert_eq!(describe(&[0xc0, 0], Some(&config), None), "PC ch1 0 ?");
+        assert_eq!(describe(&[0xf8], None, None), "Clock");
+        assert_eq!(describe(&[0xf0, 0x7e, 0x00, 0x06, 0x01, 0xf7], None, None),
+                   "UniversalDeviceInquiry { channel: 0 }");
+        assert_eq!(describe(&[0xf0, 0x01, 0x02, 0xf7], None, None), "SysEx (4 bytes)");
+    }
+
+    #[test]
+    fn filter_and_pause() {
+        let mut monitor = MidiMonitor::new();
+        assert!(monitor.record(Direction::In, vec![0xb0, 7, 1], None, None).is_some());
+        assert!(monitor.record(Direction::In, vec![0xf8], None, None).is_none());
+        assert!(monitor.record(Direction::Out, vec![0xc0, 1], None, None).is_some());
+        assert_eq!(monitor.entries().count(), 2);
+
+        monitor.filter.outgoing = false;
+        monitor.filter.realtime = true;
+        let hex = monitor.entries().map(|e| e.hex()).collect::<Vec<_>>();
+        assert_eq!(hex, vec!["b0 07 01", "f8"]);
+
+        monitor.paused = true;
+        assert!(monitor.record(Direction::In, vec![0xb0, 7, 2], None, None).is_none());
+        monitor.clear();
+        assert_eq!(monitor.entries().count(), 0);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::context::Ctx;
+use crate::dump::ProgramsDump;
+use crate::event::*;
+use crate::generic::num_program;
+use crate::model::Config;
+use crate::program;
+
+/// Programs edited while no device was connected. These are captured
+/// when the device gets connected, before the programs are loaded from
+/// the device, so that they can be compared with what the device has.
+#[derive(Clone, Debug)]
+pub struct OfflineEdits {
+    config: &'static Config,
+    programs: Vec<(usize, String, Vec<u8>)>
+}
+
+/// A program slot that differs between the local (offline-edited)
+/// copy and the device
+#[derive(Clone, Debug)]
+pub struct SyncSlot {
+    pub program: usize,
+    pub local_name: String,
+    pub device_name: String,
+    pub data: Vec<u8>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncAction {
+    /// Send the local program to the device
+    Push,
+    /// Keep the program loaded from the device, discarding local edits
+    Pull,
+    /// Keep the local program, but do not send it to the device yet
+    KeepLocal
+}
+
+impl OfflineEdits {
+    pub fn capture(ctx: &Ctx) -> Self {
+        let edit = ctx.edit.lock().unwrap();
+        let dump = ctx.dump.lock().unwrap();
+        let current = num_program(&ctx.program());
+
+        let mut programs = vec![];
+        for p in 0 .. dump.program_num() {
+            if current == Some(p) && edit.modified() {
+                programs.push((p, edit.name(), program::store_patch_dump_ctrl(&edit)));
+            } else if dump.modified(p) {
+                let name = dump.name(p).unwrap_or_default();
+                programs.push((p, name, program::store_patch_dump(&dump, p)));
+            }
+        }
+
+        Self { config: ctx.config, programs }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    /// Returns `true` if the edits can be compared with the programs of
+    /// a device with `config`: the same device, possibly with another
+    /// firmware variant of the config installed
+    pub fn applies_to(&self, config: &Config) -> bool {
+        self.config.family == config.family && self.config.member == config.member &&
+            self.config.program_num == config.program_num &&
+            self.config.program_size == config.program_size
+    }
+
+    /// Compare the captured programs with the programs dump, which by now
+    /// should contain the data loaded from the device
+    pub fn diff(&self, dump: &ProgramsDump) -> Vec<SyncSlot> {
+        self.programs.iter()
+            .filter(|(p, _, data)| dump.data(*p) != Some(data.as_slice()))
+            .map(|(p, name, data)| SyncSlot {
+                program: *p,
+                local_name: name.clone(),
+                device_name: dump.name(*p).unwrap_or_default(),
+                data: data.clone()
+            })
+            .collect()
+    }
+}
+
+/// Check if a buffer data event is the last one of an "all programs" load
+/// requested by `new_device_handler`
+pub fn is_all_programs_loaded(config: &Config, event: &BufferDataEvent) -> bool {
+    if event.origin != Origin::MIDI {
+        return false;
+    }
+    match event.buffer {
+        Buffer::All => true,
+        Buffer::Program(p) => p + 1 == config.program_num,
+        _ => false
+    }
+}
+
+pub fn apply_sync_action(slot: &SyncSlot, action: SyncAction, app_event_tx: &EventSender) {
+    if action == SyncAction::Pull {
+        // programs dump already contains data from the device
+        return;
+    }
+
+    // bring the local data back into the programs dump...
+    let e = BufferDataEvent {
+        buffer: Buffer::Program(slot.program),
+        origin: Origin::MIDI,
+        request: Origin::UI,
+        data: slot.data.clone()
+    };
+    app_event_tx.send_or_warn(AppEvent::BufferData(e));
+
+    match action {
+        SyncAction::Push => {
+            // ... and send it to the device, which clears the "modified" flag
+            let e = BufferStoreEvent { buffer: Buffer::Program(slot.program), origin: Origin::UI };
+            app_event_tx.send_or_warn(AppEvent::Store(e));
+        }
+        SyncAction::KeepLocal => {
+            // ... and mark it modified
+            let e = ModifiedEvent {
+                buffer: Buffer::Program(slot.program),
+                origin: Origin::UI,
+                modified: true
+            };
+            app_event_tx.send_or_warn(AppEvent::Modified(e));
+        }
+        SyncAction::Pull => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::broadcast;
+    use crate::controller::*;
+    use crate::dispatch::BufferReroute;
+    use crate::edit::EditBuffer;
+    use crate::handler::Handler;
+    use crate::model::VirtualSelect;
+    use crate::offline::*;
+
+    struct NullHandler;
+    impl Handler for NullHandler {}
+
+    fn config() -> &'static Config {
+        Box::leak(Box::new(Config {
+            program_size: 8,
+            program_num: 4,
+            program_name_addr: 0,
+            program_name_length: 4,
+            ..Config::empty()
+        }))
+    }
+
+    fn new_ctx(config: &'static Config, program: usize) -> Ctx {
+        let edit = EditBuffer::new(config);
+        let ui_controls = [("program".to_string(), VirtualSelect::default().into())].into_iter().collect();
+        let ui_controller = Arc::new(Mutex::new(Controller::new(ui_controls)));
+        ui_controller.set("program", program as u16, StoreOrigin::NONE);
+        Ctx {
+            config,
+            handler: Box::new(NullHandler),
+            controller: edit.controller(),
+            edit: Arc::new(Mutex::new(edit)),
+            dump: Arc::new(Mutex::new(ProgramsDump::new(config))),
+            reroute: BufferReroute::default(),
+            ui_controller,
+            app_event_tx: broadcast::channel(16).0
+        }
+    }
+
+    fn set_program(dump: &mut ProgramsDump, page: usize, data: &[u8]) {
+        dump.data_mut(page).unwrap().copy_from_slice(data);
+        dump.update_name_from_data(page, Origin::MIDI);
+    }
+
+    #[test]
+    fn capture_and_diff() {
+        let config = config();
+        let ctx = new_ctx(config, 2);
+        {
+            let mut dump = ctx.dump.lock().unwrap();
+            set_program(&mut dump, 0, b"Same\x00\x00\x00\x00");
+            set_program(&mut dump, 1, b"Mod1\x01\x02\x03\x04");
+            dump.set_modified(1, true);
+            // the current program is taken from the edit buffer
+            set_program(&mut dump, 2, b"Old2\x00\x00\x00\x00");
+            dump.set_modified(2, true);
+            ctx.edit.lock().unwrap().set_name("Edit");
+        }
+
+        let edits = OfflineEdits::capture(&ctx);
+        assert!(!edits.is_empty());
+        assert!(edits.applies_to(&Config { name: "Variant".into(), ..config.clone() }));
+        assert!(!edits.applies_to(&Config { member: 0x0001, ..config.clone() }));
+        assert!(OfflineEdits::capture(&new_ctx(config, 0)).is_empty());
+
+        // device programs loaded: program 1 matches the local copy
+        let mut dump = ProgramsDump::new(config);
+        set_program(&mut dump, 1, b"Mod1\x01\x02\x03\x04");
+        set_program(&mut dump, 2, b"Dev2\x00\x00\x00\x00");
+        let slots = edits.diff(&dump);
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].program, 2);
+        assert_eq!(slots[0].local_name, "Edit");
+        assert_eq!(slots[0].device_name, "Dev2");
+        assert_eq!(slots[0].data, b"Edit\x00\x00\x00\x00");
+    }
+
+    #[test]
+    fn all_programs_loaded() {
+        let config = config();
+        let event = |buffer, origin| BufferDataEvent { buffer, origin, request: Origin::UI, data: vec![] };
+
+        // programs loaded one by one
+        let loaded = (0 .. config.program_num)
+            .map(|p| is_all_programs_loaded(config, &event(Buffer::Program(p), Origin::MIDI)))
+            .collect::<Vec<_>>();
+        assert_eq!(loaded, vec![false, false, false, true]);
+        // all programs loaded at once
+        assert!(is_all_programs_loaded(config, &event(Buffer::All, Origin::MIDI)));
+
+        // partial loads and non-device data
+        assert!(!is_all_programs_loaded(config, &event(Buffer::EditBuffer, Origin::MIDI)));
+        assert!(!is_all_programs_loaded(config, &event(Buffer::Program(3), Origin::UI)));
+        assert!(!is_all_programs_loaded(config, &event(Buffer::All, Origin::UI)));
+    }
+}
@@ -0,0 +1,491 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use anyhow::*;
+use log::*;
+use crate::context::Ctx;
+use crate::controller::*;
+use crate::convert::{control_value_clamp, control_value_from_norm, control_value_to_norm};
+use crate::event::*;
+use crate::pcmap::{program_from_str, program_is_valid, program_to_str};
+use crate::persist::*;
+use crate::profile::Profiles;
+
+const ADDR_PREFIX: &str = "/pod";
+const CONTROL_PREFIX: &str = "/pod/control/";
+const NORM_SUFFIX: &str = "/norm";
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+
+/// Argument of an OSC message. Only the OSC 1.0 types that control
+/// surfaces commonly send are supported.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Bool(bool)
+}
+
+impl OscArg {
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            OscArg::Int(v) => Some(*v as f32),
+            OscArg::Float(v) => Some(*v),
+            OscArg::Bool(v) => Some(if *v { 1.0 } else { 0.0 }),
+            OscArg::String(_) => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            OscArg::String(v) => Some(v.as_str()),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscMessage {
+    pub addr: String,
+    pub args: Vec<OscArg>
+}
+
+fn pad4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn write_str(buf: &mut Vec<u8>, str: &str) {
+    buf.extend_from_slice(str.as_bytes());
+    let len = pad4(str.len() + 1);
+    buf.resize(buf.len() + len - str.len(), 0);
+}
+
+fn read_str(bytes: &[u8], pos: &mut usize) -> Result<String> {
+    let rest = bytes.get(*pos ..).unwrap_or_default();
+    let Some(len) = rest.iter().position(|b| *b == 0) else {
+        bail!("OSC string not terminated");
+    };
+    let str = std::str::from_utf8(&rest[.. len])
+        .map_err(|_| anyhow!("OSC string is not UTF-8"))?
+        .to_string();
+    *pos += pad4(len + 1);
+    Ok(str)
+}
+
+fn read_4(bytes: &[u8], pos: &mut usize) -> Result<[u8; 4]> {
+    let Some(v) = bytes.get(*pos .. *pos + 4) else {
+        bail!("OSC message truncated");
+    };
+    *pos += 4;
+    Ok([v[0], v[1], v[2], v[3]])
+}
+
+impl OscMessage {
+    pub fn new(addr: &str, args: Vec<OscArg>) -> Self {
+        OscMessage { addr: addr.to_string(), args }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        write_str(&mut buf, &self.addr);
+        let tags = self.args.iter()
+            .map(|arg| match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Float(_) => 'f',
+                OscArg::String(_) => 's',
+                OscArg::Bool(true) => 'T',
+                OscArg::Bool(false) => 'F'
+            })
+            .collect::<String>();
+        write_str(&mut buf, &format!(",{}", tags));
+        for arg in self.args.iter() {
+            match arg {
+                OscArg::Int(v) => buf.extend_from_slice(&v.to_be_bytes()),
+                OscArg::Float(v) => buf.extend_from_slice(&v.to_be_bytes()),
+                OscArg::String(v) => write_str(&mut buf, v),
+                OscArg::Bool(_) => {}
+            }
+        }
+        buf
+    }
+
+    fn decode_message(bytes: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+        let addr = read_str(bytes, &mut pos)?;
+        if !addr.starts_with('/') {
+            bail!("Invalid OSC address {:?}", addr);
+        }
+        // type tag string is optional in very old implementations
+        let tags = if pos < bytes.len() { read_str(bytes, &mut pos)? } else { ",".into() };
+        let Some(tags) = tags.strip_prefix(',') else {
+            bail!("Invalid OSC type tags {:?}", tags);
+        };
+
+        let mut args = vec![];
+        for tag in tags.chars() {
+            let arg = match tag {
+                'i' => OscArg::Int(i32::from_be_bytes(read_4(bytes, &mut pos)?)),
+                'f' => OscArg::Float(f32::from_be_bytes(read_4(bytes, &mut pos)?)),
+                's' => OscArg::String(read_str(bytes, &mut pos)?),
+                'T' => OscArg::Bool(true),
+                'F' => OscArg::Bool(false),
+                t => bail!("Unsupported OSC type tag {:?}", t)
+            };
+            args.push(arg);
+        }
+        Ok(OscMessage { addr, args })
+    }
+
+    /// Decode an OSC packet. Bundles are flattened into their messages,
+    /// time tags are ignored.
+    pub fn decode(bytes: &[u8]) -> Result<Vec<Self>> {
+        if !bytes.starts_with(BUNDLE_TAG) {
+            return Ok(vec![Self::decode_message(bytes)?]);
+        }
+
+        // bundle tag + time tag
+        let mut pos = BUNDLE_TAG.len() + 8;
+        let mut messages = vec![];
+        while pos < bytes.len() {
+            let size = u32::from_be_bytes(read_4(bytes, &mut pos)?) as usize;
+            let Some(element) = bytes.get(pos .. pos + size) else {
+                bail!("OSC bundle element truncated");
+            };
+            messages.extend(Self::decode(element)?);
+            pos += size;
+        }
+        Ok(messages)
+    }
+}
+
+/// OSC server settings
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscSettings {
+    pub enabled: bool,
+    /// Address to listen on
+    pub address: String,
+    pub port: u16
+}
+
+impl Default for OscSettings {
+    fn default() -> Self {
+        OscSettings { enabled: false, address: "127.0.0.1".into(), port: 9000 }
+    }
+}
+
+impl OscSettings {
+    /// Load the OSC settings from the `[osc]` section of the settings file
+    pub fn load() -> Result<Self> {
+        let mut settings = Self::default();
+        let Some(section) = load_section(&Profiles::file_path()?, "osc")? else {
+            return Ok(settings);
+        };
+
+        settings.enabled = section.get_bool("enabled").unwrap_or(settings.enabled);
+        settings.address = section.get("address").map(|a| a.to_string()).unwrap_or(settings.address);
+        settings.port = section.get_parsed("port").unwrap_or(settings.port);
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        update_section(&Profiles::file_path()?, "osc", |section| {
+            section.set_bool("enabled", self.enabled);
+            section.set("address", &self.address);
+            section.set("port", self.port);
+        })
+    }
+}
+
+/// UDP OSC server. Received messages are sent to the app event bus as
+/// `AppEvent::OscIn`, except for `/pod/subscribe [port]` and
+/// `/pod/unsubscribe [port]`, which add or remove the sender to the
+/// clients that value changes are sent to. Value changes only go back
+/// to the address and port a subscription came from, so a `port`
+/// argument, if given, must be the source port.
+pub struct OscServer {
+    socket: UdpSocket,
+    clients: Arc<Mutex<Vec<SocketAddr>>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>
+}
+
+impl OscServer {
+    pub fn start(settings: &OscSettings, app_event_tx: EventSender) -> Result<Self> {
+        let addr = format!("{}:{}", settings.address, settings.port);
+        let socket = UdpSocket::bind(&addr)
+            .with_context(|| format!("Failed to bind OSC server to {}", addr))?;
+        // periodically wake up to check for the stop flag
+        socket.set_read_timeout(Some(Duration::from_millis(250)))?;
+
+        let clients = Arc::new(Mutex::new(Vec::<SocketAddr>::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = std::thread::spawn({
+            let socket = socket.try_clone()?;
+            let clients = clients.clone();
+            let stop = stop.clone();
+
+            move || {
+                info!("OSC server listening on {}", addr);
+                let mut buf = [0u8; 8192];
+                while !stop.load(Ordering::Relaxed) {
+                    let (len, from) = match socket.recv_from(&mut buf) {
+                        std::result::Result::Ok(v) => v,
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock ||
+                            err.kind() == std::io::ErrorKind::TimedOut => continue,
+                        Err(err) => {
+                            error!("OSC server receive error: {}", err);
+                            continue;
+                        }
+                    };
+                    let messages = match OscMessage::decode(&buf[.. len]) {
+                        std::result::Result::Ok(messages) => messages,
+                        Err(err) => {
+                            warn!("OSC: ignoring packet from {}: {}", from, err);
+                            continue;
+                        }
+                    };
+                    for msg in messages {
+                        Self::handle(&clients, from, msg, &app_event_tx);
+                    }
+                }
+                info!("OSC server stopped");
+            }
+        });
+
+        Ok(OscServer { socket, clients, stop, handle: Some(handle) })
+    }
+
+    fn handle(clients: &Mutex<Vec<SocketAddr>>, from: SocketAddr, msg: OscMessage,
+              app_event_tx: &EventSender) {
+        // UDP source addresses are easily forged, so never send to any
+        // other port than the one the request came from
+        let client = |msg: &OscMessage| {
+            let port = msg.args.first()
+                .and_then(|arg| arg.as_f32())
+                .map(|port| port as u16)
+                .unwrap_or(from.port());
+            if port != from.port() {
+                warn!("OSC: ignoring {} from {} for port {}, send it from the port to reply to",
+                      msg.addr, from, port);
+                return None;
+            }
+            Some(from)
+        };
+        match msg.addr.as_str() {
+            "/pod/subscribe" => {
+                let Some(client) = client(&msg) else { return };
+                let mut clients = clients.lock().unwrap();
+                if !clients.contains(&client) {
+                    info!("OSC: client {} subscribed", client);
+                    clients.push(client);
+                }
+                // send the current state to the new client
+                app_event_tx.send_or_warn(AppEvent::OscIn(OscMessage::new("/pod/dump", vec![])));
+            }
+            "/pod/unsubscribe" => {
+                let Some(client) = client(&msg) else { return };
+                info!("OSC: client {} unsubscribed", client);
+                clients.lock().unwrap().retain(|c| *c != client);
+            }
+            _ => {
+                app_event_tx.send_or_warn(AppEvent::OscIn(msg));
+            }
+        }
+    }
+
+    pub fn has_clients(&self) -> bool {
+        !self.clients.lock().unwrap().is_empty()
+    }
+
+    /// Send a message to all subscribed clients
+    pub fn send(&self, msg: &OscMessage) {
+        let bytes = msg.encode();
+        for client in self.clients.lock().unwrap().iter() {
+            self.socket.send_to(&bytes, client)
+                .map_err(|err| warn!("OSC: failed to send to {}: {}", client, err))
+                .unwrap_or_default();
+        }
+    }
+}
+
+impl Drop for OscServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.join().unwrap_or_default();
+        }
+    }
+}
+
+fn buffer_from_args(args: &[OscArg]) -> Option<Buffer> {
+    match args.first().and_then(|arg| arg.as_str()) {
+        None | Some("edit") => Some(Buffer::EditBuffer),
+        Some("current") => Some(Buffer::Current),
+        Some("all") => Some(Buffer::All),
+        Some(_) => None
+    }
+}
+
+fn send_control(ctx: &Ctx, server: &OscServer, name: &str, value: u16) {
+    let addr = format!("{}{}", CONTROL_PREFIX, name);
+    server.send(&OscMessage::new(&addr, vec![OscArg::Int(value as i32)]));
+    if let Some(norm) = control_value_to_norm(ctx.config, name, value) {
+        let addr = format!("{}{}", addr, NORM_SUFFIX);
+        server.send(&OscMessage::new(&addr, vec![OscArg::Float(norm as f32)]));
+    }
+}
+
+fn send_program(ctx: &Ctx, server: &OscServer) {
+    let arg = match ctx.program() {
+        Program::Program(p) => OscArg::Int(p as i32),
+        p => OscArg::String(program_to_str(&p))
+    };
+    server.send(&OscMessage::new("/pod/program", vec![arg]));
+}
+
+fn set_control(ctx: &Ctx, name: &str, value: f32, normalized: bool) {
+    if !ctx.config.controls.contains_key(name) {
+        warn!("OSC: unknown control {:?}", name);
+        return;
+    }
+    let value = if normalized {
+        let Some(value) = control_value_from_norm(ctx.config, name, value as f64) else {
+            warn!("OSC: control {:?} has no known range, normalized value ignored", name);
+            return;
+        };
+        value
+    } else {
+        control_value_clamp(ctx.config, name, value as f64)
+    };
+    // UI origin, so that the value is sent to the device
+    ctx.controller.set(name, value, StoreOrigin::UI);
+}
+
+/// Handler for messages received by the OSC server:
+/// - `/pod/control/<name> [value]`: set a control in raw units, or send
+///   its current value to the clients if no value is given;
+/// - `/pod/control/<name>/norm [value]`: same, in normalized 0..1 units;
+/// - `/pod/program <N|"manual"|"tuner">`: select a program;
+/// - `/pod/load ["edit"|"current"|"all"]`, `/pod/store [...]`: load from
+///   or store to the device, edit buffer by default;
+/// - `/pod/dump`: send all control values and the current program to
+///   the clients.
+pub fn osc_in_handler(ctx: &Ctx, server: &OscServer, msg: &OscMessage) {
+    if let Some(name) = msg.addr.strip_prefix(CONTROL_PREFIX) {
+        let (name, normalized) = match name.strip_suffix(NORM_SUFFIX) {
+            Some(name) => (name, true),
+            None => (name, false)
+        };
+        match msg.args.first().and_then(|arg| arg.as_f32()) {
+            Some(value) => set_control(ctx, name, value, normalized),
+            None => {
+                if let Some(value) = ctx.controller.get(name) {
+                    send_control(ctx, server, name, value);
+                }
+            }
+        }
+        return;
+    }
+
+    match msg.addr.as_str() {
+        "/pod/program" => {
+            let program = match msg.args.first() {
+                Some(OscArg::String(str)) => program_from_str(str),
+                Some(arg) => arg.as_f32().map(|v| Program::Program(v as u16)),
+                None => None
+            };
+            match program {
+                Some(program) if program_is_valid(ctx.config, &program) => {
+                    ctx.set_program(program, Origin::UI)
+                }
+                _ => warn!("OSC: invalid program {:?}", msg.args)
+            }
+        }
+        "/pod/load" | "/pod/store" => {
+            let Some(buffer) = buffer_from_args(&msg.args) else {
+                warn!("OSC: invalid buffer {:?}", msg.args);
+                return;
+            };
+            let e = if msg.addr == "/pod/load" {
+                AppEvent::Load(BufferLoadEvent { buffer, origin: Origin::UI })
+            } else {
+                AppEvent::Store(BufferStoreEvent { buffer, origin: Origin::UI })
+            };
+            ctx.app_event_tx.send_or_warn(e);
+        }
+        "/pod/dump" => {
+            let mut names = ctx.config.controls.keys().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                if let Some(value) = ctx.controller.get(name) {
+                    send_control(ctx, server, name, value);
+                }
+            }
+            send_program(ctx, server);
+        }
+        addr if addr.starts_with(ADDR_PREFIX) => {
+            warn!("OSC: unknown address {:?}", addr);
+        }
+        // not for us
+        _ => {}
+    }
+}
+
+/// Handler for app events to be sent to the OSC clients: control value
+/// and program changes
+pub fn osc_out_handler(ctx: &Ctx, server: &OscServer, event: &AppEvent) {
+    if !server.has_clients() {
+        return;
+    }
+    match event {
+        AppEvent::ControlChange(cc) => send_control(ctx, server, &cc.name, cc.value),
+        AppEvent::ProgramChange(_) => send_program(ctx, server),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::osc::*;
+
+    #[test]
+    fn encode_decode() {
+        let msg = OscMessage::new("/pod/control/drive", vec![
+            OscArg::Int(64), OscArg::Float(0.5), OscArg::String("edit".into()), OscArg::Bool(true)
+        ]);
+        let bytes = msg.encode();
+        assert_eq!(bytes.len() % 4, 0);
+        assert_eq!(&bytes[.. 20], b"/pod/control/drive\0\0");
+        assert_eq!(OscMessage::decode(&bytes).unwrap(), vec![msg.clone()]);
+
+        let mut bundle = BUNDLE_TAG.to_vec();
+        bundle.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        for _ in 0 .. 2 {
+            bundle.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+            bundle.extend_from_slice(&bytes);
+        }
+        assert_eq!(OscMessage::decode(&bundle).unwrap(), vec![msg.clone(), msg]);
+
+        assert!(OscMessage::decode(b"/pod\0\0\0\0,i\0\0").is_err());
+        assert!(OscMessage::decode(b"pod\0").is_err());
+    }
+
+    #[test]
+    fn subscribe() {
+        let (tx, _rx) = tokio::sync::broadcast::channel(16);
+        let clients = Mutex::new(vec![]);
+        let from: SocketAddr = "192.168.1.10:9001".parse().unwrap();
+        let subscribe = |args| OscMessage::new("/pod/subscribe", args);
+
+        OscServer::handle(&clients, from, subscribe(vec![OscArg::Int(9002)]), &tx);
+        assert!(clients.lock().unwrap().is_empty());
+        OscServer::handle(&clients, from, subscribe(vec![OscArg::Int(9001)]), &tx);
+        OscServer::handle(&clients, from, subscribe(vec![]), &tx);
+        assert_eq!(*clients.lock().unwrap(), vec![from]);
+
+        OscServer::handle(&clients, from, OscMessage::new("/pod/unsubscribe", vec![]), &tx);
+        assert!(clients.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use anyhow::*;
+use log::*;
+use crate::context::Ctx;
+use crate::event::*;
+use crate::midi::{Channel, MidiMessage};
+use crate::model::Config;
+use crate::persist::*;
+
+/// MIDI input that program changes are received on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PcMapInput {
+    /// External controller input
+    Controller,
+    /// Device input
+    Device
+}
+
+/// Program change remapping table for a device model: incoming PC
+/// numbers are mapped to programs, which are then selected as if
+/// selected in the UI, so that the device receives the PC (with
+/// `pc_offset`, `pc_manual_mode` and `pc_tuner` applied) of the
+/// mapped program.
+#[derive(Clone, Debug)]
+pub struct PcMap {
+    config: &'static Config,
+    /// Apply to PCs from the external controller input
+    pub controller: bool,
+    /// Apply to PCs from the device input
+    pub device: bool,
+    /// MIDI channel of the controller input PCs, `Channel::all()` for any.
+    /// Device input PCs are always expected on the device channel.
+    pub channel: u8,
+    pub map: BTreeMap<u8, Program>
+}
+
+pub(crate) fn program_to_str(program: &Program) -> String {
+    match program {
+        Program::ManualMode => "manual".into(),
+        Program::Tuner => "tuner".into(),
+        Program::Program(p) => p.to_string()
+    }
+}
+
+pub(crate) fn program_from_str(str: &str) -> Option<Program> {
+    match str {
+        "manual" => Some(Program::ManualMode),
+        "tuner" => Some(Program::Tuner),
+        str => str.parse::<u16>().ok().map(Program::Program)
+    }
+}
+
+/// Returns `true` if `program` can be selected on the device
+pub(crate) fn program_is_valid(config: &Config, program: &Program) -> bool {
+    match program {
+        Program::ManualMode => config.pc_manual_mode.is_some(),
+        Program::Tuner => config.pc_tuner.is_some(),
+        Program::Program(p) => (*p as usize) < config.program_num
+    }
+}
+
+impl PcMap {
+    pub fn new(config: &'static Config) -> Self {
+        PcMap { config, controller: true, device: false, channel: Channel::all(), map: BTreeMap::new() }
+    }
+
+    pub fn file_path(config: &Config) -> Result<PathBuf> {
+        state_file(&format!("pcmap-{}.ini", slug(&config.name)))
+    }
+
+    /// Load the PC map saved for `config`. Failures are logged and
+    /// result in an empty map.
+    pub fn load(config: &'static Config) -> Self {
+        let mut pc_map = Self::new(config);
+        let doc = Self::file_path(config)
+            .and_then(|path| Document::load_if_exists(&path));
+        let doc = match doc {
+            std::result::Result::Ok(Some(doc)) => doc,
+            std::result::Result::Ok(None) => return pc_map,
+            Err(err) => {
+                error!("Failed to load PC map: {}", err);
+                return pc_map;
+            }
+        };
+
+        if let Some(section) = doc.section("pcmap") {
+            pc_map.controller = section.get_bool("controller").unwrap_or(pc_map.controller);
+            pc_map.device = section.get_bool("device").unwrap_or(pc_map.device);
+            pc_map.channel = section.get_parsed("channel").unwrap_or(pc_map.channel);
+        }
+        if let Some(section) = doc.section("map") {
+            for pc in 0u8 ..= 127 {
+                let Some(value) = section.get(&pc.to_string()) else { continue };
+                match program_from_str(value) {
+                    Some(program) if pc_map.is_valid(&program) => {
+                        pc_map.map.insert(pc, program);
+                    }
+                    _ => warn!("PC map: ignoring PC {} -> {:?}", pc, value)
+                }
+            }
+        }
+        pc_map
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut doc = Document::new();
+        let section = doc.section_mut("pcmap");
+        section.set_bool("controller", self.controller);
+        section.set_bool("device", self.device);
+        section.set("channel", self.channel);
+
+        let section = doc.section_mut("map");
+        for (pc, program) in self.map.iter() {
+            section.set(&pc.to_string(), program_to_str(program));
+        }
+        doc.save(&Self::file_path(self.config)?)
+    }
+
+    pub fn config(&self) -> &'static Config {
+        self.config
+    }
+
+    /// Returns `true` if `program` can be selected on the device
+    pub fn is_valid(&self, program: &Program) -> bool {
+        program_is_valid(self.config, program)
+    }
+
+    /// Look up the program mapped for a PC message received on `input`
+    pub fn lookup(&self, input: PcMapInput, msg: &MidiMessage) -> Option<Program> {
+        let MidiMessage::ProgramChange { channel, program } = msg else {
+            return None;
+        };
+        let enabled = match input {
+            PcMapInput::Controller => {
+                self.controller && (self.channel == Channel::all() || self.channel == *channel)
+            }
+            PcMapInput::Device => self.device
+        };
+        if !enabled {
+            return None;
+        }
+        self.map.get(program).cloned()
+    }
+}
+
+/// Handler for PC messages subject to remapping. Returns `true` if the
+/// PC was mapped, in which case the mapped program is selected and the
+/// message must not be handled any further.
+pub fn pc_map_handler(ctx: &Ctx, pc_map: &PcMap, input: PcMapInput, msg: &MidiMessage) -> bool {
+    if pc_map.config != ctx.config {
+        // map for a different device
+        return false;
+    }
+    if let (PcMapInput::Device, MidiMessage::ProgramChange { channel, .. }) = (input, msg) {
+        let expected_channel = ctx.midi_channel();
+        if expected_channel != Channel::all() && *channel != expected_channel {
+            return false;
+        }
+    }
+
+    let Some(program) = pc_map.lookup(input, msg) else {
+        return false;
+    };
+    debug!("PC map: {:?} -> {:?}", msg, program);
+    // same as selecting the program in the UI, which sends the PC
+    // to the device through the PC handler
+    ctx.set_program(program, Origin::UI);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use once_cell::sync::Lazy;
+    use crate::model::Config;
+    use crate::pcmap::*;
+
+    static CONFIG: Lazy<Config> = Lazy::new(|| Config {
+        program_num: 36,
+        pc_manual_mode: Some(0),
+        ..Config::empty()
+    });
+
+    #[test]
+    fn lookup() {
+        let mut pc_map = PcMap::new(&CONFIG);
+        pc_map.map.insert(0, Program::Program(12));
+        pc_map.map.insert(1, Program::ManualMode);
+        let pc = |channel, program| MidiMessage::ProgramChange { channel, program };
+
+        assert_eq!(pc_map.lookup(PcMapInput::Controller, &pc(3, 0)), Some(Program::Program(12)));
+        assert_eq!(pc_map.lookup(PcMapInput::Controller, &pc(3, 1)), Some(Program::ManualMode));
+        assert_eq!(pc_map.lookup(PcMapInput::Controller, &pc(3, 2)), None);
+        assert_eq!(pc_map.lookup(PcMapInput::Device, &pc(3, 0)), None);
+
+        pc_map.channel = 2;
+        assert_eq!(pc_map.lookup(PcMapInput::Controller, &pc(3, 0)), None);
+
+        assert!(pc_map.is_valid(&Program::Program(35)));
+        assert!(!pc_map.is_valid(&Program::Program(36)));
+        assert!(!pc_map.is_valid(&Program::Tuner));
+        assert_eq!(program_from_str(&program_to_str(&Program::Tuner)), Some(Program::Tuner));
+    }
+}
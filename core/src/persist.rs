@@ -0,0 +1,321 @@
+use std::fmt::{Display, Formatter, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use anyhow::*;
+
+const APP_DIR: &str = "pod-ui";
+
+/// Held while a state file is being updated
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Per-user directory where pod-ui keeps its state files. On Linux this
+/// follows XDG (`$XDG_CONFIG_HOME/pod-ui` or `~/.config/pod-ui`), on macOS
+/// this is `~/Library/Application Support/pod-ui` and on Windows
+/// `%APPDATA%\pod-ui`.
+pub fn state_dir() -> Result<PathBuf> {
+    let env_path = |name: &str| std::env::var_os(name)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from);
+
+    let base = if cfg!(target_os = "windows") {
+        env_path("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_path("HOME").map(|p| p.join("Library").join("Application Support"))
+    } else {
+        env_path("XDG_CONFIG_HOME")
+            .or_else(|| env_path("HOME").map(|p| p.join(".config")))
+    };
+
+    let Some(base) = base else {
+        bail!("Cannot determine user configuration directory");
+    };
+    Ok(base.join(APP_DIR))
+}
+
+/// Path to a state file `name` inside `state_dir()`
+pub fn state_file(name: &str) -> Result<PathBuf> {
+    Ok(state_dir()?.join(name))
+}
+
+/// Convert a free-form name (such as a `Config` name) into something that
+/// is safe to use as part of a file name: "PODxt Pro" -> "podxt-pro"
+pub fn slug(name: &str) -> String {
+    let mut s = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            s.push(c.to_ascii_lowercase());
+        } else if !s.ends_with('-') {
+            s.push('-');
+        }
+    }
+    s.trim_matches('-').to_string()
+}
+
+pub fn to_hex(data: &[u8]) -> String {
+    let mut s = String::with_capacity(data.len() * 2);
+    for b in data {
+        write!(s, "{:02x}", b).unwrap();
+    }
+    s
+}
+
+pub fn from_hex(str: &str) -> Result<Vec<u8>> {
+    let str = str.trim();
+    if str.len() % 2 != 0 {
+        bail!("Hex string of odd length {}", str.len());
+    }
+    (0 .. str.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&str[i .. i + 2], 16)
+            .with_context(|| format!("Invalid hex data at position {}", i)))
+        .collect()
+}
+
+/// A named section of `key = value` entries in a `Document`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub entries: Vec<(String, String)>
+}
+
+impl Section {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), entries: vec![] }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_parsed<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|v| v.parse::<T>().ok())
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+    }
+
+    pub fn set<T: ToString>(&mut self, key: &str, value: T) {
+        let value = value.to_string();
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.entries.push((key.to_string(), value))
+        }
+    }
+
+    pub fn set_bool(&mut self, key: &str, value: bool) {
+        self.set(key, value as u8)
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.entries.retain(|(k, _)| k != key);
+    }
+}
+
+/// A minimal INI-like document used for pod-ui state files:
+///
+/// ```noformat
+///   # comment
+///   [section]
+///   key = value
+/// ```
+/// Entries that come before the first section header belong to a section
+/// with an empty name. Values are kept as strings, it is up to the user
+/// to interpret them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub sections: Vec<Section>
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(str: &str) -> Result<Self> {
+        let mut doc = Document::new();
+        let mut current = Section::new("");
+
+        for (n, line) in str.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') {
+                let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) else {
+                    bail!("Malformed section header on line {}", n + 1);
+                };
+                let prev = std::mem::replace(&mut current, Section::new(name.trim()));
+                if !prev.name.is_empty() || !prev.entries.is_empty() {
+                    doc.sections.push(prev);
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                bail!("Expected 'key = value' on line {}", n + 1);
+            };
+            current.entries.push((key.trim().to_string(), value.trim().to_string()));
+        }
+        if !current.name.is_empty() || !current.entries.is_empty() {
+            doc.sections.push(current);
+        }
+
+        Ok(doc)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let str = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        Self::parse(&str)
+            .with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    /// Load a document from `path` if it exists, `None` otherwise
+    pub fn load_if_exists(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::load(path).map(Some)
+    }
+
+    /// Save the document to `path`, creating parent directories as needed.
+    /// The data is first written to a temporary file which then replaces
+    /// `path`, so that a crash mid-write does not leave a truncated file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {:?}", parent))?;
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.to_string())
+            .with_context(|| format!("Failed to write {:?}", tmp))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to replace {:?}", path))?;
+        Ok(())
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Get a mutable reference to a section `name`, creating it if needed
+    pub fn section_mut(&mut self, name: &str) -> &mut Section {
+        let idx = match self.sections.iter().position(|s| s.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.sections.push(Section::new(name));
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[idx]
+    }
+
+    pub fn remove_section(&mut self, name: &str) {
+        self.sections.retain(|s| s.name != name);
+    }
+
+    /// Iterate over sections named `<prefix> <suffix>`, yielding the suffix
+    /// along with the section
+    pub fn sections_with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a str, &'a Section)> + 'a {
+        self.sections.iter().filter_map(move |s| {
+            s.name.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_prefix(' '))
+                .map(|suffix| (suffix, s))
+        })
+    }
+}
+
+/// Load section `name` of the state file at `path`, `None` if there is
+/// no such file or section
+pub fn load_section(path: &Path, name: &str) -> Result<Option<Section>> {
+    let doc = Document::load_if_exists(path)?;
+    Ok(doc.and_then(|doc| doc.section(name).cloned()))
+}
+
+/// Modify the state file at `path` with `f`, creating the file as
+/// needed. Updates are serialized, so that sessions saving their
+/// settings at the same time do not lose each other's changes.
+pub fn update_document<F: FnOnce(&mut Document)>(path: &Path, f: F) -> Result<()> {
+    let _lock = UPDATE_LOCK.lock().unwrap();
+    let mut doc = Document::load_if_exists(path)?.unwrap_or_default();
+    f(&mut doc);
+    doc.save(path)
+}
+
+/// Modify section `name` of the state file at `path` with `f`, creating
+/// the file and the section as needed. Other sections of the file are
+/// kept as they are.
+pub fn update_section<F: FnOnce(&mut Section)>(path: &Path, name: &str, f: F) -> Result<()> {
+    update_document(path, |doc| f(doc.section_mut(name)))
+}
+
+impl Display for Document {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, section) in self.sections.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            if !section.name.is_empty() {
+                writeln!(f, "[{}]", section.name)?;
+            }
+            for (k, v) in section.entries.iter() {
+                writeln!(f, "{} = {}", k, v)?;
+            }
+        }
+        std::result::Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::persist::*;
+
+    #[test]
+    fn document_round_trip() {
+        let mut doc = Document::new();
+        doc.section_mut("session").set("config", "PODxt Pro");
+        doc.section_mut("session").set_bool("modified", true);
+        doc.section_mut("program 12").set("data", to_hex(&[0x00, 0x7f, 0xab]));
+
+        let str = doc.to_string();
+        let parsed = Document::parse(&str).unwrap();
+        assert_eq!(doc, parsed);
+
+        let session = parsed.section("session").unwrap();
+        assert_eq!(session.get("config"), Some("PODxt Pro"));
+        assert_eq!(session.get_bool("modified"), Some(true));
+
+        let programs = parsed.sections_with_prefix("program").collect::<Vec<_>>();
+        assert_eq!(programs.len(), 1);
+        assert_eq!(programs[0].0, "12");
+        assert_eq!(from_hex(programs[0].1.get("data").unwrap()).unwrap(), vec![0x00, 0x7f, 0xab]);
+    }
+
+    #[test]
+    fn section_update() {
+        let path = std::env::temp_dir().join(format!("pod-ui-test-{}.ini", std::process::id()));
+        assert_eq!(load_section(&path, "osc").unwrap(), None);
+
+        update_section(&path, "osc", |s| s.set("port", 9000)).unwrap();
+        update_section(&path, "api", |s| s.set("port", 9001)).unwrap();
+        update_section(&path, "osc", |s| s.set_bool("enabled", true)).unwrap();
+        let osc = load_section(&path, "osc").unwrap().unwrap();
+        let api = load_section(&path, "api").unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(osc.get_parsed::<u16>("port"), Some(9000));
+        assert_eq!(osc.get_bool("enabled"), Some(true));
+        assert_eq!(api.get_parsed::<u16>("port"), Some(9001));
+        assert_eq!(load_section(&path, "midi").unwrap(), None);
+    }
+
+    #[test]
+    fn slug_is_file_name_safe() {
+        assert_eq!(slug("PODxt Pro"), "podxt-pro");
+        assert_eq!(slug("Bass PODxt Live"), "bass-podxt-live");
+        assert_eq!(slug("POD 2.0"), "pod-2-0");
+    }
+}
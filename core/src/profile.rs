@@ -0,0 +1,147 @@
+use std::path::PathBuf;
+use anyhow::*;
+use log::*;
+use crate::midi_io::*;
+use crate::persist::*;
+
+/// MIDI connection settings: ports, channel, device model and the
+/// external controller input used for MIDI learn. Ports and model are
+/// stored by name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub midi_in: Option<String>,
+    pub midi_out: Option<String>,
+    pub midi_channel: Option<u8>,
+    pub model: Option<String>,
+    pub controller_in: Option<String>
+}
+
+impl Profile {
+    fn from_section(name: &str, section: &Section) -> Self {
+        Profile {
+            name: name.to_string(),
+            midi_in: section.get("midi_in").map(String::from),
+            midi_out: section.get("midi_out").map(String::from),
+            midi_channel: section.get_parsed("midi_channel"),
+            model: section.get("model").map(String::from),
+            controller_in: section.get("controller_in").map(String::from)
+        }
+    }
+
+    fn to_section(&self, section: &mut Section) {
+        let mut set = |key: &str, value: Option<String>| {
+            match value {
+                Some(v) => section.set(key, v),
+                None => section.remove(key)
+            }
+        };
+        set("midi_in", self.midi_in.clone());
+        set("midi_out", self.midi_out.clone());
+        set("midi_channel", self.midi_channel.map(|c| c.to_string()));
+        set("model", self.model.clone());
+        set("controller_in", self.controller_in.clone());
+    }
+
+    /// Returns `true` if both MIDI ports are set
+    pub fn has_ports(&self) -> bool {
+        self.midi_in.is_some() && self.midi_out.is_some()
+    }
+
+    /// Open the profile's MIDI ports, `None` if they are not set or gone
+    pub fn open_ports(&self) -> Option<(MidiIn, MidiOut)> {
+        let (Some(in_name), Some(out_name)) = (&self.midi_in, &self.midi_out) else {
+            return None;
+        };
+        let ports = MidiIn::new_for_name(in_name)
+            .and_then(|midi_in| MidiOut::new_for_name(out_name).map(|midi_out| (midi_in, midi_out)));
+        match ports {
+            std::result::Result::Ok(ports) => Some(ports),
+            Err(err) => {
+                warn!("Saved MIDI ports not available, falling back to autodetect: {}", err);
+                None
+            }
+        }
+    }
+}
+
+/// Saved connection settings: the connection used last, which is
+/// restored on start, and user-defined named profiles.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profiles {
+    pub last: Option<Profile>,
+    pub profiles: Vec<Profile>
+}
+
+impl Profiles {
+    pub fn file_path() -> Result<PathBuf> {
+        state_file("settings.ini")
+    }
+
+    pub fn load() -> Result<Self> {
+        let Some(doc) = Document::load_if_exists(&Self::file_path()?)? else {
+            return Ok(Self::default());
+        };
+
+        let last = doc.section("last")
+            .map(|section| Profile::from_section("", section));
+        let profiles = doc.sections_with_prefix("profile")
+            .map(|(name, section)| Profile::from_section(name, section))
+            .collect();
+
+        Ok(Profiles { last, profiles })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        // keep whatever other sections the file has
+        update_document(&Self::file_path()?, |doc| {
+            match &self.last {
+                Some(last) => last.to_section(doc.section_mut("last")),
+                None => doc.remove_section("last")
+            }
+            let stale = doc.sections_with_prefix("profile")
+                .map(|(name, _)| name.to_string())
+                .filter(|name| self.get(name).is_none())
+                .collect::<Vec<_>>();
+            for name in stale {
+                doc.remove_section(&format!("profile {}", name));
+            }
+            for profile in self.profiles.iter() {
+                profile.to_section(doc.section_mut(&format!("profile {}", profile.name)));
+            }
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Add a profile, replacing the profile with the same name
+    pub fn set(&mut self, profile: Profile) {
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(p) => *p = profile,
+            None => self.profiles.push(profile)
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.profiles.retain(|p| p.name != name);
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.profiles.iter().map(|p| p.name.clone()).collect()
+    }
+}
+
+/// Remember the MIDI connection in use, so that it can be restored on
+/// the next start without autodetect
+pub fn save_last_connection(last: Profile) {
+    let res = Profiles::load().and_then(|mut profiles| {
+        if profiles.last.as_ref() == Some(&last) {
+            return Ok(());
+        }
+        profiles.last = Some(last);
+        profiles.save()
+    });
+    res.unwrap_or_else(|err| error!("Failed to save settings: {}", err));
+}
@@ -2,6 +2,9 @@ use crate::controller::Controller;
 use crate::dump::ProgramsDump;
 use crate::edit::*;
 use crate::event::Origin;
+use crate::handler::Handler;
+use crate::model::Config;
+use crate::str_encoder::StrEncoder;
 
 
 pub fn store_patch_dump_ctrl_buf(edit: &EditBuffer, buffer: &mut [u8]) {
@@ -23,6 +26,21 @@ pub fn store_patch_dump_ctrl(edit: &EditBuffer) -> Vec<u8> {
     data
 }
 
+/// An "init" program to reset program slots to: all controls at their
+/// initial values, as in a new edit buffer, and the name set to "Init".
+/// The values are written by the device `handler`, since an initial
+/// value is not necessarily stored as zero.
+pub fn init_program(config: &Config, handler: &dyn Handler) -> Vec<u8> {
+    let controller = Controller::new(config.controls.clone());
+    let mut data = vec![0; config.program_size];
+    for name in config.controls.keys() {
+        handler.control_value_to_buffer(&controller, name, &mut data);
+    }
+    StrEncoder::new(config).str_to_buffer("Init", &mut data);
+
+    data
+}
+
 // --
 
 pub fn load_patch_dump(programs_dump: &mut ProgramsDump,
@@ -72,4 +90,39 @@ pub fn store_all_dump(programs_dump: &ProgramsDump) -> Vec<u8> {
     }
 
     data
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::controller::*;
+    use crate::model::*;
+    use crate::program::*;
+
+    /// Writes MIDI values to the buffer, like the PODxt
+    struct MidiHandler;
+
+    impl Handler for MidiHandler {
+        fn control_value_to_buffer(&self, controller: &Controller, name: &str, buffer: &mut [u8]) {
+            let Some(control) = controller.get_config(name) else { return };
+            let Some((addr, _)) = control.get_addr() else { return };
+            buffer[addr as usize] = control.value_to_midi(controller.get(name).unwrap());
+        }
+    }
+
+    #[test]
+    fn init_program_values() {
+        let config = Config {
+            program_size: 8,
+            program_name_addr: 4,
+            program_name_length: 4,
+            controls: [
+                ("drive_enable", SwitchControl { cc: 25, addr: 0, inverted: false }.into()),
+                ("eq_enable", SwitchControl { cc: 26, addr: 1, inverted: true }.into()),
+                ("tempo", VirtualRangeControl::default().into())
+            ].into_iter().map(|(n, c): (&str, Control)| (n.to_string(), c)).collect(),
+            ..Config::empty()
+        };
+
+        assert_eq!(init_program(&config, &MidiHandler), b"\x00\x7f\x00\x00Init");
+    }
+}
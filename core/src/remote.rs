@@ -0,0 +1,151 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use anyhow::*;
+use log::*;
+use crate::bank::{check_bank_file, Bank};
+use crate::context::Ctx;
+use crate::controller::*;
+use crate::convert::control_value_clamp;
+use crate::event::*;
+use crate::pcmap::{program_from_str, program_is_valid, program_to_str};
+
+/// A command given on the command line of a secondary pod-ui instance,
+/// forwarded to the primary (already-running) instance
+#[derive(Clone, Debug, PartialEq)]
+pub enum RemoteCommand {
+    /// Select a program
+    Program(Program),
+    /// Store all programs to the device
+    StoreAll,
+    /// Import programs from a bank file into the programs dump
+    Import(PathBuf),
+    /// Set a control value, in raw units
+    Set { name: String, value: f64 }
+}
+
+impl RemoteCommand {
+    /// Parse a `--program` argument: a program number, "manual" or "tuner"
+    pub fn program(str: &str) -> Result<Self> {
+        program_from_str(str.trim())
+            .map(RemoteCommand::Program)
+            .ok_or_else(|| anyhow!("Invalid program {:?}", str))
+    }
+
+    /// Parse a `--set` argument of the form "name=value"
+    pub fn set(str: &str) -> Result<Self> {
+        let Some((name, value)) = str.split_once('=') else {
+            bail!("Invalid control assignment {:?}, expected <name>=<value>", str);
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("Invalid control assignment {:?}, control name missing", str);
+        }
+        let value = value.trim().parse::<f64>()
+            .map_err(|_| anyhow!("Invalid control value in {:?}", str))?;
+        Ok(RemoteCommand::Set { name: name.to_string(), value })
+    }
+
+    /// Parse an `--import` argument. The path is made absolute, since the
+    /// primary instance may be running in a different directory.
+    pub fn import(str: &str) -> Result<Self> {
+        let path = std::fs::canonicalize(str)
+            .with_context(|| format!("Cannot import {:?}", str))?;
+        check_bank_file(&path)?;
+        Ok(RemoteCommand::Import(path))
+    }
+
+    /// Parse a command from its `Display` form, as sent to the
+    /// primary instance
+    pub fn parse(str: &str) -> Result<Self> {
+        let (cmd, arg) = str.split_once(' ').unwrap_or((str, ""));
+        match cmd {
+            "program" => Self::program(arg),
+            "store-all" => Ok(RemoteCommand::StoreAll),
+            "import" => Ok(RemoteCommand::Import(PathBuf::from(arg))),
+            "set" => Self::set(arg),
+            _ => bail!("Unknown remote command {:?}", str)
+        }
+    }
+}
+
+impl Display for RemoteCommand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteCommand::Program(p) => write!(f, "program {}", program_to_str(p)),
+            RemoteCommand::StoreAll => write!(f, "store-all"),
+            RemoteCommand::Import(path) => write!(f, "import {}", path.display()),
+            RemoteCommand::Set { name, value } => write!(f, "set {}={}", name, value)
+        }
+    }
+}
+
+/// Handler for commands forwarded from other pod-ui instances. Commands
+/// are turned into the same app events the UI would send.
+pub fn remote_handler(ctx: &Ctx, command: &RemoteCommand) {
+    info!("Remote command: {}", command);
+    match command {
+        RemoteCommand::Program(program) => {
+            if !program_is_valid(ctx.config, program) {
+                warn!("Remote: invalid program {:?}", program);
+                return;
+            }
+            ctx.set_program(program.clone(), Origin::UI);
+        }
+        RemoteCommand::StoreAll => {
+            let e = BufferStoreEvent { buffer: Buffer::All, origin: Origin::UI };
+            ctx.app_event_tx.send_or_warn(AppEvent::Store(e));
+        }
+        RemoteCommand::Import(path) => {
+            let msg = match Bank::load(path, ctx.config) {
+                std::result::Result::Ok(bank) => {
+                    let msg = format!("Imported {} program(s) from {}",
+                                      bank.programs.len(), path.display());
+                    let programs = bank.programs.into_iter()
+                        .map(|(p, _, data)| (p, data))
+                        .collect();
+                    ctx.app_event_tx.send_or_warn(AppEvent::Reorder(ReorderEvent::Import { programs }));
+                    msg
+                }
+                Err(err) => {
+                    error!("Failed to import programs: {}", err);
+                    format!("Failed to import programs: {}", err)
+                }
+            };
+            ctx.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+        }
+        RemoteCommand::Set { name, value } => {
+            if !ctx.config.controls.contains_key(name) {
+                warn!("Remote: unknown control {:?}", name);
+                return;
+            }
+            let value = control_value_clamp(ctx.config, name, *value);
+            // UI origin, so that the value is sent to the device
+            ctx.controller.set(name, value, StoreOrigin::UI);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remote::*;
+
+    #[test]
+    fn round_trip() {
+        let commands = vec![
+            RemoteCommand::program("12").unwrap(),
+            RemoteCommand::program("tuner").unwrap(),
+            RemoteCommand::StoreAll,
+            RemoteCommand::Import(PathBuf::from("/tmp/my bank.ini")),
+            RemoteCommand::set("drive = 64").unwrap()
+        ];
+        for command in commands {
+            assert_eq!(RemoteCommand::parse(&command.to_string()).unwrap(), command);
+        }
+        assert_eq!(RemoteCommand::set("drive=64").unwrap(),
+                   RemoteCommand::Set { name: "drive".into(), value: 64.0 });
+        assert!(RemoteCommand::set("drive").is_err());
+        assert!(RemoteCommand::set("=1").is_err());
+        assert!(RemoteCommand::program("x").is_err());
+        assert!(RemoteCommand::parse("reboot").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::controller::*;
+use crate::controller::StoreOrigin::{MIDI, NONE, UI};
+
+type RuleFn = dyn Fn(u16, &mut Controller, StoreOrigin) + Send + Sync;
+
+#[derive(Clone)]
+struct Rule {
+    origin: Vec<StoreOrigin>,
+    f: Arc<RuleFn>
+}
+
+/// Reactive parameter rules of a device: when a control changes, rules
+/// registered for it update the controls that depend on it (14-bit
+/// MSB/LSB pairs, note-synced times, virtual selects...).
+///
+/// Rules are installed into a `Controller` and run by it on every
+/// signalled value change, so every frontend gets the same behaviour.
+#[derive(Clone, Default)]
+pub struct Rules {
+    rules: HashMap<String, Vec<Rule>>
+}
+
+impl Rules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on(&mut self, name: &str) -> RulesOnBuilder<'_> {
+        RulesOnBuilder { rules: self, name: name.into(), origin: vec![] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Run the rules for control `name` that was set to `value`
+    pub fn run(&self, name: &str, value: u16, origin: StoreOrigin, controller: &mut Controller) {
+        let Some(rules) = self.rules.get(name) else { return };
+        for rule in rules {
+            if rule.origin.is_empty() || rule.origin.contains(&origin) {
+                (rule.f)(value, controller, origin);
+            }
+        }
+    }
+}
+
+pub struct RulesOnBuilder<'a> {
+    rules: &'a mut Rules,
+    name: String,
+    origin: Vec<StoreOrigin>
+}
+
+impl <'a> RulesOnBuilder<'a> {
+    /// Only run the following rule for values set with `origin`.
+    /// May be given multiple times.
+    pub fn from(&mut self, origin: StoreOrigin) -> &mut Self {
+        self.origin.push(origin);
+        self
+    }
+
+    pub fn run<F>(&mut self, f: F) -> &mut Self
+        where F: Fn(u16, &mut Controller, StoreOrigin) + Send + Sync + 'static {
+        let rule = Rule { origin: self.origin.clone(), f: Arc::new(f) };
+        self.rules.rules.entry(self.name.clone()).or_default().push(rule);
+        self
+    }
+
+    pub fn on(&mut self, name: &str) -> &mut Self {
+        self.name = name.into();
+        self.origin = vec![];
+        self
+    }
+}
+
+/// Split a 14-bit `control_name` value into `msb_name` and `lsb_name`
+/// 7-bit controls and join them back
+pub fn rules_14bit(rules: &mut Rules, control_name: &str, msb_name: &str, lsb_name: &str,
+                   big_endian: bool) {
+    rules
+        .on(control_name)
+        .run({
+            let lsb_name = lsb_name.to_string();
+            let msb_name = msb_name.to_string();
+
+            move |value, controller, origin| {
+                let msb = (value & 0x3f80) >> 7;
+                let lsb = value & 0x7f;
+
+                // Make sure GUI event always generates both MSB and LSB MIDI messages
+                let signal = if origin == UI { Signal::Force } else { Signal::Change };
+                if big_endian {
+                    // PODxt/L6E sends msb,lsb
+                    controller.set_full(&msb_name, msb, origin, signal.clone());
+                    controller.set_full(&lsb_name, lsb, origin, signal);
+                } else {
+                    // L6E sends lsb,msb; POD2.0 only sends msb
+                    controller.set_full(&lsb_name, lsb, origin, signal.clone());
+                    controller.set_full(&msb_name, msb, origin, signal);
+                }
+            }
+        })
+        .on(msb_name).from(MIDI).from(NONE)
+        .run({
+            let control_name = control_name.to_string();
+
+            move |value, controller, origin| {
+                let control_value = controller.get(&control_name).unwrap();
+                let lsb = control_value & 0x7f;
+                let control_value = ((value & 0x7f) << 7) | lsb;
+                controller.set(&control_name, control_value, origin);
+            }
+        })
+        .on(lsb_name).from(MIDI).from(NONE)
+        .run({
+            let control_name = control_name.to_string();
+
+            move |value, controller, origin| {
+                let control_value = controller.get(&control_name).unwrap();
+                let msb = control_value & 0x3f80;
+                let control_value = msb | (value & 0x7f);
+                controller.set(&control_name, control_value, origin);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::model::{Control, RangeControl, VirtualRangeControl};
+    use crate::rules::*;
+
+    #[test]
+    fn split_14bit() {
+        let controls: HashMap<String, Control> = [
+            ("tempo", VirtualRangeControl::default().into()),
+            ("tempo:msb", RangeControl::default().into()),
+            ("tempo:lsb", RangeControl::default().into())
+        ].into_iter().map(|(n, c)| (n.to_string(), c)).collect();
+        let mut controller = Controller::new(controls);
+        let mut rules = Rules::new();
+        rules_14bit(&mut rules, "tempo", "tempo:msb", "tempo:lsb", true);
+        controller.set_rules(rules);
+
+        controller.set("tempo", 1200, UI);
+        assert_eq!(controller.get("tempo:msb"), Some(1200 >> 7));
+        assert_eq!(controller.get("tempo:lsb"), Some(1200 & 0x7f));
+
+        controller.set("tempo:lsb", 0, MIDI);
+        assert_eq!(controller.get("tempo"), Some(1200 & 0x3f80));
+        // UI-only rules do not run for MIDI values
+        controller.set("tempo:msb", 1, UI);
+        assert_eq!(controller.get("tempo"), Some(1200 & 0x3f80));
+    }
+
+    #[test]
+    fn deferred_rules() {
+        let controls: HashMap<String, Control> = [
+            ("tempo", VirtualRangeControl::default().into()),
+            ("tempo:msb", RangeControl::default().into()),
+            ("tempo:lsb", RangeControl::default().into())
+        ].into_iter().map(|(n, c)| (n.to_string(), c)).collect();
+        let mut controller = Controller::new(controls);
+        let mut rules = Rules::new();
+        rules_14bit(&mut rules, "tempo", "tempo:msb", "tempo:lsb", true);
+        controller.set_rules(rules);
+
+        // PODxt programs have the LSB before the MSB
+        controller.defer_rules();
+        controller.set("tempo:lsb", 48, NONE);
+        controller.set("tempo:msb", 10, NONE);
+        assert_eq!(controller.get("tempo"), Some(0));
+
+        controller.run_deferred_rules();
+        assert_eq!(controller.get("tempo"), Some((10 << 7) | 48));
+        assert_eq!(controller.get("tempo:msb"), Some(10));
+        assert_eq!(controller.get("tempo:lsb"), Some(48));
+        // back to running rules right away
+        controller.set("tempo:lsb", 0, MIDI);
+        assert_eq!(controller.get("tempo"), Some(10 << 7));
+    }
+}
@@ -0,0 +1,165 @@
+use std::path::PathBuf;
+use anyhow::*;
+use log::*;
+use crate::dump::ProgramsDump;
+use crate::edit::EditBuffer;
+use crate::event::*;
+use crate::generic::num_program;
+use crate::model::Config;
+use crate::persist::*;
+use crate::program;
+
+/// A snapshot of the unsaved edits made to the edit buffer and the
+/// programs dump, kept on disk so that they survive a crash or an
+/// unexpected shutdown. Sessions are keyed by device model name.
+///
+/// Only the modified programs are saved, since the unmodified ones are
+/// re-read from the device anyway. If the edit buffer is modified while
+/// a program is selected, the edit buffer is saved in place of that
+/// program's data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub config_name: String,
+    pub program: Program,
+    /// Modified programs as (program index, program data)
+    pub programs: Vec<(usize, Vec<u8>)>,
+    /// Modified edit buffer when no program is selected (manual mode)
+    pub edit: Option<Vec<u8>>
+}
+
+impl Session {
+    pub fn capture(config: &Config, program: Program, edit: &EditBuffer, dump: &ProgramsDump) -> Self {
+        let current = num_program(&program)
+            .filter(|p| *p < dump.program_num());
+
+        let mut programs = vec![];
+        for p in 0 .. dump.program_num() {
+            if current == Some(p) && edit.modified() {
+                programs.push((p, program::store_patch_dump_ctrl(edit)));
+            } else if dump.modified(p) {
+                programs.push((p, program::store_patch_dump(dump, p)));
+            }
+        }
+
+        let edit = if current.is_none() && edit.modified() {
+            Some(program::store_patch_dump_ctrl(edit))
+        } else {
+            None
+        };
+
+        Session { config_name: config.name.clone(), program, programs, edit }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty() && self.edit.is_none()
+    }
+
+    pub fn file_path(config: &Config) -> Result<PathBuf> {
+        state_file(&format!("session-{}.ini", slug(&config.name)))
+    }
+
+    /// Load a previously saved session for the device model `config`
+    pub fn load(config: &Config) -> Result<Option<Self>> {
+        let path = Self::file_path(config)?;
+        let Some(doc) = Document::load_if_exists(&path)? else {
+            return Ok(None);
+        };
+
+        let Some(section) = doc.section("session") else {
+            bail!("Session file {:?} has no [session] section", path);
+        };
+        let config_name = section.get("config").unwrap_or_default().to_string();
+        if config_name != config.name {
+            bail!("Session file {:?} is for {:?}, not {:?}", path, config_name, config.name);
+        }
+        let program = section.get_parsed::<u16>("program")
+            .map(Program::from)
+            .unwrap_or(Program::ManualMode);
+        let edit = section.get("edit")
+            .map(from_hex).transpose()?
+            .filter(|data| data.len() == config.program_size);
+
+        let mut programs = vec![];
+        for (suffix, section) in doc.sections_with_prefix("program") {
+            let Some(p) = suffix.parse::<usize>().ok().filter(|p| *p < config.program_num) else {
+                warn!("Session: ignoring program {:?}", suffix);
+                continue;
+            };
+            let data = from_hex(section.get("data").unwrap_or_default())?;
+            if data.len() != config.program_size {
+                warn!("Session: ignoring program {} with data size {}", p, data.len());
+                continue;
+            }
+            programs.push((p, data));
+        }
+
+        Ok(Some(Session { config_name, program, programs, edit }))
+    }
+
+    /// Save the session to disk. An empty session removes the session file.
+    pub fn save(&self, config: &Config) -> Result<()> {
+        if self.is_empty() {
+            return Self::remove(config);
+        }
+
+        let mut doc = Document::new();
+        let section = doc.section_mut("session");
+        section.set("config", &self.config_name);
+        section.set("program", Into::<u16>::into(self.program.clone()));
+        if let Some(edit) = &self.edit {
+            section.set("edit", to_hex(edit));
+        }
+        for (p, data) in self.programs.iter() {
+            doc.section_mut(&format!("program {}", p)).set("data", to_hex(data));
+        }
+
+        doc.save(&Self::file_path(config)?)
+    }
+
+    pub fn remove(config: &Config) -> Result<()> {
+        let path = Self::file_path(config)?;
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {:?}", path))?;
+        }
+        Ok(())
+    }
+
+    /// Restore the session by loading the saved program data into the
+    /// programs dump (as if it came from the device) and then marking
+    /// these programs modified so that they can be stored to the device.
+    /// The program selection itself is left to the caller.
+    pub fn restore(&self, app_event_tx: &EventSender) {
+        for (p, data) in self.programs.iter() {
+            let e = BufferDataEvent {
+                buffer: Buffer::Program(*p),
+                origin: Origin::MIDI,
+                request: Origin::UI,
+                data: data.clone()
+            };
+            app_event_tx.send_or_warn(AppEvent::BufferData(e));
+
+            let e = ModifiedEvent {
+                buffer: Buffer::Program(*p),
+                origin: Origin::UI,
+                modified: true
+            };
+            app_event_tx.send_or_warn(AppEvent::Modified(e));
+        }
+
+        if let Some(data) = &self.edit {
+            // load into the edit buffer, which marks it modified,
+            // and send it to the device
+            let e = BufferDataEvent {
+                buffer: Buffer::EditBuffer,
+                origin: Origin::MIDI,
+                request: Origin::UI,
+                data: data.clone()
+            };
+            app_event_tx.send_or_warn(AppEvent::BufferData(e));
+
+            let e = BufferStoreEvent { buffer: Buffer::EditBuffer, origin: Origin::UI };
+            app_event_tx.send_or_warn(AppEvent::Store(e));
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::*;
+use log::*;
+use crate::context::Ctx;
+use crate::event::*;
+use crate::midi::MidiMessage;
+use crate::model::Config;
+use crate::pcmap::{program_from_str, program_to_str};
+use crate::persist::*;
+use crate::profile::Profiles;
+
+const FILE_PREFIX: &str = "setlist-";
+const FILE_EXT: &str = "ini";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SetlistItem {
+    /// A program on the device, selected with a PC
+    Program(Program),
+    /// A patch kept in the setlist, sent to the device as an edit
+    /// buffer dump
+    Patch { name: String, data: Vec<u8> }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetlistEntry {
+    pub song: String,
+    pub item: SetlistItem
+}
+
+/// An ordered list of programs and patches for a gig
+#[derive(Clone, Debug, PartialEq)]
+pub struct Setlist {
+    pub name: String,
+    pub config_name: String,
+    pub entries: Vec<SetlistEntry>,
+    /// Index of the current entry
+    pub position: Option<usize>
+}
+
+impl Setlist {
+    pub fn new(name: &str, config: &Config) -> Self {
+        Setlist {
+            name: name.to_string(),
+            config_name: config.name.clone(),
+            entries: vec![],
+            position: None
+        }
+    }
+
+    pub fn file_path(name: &str) -> Result<PathBuf> {
+        state_file(&format!("{}{}.{}", FILE_PREFIX, slug(name), FILE_EXT))
+    }
+
+    /// Names of the saved setlists
+    pub fn list() -> Result<Vec<String>> {
+        let dir = state_dir()?;
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut names = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let is_setlist = path.file_name().and_then(|n| n.to_str())
+                .map(|n| n.starts_with(FILE_PREFIX) && n.ends_with(FILE_EXT))
+                .unwrap_or(false);
+            if !is_setlist {
+                continue;
+            }
+            match Self::load(&path) {
+                std::result::Result::Ok(setlist) => names.push(setlist.name),
+                Err(err) => warn!("Ignoring setlist {:?}: {}", path, err)
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn load_named(name: &str) -> Result<Self> {
+        Self::load(&Self::file_path(name)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let doc = Document::load(path)?;
+        let Some(section) = doc.section("setlist") else {
+            bail!("Setlist file {:?} has no [setlist] section", path);
+        };
+        let name = section.get("name").unwrap_or_default().to_string();
+        let config_name = section.get("config").unwrap_or_default().to_string();
+
+        let mut entries = vec![];
+        for (suffix, section) in doc.sections_with_prefix("entry") {
+            let song = section.get("song").unwrap_or_default().to_string();
+            let item = match (section.get("program"), section.get("data")) {
+                (Some(program), _) => program_from_str(program).map(SetlistItem::Program),
+                (None, Some(data)) => Some(SetlistItem::Patch {
+                    name: section.get("name").unwrap_or_default().to_string(),
+                    data: from_hex(data)?
+                }),
+                _ => None
+            };
+            let Some(item) = item else {
+                warn!("Setlist {:?}: ignoring entry {}", name, suffix);
+                continue;
+            };
+            entries.push(SetlistEntry { song, item });
+        }
+
+        Ok(Setlist { name, config_name, entries, position: None })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut doc = Document::new();
+        let section = doc.section_mut("setlist");
+        section.set("name", &self.name);
+        section.set("config", &self.config_name);
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let section = doc.section_mut(&format!("entry {}", i + 1));
+            section.set("song", &entry.song);
+            match &entry.item {
+                SetlistItem::Program(program) => {
+                    section.set("program", program_to_str(program));
+                }
+                SetlistItem::Patch { name, data } => {
+                    section.set("name", name);
+                    section.set("data", to_hex(data));
+                }
+            }
+        }
+
+        doc.save(&Self::file_path(&self.name)?)
+    }
+
+    pub fn delete(name: &str) -> Result<()> {
+        let path = Self::file_path(name)?;
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove {:?}", path))
+    }
+
+    pub fn current(&self) -> Option<&SetlistEntry> {
+        self.position.and_then(|p| self.entries.get(p))
+    }
+
+    /// Move the current position. Returns the new current entry or `None`
+    /// if the position did not change (end of the setlist, etc).
+    pub fn step(&mut self, event: &SetlistEvent) -> Option<&SetlistEntry> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let last = self.entries.len() - 1;
+        let position = match (event, self.position) {
+            (SetlistEvent::Next, None) => 0,
+            (SetlistEvent::Next, Some(p)) => (p + 1).min(last),
+            (SetlistEvent::Prev, None) => 0,
+            (SetlistEvent::Prev, Some(p)) => p.saturating_sub(1),
+            (SetlistEvent::Select(p), _) if *p <= last => *p,
+            (SetlistEvent::Select(_), _) => return None
+        };
+        if self.position == Some(position) && *event != SetlistEvent::Select(position) {
+            return None;
+        }
+        self.position = Some(position);
+        self.current()
+    }
+}
+
+/// MIDI messages on the external controller input that step through
+/// the setlist: CC numbers, triggered by values of 64 and above.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SetlistTriggers {
+    pub next_cc: Option<u8>,
+    pub prev_cc: Option<u8>
+}
+
+impl SetlistTriggers {
+    /// Load the triggers from the `[setlist]` section of the settings file
+    pub fn load() -> Result<Self> {
+        let Some(section) = load_section(&Profiles::file_path()?, "setlist")? else {
+            return Ok(Self::default());
+        };
+        Ok(SetlistTriggers {
+            next_cc: section.get_parsed("next_cc"),
+            prev_cc: section.get_parsed("prev_cc")
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        update_section(&Profiles::file_path()?, "setlist", |section| {
+            let mut set = |key: &str, value: Option<u8>| {
+                match value {
+                    Some(v) => section.set(key, v),
+                    None => section.remove(key)
+                }
+            };
+            set("next_cc", self.next_cc);
+            set("prev_cc", self.prev_cc);
+        })
+    }
+
+    /// Returns `true` if `msg` is one of the trigger CCs, whatever the value
+    pub fn matches(&self, msg: &MidiMessage) -> bool {
+        let MidiMessage::ControlChange { control, .. } = msg else { return false };
+        self.next_cc == Some(*control) || self.prev_cc == Some(*control)
+    }
+
+    pub fn event(&self, msg: &MidiMessage) -> Option<SetlistEvent> {
+        let MidiMessage::ControlChange { control, value, .. } = msg else { return None };
+        if *value < 64 {
+            return None;
+        }
+        match Some(*control) {
+            c if c == self.next_cc => Some(SetlistEvent::Next),
+            c if c == self.prev_cc => Some(SetlistEvent::Prev),
+            _ => None
+        }
+    }
+}
+
+/// Handler for setlist events: step through the setlist and send the
+/// program of the new current entry to the device. Programs are selected
+/// as if selected in the UI, which sends a PC through the PC handler,
+/// patches are loaded into the edit buffer and sent as an edit buffer
+/// dump. Returns the new position.
+pub fn setlist_handler(ctx: &Ctx, setlist: &mut Setlist, event: &SetlistEvent) -> Option<usize> {
+    if setlist.config_name != ctx.config.name {
+        warn!("Setlist {:?} is for {:?}, not {:?}", setlist.name, setlist.config_name, ctx.config.name);
+        return None;
+    }
+
+    let entry = setlist.step(event)?.clone();
+    info!("Setlist: {:?}", entry.song);
+    match entry.item {
+        SetlistItem::Program(program) => {
+            ctx.set_program(program, Origin::UI);
+        }
+        SetlistItem::Patch { name, data } => {
+            if data.len() != ctx.config.program_size {
+                error!("Setlist patch {:?} data size {} does not match program size {}",
+                    name, data.len(), ctx.config.program_size);
+                return setlist.position;
+            }
+            let e = BufferDataEvent {
+                buffer: Buffer::EditBuffer,
+                origin: Origin::MIDI,
+                request: Origin::UI,
+                data
+            };
+            ctx.app_event_tx.send_or_warn(AppEvent::BufferData(e));
+            let e = BufferStoreEvent { buffer: Buffer::EditBuffer, origin: Origin::UI };
+            ctx.app_event_tx.send_or_warn(AppEvent::Store(e));
+        }
+    }
+    setlist.position
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::setlist::*;
+
+    #[test]
+    fn step() {
+        let entry = |song: &str, p| SetlistEntry { song: song.into(), item: SetlistItem::Program(Program::Program(p)) };
+        let mut setlist = Setlist {
+            name: "Gig".into(),
+            config_name: "POD".into(),
+            entries: vec![entry("One", 3), entry("Two", 0), entry("Three", 7)],
+            position: None
+        };
+
+        assert_eq!(setlist.step(&SetlistEvent::Next).map(|e| e.song.as_str()), Some("One"));
+        assert_eq!(setlist.step(&SetlistEvent::Next).map(|e| e.song.as_str()), Some("Two"));
+        assert_eq!(setlist.step(&SetlistEvent::Select(2)).map(|e| e.song.as_str()), Some("Three"));
+        // end of the setlist
+        assert_eq!(setlist.step(&SetlistEvent::Next), None);
+        assert_eq!(setlist.position, Some(2));
+        // re-selecting resends the entry
+        assert!(setlist.step(&SetlistEvent::Select(2)).is_some());
+        assert_eq!(setlist.step(&SetlistEvent::Select(3)), None);
+        assert_eq!(setlist.step(&SetlistEvent::Prev).map(|e| e.song.as_str()), Some("Two"));
+
+        let triggers = SetlistTriggers { next_cc: Some(80), prev_cc: Some(81) };
+        let cc = |control, value| MidiMessage::ControlChange { channel: 0, control, value };
+        assert_eq!(triggers.event(&cc(80, 127)), Some(SetlistEvent::Next));
+        assert_eq!(triggers.event(&cc(81, 64)), Some(SetlistEvent::Prev));
+        assert_eq!(triggers.event(&cc(81, 0)), None);
+        assert!(triggers.matches(&cc(81, 0)) && !triggers.matches(&cc(82, 127)));
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::*;
+use crate::context::Ctx;
+use crate::dispatch::{midi_cc_in_handler, midi_pc_in_handler};
+use crate::event::*;
+use crate::midi::{Channel, MidiMessage};
+use crate::persist::*;
+use crate::profile::Profiles;
+
+/// Type of a raw MIDI message, as far as thru filtering goes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    ControlChange,
+    ProgramChange,
+    SysEx,
+    /// System real-time messages: clock, start/stop, active sensing...
+    Realtime,
+    /// Notes, pitch bend, aftertouch and other system messages
+    Other
+}
+
+impl MessageType {
+    pub fn of(bytes: &[u8]) -> Option<Self> {
+        let status = *bytes.first()?;
+        let t = match status {
+            0xb0 ..= 0xbf => MessageType::ControlChange,
+            0xc0 ..= 0xcf => MessageType::ProgramChange,
+            0xf0 => MessageType::SysEx,
+            0xf8 ..= 0xff => MessageType::Realtime,
+            0x80 ..= 0xff => MessageType::Other,
+            // running status or garbage
+            _ => return None
+        };
+        Some(t)
+    }
+}
+
+/// Channel remapping of forwarded channel messages
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThruChannel {
+    /// Keep the channel of the incoming message
+    #[default]
+    Keep,
+    /// Send on the MIDI channel of the device
+    Device,
+    /// Send on a fixed channel
+    Channel(u8)
+}
+
+/// MIDI thru routing: messages from the external controller input that
+/// pass the filters are merged into the output to the device.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MidiThru {
+    pub enabled: bool,
+    pub cc: bool,
+    pub pc: bool,
+    pub sysex: bool,
+    pub realtime: bool,
+    pub other: bool,
+    pub channel: ThruChannel
+}
+
+impl Default for MidiThru {
+    fn default() -> Self {
+        MidiThru {
+            enabled: false,
+            cc: true,
+            pc: true,
+            sysex: false,
+            realtime: false,
+            other: true,
+            channel: ThruChannel::Keep
+        }
+    }
+}
+
+impl MidiThru {
+    /// Load the thru settings from the `[thru]` section of the settings file
+    pub fn load() -> Result<Self> {
+        let mut thru = Self::default();
+        let Some(section) = load_section(&Profiles::file_path()?, "thru")? else {
+            return Ok(thru);
+        };
+
+        thru.enabled = section.get_bool("enabled").unwrap_or(thru.enabled);
+        thru.cc = section.get_bool("cc").unwrap_or(thru.cc);
+        thru.pc = section.get_bool("pc").unwrap_or(thru.pc);
+        thru.sysex = section.get_bool("sysex").unwrap_or(thru.sysex);
+        thru.realtime = section.get_bool("realtime").unwrap_or(thru.realtime);
+        thru.other = section.get_bool("other").unwrap_or(thru.other);
+        thru.channel = match section.get("channel") {
+            Some("device") => ThruChannel::Device,
+            Some(v) => v.parse::<u8>().ok()
+                .filter(|c| (1 ..= 16).contains(c))
+                .map(|c| ThruChannel::Channel(c - 1))
+                .unwrap_or_default(),
+            None => ThruChannel::Keep
+        };
+        Ok(thru)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        update_section(&Profiles::file_path()?, "thru", |section| {
+            section.set_bool("enabled", self.enabled);
+            section.set_bool("cc", self.cc);
+            section.set_bool("pc", self.pc);
+            section.set_bool("sysex", self.sysex);
+            section.set_bool("realtime", self.realtime);
+            section.set_bool("other", self.other);
+            match self.channel {
+                ThruChannel::Keep => section.set("channel", "keep"),
+                ThruChannel::Device => section.set("channel", "device"),
+                ThruChannel::Channel(c) => section.set("channel", c + 1)
+            }
+        })
+    }
+
+    pub fn accepts(&self, message_type: MessageType) -> bool {
+        match message_type {
+            MessageType::ControlChange => self.cc,
+            MessageType::ProgramChange => self.pc,
+            MessageType::SysEx => self.sysex,
+            MessageType::Realtime => self.realtime,
+            MessageType::Other => self.other
+        }
+    }
+
+    /// Filter and remap a raw MIDI message. Returns `None` if the message
+    /// is not to be forwarded.
+    pub fn route(&self, bytes: &[u8], device_channel: u8) -> Option<Vec<u8>> {
+        if !self.enabled {
+            return None;
+        }
+        let message_type = MessageType::of(bytes)?;
+        if !self.accepts(message_type) {
+            return None;
+        }
+
+        let mut bytes = bytes.to_vec();
+        let status = bytes[0];
+        if status < 0xf0 {
+            let channel = match self.channel {
+                ThruChannel::Keep => None,
+                // omni device takes whatever channel
+                ThruChannel::Device if device_channel == Channel::all() => None,
+                ThruChannel::Device => Some(device_channel),
+                ThruChannel::Channel(c) => Some(c)
+            };
+            if let Some(channel) = channel {
+                bytes[0] = (status & 0xf0) | (channel & 0x0f);
+            }
+        }
+        Some(bytes)
+    }
+}
+
+/// Handler for raw MIDI messages from the external controller input.
+/// Forwarded CC and PC messages are also run through the MIDI in handlers,
+/// so that the UI follows what the external controller did to the device.
+pub fn ext_midi_thru_handler(ctx: &Ctx, thru: &MidiThru, bytes: &[u8]) {
+    let Some(bytes) = thru.route(bytes, ctx.midi_channel()) else { return };
+
+    match MessageType::of(&bytes) {
+        Some(MessageType::ControlChange) | Some(MessageType::ProgramChange) => {
+            match MidiMessage::from_bytes(bytes.clone()) {
+                std::result::Result::Ok(msg @ MidiMessage::ControlChange { .. }) => {
+                    midi_cc_in_handler(ctx, &msg);
+                }
+                std::result::Result::Ok(msg @ MidiMessage::ProgramChange { .. }) => {
+                    midi_pc_in_handler(ctx, &msg);
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+
+    ctx.app_event_tx.send_or_warn(AppEvent::MidiOut(bytes));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thru::*;
+
+    #[test]
+    fn route_filter_and_remap() {
+        let mut thru = MidiThru { enabled: true, ..Default::default() };
+        let cc = [0xb3, 7, 100];
+        assert_eq!(thru.route(&cc, 0), Some(cc.to_vec()));
+        assert_eq!(thru.route(&[0xf8], 0), None);
+        assert_eq!(thru.route(&[0xf0, 0x7e, 0xf7], 0), None);
+
+        thru.channel = ThruChannel::Device;
+        assert_eq!(thru.route(&cc, 1), Some(vec![0xb1, 7, 100]));
+        assert_eq!(thru.route(&cc, Channel::all()), Some(cc.to_vec()));
+
+        thru.channel = ThruChannel::Channel(15);
+        assert_eq!(thru.route(&[0xc0, 5], 0), Some(vec![0xcf, 5]));
+
+        thru.cc = false;
+        assert_eq!(thru.route(&cc, 0), None);
+        thru.enabled = false;
+        assert_eq!(thru.route(&[0xc0, 5], 0), None);
+    }
+}
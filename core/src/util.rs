@@ -1,3 +1,4 @@
+use std::sync::atomic;
 use arrayref::{array_mut_ref, array_ref};
 use log::*;
 
@@ -103,4 +104,12 @@ pub fn is_valid_char(c: char) -> bool {
 pub fn program_id_string(i: usize) -> String {
     let (a, b) = (i / 4, i % 4);
     format!("{}{}", a + 1, char::from_u32('A' as u32 + b as u32).unwrap())
-}
\ No newline at end of file
+}
+
+/// A virtual thread id to show in logs to be able to trace thread start/stop.
+/// This is just a running number with no connection to the real thread id.
+
+static THREAD_ID_COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
+pub fn next_thread_id() -> usize {
+    THREAD_ID_COUNTER.fetch_add(1, atomic::Ordering::SeqCst)
+}
@@ -4,16 +4,17 @@ use pod_core::model::Config;
 use anyhow::Result;
 use multimap::MultiMap;
 use pod_core::edit::EditBuffer;
-use pod_core::handler::BoxedHandler;
+use pod_core::device::BoxedDevice;
 
 use crate::ObjectList;
 
 pub type Callbacks = MultiMap<String, Rc<dyn Fn() -> ()>>;
 
-pub trait Module {
-    fn config(&self) -> Box<[Config]>;
+/// The GTK interface part of a device module. The configs & handler
+/// are provided by the toolkit-independent `Device`.
+pub trait Module: Send + Sync {
+    fn device(&self) -> BoxedDevice;
     fn init(&self, config: &'static Config) -> Box<dyn Interface>;
-    fn handler(&self, config: &'static Config) -> BoxedHandler;
 }
 
 pub trait Interface {
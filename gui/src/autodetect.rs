@@ -2,48 +2,66 @@ use std::sync::{Arc, Mutex};
 use anyhow::*;
 use core::result::Result::Ok;
 use log::*;
-use pod_core::config::configs;
+use pod_core::config::{config_for_str, configs};
+use pod_core::engine::{set_ext_midi_in, State};
+use pod_core::event::{AppEvent, NotificationEvent, SenderExt};
 use pod_core::midi::Channel;
 use pod_core::midi_io::*;
 use pod_core::model::Config;
+use pod_core::profile::{Profile, Profiles};
 use pod_gtk::prelude::*;
 use crate::opts::Opts;
-use crate::{set_midi_in_out, State};
+use crate::set_midi_in_out;
 
-fn config_for_str(config_str: &str) -> Result<&'static Config> {
-    use std::str::FromStr;
-    use regex::Regex;
+/// An autodetected device: its MIDI ports, channel and config
+pub type Detected = (MidiIn, MidiOut, u8, &'static Config);
 
-    let n_re = Regex::new(r"\d+").unwrap();
-
-    let mut found = None;
-    if n_re.is_match(&config_str) {
-        let index = usize::from_str(&config_str)
-            .with_context(|| format!("Unrecognized config index {:?}", config_str))?;
-        let config = configs().get(index)
-            .with_context(|| format!("Config with index {} not found!", index))?;
-        found = Some(config);
-    } else {
-        for c in configs().iter() {
-            if c.name.eq_ignore_ascii_case(&config_str) {
-                found = Some(c);
-                break;
+/// The saved connection to use when no ports are given on the command
+/// line: the `--profile` one or the one used last
+fn saved_profile(opts: &Opts) -> Option<Profile> {
+    let profiles = Profiles::load()
+        .map_err(|err| warn!("Failed to load saved settings: {}", err))
+        .ok()?;
+    match &opts.profile {
+        Some(name) => {
+            let profile = profiles.get(name).cloned();
+            if profile.is_none() {
+                warn!("Profile {:?} not found, available profiles: {:?}", name, profiles.names());
             }
+            profile
         }
-        if found.is_none() {
-            bail!("Config with name {:?} not found!", config_str);
-        }
+        None => profiles.last
     }
-
-    Ok(found.unwrap())
 }
 
-pub fn detect(state: Arc<Mutex<State>>, opts: Opts, window: &gtk::Window) -> Result<()> {
+/// Open the device connection of the primary window on start: the ports
+/// given on the command line, the saved connection or the autodetected
+/// device. Any other autodetected devices are passed to `other` to be
+/// opened in windows of their own.
+pub fn detect<F>(state: Arc<Mutex<State>>, opts: Opts, window: &gtk::Window, other: F) -> Result<()>
+    where F: Fn(Detected) + 'static
+{
     let mut ports = None;
     let mut config = None;
 
+    let saved = match (&opts.input, &opts.output) {
+        (None, None) => saved_profile(&opts),
+        _ => None
+    };
+
     // autodetect/open midi
     let autodetect = match (&opts.input, &opts.output, &opts.model) {
+        (None, None, _) if saved.as_ref().map(|p| p.has_ports()).unwrap_or(false) => {
+            let profile = saved.as_ref().unwrap();
+            ports = profile.open_ports();
+            config = profile.model.as_ref()
+                .and_then(|name| configs().iter().find(|c| &c.name == name));
+            if opts.model.is_some() {
+                config = opts.model.as_ref().map(|m| config_for_str(m)).transpose()?;
+            }
+            info!("Using saved connection {:?}", profile);
+            ports.is_none() || config.is_none()
+        }
         (None, None, None) => true,
         (None, None, Some(_)) => {
             warn!("Model set on command line, but not input/output ports. \
@@ -68,6 +86,7 @@ pub fn detect(state: Arc<Mutex<State>>, opts: Opts, window: &gtk::Window) -> Res
         }
     };
     let midi_channel = match opts.channel {
+        None if ports.is_some() => saved.as_ref().and_then(|p| p.midi_channel),
         None  => None,
         Some(x) if x == 0 => Some(Channel::all()),
         Some(x) if (1u8 ..= 16).contains(&x) => Some(x - 1),
@@ -78,6 +97,14 @@ pub fn detect(state: Arc<Mutex<State>>, opts: Opts, window: &gtk::Window) -> Res
     // channel, when not auto-detected
     let midi_channel_u8 = midi_channel.unwrap_or(Channel::all());
 
+    // external controller input (MIDI learn)
+    if let Some(name) = saved.as_ref().and_then(|p| p.controller_in.as_ref()) {
+        match MidiIn::new_for_name(name) {
+            Ok(midi_in) => set_ext_midi_in(&mut state.lock().unwrap(), Some(midi_in)),
+            Err(err) => warn!("Failed to open controller MIDI input {:?}: {}", name, err)
+        }
+    }
+
     let state = state.clone();
     let window = window.clone();
     let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
@@ -88,24 +115,28 @@ pub fn detect(state: Arc<Mutex<State>>, opts: Opts, window: &gtk::Window) -> Res
                 // autodetect device on provided ports
                 pod_core::midi_io::autodetect_with_ports(
                     vec![midi_in], vec![midi_out], midi_channel
-                ).await
+                ).await.map(|detected| vec![detected])
             } else {
-                // autodetect device
-                pod_core::midi_io::autodetect(midi_channel).await
+                // autodetect all devices
+                pod_core::midi_io::autodetect_all(midi_channel).await
             };
             tx.send(res).ok();
         });
     } else {
         // manually configured device
         let (midi_in, midi_out) = ports.unwrap();
-        tx.send(Ok((midi_in, midi_out, midi_channel_u8, config.unwrap()))).ok();
+        tx.send(Ok(vec![(midi_in, midi_out, midi_channel_u8, config.unwrap())])).ok();
     }
 
     rx.attach(None, move |autodetect| {
         match autodetect {
-            Ok((midi_in, midi_out, midi_channel, config)) => {
-                set_midi_in_out(&mut state.lock().unwrap(),
-                                Some(midi_in), Some(midi_out), midi_channel, Some(config));
+            Ok(detected) => {
+                let mut detected = detected.into_iter();
+                if let Some((midi_in, midi_out, midi_channel, config)) = detected.next() {
+                    set_midi_in_out(&mut state.lock().unwrap(),
+                                    Some(midi_in), Some(midi_out), midi_channel, Some(config), true);
+                }
+                detected.for_each(&other);
             }
             Err(e) => {
                 error!("MIDI autodetect failed: {}", e);
@@ -128,9 +159,20 @@ pub fn detect(state: Arc<Mutex<State>>, opts: Opts, window: &gtk::Window) -> Res
 
                 let config = opts.model.as_ref()
                     .and_then(|str| config_for_str(&str).ok())
+                    .or_else(|| {
+                        let model = saved.as_ref().and_then(|p| p.model.as_ref())?;
+                        configs().iter().find(|c| &c.name == model)
+                    })
                     .or_else(|| configs().iter().next());
-                set_midi_in_out(&mut state.lock().unwrap(),
-                                None, None, midi_channel_u8, config);
+                let mut state = state.lock().unwrap();
+                set_midi_in_out(&mut state, None, None, midi_channel_u8, config, true);
+
+                if let Some(config) = config {
+                    let msg = format!("No device found, editing offline as {}. \
+                                       Pick a different model or connect a device in Settings.",
+                                      config.name);
+                    state.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+                }
             }
         };
 
@@ -138,4 +180,48 @@ pub fn detect(state: Arc<Mutex<State>>, opts: Opts, window: &gtk::Window) -> Res
     });
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Start the device connection of another window with a device found
+/// by autodetect. Only the primary window connection is remembered.
+pub fn connect_detected(state: &Arc<Mutex<State>>, detected: Detected) {
+    let (midi_in, midi_out, midi_channel, config) = detected;
+    set_midi_in_out(&mut state.lock().unwrap(),
+                    Some(midi_in), Some(midi_out), midi_channel, Some(config), false);
+}
+
+/// Autodetect a device for a new window, skipping the devices on the
+/// MIDI input ports of the other windows, `in_use`. If none is found,
+/// the first device model is edited offline.
+pub fn detect_other(state: Arc<Mutex<State>>, in_use: Vec<String>) {
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    tokio::spawn(async move {
+        let res = pod_core::midi_io::autodetect_all(None).await
+            .and_then(|detected| {
+                detected.into_iter()
+                    .find(|(midi_in, _, _, _)| !in_use.contains(&midi_in.name))
+                    .ok_or_else(|| anyhow!("All detected devices are already open in other windows"))
+            });
+        tx.send(res).ok();
+    });
+
+    rx.attach(None, move |detected| {
+        match detected {
+            Ok(detected) => connect_detected(&state, detected),
+            Err(e) => {
+                error!("MIDI autodetect failed: {}", e);
+                let config = configs().iter().next();
+                let mut state = state.lock().unwrap();
+                set_midi_in_out(&mut state, None, None, Channel::all(), config, false);
+
+                if let Some(config) = config {
+                    let msg = format!("No other device found, editing offline as {}. \
+                                       Pick a different model or connect a device in Settings.",
+                                      config.name);
+                    state.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+                }
+            }
+        }
+        Continue(false)
+    });
+}
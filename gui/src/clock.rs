@@ -0,0 +1,72 @@
+use std::sync::{Arc, Mutex};
+use log::*;
+use pod_core::clock::{ClockSettings, ClockSource, MidiClock};
+use pod_gtk::prelude::*;
+use gtk::ResponseType;
+
+/// MIDI clock tempo tracking shared with the app event thread
+pub type MidiClockShare = Arc<Mutex<MidiClock>>;
+
+/// Show the tempo sync settings: which MIDI input the clock is taken
+/// from and how much the tempo must change before it is sent to the device
+pub fn show_clock_dialog(window: &gtk::Window, share: MidiClockShare) {
+    let settings = share.lock().unwrap().settings().clone();
+
+    let dialog = gtk::Dialog::with_buttons(
+        Some("MIDI clock sync"),
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT | gtk::DialogFlags::MODAL,
+        &[("Cancel", ResponseType::Cancel), ("OK", ResponseType::Ok)]
+    );
+    dialog.set_default_response(ResponseType::Ok);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(4);
+    grid.set_column_spacing(12);
+    grid.set_border_width(12);
+
+    let enabled = gtk::CheckButton::with_label("Follow the tempo of incoming MIDI clock");
+    enabled.set_active(settings.enabled);
+    grid.attach(&enabled, 0, 0, 2, 1);
+
+    let label = gtk::Label::new(Some("Clock source:"));
+    label.set_halign(gtk::Align::End);
+    let source = gtk::ComboBoxText::new();
+    source.append(Some("device"), "Device MIDI input");
+    source.append(Some("controller"), "Controller MIDI input");
+    source.set_active_id(Some(match settings.source {
+        ClockSource::Device => "device",
+        ClockSource::Controller => "controller"
+    }));
+    grid.attach(&label, 0, 1, 1, 1);
+    grid.attach(&source, 1, 1, 1, 1);
+
+    let label = gtk::Label::new(Some("Minimum change (bpm):"));
+    label.set_halign(gtk::Align::End);
+    let min_change = gtk::SpinButton::with_range(0.1, 10.0, 0.1);
+    min_change.set_digits(1);
+    min_change.set_value(settings.min_change);
+    min_change.set_tooltip_text(Some("Smaller tempo changes are not sent to the device"));
+    grid.attach(&label, 0, 2, 1, 1);
+    grid.attach(&min_change, 1, 2, 1, 1);
+
+    dialog.content_area().add(&grid);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Ok {
+            let settings = ClockSettings {
+                enabled: enabled.is_active(),
+                source: match source.active_id().as_deref() {
+                    Some("controller") => ClockSource::Controller,
+                    _ => ClockSource::Device
+                },
+                min_change: min_change.value()
+            };
+            settings.save()
+                .unwrap_or_else(|err| error!("Failed to save MIDI clock settings: {}", err));
+            share.lock().unwrap().set_settings(settings);
+        }
+        dialog.close();
+    });
+    dialog.show_all();
+}
@@ -0,0 +1,235 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use log::*;
+use tokio::sync::broadcast;
+use pod_core::console::*;
+use pod_core::controller::*;
+use pod_core::event::*;
+use pod_core::monitor::{describe, format_hex, Direction};
+use pod_core::thru::MessageType;
+use pod_gtk::prelude::*;
+use gtk::gdk;
+
+#[derive(Default)]
+struct ConsoleState {
+    history: ConsoleHistory,
+    /// Position in the history while stepping through it with Up/Down
+    position: Option<usize>
+}
+
+/// Developer console for protocol exploration: send arbitrary MIDI
+/// messages, typed as hex or built from a `MidiMessage` template, to
+/// the device and see the replies
+#[derive(Clone)]
+pub struct ConsoleWindow {
+    window: gtk::Window,
+    app_event_tx: broadcast::Sender<AppEvent>,
+    ui_controller: Arc<Mutex<Controller>>,
+    state: Rc<RefCell<ConsoleState>>,
+
+    text_view: gtk::TextView,
+    buffer: gtk::TextBuffer,
+    end_mark: gtk::TextMark,
+    entry: gtk::Entry
+}
+
+impl ConsoleWindow {
+    pub fn new(parent: &gtk::Window, app_event_tx: broadcast::Sender<AppEvent>,
+               ui_controller: Arc<Mutex<Controller>>) -> Self {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title("SysEx console");
+        window.set_transient_for(Some(parent));
+        window.set_destroy_with_parent(true);
+        window.set_default_size(640, 420);
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 8);
+        vbox.set_border_width(12);
+
+        // templates
+        let template_combo = gtk::ComboBoxText::new();
+        for t in TEMPLATES {
+            template_combo.append(Some(t.name), t.name);
+        }
+        let param_label = gtk::Label::new(None);
+        let param_spin = gtk::SpinButton::with_range(0.0, 127.0, 1.0);
+        let insert_button = gtk::Button::with_label("Insert");
+        insert_button.set_tooltip_text(Some("Insert the message built from the template"));
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        hbox.pack_start(&template_combo, true, true, 0);
+        hbox.pack_start(&param_label, false, false, 0);
+        hbox.pack_start(&param_spin, false, false, 0);
+        hbox.pack_start(&insert_button, false, false, 0);
+        vbox.pack_start(&hbox, false, false, 0);
+
+        // sent messages & replies
+        let buffer = gtk::TextBuffer::new(None::<&gtk::TextTagTable>);
+        let end_mark = buffer.create_mark(None, &buffer.end_iter(), false).unwrap();
+        let text_view = gtk::TextView::with_buffer(&buffer);
+        text_view.set_editable(false);
+        text_view.set_cursor_visible(false);
+        text_view.set_monospace(true);
+        let scrolled = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Automatic)
+            .vscrollbar_policy(gtk::PolicyType::Automatic)
+            .build();
+        scrolled.add(&text_view);
+        vbox.pack_start(&scrolled, true, true, 0);
+
+        // input
+        let entry = gtk::Entry::new();
+        entry.set_placeholder_text(Some("f0 7e 7f 06 01 f7"));
+        entry.set_tooltip_text(Some("Hex bytes to send. Up/Down steps through the history."));
+        entry.style_context().add_class("monospace");
+        let send_button = gtk::Button::with_label("Send");
+        let clear_button = gtk::Button::with_label("Clear");
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        hbox.pack_start(&entry, true, true, 0);
+        hbox.pack_start(&send_button, false, false, 0);
+        hbox.pack_start(&clear_button, false, false, 0);
+        vbox.pack_start(&hbox, false, false, 0);
+
+        window.add(&vbox);
+
+        let console = ConsoleWindow {
+            window, app_event_tx, ui_controller,
+            state: Rc::new(RefCell::new(ConsoleState {
+                history: ConsoleHistory::load(),
+                position: None
+            })),
+            text_view, buffer, end_mark, entry
+        };
+
+        template_combo.connect_changed({
+            let param_label = param_label.clone();
+            let param_spin = param_spin.clone();
+            move |combo| {
+                let template = combo.active_id().and_then(|id| Template::by_name(&id));
+                let param = template.and_then(|t| t.param);
+                param_label.set_text(param.map(|(name, _)| name).unwrap_or_default());
+                param_spin.set_sensitive(param.is_some());
+                if let Some((_, max)) = param {
+                    param_spin.set_range(0.0, max as f64);
+                }
+            }
+        });
+        template_combo.set_active(Some(0));
+        insert_button.connect_clicked({
+            let console = console.clone();
+            move |_| {
+                let Some(template) = template_combo.active_id().and_then(|id| Template::by_name(&id)) else {
+                    return;
+                };
+                let channel = console.ui_controller.get("midi_channel").unwrap_or_default() as u8;
+                let bytes = template.to_bytes(param_spin.value_as_int() as u16, channel);
+                console.entry.set_text(&format_hex(&bytes));
+                console.entry.grab_focus();
+            }
+        });
+        console.entry.connect_activate({
+            let console = console.clone();
+            move |_| console.send()
+        });
+        send_button.connect_clicked({
+            let console = console.clone();
+            move |_| console.send()
+        });
+        clear_button.connect_clicked({
+            let console = console.clone();
+            move |_| console.buffer.set_text("")
+        });
+        console.entry.connect_key_press_event({
+            let console = console.clone();
+            move |_, event| {
+                match event.keyval() {
+                    gdk::keys::constants::Up => console.step_history(-1),
+                    gdk::keys::constants::Down => console.step_history(1),
+                    _ => return Inhibit(false)
+                }
+                Inhibit(true)
+            }
+        });
+        console.window.connect_delete_event(|window, _| {
+            window.hide();
+            Inhibit(true)
+        });
+
+        console
+    }
+
+    pub fn show(&self) {
+        self.window.show_all();
+        self.window.present();
+        self.entry.grab_focus();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_visible()
+    }
+
+    /// Call `f` when the window is shown or hidden
+    pub fn connect_open_changed<F: Fn() + 'static>(&self, f: F) {
+        self.window.connect_visible_notify(move |_| f());
+    }
+
+    /// Show a reply from the device while the console is open. Real-time
+    /// messages are skipped.
+    pub fn record(&self, direction: Direction, bytes: &[u8]) {
+        if direction != Direction::In || !self.window.is_visible() {
+            return;
+        }
+        if MessageType::of(bytes) == Some(MessageType::Realtime) {
+            return;
+        }
+        self.append(direction, bytes);
+    }
+
+    fn append(&self, direction: Direction, bytes: &[u8]) {
+        let line = format!("{} {}\n   {}\n", direction.arrow(), describe(bytes, None, None),
+                           format_hex(bytes));
+        self.buffer.insert(&mut self.buffer.end_iter(), &line);
+        self.text_view.scroll_mark_onscreen(&self.end_mark);
+    }
+
+    fn send(&self) {
+        let command = self.entry.text().to_string();
+        let bytes = match parse_hex(&command) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                self.buffer.insert(&mut self.buffer.end_iter(), &format!("!! {}\n", err));
+                self.text_view.scroll_mark_onscreen(&self.end_mark);
+                return;
+            }
+        };
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.history.push(&command);
+            state.position = None;
+            state.history.save()
+                .unwrap_or_else(|err| error!("Failed to save console history: {}", err));
+        }
+        self.append(Direction::Out, &bytes);
+        self.app_event_tx.send_or_warn(AppEvent::MidiOut(bytes));
+        self.entry.set_text("");
+    }
+
+    /// Step through the history: -1 for older, 1 for newer commands
+    fn step_history(&self, offset: i32) {
+        let mut state = self.state.borrow_mut();
+        let len = state.history.commands.len();
+        if len == 0 {
+            return;
+        }
+        let position = match (state.position, offset < 0) {
+            (None, true) => Some(len - 1),
+            (None, false) => None,
+            (Some(p), true) => Some(p.saturating_sub(1)),
+            (Some(p), false) => Some(p + 1).filter(|p| *p < len)
+        };
+        state.position = position;
+        let text = position.map(|p| state.history.commands[p].as_str()).unwrap_or_default();
+        self.entry.set_text(text);
+        self.entry.set_position(-1);
+    }
+}
@@ -0,0 +1,231 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use log::*;
+use pod_core::learn::{Binding, MidiLearn};
+use pod_core::midi::Channel;
+use pod_core::model::Control;
+use pod_gtk::prelude::*;
+use gtk::ResponseType;
+
+/// MIDI learn state shared between the app event thread, which feeds
+/// it with the external controller messages, and the GTK thread
+pub type MidiLearnShare = Arc<Mutex<Option<MidiLearn>>>;
+
+struct Row {
+    control: String,
+    binding: gtk::Label,
+    learn: gtk::Button,
+    from: gtk::SpinButton,
+    to: gtk::SpinButton,
+    invert: gtk::CheckButton,
+    remove: gtk::Button
+}
+
+fn binding_text(binding: &Binding) -> String {
+    if binding.channel == Channel::all() {
+        format!("CC {}", binding.cc)
+    } else {
+        format!("CC {} (ch {})", binding.cc, binding.channel + 1)
+    }
+}
+
+fn update_rows(rows: &[Row], share: &MidiLearnShare) {
+    // copy the state out, widget updates below fire signal handlers
+    // that lock the learn state again
+    let (bindings, learning) = {
+        let learn = share.lock().unwrap();
+        let Some(learn) = learn.as_ref() else { return };
+        (learn.bindings.clone(), learn.learning().map(String::from))
+    };
+
+    for row in rows {
+        let binding = bindings.iter().find(|b| b.control == row.control);
+        let learning = learning.as_deref() == Some(row.control.as_str());
+
+        let text = match binding {
+            _ if learning => "Move a controller knob...".to_string(),
+            Some(b) => binding_text(b),
+            None => String::new()
+        };
+        row.binding.set_text(&text);
+        row.learn.set_label(if learning { "Cancel" } else { "Learn" });
+
+        if let Some(b) = binding {
+            row.from.set_value(b.from as f64);
+            row.to.set_value(b.to as f64);
+            row.invert.set_active(b.invert);
+        }
+        row.from.set_sensitive(binding.is_some());
+        row.to.set_sensitive(binding.is_some());
+        row.invert.set_sensitive(binding.is_some());
+        row.remove.set_sensitive(binding.is_some());
+    }
+}
+
+fn update_binding(share: &MidiLearnShare, control: &str, f: impl FnOnce(&mut Binding)) {
+    let mut learn = share.lock().unwrap();
+    let Some(learn) = learn.as_mut() else { return };
+    if let Some(b) = learn.bindings.iter_mut().find(|b| b.control == control) {
+        f(b);
+    }
+}
+
+/// Show the MIDI learn dialog listing the controls of the current device
+/// with their external controller bindings. Bindings are saved when the
+/// dialog is closed.
+pub fn show_learn_dialog(window: &gtk::Window, share: MidiLearnShare, controller_in: Option<String>) {
+    let Some(config) = share.lock().unwrap().as_ref().map(|l| l.config()) else {
+        warn!("MIDI learn: no device");
+        return;
+    };
+
+    let dialog = gtk::Dialog::with_buttons(
+        Some("MIDI learn"),
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[("Close", ResponseType::Close)]
+    );
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(4);
+    grid.set_column_spacing(12);
+    grid.set_border_width(12);
+
+    let header = gtk::Label::new(None);
+    match &controller_in {
+        Some(name) => header.set_markup(&format!(
+            "Controls of {} bound to CCs of the external controller at <b>{}</b>:",
+            glib::markup_escape_text(&config.name), glib::markup_escape_text(name)
+        )),
+        None => header.set_markup(
+            "No controller MIDI input selected, choose one in the settings dialog"
+        )
+    }
+    header.set_halign(gtk::Align::Start);
+    grid.attach(&header, 0, 0, 7, 1);
+
+    for (i, title) in ["Control", "Binding", "", "From", "To", "Invert", ""].iter().enumerate() {
+        let label = gtk::Label::new(None);
+        label.set_markup(&format!("<b>{}</b>", title));
+        label.set_halign(gtk::Align::Start);
+        grid.attach(&label, i as i32, 1, 1, 1);
+    }
+
+    let mut controls = config.controls.iter()
+        .filter(|(_, c)| !matches!(c, Control::Button(_)))
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    controls.sort();
+
+    let mut rows = vec![];
+    for (row, control) in controls.into_iter().enumerate() {
+        let row = row as i32 + 2;
+        let name = gtk::Label::new(Some(&control));
+        name.set_halign(gtk::Align::Start);
+        let binding = gtk::Label::new(None);
+        binding.set_halign(gtk::Align::Start);
+        let learn = gtk::Button::with_label("Learn");
+        learn.set_sensitive(controller_in.is_some());
+        let from = gtk::SpinButton::with_range(0.0, 127.0, 1.0);
+        let to = gtk::SpinButton::with_range(0.0, 127.0, 1.0);
+        let invert = gtk::CheckButton::new();
+        let remove = gtk::Button::from_icon_name(Some("edit-delete-symbolic"), gtk::IconSize::Button);
+        remove.set_tooltip_text(Some("Remove binding"));
+
+        grid.attach(&name, 0, row, 1, 1);
+        grid.attach(&binding, 1, row, 1, 1);
+        grid.attach(&learn, 2, row, 1, 1);
+        grid.attach(&from, 3, row, 1, 1);
+        grid.attach(&to, 4, row, 1, 1);
+        grid.attach(&invert, 5, row, 1, 1);
+        grid.attach(&remove, 6, row, 1, 1);
+        rows.push(Row { control, binding, learn, from, to, invert, remove });
+    }
+    let rows = Rc::new(rows);
+    update_rows(&rows, &share);
+
+    for row in rows.iter() {
+        let control = row.control.clone();
+        row.learn.connect_clicked({
+            let share = share.clone();
+            let rows = rows.clone();
+            let control = control.clone();
+            move |_| {
+                if let Some(learn) = share.lock().unwrap().as_mut() {
+                    let learning = learn.learning() == Some(control.as_str());
+                    learn.learn(if learning { None } else { Some(control.clone()) });
+                }
+                update_rows(&rows, &share);
+            }
+        });
+        row.from.connect_value_changed({
+            let share = share.clone();
+            let control = control.clone();
+            move |spin| update_binding(&share, &control, |b| b.from = spin.value_as_int() as u8)
+        });
+        row.to.connect_value_changed({
+            let share = share.clone();
+            let control = control.clone();
+            move |spin| update_binding(&share, &control, |b| b.to = spin.value_as_int() as u8)
+        });
+        row.invert.connect_toggled({
+            let share = share.clone();
+            let control = control.clone();
+            move |check| update_binding(&share, &control, |b| b.invert = check.is_active())
+        });
+        row.remove.connect_clicked({
+            let share = share.clone();
+            let rows = rows.clone();
+            move |_| {
+                if let Some(learn) = share.lock().unwrap().as_mut() {
+                    learn.unbind(&control);
+                }
+                update_rows(&rows, &share);
+            }
+        });
+    }
+
+    // learning completes in the app event thread, poll for it
+    let was_learning = Cell::new(false);
+    let visible = Rc::new(Cell::new(true));
+    glib::timeout_add_local(Duration::from_millis(200), {
+        let share = share.clone();
+        let rows = rows.clone();
+        let visible = visible.clone();
+        move || {
+            if !visible.get() {
+                return Continue(false);
+            }
+            let learning = share.lock().unwrap().as_ref()
+                .map(|l| l.learning().is_some())
+                .unwrap_or_default();
+            if was_learning.get() && !learning {
+                update_rows(&rows, &share);
+            }
+            was_learning.set(learning);
+            Continue(true)
+        }
+    });
+
+    let scrolled = gtk::ScrolledWindow::builder()
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .vscrollbar_policy(gtk::PolicyType::Automatic)
+        .propagate_natural_height(true)
+        .max_content_height(500)
+        .build();
+    scrolled.add(&grid);
+    dialog.content_area().add(&scrolled);
+
+    dialog.connect_response(move |dialog, _| {
+        visible.set(false);
+        if let Some(learn) = share.lock().unwrap().as_mut() {
+            learn.learn(None);
+            learn.save()
+                .unwrap_or_else(|err| error!("Failed to save MIDI learn bindings: {}", err));
+        }
+        dialog.close();
+    });
+    dialog.show_all();
+}
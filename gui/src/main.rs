@@ -7,7 +7,21 @@ mod widgets;
 mod autodetect;
 mod check;
 mod icon;
-
+mod offline;
+mod session;
+mod learn;
+mod thru;
+mod pcmap;
+mod setlist;
+mod stage;
+mod monitor;
+mod console;
+mod osc;
+mod api;
+mod clock;
+mod windows;
+
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::sync::{Arc, atomic, Mutex};
 use std::time::{Duration, Instant};
@@ -16,40 +30,57 @@ use clap::{Args, Command, FromArgMatches};
 use core::result::Result::Ok;
 use std::env;
 use std::rc::Rc;
-use futures_util::future::{join_all, JoinAll};
-use futures_util::FutureExt;
 use log::*;
 use maplit::*;
 use once_cell::sync::Lazy;
-use tokio::sync::{broadcast, oneshot};
+use tokio::sync::broadcast;
 use tokio::sync::broadcast::error::RecvError;
-use tokio::task::JoinHandle;
-use tokio::time::sleep;
 use pod_gtk::prelude::*;
 use pod_core::midi_io::*;
+use pod_core::bank::Bank;
 use pod_core::context::Ctx;
 use pod_core::controller::*;
+use pod_core::engine;
+use pod_core::engine::{Engine, EngineEvent, Services, State};
 use pod_core::event::*;
+use pod_core::osc::OscSettings;
+use pod_core::profile::save_last_connection;
+use pod_core::api::{ApiContext, ApiSettings};
+use pod_core::remote::RemoteCommand;
+use pod_core::device::init_device_controls;
 use pod_core::dispatch::*;
 use pod_core::dump::ProgramsDump;
-use pod_core::midi::MidiMessage;
-use pod_core::model::{Button, Config, Control, DeviceFlags, MidiQuirks, VirtualSelect};
-use pod_core::program_id_string;
+use pod_core::model::{Button, Config, Control, DeviceFlags, VirtualSelect};
+use pod_core::monitor::Direction;
+use pod_core::offline::SyncSlot;
+use pod_core::{next_thread_id, program_id_string};
 use pod_gtk::logic::LogicBuilder;
 use pod_gtk::prelude::gtk::gdk;
+use crate::autodetect::{connect_detected, detect_other, Detected};
 use crate::check::{current_platform, new_release_check};
 use crate::icon::set_app_icon;
+use crate::learn::*;
+use crate::thru::*;
+use crate::pcmap::*;
+use crate::setlist::*;
+use crate::stage::*;
+use crate::monitor::*;
+use crate::console::*;
+use crate::osc::*;
+use crate::api::*;
+use crate::clock::*;
+use crate::offline::*;
 use crate::opts::*;
 use crate::panic::*;
 use crate::registry::*;
+use crate::session::*;
 use crate::settings::*;
-use crate::util::{next_thread_id, SenderExt as SenderExt2};
+use crate::util::SenderExt as SenderExt2;
 use crate::widgets::*;
 use crate::widgets::templated::Templated;
+use crate::windows::*;
 
 const MIDI_OUT_CHANNEL_CAPACITY: usize = 512;
-const CLOSE_QUIET_DURATION_MS: u64 = 1000;
-
 
 #[derive(Clone, Debug)]
 pub enum UIEvent {
@@ -58,32 +89,18 @@ pub enum UIEvent {
     NewConfig,
     MidiTx,
     MidiRx,
+    /// Device MIDI traffic for the MIDI monitor and the SysEx console
+    MidiMonitor(Direction, Vec<u8>),
     Panic,
     Modified(usize, bool),
     Name(usize, String),
+    Sync(Vec<SyncSlot>),
+    Setlist(Option<usize>),
     Notification(String, Option<String>),
     Shutdown,
     Quit
 }
 
-pub struct State {
-    pub midi_in_name: Option<String>,
-    pub midi_in_cancel: Option<oneshot::Sender<()>>,
-    pub midi_in_handle: Option<JoinHandle<()>>,
-
-    pub midi_out_name: Option<String>,
-    pub midi_out_cancel: Option<oneshot::Sender<()>>,
-    pub midi_out_handle: Option<JoinHandle<()>>,
-
-    pub midi_channel_num: u8,
-
-    pub app_event_tx: broadcast::Sender<AppEvent>,
-    pub ui_event_tx: glib::Sender<UIEvent>,
-
-    pub config: Option<&'static Config>,
-    pub detected: Option<DeviceDetectedEvent>,
-}
-
 static UI_CONTROLS: Lazy<HashMap<String, Control>> = Lazy::new(|| {
     convert_args!(hashmap!(
         "midi_channel" => VirtualSelect::default(),
@@ -101,6 +118,8 @@ static UI_CONTROLS: Lazy<HashMap<String, Control>> = Lazy::new(|| {
         "manual_mode_present" => VirtualSelect::default(),
         // This will be always-on for now
         "tuner_present" => VirtualSelect::default(),
+        // Set when there is no MIDI device connected
+        "offline" => VirtualSelect::default(),
     ))
 });
 
@@ -135,173 +154,23 @@ fn sentry_set_device_tags(detected_name: &String, detected_ver: &String, config_
     })
 }
 
-pub fn midi_in_out_stop(state: &mut State) -> JoinAll<JoinHandle<()>> {
-    state.midi_in_cancel.take().map(|cancel| cancel.send(()));
-    state.midi_out_cancel.take().map(|cancel| cancel.send(()));
-
-    let handles = state.midi_in_handle.take().into_iter()
-        .chain(state.midi_out_handle.take().into_iter());
-    join_all(handles)
-}
-
-pub fn midi_in_out_start(state: &mut State,
-                         midi_in: Option<MidiIn>, midi_out: Option<MidiOut>,
-                         midi_channel: u8, quirks: MidiQuirks,
-                         config_changed: bool) {
-
-    let notify = |state: &mut State| {
-        sentry_set_midi_tags(state.midi_in_name.as_ref(), state.midi_out_name.as_ref());
-
-        let e = NewConfigEvent {
-            midi_changed: true,
-            midi_channel: state.midi_channel_num,
-            config_changed
-        };
-        state.app_event_tx.send_or_warn(AppEvent::NewConfig(e));
-    };
-
-    if midi_in.is_none() || midi_out.is_none() {
-        warn!("Not starting MIDI because in/out is None");
-        state.midi_in_name = None;
-        state.midi_in_cancel = None;
-        state.midi_out_name = None;
-        state.midi_out_cancel = None;
-        state.midi_channel_num = midi_channel;
-        notify(state);
-        return;
-    }
-
-    let mut midi_in = midi_in.unwrap();
-    let mut midi_out = midi_out.unwrap();
-
-    let (in_cancel_tx, in_cancel_rx) = oneshot::channel::<()>();
-    let (out_cancel_tx, out_cancel_rx) = oneshot::channel::<()>();
-
-    state.midi_in_name = Some(midi_in.name.clone());
-    state.midi_in_cancel = Some(in_cancel_tx);
-
-    state.midi_out_name = Some(midi_out.name.clone());
-    state.midi_out_cancel = Some(out_cancel_tx);
-
-    state.midi_channel_num = midi_channel;
-
-    notify(state);
-
-    // midi in
-    let midi_in_handle =
-        tokio::spawn({
-            let app_event_tx = state.app_event_tx.clone();
-            let ui_event_tx = state.ui_event_tx.clone();
-            let mut in_cancel_rx = in_cancel_rx.fuse();
-
-            async move {
-                let id = next_thread_id();
-                let mut close_quiet_duration: Option<Duration> = None;
-
-                info!("MIDI in thread {:?} start", id);
-                loop {
-                    tokio::select! {
-                        msg = midi_in.recv() => {
-                            match msg {
-                                Some(bytes) => {
-                                    app_event_tx.send_or_warn(AppEvent::MidiIn(bytes));
-                                    ui_event_tx.send_or_warn(UIEvent::MidiRx);
-                                }
-                                _ => {}
-                            }
-                        }
-                        _ = &mut in_cancel_rx => {
-                            if quirks.contains(MidiQuirks::MIDI_CLOSE_QUIET_TIMEOUT) {
-                                debug!("close_quiet_duration set!");
-                                close_quiet_duration = Some(Duration::from_millis(CLOSE_QUIET_DURATION_MS));
-                            } else {
-                                break;
-                            }
-                        }
-                        _ = async {
-                            if let Some(d) = close_quiet_duration {
-                                sleep(d).await
-                            } else {
-                                std::future::pending::<()>().await
-                            }
-                        } => {
-                            break;
-                        }
-                    }
-                }
-                midi_in.close();
-                info!("MIDI in thread {:?} finish", id);
-            }
-        });
-
-    // midi out
-    let midi_out_handle =
-        tokio::spawn({
-            let ui_event_tx = state.ui_event_tx.clone();
-            let mut app_event_rx = state.app_event_tx.subscribe();
-            let mut out_cancel_rx = out_cancel_rx.fuse();
-
-            async move {
-                let id = next_thread_id();
-                info!("MIDI out thread {:?} start", id);
-                loop {
-                    tokio::select! {
-                        msg = app_event_rx.recv() => {
-                            match msg {
-                                Ok(AppEvent::MidiOut(bytes)) => {
-                                    midi_out.send(&bytes)
-                                    .unwrap_or_else(|e| error!("MIDI OUT thread tx error: {}", e));
-                                    ui_event_tx.send_or_warn(UIEvent::MidiTx);
-                                }
-                                Err(err) => {
-                                    error!("MIDI OUT thread rx error: {:?}", err);
-                                }
-                                _ => {}
-                            }
-                        }
-                        _ = &mut out_cancel_rx => {
-                            midi_out.close();
-                            break;
-                        }
-                    }
-                }
-                midi_out.close();
-                info!("MIDI out thread {:?} finish", id);
-            }
-        });
-
-    state.midi_in_handle = Some(midi_in_handle);
-    state.midi_out_handle = Some(midi_out_handle);
-}
-
+/// Install `config` & start the device MIDI connection. With `remember`,
+/// the connection is saved as the last one used. Returns `true` if the
+/// config changed.
 pub fn set_midi_in_out(state: &mut State, midi_in: Option<MidiIn>, midi_out: Option<MidiOut>,
-                       midi_channel: u8, config: Option<&'static Config>) -> bool {
+                       midi_channel: u8, config: Option<&'static Config>, remember: bool) -> bool {
     if state.midi_in_cancel.is_some() || state.midi_out_cancel.is_some() {
-        error!("Midi still running when entering send_midi_in_out");
         // Not sure if we ever end up in this situation anymore,
         // let's just register it to sentry for not
         sentry::capture_message("Midi still running when entering send_midi_in_out",
                                 sentry::Level::Error);
-        //midi_in_out_stop(state);
     }
 
-    let config_changed = match (config, state.config) {
-        (Some(a), Some(b)) => { *a != *b }
-        (Some(_), None) => { true }
-        _ => { false }
-    };
-    if config_changed {
-        // config changed, update config & edit buffer
-        let config = config.unwrap();
-        info!("Installing config {:?}", &config.name);
-
-        state.config.replace(config);
+    let config_changed = engine::set_midi_in_out(state, midi_in, midi_out, midi_channel, config);
+    if let Some(last) = state.connection().filter(|_| remember) {
+        save_last_connection(last);
     }
 
-    let quirks = state.config.map(|c| c.midi_quirks)
-        .unwrap_or_else(|| MidiQuirks::empty());
-    midi_in_out_start(state, midi_in, midi_out, midi_channel, quirks, config_changed);
-
     config_changed
 }
 
@@ -542,30 +411,6 @@ fn make_window_smaller(window: gtk::Window) {
         });
 }
 
-pub fn ui_modified_handler(ctx: &Ctx, event: &ModifiedEvent, ui_event_tx: &glib::Sender<UIEvent>) {
-    match event.buffer {
-        Buffer::EditBuffer => { /* don't touch event buffer */ }
-        Buffer::Current => {
-            let program = match ctx.program() {
-                Program::Program(v) => { v as usize }
-                _ => { return; }
-            };
-            ui_event_tx.send(UIEvent::Modified(program, event.modified))
-                .unwrap();
-        }
-        Buffer::Program(program) => {
-            ui_event_tx.send(UIEvent::Modified(program, event.modified))
-                .unwrap();
-        }
-        Buffer::All => {
-            for program in 0 .. ctx.config.program_num {
-                ui_event_tx.send(UIEvent::Modified(program, event.modified))
-                    .unwrap();
-            }
-        }
-    }
-}
-
 #[tokio::main]
 async fn main() -> Result<()> {
     let _guard = sentry::init((option_env!("SENTRY_DSN"), sentry::ClientOptions {
@@ -594,6 +439,7 @@ async fn main() -> Result<()> {
 
     let cli = Opts::augment_args(cli);
     let opts: Opts = Opts::from_arg_matches(&cli.get_matches())?;
+    let commands = opts.remote_commands()?;
     drop(help_text);
 
     // glib::set_program_name needs to come before gtk::init!
@@ -607,6 +453,22 @@ async fn main() -> Result<()> {
             gio::ApplicationFlags::empty()
         }
     );
+
+    // forward commands to an already-running instance, if there is one
+    if !opts.standalone && !commands.is_empty() {
+        app.register(gio::Cancellable::NONE)?;
+        if app.is_remote() {
+            for command in commands.iter() {
+                info!("Forwarding command: {}", command);
+                app.activate_action("remote", Some(&command.to_string().to_variant()));
+            }
+            if let Some(connection) = app.dbus_connection() {
+                connection.flush_sync(gio::Cancellable::NONE)?;
+            }
+            return Ok(());
+        }
+    }
+
     app.connect_activate(move |app| {
         if let Some(win) = app.windows().first() {
             win.present();
@@ -620,7 +482,7 @@ async fn main() -> Result<()> {
         debug!("Application menu: {}", app.prefers_app_menu());
         if app.prefers_app_menu() {
             let menu = gio::Menu::new();
-            menu.append(Some("Settings"), Some("app.preferences"));
+            menu.append(Some("Settings"), Some("win.preferences"));
             menu.append(Some("Quit"), Some("app.quit"));
             app.set_app_menu(Some(&menu));
         }
@@ -630,24 +492,92 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// How a device window finds its device
+enum WindowStart {
+    /// The primary window: the ports given on the command line, the
+    /// saved connection or the autodetected device
+    Primary(Opts),
+    /// Another device found by autodetect on start
+    Detected(Detected),
+    /// A window opened from the menu: autodetect a device that is not
+    /// open in another window
+    Detect
+}
+
 fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled: bool) {
-    let (app_event_tx, mut app_event_rx) = broadcast::channel::<AppEvent>(MIDI_OUT_CHANNEL_CAPACITY);
-    let (ui_event_tx, ui_event_rx) = glib::MainContext::channel::<UIEvent>(glib::PRIORITY_DEFAULT);
-    let state = Arc::new(Mutex::new(State {
-        midi_in_name: None,
-        midi_in_cancel: None,
-        midi_in_handle: None,
-        midi_out_name: None,
-        midi_out_cancel: None,
-        midi_out_handle: None,
-        midi_channel_num: 0,
-        app_event_tx: app_event_tx.clone(),
-        ui_event_tx: ui_event_tx.clone(),
-        config: None,
-        detected: None,
-    }));
+    let windows = DeviceWindows::new(Services::load());
 
-    let ctx_share = Arc::new(Mutex::new(Option::<Ctx>::None));
+    // app-wide actions, the others are actions of the device windows
+    let quit_action = gio::ActionEntry::builder("quit")
+        .activate({
+            let windows = windows.clone();
+            move |_, _, _| {
+                info!("Shutting down...");
+                for w in windows.all() {
+                    w.app_event_tx.send_or_warn(AppEvent::Shutdown);
+                }
+            }
+        }).build();
+    let new_window_action = gio::ActionEntry::builder("new-window")
+        .activate({
+            let windows = windows.clone();
+            let title = title.clone();
+            move |app: &gtk::Application, _, _| {
+                open_window(app, &title, WindowStart::Detect, sentry_enabled, &windows);
+            }
+        }).build();
+    let remote_action = gio::ActionEntry::builder("remote")
+        .parameter_type(String::static_variant_type().as_str())
+        .activate({
+            let windows = windows.clone();
+            move |_, _, param| {
+                let Some(str) = param.and_then(|p| p.get::<String>()) else {
+                    warn!("Remote command without a parameter");
+                    return;
+                };
+                // remote commands control the primary window
+                let Some(primary) = windows.primary() else { return };
+                match RemoteCommand::parse(&str) {
+                    Ok(command) => primary.app_event_tx.send_or_warn(AppEvent::Remote(command)),
+                    Err(err) => warn!("{}", err)
+                }
+            }
+        }).build();
+    app.add_action_entries([quit_action, new_window_action, remote_action]).unwrap();
+    app.set_accels_for_action("win.setlist-next", &["Page_Down"]);
+    app.set_accels_for_action("win.setlist-prev", &["Page_Up"]);
+    app.set_accels_for_action("win.stage", &["F11"]);
+
+    open_window(app, title, WindowStart::Primary(opts), sentry_enabled, &windows);
+}
+
+/// Open a device window with an engine of its own, sharing the app
+/// services with the other windows
+fn open_window(app: &gtk::Application, title: &String, start: WindowStart, sentry_enabled: bool,
+               windows: &DeviceWindows) {
+    let primary = matches!(start, WindowStart::Primary(_));
+    // the HTTP API serves the device of the primary window, the other
+    // windows get an API context of their own that no server uses
+    let services = if primary {
+        windows.services.clone()
+    } else {
+        Services { api_context: ApiContext::default(), ..windows.services.clone() }
+    };
+    let engine = Engine::new(services);
+    let app_event_tx = engine.sender();
+    let (ui_event_tx, ui_event_rx) = glib::MainContext::channel::<UIEvent>(glib::PRIORITY_DEFAULT);
+    let state = engine.state();
+
+    let services = engine.services().clone();
+    let midi_learn: MidiLearnShare = services.midi_learn;
+    let pc_map: PcMapShare = services.pc_map;
+    let setlist: SetlistShare = services.setlist;
+    let setlist_triggers: SetlistTriggersShare = services.setlist_triggers;
+    let midi_thru: MidiThruShare = services.midi_thru;
+    let midi_clock: MidiClockShare = services.midi_clock;
+    let osc_server: OscServerShare = services.osc_server;
+    let api_server: ApiServerShare = windows.api_server.clone();
+    let api_context = windows.services.api_context.clone();
 
     if let Some(path) = env::var("GTK_ADD_ICON_PATH").ok() {
         let icon_theme = gtk::IconTheme::default().unwrap();
@@ -666,26 +596,184 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
     let window: gtk::Window = app_window.clone().into();
     window.set_application(Some(app));
     window.set_title(&title);
+
+    let device_window = DeviceWindow {
+        window: app_window.clone(),
+        state: state.clone(),
+        app_event_tx: app_event_tx.clone(),
+        ui_controller: ui_controller.clone(),
+        device: Rc::new(RefCell::new(None)),
+        primary
+    };
+    windows.add(device_window.clone());
+    // the OSC and HTTP API servers send their commands to the primary window
+    let remote_tx = windows.primary()
+        .map(|w| w.app_event_tx)
+        .unwrap_or_else(|| app_event_tx.clone());
+
+    // closing the primary window quits, closing another one only shuts
+    // down its engine
+    let closing = Rc::new(Cell::new(false));
     window.connect_delete_event({
         let app = app.clone();
+        let app_event_tx = app_event_tx.clone();
+        let closing = closing.clone();
         move |_, _| {
+            if closing.get() {
+                return Inhibit(false);
+            }
             info!("Window delete...");
-            app.activate_action("quit", None);
+            if primary {
+                app.activate_action("quit", None);
+            } else {
+                app_event_tx.send_or_warn(AppEvent::Shutdown);
+            }
             Inhibit(true)
         }
     });
 
     // connect & register signals
-    let quit_action = gio::ActionEntry::builder("quit")
+    let preferences_action = create_settings_action(state.clone(), &ui, primary);
+    let learn_action = gio::ActionEntry::builder("midi-learn")
         .activate({
-            let app_event_tx = app_event_tx.clone();
+            let state = state.clone();
+            let midi_learn = midi_learn.clone();
+            let window = window.clone();
             move |_, _, _| {
-                info!("Shutting down...");
-                app_event_tx.send_or_warn(AppEvent::Shutdown);
+                let controller_in = state.lock().unwrap().ext_in_name.clone();
+                show_learn_dialog(&window, midi_learn.clone(), controller_in);
+            }
+        }).build();
+    let thru_action = gio::ActionEntry::builder("midi-thru")
+        .activate({
+            let midi_thru = midi_thru.clone();
+            let window = window.clone();
+            move |_, _, _| {
+                show_thru_dialog(&window, midi_thru.clone());
+            }
+        }).build();
+    let clock_action = gio::ActionEntry::builder("midi-clock")
+        .activate({
+            let midi_clock = midi_clock.clone();
+            let window = window.clone();
+            move |_, _, _| {
+                show_clock_dialog(&window, midi_clock.clone());
+            }
+        }).build();
+    let pc_map_action = gio::ActionEntry::builder("pc-map")
+        .activate({
+            let pc_map = pc_map.clone();
+            let window = window.clone();
+            move |_, _, _| {
+                show_pc_map_dialog(&window, pc_map.clone());
+            }
+        }).build();
+    let osc_action = gio::ActionEntry::builder("osc")
+        .activate({
+            let osc_server = osc_server.clone();
+            let remote_tx = remote_tx.clone();
+            let window = window.clone();
+            move |_, _, _| {
+                show_osc_dialog(&window, osc_server.clone(), remote_tx.clone());
+            }
+        }).build();
+    let api_action = gio::ActionEntry::builder("http-api")
+        .activate({
+            let api_server = api_server.clone();
+            let api_context = api_context.clone();
+            let remote_tx = remote_tx.clone();
+            let window = window.clone();
+            move |_, _, _| {
+                show_api_dialog(&window, api_server.clone(), api_context.clone(), remote_tx.clone());
             }
         }).build();
-    let preferences_action = create_settings_action(state.clone(), &ui);
-    app.add_action_entries([quit_action, preferences_action]).unwrap();
+    let setlist_window = SetlistWindow::new(&app_window, setlist.clone(),
+                                            setlist_triggers.clone(), app_event_tx.clone());
+    let setlist_action = gio::ActionEntry::builder("setlist")
+        .activate({
+            let setlist_window = setlist_window.clone();
+            move |_, _, _| setlist_window.show()
+        }).build();
+    let stage_view = StageView::new(&app_window, ui_controller.clone());
+    let stage_action = gio::ActionEntry::builder("stage")
+        .activate({
+            let stage_view = stage_view.clone();
+            move |_, _, _| stage_view.show()
+        }).build();
+    let monitor_window = MonitorWindow::new(&window, app_event_tx.clone());
+    let monitor_action = gio::ActionEntry::builder("midi-monitor")
+        .activate({
+            let monitor_window = monitor_window.clone();
+            move |_, _, _| monitor_window.show()
+        }).build();
+    let console_window = ConsoleWindow::new(&window, app_event_tx.clone(), ui_controller.clone());
+    let console_action = gio::ActionEntry::builder("sysex-console")
+        .activate({
+            let console_window = console_window.clone();
+            move |_, _, _| console_window.show()
+        }).build();
+    // device MIDI traffic only goes to the GTK thread while there is a
+    // window to show it in
+    let midi_monitored = Arc::new(atomic::AtomicBool::new(false));
+    let update_monitored = Rc::new({
+        let monitor_window = monitor_window.clone();
+        let console_window = console_window.clone();
+        let midi_monitored = midi_monitored.clone();
+        move || {
+            let open = monitor_window.is_open() || console_window.is_open();
+            midi_monitored.store(open, atomic::Ordering::Relaxed);
+        }
+    });
+    monitor_window.connect_open_changed({
+        let update_monitored = update_monitored.clone();
+        move || update_monitored()
+    });
+    console_window.connect_open_changed(move || update_monitored());
+    let copy_action = gio::ActionEntry::builder("copy-program")
+        .activate({
+            let windows = windows.clone();
+            let device_window = device_window.clone();
+            move |_, _, _| windows.copy_program(&device_window)
+        }).build();
+    let paste_action = gio::ActionEntry::builder("paste-program")
+        .activate({
+            let windows = windows.clone();
+            let device_window = device_window.clone();
+            move |_, _, _| windows.paste_program(&device_window)
+        }).build();
+    let setlist_step_actions = [("setlist-next", SetlistEvent::Next), ("setlist-prev", SetlistEvent::Prev)]
+        .into_iter()
+        .map(|(name, event)| {
+            let app_event_tx = app_event_tx.clone();
+            gio::ActionEntry::builder(name)
+                .activate(move |_, _, _| {
+                    app_event_tx.send_or_warn(AppEvent::Setlist(event.clone()));
+                }).build()
+        });
+    app_window.add_action_entries([preferences_action, learn_action, thru_action,
+        clock_action, pc_map_action, setlist_action, stage_action, monitor_action, console_action, osc_action,
+        api_action, copy_action, paste_action]).unwrap();
+    app_window.add_action_entries(setlist_step_actions).unwrap();
+    setlist_window.set_actions_enabled(false);
+
+    let menu = gio::Menu::new();
+    menu.append(Some("MIDI learn..."), Some("win.midi-learn"));
+    menu.append(Some("MIDI thru..."), Some("win.midi-thru"));
+    menu.append(Some("MIDI clock sync..."), Some("win.midi-clock"));
+    menu.append(Some("Program change map..."), Some("win.pc-map"));
+    menu.append(Some("Setlist..."), Some("win.setlist"));
+    menu.append(Some("Stage view"), Some("win.stage"));
+    menu.append(Some("MIDI monitor..."), Some("win.midi-monitor"));
+    menu.append(Some("SysEx console..."), Some("win.sysex-console"));
+    menu.append(Some("OSC server..."), Some("win.osc"));
+    menu.append(Some("HTTP API..."), Some("win.http-api"));
+    let devices_menu = gio::Menu::new();
+    devices_menu.append(Some("New device window"), Some("app.new-window"));
+    devices_menu.append(Some("Copy program"), Some("win.copy-program"));
+    devices_menu.append(Some("Paste program"), Some("win.paste-program"));
+    menu.append_section(None, &devices_menu);
+    let menu_button: gtk::MenuButton = ui.object("menu_button").unwrap();
+    menu_button.set_menu_model(Some(&menu));
 
     set_app_icon(&window).expect("Failed to test application icon");
     // Re-parent window content into a notification overlay
@@ -698,7 +786,7 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
     wire_ui_controls(ui_controller.clone(), &ui_objects, &mut ui_callbacks,
                      app_event_tx.clone())
         .expect("Failed to wire controls");
-    wire_panic_indicator(state.clone());
+    wire_panic_indicator(ui_event_tx.clone());
     wire_open_button(&ui, &window);
 
     let css = gtk::CssProvider::new();
@@ -710,183 +798,127 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
         gtk::STYLE_PROVIDER_PRIORITY_APPLICATION
     );
 
-    // autodetect or open devices specified on command line
-    autodetect::detect(state.clone(), opts, &window)
-        .expect("Autodetect failed");
-    new_release_check(&app_event_tx);
+    // commands given on the command line of this (primary) instance
+    // are executed once the device is ready
+    let startup_commands = match &start {
+        WindowStart::Primary(opts) => opts.remote_commands()
+            .map_err(|err| error!("{}", err))
+            .unwrap_or_default(),
+        _ => vec![]
+    };
 
-    // app event handling in a separate thread
+    // engine events are handled on the GTK thread
     tokio::spawn({
-        let app_event_tx = app_event_tx.clone();
+        let mut engine_event_rx = engine.subscribe();
         let ui_event_tx = ui_event_tx.clone();
-        let ctx_share = ctx_share.clone();
 
         async move {
-            let mut ctx: Option<Ctx> = None;
-
             loop {
-                let msg = match app_event_rx.recv().await {
-                    Ok(msg) => { msg }
+                let event = match engine_event_rx.recv().await {
+                    Ok(event) => { event }
                     Err(RecvError::Closed) => {
-                        info!("App event bus closed");
+                        info!("Engine event bus closed");
                         return;
                     }
                     Err(RecvError::Lagged(n)) => {
-                        error!("App event bus lagged: {}", n);
+                        error!("Engine event bus lagged: {}", n);
                         continue;
                     }
                 };
-                debug!("== {:?}", msg);
-
-                // execute device-specific handlers
-                if let Some(ctx) = &ctx {
-                    match &msg {
-                        // device inquiry
-                        AppEvent::MidiMsgIn(msg @ MidiMessage::UniversalDeviceInquiry { .. }) |
-                        AppEvent::MidiMsgIn(msg @ MidiMessage::UniversalDeviceInquiryResponse { .. }) => {
-                            midi_udi_handler(ctx, msg);
-                        }
-
-                        // control change
-                        AppEvent::MidiMsgIn(msg @ MidiMessage::ControlChange { .. }) => {
-                            midi_cc_in_handler(ctx, msg);
-                        }
-                        AppEvent::MidiMsgOut(msg @ MidiMessage::ControlChange { .. }) => {
-                            midi_cc_out_handler(ctx, msg);
-                        }
-                        AppEvent::ControlChange(cc) => {
-                            cc_handler(ctx, cc);
-                        }
-
-                        // program change
-                        AppEvent::MidiMsgIn(msg @ MidiMessage::ProgramChange { .. }) => {
-                            midi_pc_in_handler(ctx, msg);
-                        }
-                        AppEvent::MidiMsgOut(msg @ MidiMessage::ProgramChange { .. }) => {
-                            midi_pc_out_handler(ctx, msg);
-                        }
-                        AppEvent::ProgramChange(pc) => {
-                            pc_handler(ctx, pc);
-                        }
-
-                        // store & load
-                        AppEvent::Load(event) => {
-                            load_handler(ctx, event)
-                        }
-                        AppEvent::Store(event) => {
-                            store_handler(ctx, event)
-                        }
-                        AppEvent::Copy(event) => {
-                            copy_handler(ctx, event)
-                        }
-                        AppEvent::BufferData(event) => {
-                            buffer_handler(ctx, event)
-                        }
-                        AppEvent::Modified(event) => {
-                            modified_handler(ctx, event);
-                            ui_modified_handler(ctx, event, &ui_event_tx)
-                        }
-
-                        // other
-                        AppEvent::MidiMsgIn(msg) => {
-                            midi_in_handler(ctx, msg);
-                        }
-                        AppEvent::MidiMsgOut(msg) => {
-                            midi_out_handler(ctx, msg);
-                        }
-                        AppEvent::Marker(marker) => {
-                           marker_handler(ctx, *marker);
-                        }
-
-                        // silently ignoring
-                        AppEvent::MidiIn(_) | AppEvent::MidiOut(_)  => { /* handled in MIDI OUT thread */ }
-                        e if is_system_app_event(e) => {}
-
-                        // error message
-                        _ => {
-                            error!("Unhandled app event: {:?}", msg);
-                        }
-                    }
-                } else {
-                    if !is_system_app_event(&msg) {
-                        warn!("MIDI CC event {:?} without context", msg);
-                    }
-                }
-
-                // execute system handlers
-                match &msg {
-                    // device detected
-                    AppEvent::DeviceDetected(event) => {
-                        sentry_set_device_tags(
-                            &event.name,
-                            &event.version,
-                            &ctx.as_ref().map(|ctx| ctx.config.name.clone())
-                                .unwrap_or_else(|| "?".into())
-                        );
-                        ui_event_tx.send_or_warn(UIEvent::DeviceDetected(event.clone()));
-                    }
-                    // forward notification messages to the UI thread
-                    AppEvent::Notification(event) => {
-                        ui_event_tx.send_or_warn(UIEvent::Notification(event.msg.clone(), event.id.clone()));
-                    }
-                    // new config & shutdown
-                    AppEvent::NewConfig(event) => {
-                        if event.midi_changed {
-                            if let Some(ctx) = &ctx {
-                                ctx.set_midi_channel(event.midi_channel);
-                            }
-                            ui_event_tx.send_or_warn(UIEvent::NewMidiConnection);
-                        }
-                        if event.config_changed {
-                            // transfer Ctx ownership to the UI thread and
-                            // ask it to initialize a new Ctx
-                            let mut ctx_share = ctx_share.lock().unwrap();
-                            *ctx_share = ctx.take();
+                let event = match event {
+                    EngineEvent::DeviceDetected(event) => UIEvent::DeviceDetected(event),
+                    EngineEvent::NewMidiConnection => UIEvent::NewMidiConnection,
+                    EngineEvent::NewConfig => UIEvent::NewConfig,
+                    EngineEvent::MidiTx => UIEvent::MidiTx,
+                    EngineEvent::MidiRx => UIEvent::MidiRx,
+                    EngineEvent::Modified(program, modified) => UIEvent::Modified(program, modified),
+                    EngineEvent::Sync(slots) => UIEvent::Sync(slots),
+                    EngineEvent::Setlist(position) => UIEvent::Setlist(position),
+                    EngineEvent::Notification(msg, id) => UIEvent::Notification(msg, id),
+                    EngineEvent::Shutdown => UIEvent::Shutdown
+                };
+                ui_event_tx.send_or_warn(event);
+            }
+        }
+    });
 
-                            ui_event_tx.send_or_warn(UIEvent::NewConfig);
-                        }
-                    }
-                    AppEvent::NewCtx => {
-                        trace!("New context installed...");
-                        let mut ctx_share = ctx_share.lock().unwrap();
-                        ctx.replace(ctx_share.take().unwrap());
-                        new_device_handler(ctx.as_ref().unwrap());
-                    }
-                    AppEvent::Shutdown => {
-                        ui_event_tx.send_or_warn(UIEvent::Shutdown);
-                    }
+    // device MIDI traffic is shown by the MIDI monitor and the SysEx console
+    // on the GTK thread, while one of them is open
+    tokio::spawn({
+        let mut app_event_rx = app_event_tx.subscribe();
+        let ui_event_tx = ui_event_tx.clone();
 
-                    // message conversion
-                    AppEvent::MidiIn(bytes) => {
-                        // todo: do not clone
-                        // todo: report to sentry if message conversion failed?
-                        if let Some(msg) = MidiMessage::from_bytes(bytes.clone())
-                            .map_err(|e| error!("{}", e)).ok() {
-                            app_event_tx.send_or_warn(AppEvent::MidiMsgIn(msg));
-                        }
+        async move {
+            loop {
+                let event = match app_event_rx.recv().await {
+                    Ok(AppEvent::MidiIn(_) | AppEvent::MidiOut(_))
+                        if !midi_monitored.load(atomic::Ordering::Relaxed) => continue,
+                    Ok(AppEvent::MidiIn(bytes)) => UIEvent::MidiMonitor(Direction::In, bytes),
+                    Ok(AppEvent::MidiOut(bytes)) => UIEvent::MidiMonitor(Direction::Out, bytes),
+                    Ok(_) => continue,
+                    Err(RecvError::Closed) => {
+                        info!("App event bus closed");
+                        return;
                     }
-                    AppEvent::MidiMsgOut(msg) => {
-                        let bytes = MidiMessage::to_bytes(&msg);
-                        app_event_tx.send_or_warn(AppEvent::MidiOut(bytes));
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("MIDI monitor lagged: {}", n);
+                        continue;
                     }
-
-                    // silently ignore everything else
-                    _ => {}
-                }
+                };
+                ui_event_tx.send_or_warn(event);
             }
-
         }
     });
 
+    // app event handling in a separate thread
+    engine.start(startup_commands);
+
+    match start {
+        WindowStart::Primary(opts) => {
+            // autodetect or open devices specified on command line,
+            // other detected devices get windows of their own
+            autodetect::detect(state.clone(), opts, &window, {
+                let app = app.clone();
+                let title = title.clone();
+                let windows = windows.clone();
+                move |detected| {
+                    open_window(&app, &title, WindowStart::Detected(detected), sentry_enabled, &windows);
+                }
+            }).expect("Autodetect failed");
+        }
+        WindowStart::Detected(detected) => connect_detected(&state, detected),
+        WindowStart::Detect => detect_other(state.clone(), windows.ports_in_use())
+    }
+    if primary {
+        new_release_check(&app_event_tx);
+        match OscSettings::load() {
+            Ok(settings) => start_osc_server(&osc_server, &settings, &app_event_tx),
+            Err(err) => error!("Failed to load OSC settings: {}", err)
+        }
+        match ApiSettings::load() {
+            Ok(settings) => start_api_server(&api_server, &settings, &api_context, &app_event_tx),
+            Err(err) => error!("Failed to load HTTP API settings: {}", err)
+        }
+    }
+
     // run UI controller callback on the GTK thread
-    start_controller_rx(ui_controller.clone(), ui_objects, ui_callbacks, |_,_,_| {});
+    start_controller_rx(ui_controller.clone(), ui_objects, ui_callbacks, {
+        let stage_view = stage_view.clone();
+        move |name, value, _| {
+            if name == "program" {
+                stage_view.set_program(value);
+            }
+        }
+    });
 
     // run UI event handling on the GTK thread
     ui_event_rx.attach(None, {
         let ui_event_tx = ui_event_tx.clone();
         let app = app.clone();
+        let windows = windows.clone();
 
         let mut program_grid: Option<ProgramGrid> = None;
+        let mut session_autosave: Option<SessionAutosave> = None;
         let mut shutting_down = false;
         let window = window.clone();
         let header_bar: gtk::HeaderBar = ui.object("header_bar").unwrap();
@@ -902,8 +934,6 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
         move |event| {
             match event {
                 UIEvent::NewConfig => {
-                    dispatch_buffer_clear();
-
                     let state = state.lock().unwrap();
                     let config = state.config.unwrap();
 
@@ -916,7 +946,7 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                         return Continue(true);
                     };
 
-                    if let Some(ctx) = &*ctx_share.lock().unwrap() {
+                    if let Some(ctx) = engine.take_ctx() {
                         // close channels
                         ctx.controller.broadcast(None);
                         ctx.dump.lock().unwrap().broadcast_names(None);
@@ -930,9 +960,11 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                     {
                         // start event handlers
                         let app_event_tx = app_event_tx.clone();
+                        let stage_view = stage_view.clone();
                         start_controller_rx(
                             controller.clone(), objs, callbacks,
                             move |name, value, origin| {
+                                stage_view.set_control(&name, value);
                                 let e = ControlChangeEvent { name, value, origin };
                                 app_event_tx.send_or_warn(AppEvent::ControlChange(e));
                             }
@@ -941,17 +973,18 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                         start_names_rx(ui_event_tx.clone(), interface.dump.clone());
                     }
 
+                    let reroute = BufferReroute::default();
                     let ctx = Ctx {
                         config,
                         controller,
                         handler,
                         edit: interface.edit_buffer.clone(),
                         dump: interface.dump.clone(),
+                        reroute: reroute.clone(),
                         ui_controller: ui_controller.clone(),
                         app_event_tx: app_event_tx.clone()
                     };
-                    ctx_share.lock().unwrap().replace(ctx);
-                    app_event_tx.send_or_warn(AppEvent::NewCtx);
+                    engine.set_ctx(ctx);
 
                     // attach new device UI
 
@@ -964,7 +997,7 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                     // emit other (synthetic or otherwise) animate() calls in their
                     // wiring. So, we both animate() as part of init_module()
                     // (needed to hide most controls that hide before first show)
-                    // and defer an init_module_controls() call that needs to happen
+                    // and defer an init_device_controls() call that needs to happen
                     // after the rx is subscribed to again!
                     glib::idle_add_local_once({
                         let config = config.clone();
@@ -972,7 +1005,7 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
 
                         move || {
                             let edit_buffer = edit_buffer.lock().unwrap();
-                            init_module_controls(&config, &edit_buffer)
+                            init_device_controls(&config, &edit_buffer)
                                 .unwrap_or_else(|err| error!("{}", err));
                         }
                     });
@@ -996,11 +1029,25 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                             p.join_radio_group(Option::<&gtk::RadioButton>::None);
                         });
 
+                    device_window.device.replace(Some(WindowDevice {
+                        config,
+                        edit: interface.edit_buffer.clone(),
+                        dump: interface.dump.clone()
+                    }));
+                    setlist_window.set_device(config, interface.edit_buffer.clone(),
+                                              interface.dump.clone());
+                    stage_view.set_device(config, &interface.dump.lock().unwrap());
+                    monitor_window.set_device(config, interface.dump.clone());
+
                     let program_num = config.program_num;
                     let g = ProgramGrid::new(program_num);
                     grid.attach(&g, 0, 1, 2, 18);
                     g.connect_action({
                         let app_event_tx = app_event_tx.clone();
+                        let edit = interface.edit_buffer.clone();
+                        let dump = interface.dump.clone();
+                        let ui_controller = ui_controller.clone();
+                        let reroute = reroute.clone();
                         move |action| {
                             match action {
                                 ProgramGridAction::Load { program } => {
@@ -1011,7 +1058,7 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                                     app_event_tx.send_or_warn(AppEvent::Copy(e));
                                 }
                                 ProgramGridAction::LoadUnmodified { program } => {
-                                    dispatch_buffer_set(Buffer::Program(program), Buffer::EditBuffer);
+                                    reroute.set(Buffer::Program(program), Buffer::EditBuffer);
                                     let e = BufferLoadEvent { buffer: Buffer::Program(program), origin: Origin::UI };
                                     app_event_tx.send_or_warn(AppEvent::Load(e));
                                 }
@@ -1030,6 +1077,54 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                                     let e = BufferStoreEvent { buffer: Buffer::Program(program), origin: Origin::UI };
                                     app_event_tx.send_or_warn(AppEvent::Store(e));
                                 }
+                                ProgramGridAction::Swap { program, with } => {
+                                    let e = ReorderEvent::Swap { a: program, b: with };
+                                    app_event_tx.send_or_warn(AppEvent::Reorder(e));
+                                }
+                                ProgramGridAction::Move { from, to } => {
+                                    let e = ReorderEvent::Move { from, to };
+                                    app_event_tx.send_or_warn(AppEvent::Reorder(e));
+                                }
+                                ProgramGridAction::Insert { program } => {
+                                    let e = ReorderEvent::Insert { at: program };
+                                    app_event_tx.send_or_warn(AppEvent::Reorder(e));
+                                }
+                                ProgramGridAction::LoadDeviceMany { programs } => {
+                                    for program in programs {
+                                        let e = BufferLoadEvent { buffer: Buffer::Program(program), origin: Origin::UI };
+                                        app_event_tx.send_or_warn(AppEvent::Load(e));
+                                    }
+                                }
+                                ProgramGridAction::StoreDeviceMany { programs } => {
+                                    for program in programs {
+                                        let e = BufferStoreEvent { buffer: Buffer::Program(program), origin: Origin::UI };
+                                        app_event_tx.send_or_warn(AppEvent::Store(e));
+                                    }
+                                }
+                                ProgramGridAction::Reset { programs } => {
+                                    let e = ReorderEvent::Reset { programs };
+                                    app_event_tx.send_or_warn(AppEvent::Reorder(e));
+                                }
+                                ProgramGridAction::CopyTo { from, to } => {
+                                    let e = ReorderEvent::Copy { from, to };
+                                    app_event_tx.send_or_warn(AppEvent::Reorder(e));
+                                }
+                                ProgramGridAction::Export { programs, path } => {
+                                    let program: Program = ui_controller.get("program").unwrap().into();
+                                    let bank = Bank::capture(
+                                        config, program, &edit.lock().unwrap(),
+                                        &dump.lock().unwrap(), &programs
+                                    );
+                                    let msg = match bank.save(&path) {
+                                        Ok(_) => format!("Exported {} program(s) to {}",
+                                                         bank.programs.len(), path.display()),
+                                        Err(err) => {
+                                            error!("Failed to export programs: {}", err);
+                                            format!("Failed to export programs: {}", err)
+                                        }
+                                    };
+                                    app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+                                }
                             };
                         }
                     });
@@ -1055,23 +1150,45 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                                            state.config.unwrap().flags.contains(DeviceFlags::MANUAL_MODE) as u16,
                                            StoreOrigin::NONE, Signal::Force);
                     ui_controller.set_full("tuner_present",
-                                           1, StoreOrigin::NONE, Signal::Force);
+                                           state.config.unwrap().has_tuner() as u16,
+                                           StoreOrigin::NONE, Signal::Force);
                     // activate the hidden "program:1000" button, deactivating
                     // all other program buttons
                     ui_controller.set_full("program",
                                            1000, StoreOrigin::NONE, Signal::Force);
 
                     make_window_smaller(window.clone());
+
+                    // save the session of the previous device and offer
+                    // to restore the saved session of this one
+                    if let Some(autosave) = session_autosave.take() {
+                        autosave.save();
+                        autosave.stop();
+                    }
+                    let autosave = SessionAutosave::new(
+                        config, interface.edit_buffer.clone(), interface.dump.clone(),
+                        ui_controller.clone()
+                    );
+                    offer_restore(&window, autosave.clone(), app_event_tx.clone());
+                    session_autosave.replace(autosave);
                 }
                 UIEvent::Modified(page, modified) => {
                     if let Some(grid) = &program_grid {
                         grid.set_program_modified(page, modified);
                     }
+                    stage_view.set_modified(page, modified);
                 }
                 UIEvent::Name(page, name) => {
                     if let Some(grid) = &program_grid {
                         grid.set_program_name(page, &name);
                     }
+                    stage_view.set_name(page, &name);
+                }
+                UIEvent::Sync(slots) => {
+                    show_sync_dialog(&window, slots, app_event_tx.clone());
+                }
+                UIEvent::Setlist(position) => {
+                    setlist_window.set_position(position);
                 }
                 UIEvent::MidiTx => {
                     transfer_icon_up.set_opacity(1.0);
@@ -1105,10 +1222,20 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                             });
                     }
                 }
+                UIEvent::MidiMonitor(direction, bytes) => {
+                    console_window.record(direction, &bytes);
+                    monitor_window.record(direction, bytes);
+                }
                 UIEvent::DeviceDetected(event) => {
                     // TODO: this, strictly speaking, doesn't need to be in State,
                     //       it can be a local to the UI thread
                     let mut state = state.lock().unwrap();
+                    sentry_set_device_tags(
+                        &event.name,
+                        &event.version,
+                        &state.config.map(|c| c.name.clone())
+                            .unwrap_or_else(|| "?".into())
+                    );
                     state.detected.replace(event);
                     ui_event_tx.send_or_warn(UIEvent::NewMidiConnection);
                 }
@@ -1116,6 +1243,7 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                     let state = state.lock().unwrap();
                     let midi_in_name = state.midi_in_name.as_ref();
                     let midi_out_name = state.midi_out_name.as_ref();
+                    sentry_set_midi_tags(midi_in_name, midi_out_name);
                     let name = {
                         let config_name = &state.config.map(|c| c.name.clone())
                             .unwrap_or_else(|| String::new());
@@ -1141,7 +1269,7 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                     };
                     let subtitle = match (midi_in_name, midi_out_name) {
                         (None, _) | (_, None) => {
-                            "no device connected".to_string()
+                            "offline, no device connected".to_string()
                         }
                         (Some(a), Some(b)) if a == b => {
                             format!("{}", a)
@@ -1172,8 +1300,16 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                     header_bar.set_subtitle(Some("Shutting down..."));
                     shutting_down = true;
 
-                    let mut state = state.lock().unwrap();
-                    let handle = midi_in_out_stop(&mut state);
+                    if let Some(autosave) = &session_autosave {
+                        autosave.save();
+                        autosave.stop();
+                    }
+
+                    if primary {
+                        osc_server.lock().unwrap().take();
+                        api_server.lock().unwrap().take();
+                    }
+                    let handle = engine.stop();
                     let ui_tx = ui_event_tx.clone();
                     tokio::spawn(async move {
                         handle.await;
@@ -1214,8 +1350,13 @@ fn activate(app: &gtk::Application, title: &String, opts: Opts, sentry_enabled:
                     }
                     r.emit_by_name::<()>("group-changed", &[]);
 
-                    // quit
-                    app.quit();
+                    // close the window, quitting once the last one is closed
+                    if windows.remove(&app_window) {
+                        app.quit();
+                    } else {
+                        closing.set(true);
+                        window.close();
+                    }
                 }
             }
 
@@ -0,0 +1,242 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use log::*;
+use tokio::sync::broadcast;
+use pod_core::dump::ProgramsDump;
+use pod_core::event::*;
+use pod_core::model::Config;
+use pod_core::monitor::*;
+use pod_gtk::prelude::*;
+
+const COLUMNS: &[&str] = &["Time", "", "Message", "Hex"];
+
+#[derive(Clone)]
+struct Device {
+    config: &'static Config,
+    dump: Arc<Mutex<ProgramsDump>>
+}
+
+/// Live view of the MIDI messages to and from the device: time,
+/// direction, the decoded message and the raw bytes
+#[derive(Clone)]
+pub struct MonitorWindow {
+    window: gtk::Window,
+    app_event_tx: broadcast::Sender<AppEvent>,
+    monitor: Rc<RefCell<MidiMonitor>>,
+    device: Rc<RefCell<Option<Device>>>,
+
+    store: gtk::ListStore,
+    scrolled: gtk::ScrolledWindow
+}
+
+impl MonitorWindow {
+    pub fn new(parent: &gtk::Window, app_event_tx: broadcast::Sender<AppEvent>) -> Self {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title("MIDI monitor");
+        window.set_transient_for(Some(parent));
+        window.set_destroy_with_parent(true);
+        window.set_default_size(760, 480);
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 8);
+        vbox.set_border_width(12);
+
+        // filters
+        let filter = MonitorFilter::default();
+        let checks = [
+            ("In", filter.incoming),
+            ("Out", filter.outgoing),
+            ("CC", filter.cc),
+            ("PC", filter.pc),
+            ("SysEx", filter.sysex),
+            ("Real-time", filter.realtime),
+            ("Other", filter.other),
+        ].iter().map(|(title, active)| {
+            let check = gtk::CheckButton::with_label(title);
+            check.set_active(*active);
+            check
+        }).collect::<Vec<_>>();
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        for check in checks.iter() {
+            hbox.pack_start(check, false, false, 0);
+        }
+
+        let pause_button = gtk::ToggleButton::with_label("Pause");
+        let clear_button = gtk::Button::with_label("Clear");
+        let save_button = gtk::Button::with_label("Save...");
+        hbox.pack_end(&save_button, false, false, 0);
+        hbox.pack_end(&clear_button, false, false, 0);
+        hbox.pack_end(&pause_button, false, false, 0);
+        vbox.pack_start(&hbox, false, false, 0);
+
+        // messages
+        let store = gtk::ListStore::new(&[glib::Type::STRING; 4]);
+        let tree_view = gtk::TreeView::with_model(&store);
+        for (i, title) in COLUMNS.iter().enumerate() {
+            let cell = gtk::CellRendererText::new();
+            if i != 2 {
+                cell.set_property("family", "monospace");
+            }
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            TreeViewColumnExt::pack_start(&column, &cell, true);
+            TreeViewColumnExt::add_attribute(&column, &cell, "text", i as i32);
+            tree_view.append_column(&column);
+        }
+        let scrolled = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Automatic)
+            .vscrollbar_policy(gtk::PolicyType::Automatic)
+            .build();
+        scrolled.add(&tree_view);
+        vbox.pack_start(&scrolled, true, true, 0);
+
+        window.add(&vbox);
+
+        let monitor = MonitorWindow {
+            window, app_event_tx,
+            monitor: Rc::new(RefCell::new(MidiMonitor::new())),
+            device: Rc::new(RefCell::new(None)),
+            store, scrolled
+        };
+
+        for check in checks.iter() {
+            let monitor = monitor.clone();
+            let checks = checks.clone();
+            check.connect_toggled(move |_| {
+                let active = checks.iter().map(|c| c.is_active()).collect::<Vec<_>>();
+                monitor.monitor.borrow_mut().filter = MonitorFilter {
+                    incoming: active[0],
+                    outgoing: active[1],
+                    cc: active[2],
+                    pc: active[3],
+                    sysex: active[4],
+                    realtime: active[5],
+                    other: active[6]
+                };
+                monitor.refresh();
+            });
+        }
+        pause_button.connect_toggled({
+            let monitor = monitor.clone();
+            move |button| {
+                monitor.monitor.borrow_mut().paused = button.is_active();
+            }
+        });
+        clear_button.connect_clicked({
+            let monitor = monitor.clone();
+            move |_| {
+                monitor.monitor.borrow_mut().clear();
+                monitor.store.clear();
+            }
+        });
+        save_button.connect_clicked({
+            let monitor = monitor.clone();
+            move |_| monitor.save()
+        });
+        monitor.window.connect_delete_event(|window, _| {
+            window.hide();
+            Inhibit(true)
+        });
+
+        monitor
+    }
+
+    pub fn show(&self) {
+        self.refresh();
+        self.window.show_all();
+        self.window.present();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_visible()
+    }
+
+    /// Call `f` when the window is shown or hidden
+    pub fn connect_open_changed<F: Fn() + 'static>(&self, f: F) {
+        self.window.connect_visible_notify(move |_| f());
+    }
+
+    /// Update the device used to decode control and program names
+    pub fn set_device(&self, config: &'static Config, dump: Arc<Mutex<ProgramsDump>>) {
+        self.device.replace(Some(Device { config, dump }));
+    }
+
+    /// Record a message to or from the device. Device traffic is only
+    /// forwarded while the window or the SysEx console is open.
+    pub fn record(&self, direction: Direction, bytes: Vec<u8>) {
+        let device = self.device.borrow();
+        let dump = device.as_ref().map(|d| d.dump.lock().unwrap());
+        let mut monitor = self.monitor.borrow_mut();
+        let Some(entry) = monitor.record(direction, bytes, device.as_ref().map(|d| d.config),
+                                         dump.as_deref()) else {
+            return;
+        };
+        if !self.window.is_visible() {
+            return;
+        }
+
+        self.append(entry);
+        if self.store.iter_n_children(None) as usize > MONITOR_CAPACITY {
+            if let Some(iter) = self.store.iter_first() {
+                self.store.remove(&iter);
+            }
+        }
+        self.scroll_to_end();
+    }
+
+    fn append(&self, entry: &MonitorEntry) {
+        self.store.insert_with_values(None, &[
+            (0, &entry.time_string()),
+            (1, &entry.direction.arrow()),
+            (2, &entry.description),
+            (3, &entry.hex())
+        ]);
+    }
+
+    /// Re-populate the view after a filter change
+    fn refresh(&self) {
+        self.store.clear();
+        for entry in self.monitor.borrow().entries() {
+            self.append(entry);
+        }
+        self.scroll_to_end();
+    }
+
+    fn scroll_to_end(&self) {
+        // the adjustment is updated once the new rows are laid out
+        let adj = self.scrolled.vadjustment();
+        glib::idle_add_local_once(move || {
+            adj.set_value(adj.upper() - adj.page_size());
+        });
+    }
+
+    fn save(&self) {
+        let dialog = gtk::FileChooserDialog::with_buttons(
+            Some("Save MIDI monitor log"),
+            Some(&self.window),
+            gtk::FileChooserAction::Save,
+            &[("_Cancel", gtk::ResponseType::Cancel), ("_Save", gtk::ResponseType::Accept)]
+        );
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name("midi-monitor.txt");
+
+        let monitor = self.clone();
+        dialog.connect_response(move |dialog, response| {
+            if response == gtk::ResponseType::Accept {
+                if let Some(path) = dialog.file().and_then(|f| f.path()) {
+                    let msg = match monitor.monitor.borrow().save(&path) {
+                        Ok(_) => format!("Saved MIDI monitor log to {}", path.display()),
+                        Err(err) => {
+                            error!("{}", err);
+                            format!("{}", err)
+                        }
+                    };
+                    monitor.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+                }
+            }
+            dialog.close();
+        });
+        dialog.show();
+    }
+}
@@ -0,0 +1,96 @@
+use log::*;
+use tokio::sync::broadcast;
+use pod_core::event::AppEvent;
+use pod_core::offline::*;
+use pod_core::program_id_string;
+use pod_gtk::prelude::*;
+use gtk::ResponseType;
+
+const ACTIONS: &[(SyncAction, &str, &str)] = &[
+    (SyncAction::Push, "push", "Push to device"),
+    (SyncAction::Pull, "pull", "Pull from device"),
+    (SyncAction::KeepLocal, "keep", "Keep local, store later"),
+];
+
+/// Show the programs edited offline that differ from the ones on the
+/// device that just got connected and let the user choose, per program,
+/// whether to push the local program to the device or pull the one on
+/// the device. Closing the dialog keeps the local programs (modified).
+pub fn show_sync_dialog(window: &gtk::Window, slots: Vec<SyncSlot>,
+                        app_event_tx: broadcast::Sender<AppEvent>) {
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Sync offline edits"),
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[("Later", ResponseType::Cancel), ("Apply", ResponseType::Ok)]
+    );
+    dialog.set_default_response(ResponseType::Ok);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(4);
+    grid.set_column_spacing(12);
+    grid.set_border_width(12);
+
+    let header = gtk::Label::new(None);
+    header.set_markup(&format!(
+        "{} program(s) edited while offline differ from the ones on the device:",
+        slots.len()
+    ));
+    header.set_halign(gtk::Align::Start);
+    grid.attach(&header, 0, 0, 4, 1);
+
+    for (i, title) in ["", "Local", "Device", ""].iter().enumerate() {
+        let label = gtk::Label::new(None);
+        label.set_markup(&format!("<b>{}</b>", title));
+        label.set_halign(gtk::Align::Start);
+        grid.attach(&label, i as i32, 1, 1, 1);
+    }
+
+    let mut combos = vec![];
+    for (row, slot) in slots.iter().enumerate() {
+        let row = row as i32 + 2;
+        let id = gtk::Label::new(Some(&program_id_string(slot.program)));
+        let local = gtk::Label::new(Some(&slot.local_name));
+        let device = gtk::Label::new(Some(&slot.device_name));
+        local.set_halign(gtk::Align::Start);
+        device.set_halign(gtk::Align::Start);
+
+        let combo = gtk::ComboBoxText::new();
+        for (_, id, text) in ACTIONS {
+            combo.append(Some(id), text);
+        }
+        combo.set_active_id(Some("push"));
+
+        grid.attach(&id, 0, row, 1, 1);
+        grid.attach(&local, 1, row, 1, 1);
+        grid.attach(&device, 2, row, 1, 1);
+        grid.attach(&combo, 3, row, 1, 1);
+        combos.push(combo);
+    }
+
+    let scrolled = gtk::ScrolledWindow::builder()
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .vscrollbar_policy(gtk::PolicyType::Automatic)
+        .propagate_natural_height(true)
+        .max_content_height(400)
+        .build();
+    scrolled.add(&grid);
+    dialog.content_area().add(&scrolled);
+
+    dialog.connect_response(move |dialog, response| {
+        for (slot, combo) in slots.iter().zip(combos.iter()) {
+            let action = if response == ResponseType::Ok {
+                combo.active_id()
+                    .and_then(|id| ACTIONS.iter().find(|(_, i, _)| *i == id.as_str()))
+                    .map(|(action, _, _)| *action)
+                    .unwrap_or(SyncAction::KeepLocal)
+            } else {
+                SyncAction::KeepLocal
+            };
+            debug!("Sync program {}: {:?}", slot.program, action);
+            apply_sync_action(slot, action, &app_event_tx);
+        }
+        dialog.close();
+    });
+    dialog.show_all();
+}
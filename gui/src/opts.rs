@@ -3,6 +3,8 @@ use anyhow::Result;
 use std::fmt::Write;
 use pod_core::config::configs;
 use pod_core::midi_io::{MidiIn, MidiOut, MidiPorts};
+use pod_core::profile::Profiles;
+use pod_core::remote::RemoteCommand;
 
 #[derive(Parser, Clone)]
 pub struct Opts {
@@ -39,11 +41,67 @@ pub struct Opts {
     /// omitted, the device model on specified ports will be detected.
     pub model: Option<String>,
 
+    #[clap(long)]
+    /// Use the MIDI ports, channel and model saved in the named connection
+    /// profile (see the Settings dialog). Ignored if `-i` and `-o` are
+    /// given. If the profile's ports are not present, the device will be
+    /// autodetected. Without this option, the connection used last is
+    /// restored.
+    pub profile: Option<String>,
+
     #[clap(short, long)]
     /// Run a stand-alone instance of the pod-ui GTK application
     /// instead of triggering any events on an already-running
     /// pod-ui application.
     pub standalone: bool,
+
+    #[clap(long)]
+    /// Select a program. <PROGRAM> is a program number, "manual" or
+    /// "tuner". Forwarded to an already-running pod-ui application,
+    /// if there is one.
+    pub program: Option<String>,
+
+    #[clap(long, value_name = "NAME=VALUE")]
+    /// Set a control to a value in raw units, e.g. "drive=64". May be
+    /// given multiple times. Forwarded to an already-running pod-ui
+    /// application, if there is one.
+    pub set: Vec<String>,
+
+    #[clap(long, value_name = "FILE")]
+    /// Import programs from a bank file (.ini) written by the pod-ui
+    /// program export. Line 6 Edit (.l6t) and SysEx (.syx) files are
+    /// not supported. Imported programs are marked modified, but not
+    /// stored to the device.
+    /// Forwarded to an already-running pod-ui application, if there
+    /// is one.
+    pub import: Option<String>,
+
+    #[clap(long)]
+    /// Store all programs to the device. Runs after `--import`.
+    /// Forwarded to an already-running pod-ui application, if there
+    /// is one.
+    pub store_all: bool,
+}
+
+impl Opts {
+    /// Commands given on the command line, in the order they are
+    /// to be executed
+    pub fn remote_commands(&self) -> Result<Vec<RemoteCommand>> {
+        let mut commands = vec![];
+        if let Some(path) = &self.import {
+            commands.push(RemoteCommand::import(path)?);
+        }
+        if let Some(program) = &self.program {
+            commands.push(RemoteCommand::program(program)?);
+        }
+        for set in self.set.iter() {
+            commands.push(RemoteCommand::set(set)?);
+        }
+        if self.store_all {
+            commands.push(RemoteCommand::StoreAll);
+        }
+        Ok(commands)
+    }
 }
 
 pub fn generate_help_text() -> Result<String> {
@@ -54,17 +112,26 @@ pub fn generate_help_text() -> Result<String> {
     for (i, c) in configs().iter().enumerate() {
         writeln!(s, "{}[{}] {}", tab, i, &c.name)?;
     }
-    writeln!(s, "")?;
+    writeln!(s)?;
     writeln!(s, "MIDI input ports (-i):")?;
     for (i, n) in MidiIn::ports().ok().unwrap_or_default().iter().enumerate() {
         writeln!(s, "{}[{}] {}", tab, i, n)?;
     }
-    writeln!(s, "")?;
+    writeln!(s)?;
     writeln!(s, "MIDI output ports (-o):")?;
     for (i, n) in MidiOut::ports().ok().unwrap_or_default().iter().enumerate() {
         writeln!(s, "{}[{}] {}", tab, i, n)?;
     }
-    writeln!(s, "")?;
+    writeln!(s)?;
+
+    let profiles = Profiles::load().map(|p| p.names()).unwrap_or_default();
+    if !profiles.is_empty() {
+        writeln!(s, "Connection profiles (--profile):")?;
+        for n in profiles.iter() {
+            writeln!(s, "{}{}", tab, n)?;
+        }
+        writeln!(s)?;
+    }
 
     Ok(s)
 }
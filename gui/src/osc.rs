@@ -0,0 +1,99 @@
+use std::sync::{Arc, Mutex};
+use log::*;
+use pod_core::event::*;
+use pod_core::osc::{OscServer, OscSettings};
+use pod_gtk::prelude::*;
+use gtk::ResponseType;
+
+/// OSC server shared with the app event thread
+pub type OscServerShare = Arc<Mutex<Option<OscServer>>>;
+
+/// Start the OSC server if enabled in `settings`, stopping the previous
+/// one. Failures are reported as notifications.
+pub fn start_osc_server(share: &OscServerShare, settings: &OscSettings, app_event_tx: &EventSender) {
+    // stop first, so that the new server can bind to the same port
+    share.lock().unwrap().take();
+    if !settings.enabled {
+        return;
+    }
+
+    match OscServer::start(settings, app_event_tx.clone()) {
+        Ok(server) => {
+            share.lock().unwrap().replace(server);
+        }
+        Err(err) => {
+            error!("{:#}", err);
+            let msg = format!("OSC server not started: {:#}", err);
+            app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+        }
+    }
+}
+
+/// Show the OSC server settings
+pub fn show_osc_dialog(window: &gtk::Window, share: OscServerShare, app_event_tx: EventSender) {
+    let settings = OscSettings::load()
+        .map_err(|err| error!("Failed to load OSC settings: {}", err))
+        .unwrap_or_default();
+
+    let dialog = gtk::Dialog::with_buttons(
+        Some("OSC server"),
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT | gtk::DialogFlags::MODAL,
+        &[("Cancel", ResponseType::Cancel), ("OK", ResponseType::Ok)]
+    );
+    dialog.set_default_response(ResponseType::Ok);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(4);
+    grid.set_column_spacing(12);
+    grid.set_border_width(12);
+
+    let enabled = gtk::CheckButton::with_label("Enable the OSC server");
+    enabled.set_active(settings.enabled);
+    grid.attach(&enabled, 0, 0, 2, 1);
+
+    let label = gtk::Label::new(Some("Address:"));
+    label.set_halign(gtk::Align::End);
+    let address = gtk::Entry::new();
+    address.set_text(&settings.address);
+    address.set_tooltip_text(Some("127.0.0.1 for local clients only, 0.0.0.0 to accept clients on the network"));
+    grid.attach(&label, 0, 1, 1, 1);
+    grid.attach(&address, 1, 1, 1, 1);
+
+    let label = gtk::Label::new(Some("Port:"));
+    label.set_halign(gtk::Align::End);
+    let port = gtk::SpinButton::with_range(1024.0, 65535.0, 1.0);
+    port.set_value(settings.port as f64);
+    grid.attach(&label, 0, 2, 1, 1);
+    grid.attach(&port, 1, 2, 1, 1);
+
+    let help = gtk::Label::new(None);
+    help.set_markup(
+        "<small>Controls: <tt>/pod/control/&lt;name&gt;</tt>, <tt>/pod/control/&lt;name&gt;/norm</tt>\n\
+         Commands: <tt>/pod/program N</tt>, <tt>/pod/load</tt>, <tt>/pod/store</tt>\n\
+         Send <tt>/pod/subscribe</tt> to receive value changes on the port it is sent from</small>"
+    );
+    help.set_halign(gtk::Align::Start);
+    grid.attach(&help, 0, 3, 2, 1);
+
+    dialog.content_area().add(&grid);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Ok {
+            let address = address.text().trim().to_string();
+            let new_settings = OscSettings {
+                enabled: enabled.is_active(),
+                address: if address.is_empty() { OscSettings::default().address } else { address },
+                port: port.value_as_int() as u16
+            };
+            new_settings.save()
+                .unwrap_or_else(|err| error!("Failed to save OSC settings: {}", err));
+            let running = share.lock().unwrap().is_some();
+            if new_settings != settings || new_settings.enabled != running {
+                start_osc_server(&share, &new_settings, &app_event_tx);
+            }
+        }
+        dialog.close();
+    });
+    dialog.show_all();
+}
@@ -1,9 +1,8 @@
-use std::sync::{Arc, Mutex};
-use crate::{State, UIEvent};
+use crate::UIEvent;
 use crate::util::SenderExt;
+use pod_gtk::prelude::glib;
 
-pub fn wire_panic_indicator(state: Arc<Mutex<State>>) {
-    let ui_event_tx = state.lock().unwrap().ui_event_tx.clone();
+pub fn wire_panic_indicator(ui_event_tx: glib::Sender<UIEvent>) {
     let prev = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
         prev(info);
@@ -11,4 +10,4 @@ pub fn wire_panic_indicator(state: Arc<Mutex<State>>) {
         // send a panic event to the UI thread
         ui_event_tx.send_or_warn(UIEvent::Panic);
     }));
-}
\ No newline at end of file
+}
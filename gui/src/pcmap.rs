@@ -0,0 +1,157 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use log::*;
+use pod_core::event::Program;
+use pod_core::midi::Channel;
+use pod_core::pcmap::PcMap;
+use pod_core::program_id_string;
+use pod_gtk::prelude::*;
+use gtk::ResponseType;
+
+/// PC map shared with the app event thread
+pub type PcMapShare = Arc<Mutex<Option<PcMap>>>;
+
+fn program_label(program: &Program) -> String {
+    match program {
+        Program::ManualMode => "Manual mode".into(),
+        Program::Tuner => "Tuner".into(),
+        Program::Program(p) => program_id_string(*p as usize)
+    }
+}
+
+fn program_choices(pc_map: &PcMap) -> Vec<Program> {
+    let config = pc_map.config();
+    let mut programs = (0 .. config.program_num)
+        .map(|p| Program::Program(p as u16))
+        .collect::<Vec<_>>();
+    if config.pc_manual_mode.is_some() {
+        programs.push(Program::ManualMode);
+    }
+    if config.pc_tuner.is_some() {
+        programs.push(Program::Tuner);
+    }
+    programs
+}
+
+fn populate_list(list: &gtk::ListBox, pc_map: &Rc<RefCell<PcMap>>) {
+    list.foreach(|w| list.remove(w));
+
+    let entries = pc_map.borrow().map.iter()
+        .map(|(pc, program)| (*pc, program.clone()))
+        .collect::<Vec<_>>();
+    for (pc, program) in entries {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let label = gtk::Label::new(Some(&format!("PC {}  →  {}", pc, program_label(&program))));
+        label.set_halign(gtk::Align::Start);
+        let remove = gtk::Button::from_icon_name(Some("edit-delete-symbolic"), gtk::IconSize::Button);
+        remove.set_tooltip_text(Some("Remove mapping"));
+        remove.connect_clicked({
+            let list = list.clone();
+            let pc_map = pc_map.clone();
+            move |_| {
+                pc_map.borrow_mut().map.remove(&pc);
+                populate_list(&list, &pc_map);
+            }
+        });
+        row.pack_start(&label, true, true, 0);
+        row.pack_end(&remove, false, false, 0);
+        list.add(&row);
+    }
+    list.show_all();
+}
+
+/// Show the program change remapping table of the current device
+pub fn show_pc_map_dialog(window: &gtk::Window, share: PcMapShare) {
+    let Some(pc_map) = share.lock().unwrap().clone() else {
+        warn!("PC map: no device");
+        return;
+    };
+    let choices = program_choices(&pc_map);
+    let pc_map = Rc::new(RefCell::new(pc_map));
+
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Program change map"),
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT | gtk::DialogFlags::MODAL,
+        &[("Cancel", ResponseType::Cancel), ("OK", ResponseType::Ok)]
+    );
+    dialog.set_default_response(ResponseType::Ok);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(4);
+    grid.set_column_spacing(12);
+    grid.set_border_width(12);
+
+    let controller = gtk::CheckButton::with_label("Map PCs from the controller input");
+    controller.set_active(pc_map.borrow().controller);
+    grid.attach(&controller, 0, 0, 3, 1);
+
+    let label = gtk::Label::new(Some("Controller channel:"));
+    label.set_halign(gtk::Align::End);
+    let channel = gtk::ComboBoxText::new();
+    channel.append(Some(&Channel::all().to_string()), "Any");
+    for c in 0u8 .. 16 {
+        channel.append(Some(&c.to_string()), &(c + 1).to_string());
+    }
+    channel.set_active_id(Some(&pc_map.borrow().channel.to_string()));
+    grid.attach(&label, 0, 1, 1, 1);
+    grid.attach(&channel, 1, 1, 2, 1);
+
+    let device = gtk::CheckButton::with_label("Map PCs from the device input");
+    device.set_active(pc_map.borrow().device);
+    grid.attach(&device, 0, 2, 3, 1);
+
+    let list = gtk::ListBox::new();
+    list.set_selection_mode(gtk::SelectionMode::None);
+    let scrolled = gtk::ScrolledWindow::builder()
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .vscrollbar_policy(gtk::PolicyType::Automatic)
+        .min_content_height(200)
+        .build();
+    scrolled.add(&list);
+    grid.attach(&scrolled, 0, 3, 3, 1);
+    populate_list(&list, &pc_map);
+
+    let pc = gtk::SpinButton::with_range(0.0, 127.0, 1.0);
+    let program = gtk::ComboBoxText::new();
+    for (i, p) in choices.iter().enumerate() {
+        program.append(Some(&i.to_string()), &program_label(p));
+    }
+    program.set_active(Some(0));
+    let add = gtk::Button::with_label("Add");
+    add.connect_clicked({
+        let list = list.clone();
+        let pc_map = pc_map.clone();
+        let pc = pc.clone();
+        let program = program.clone();
+        move |_| {
+            let Some(p) = program.active().and_then(|i| choices.get(i as usize)) else { return };
+            pc_map.borrow_mut().map.insert(pc.value_as_int() as u8, p.clone());
+            populate_list(&list, &pc_map);
+            // ready for the next footswitch
+            pc.spin(gtk::SpinType::StepForward, 1.0);
+        }
+    });
+    grid.attach(&pc, 0, 4, 1, 1);
+    grid.attach(&program, 1, 4, 1, 1);
+    grid.attach(&add, 2, 4, 1, 1);
+
+    dialog.content_area().add(&grid);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Ok {
+            let mut pc_map = pc_map.borrow().clone();
+            pc_map.controller = controller.is_active();
+            pc_map.device = device.is_active();
+            pc_map.channel = channel.active_id()
+                .and_then(|id| id.parse::<u8>().ok())
+                .unwrap_or(Channel::all());
+            pc_map.save()
+                .unwrap_or_else(|err| error!("Failed to save PC map: {}", err));
+            share.lock().unwrap().replace(pc_map);
+        }
+        dialog.close();
+    });
+    dialog.show_all();
+}
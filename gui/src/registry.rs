@@ -1,60 +1,37 @@
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
 use anyhow::*;
-use log::error;
-use pod_core::config::register_config;
+use once_cell::sync::Lazy;
+use pod_core::config::{config_index, configs};
+use pod_core::device::{init_device, register_device, InitializedDevice};
 use pod_core::dump::ProgramsDump;
 use pod_core::edit::EditBuffer;
 use pod_core::handler::BoxedHandler;
-use pod_core::model::{AbstractControl, Config};
-use pod_core::store::{Signal, Store};
-use pod_core::store::Origin::NONE;
+use pod_core::model::Config;
+use pod_core::store::Store;
 use pod_gtk::prelude::*;
 
-static mut MODULES: Vec<Box<dyn Module>> = vec![];
+/// A registered module with the indexes of its device configs in `configs()`
+type ModuleEntry = (Range<usize>, &'static dyn Module);
 
-fn validate_unique_cc(config: &Config) -> bool {
-    let mut seen_cc = vec![];
-    let mut ok = true;
-    for (_, v) in config.controls.iter() {
-        if let Some(cc) = v.get_cc() {
-            if seen_cc.contains(&cc) {
-                error!("Config {:?} contains multiple controls for CC={}", config.name, cc);
-                ok = false;
-            }
-            seen_cc.push(cc);
-        }
-    }
-
-    ok
-}
+static MODULES: Lazy<Mutex<Vec<ModuleEntry>>> = Lazy::new(|| Mutex::new(Vec::new()));
 
 pub fn register_module(module: impl Module + 'static) -> Result<()> {
-    for config in module.config().iter() {
-        if !validate_unique_cc(config) {
-            bail!("Config {:?} failed unique CC validation!", config.name);
-        }
-        register_config(config);
-    }
+    let start = configs().len();
+    register_device(module.device())?;
 
-    unsafe {
-        MODULES.push(Box::new(module))
-    }
+    // kept for good, like the registered configs
+    let module: &'static dyn Module = Box::leak(Box::new(module));
+    MODULES.lock().unwrap().push((start .. configs().len(), module));
 
     Ok(())
 }
 
-pub fn module_for_config(config: &Config) -> Option<&Box<dyn Module>> {
-    unsafe {
-        for module in MODULES.iter() {
-            for c in module.config().iter() {
-                if *c == *config {
-                    return Some(module);
-                }
-            }
-        }
-    }
-
-    None
+pub fn module_for_config(config: &Config) -> Option<&'static dyn Module> {
+    let index = config_index(config)?;
+    MODULES.lock().unwrap().iter()
+        .find(|(configs, _)| configs.contains(&index))
+        .map(|(_, module)| *module)
 }
 
 pub struct InitializedInterface {
@@ -69,10 +46,7 @@ pub struct InitializedInterface {
 pub fn init_module(config: &'static Config) -> Result<InitializedInterface> {
     let module = module_for_config(config).unwrap();
     let interface = module.init(config);
-    let handler = module.handler(config);
-
-    let edit_buffer = Arc::new(Mutex::new(EditBuffer::new(config)));
-    let dump = Arc::new(Mutex::new(ProgramsDump::new(config)));
+    let InitializedDevice { handler, edit_buffer, dump } = init_device(config)?;
     let mut callbacks = Callbacks::new();
 
     let widget = interface.widget();
@@ -105,15 +79,3 @@ pub fn init_module(config: &'static Config) -> Result<InitializedInterface> {
         objects
     })
 }
-
-pub fn init_module_controls(config: &Config, edit_buffer: &EditBuffer) -> Result<()> {
-    let mut controller = edit_buffer.controller_locked();
-
-    for name in &config.init_controls {
-        let value = controller.get(name)
-            .with_context(|| format!("Initializing control {:?} value not found!", &name))?;
-        controller.set_full(name, value, NONE, Signal::Force);
-    }
-
-    Ok(())
-}
@@ -0,0 +1,164 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use log::*;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use pod_core::controller::*;
+use pod_core::dump::ProgramsDump;
+use pod_core::edit::EditBuffer;
+use pod_core::event::{AppEvent, Program};
+use pod_core::model::Config;
+use pod_core::session::Session;
+use pod_gtk::prelude::*;
+
+const AUTOSAVE_INTERVAL_SEC: u64 = 30;
+
+/// Periodically saves the unsaved edits of the current device
+/// to disk, see `pod_core::session::Session`.
+#[derive(Clone)]
+pub struct SessionAutosave {
+    config: &'static Config,
+    edit: Arc<Mutex<EditBuffer>>,
+    dump: Arc<Mutex<ProgramsDump>>,
+    ui_controller: Arc<Mutex<Controller>>,
+    last: Arc<Mutex<Option<Session>>>,
+    handle: Rc<RefCell<Option<JoinHandle<()>>>>
+}
+
+impl SessionAutosave {
+    pub fn new(config: &'static Config,
+               edit: Arc<Mutex<EditBuffer>>,
+               dump: Arc<Mutex<ProgramsDump>>,
+               ui_controller: Arc<Mutex<Controller>>) -> Self {
+        Self {
+            config, edit, dump, ui_controller,
+            last: Arc::new(Mutex::new(None)),
+            handle: Rc::new(RefCell::new(None))
+        }
+    }
+
+    pub fn start(&self) {
+        self.stop();
+
+        let handle = tokio::spawn({
+            let config = self.config;
+            let edit = self.edit.clone();
+            let dump = self.dump.clone();
+            let ui_controller = self.ui_controller.clone();
+            let last = self.last.clone();
+
+            async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(AUTOSAVE_INTERVAL_SEC));
+                loop {
+                    interval.tick().await;
+                    save_session(config, &edit, &dump, &ui_controller, &last);
+                }
+            }
+        });
+        self.handle.replace(Some(handle));
+    }
+
+    pub fn stop(&self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+    }
+
+    /// Save the session right away, used at shut down
+    pub fn save(&self) {
+        if self.handle.borrow().is_none() {
+            // Autosave not started (restore prompt still pending),
+            // do not overwrite the previous session
+            return;
+        }
+        save_session(self.config, &self.edit, &self.dump, &self.ui_controller, &self.last);
+    }
+}
+
+fn save_session(config: &'static Config,
+                edit: &Arc<Mutex<EditBuffer>>,
+                dump: &Arc<Mutex<ProgramsDump>>,
+                ui_controller: &Arc<Mutex<Controller>>,
+                last: &Mutex<Option<Session>>) {
+    let program: Program = ui_controller.get("program").unwrap().into();
+    let session = {
+        let edit = edit.lock().unwrap();
+        let dump = dump.lock().unwrap();
+        Session::capture(config, program, &edit, &dump)
+    };
+
+    let mut last = last.lock().unwrap();
+    if last.as_ref() == Some(&session) {
+        return;
+    }
+    match session.save(config) {
+        Ok(_) => {
+            debug!("Session saved: {} program(s) modified", session.programs.len());
+            last.replace(session);
+        }
+        Err(err) => {
+            error!("Failed to save session: {}", err);
+        }
+    }
+}
+
+/// Check if there is a saved session for the device and, if so, ask the
+/// user whether to restore it. Autosave is started once the question is
+/// answered, so that the saved session is not overwritten before that.
+pub fn offer_restore(window: &gtk::Window, autosave: SessionAutosave,
+                     app_event_tx: broadcast::Sender<AppEvent>) {
+    let config = autosave.config;
+    let session = match Session::load(config) {
+        Ok(Some(session)) if !session.is_empty() => session,
+        Ok(_) => {
+            autosave.start();
+            return;
+        }
+        Err(err) => {
+            error!("Failed to load session: {}", err);
+            autosave.start();
+            return;
+        }
+    };
+
+    let m = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::YesNo,
+        "Restore unsaved changes from the previous session?"
+    );
+    let what = match (session.programs.len(), session.edit.is_some()) {
+        (0, _) => "the edit buffer".to_string(),
+        (1, false) => "1 program".to_string(),
+        (n, false) => format!("{} programs", n),
+        (n, true) => format!("{} program(s) and the edit buffer", n)
+    };
+    m.set_secondary_text(Some(&format!(
+        "Unsaved changes to {} of {} were found. Restored programs will be \
+        marked as modified, so that they can be stored to the device.",
+        what, config.name
+    )));
+    m.connect_response({
+        let ui_controller = autosave.ui_controller.clone();
+        move |dialog, response| {
+            if response == gtk::ResponseType::Yes {
+                info!("Restoring session: {} program(s)", session.programs.len());
+                session.restore(&app_event_tx);
+                if let Program::Program(p) = session.program {
+                    if (p as usize) < config.program_num {
+                        ui_controller.set("program", p, StoreOrigin::UI);
+                    }
+                }
+            } else {
+                Session::remove(config)
+                    .unwrap_or_else(|err| error!("Failed to remove session: {}", err));
+            }
+            autosave.start();
+            dialog.close();
+        }
+    });
+    m.show();
+}
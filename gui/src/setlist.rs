@@ -0,0 +1,472 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use log::*;
+use tokio::sync::broadcast;
+use pod_core::dump::ProgramsDump;
+use pod_core::edit::EditBuffer;
+use pod_core::event::*;
+use pod_core::model::Config;
+use pod_core::program;
+use pod_core::program_id_string;
+use pod_core::setlist::*;
+use pod_gtk::prelude::*;
+
+/// The active setlist, shared with the app event thread. Setlist mode is
+/// on while the setlist window is open.
+pub type SetlistShare = Arc<Mutex<Option<Setlist>>>;
+pub type SetlistTriggersShare = Arc<Mutex<SetlistTriggers>>;
+
+pub const SETLIST_ACTIONS: &[&str] = &["setlist-next", "setlist-prev"];
+
+#[derive(Clone)]
+struct Device {
+    config: &'static Config,
+    edit: Arc<Mutex<EditBuffer>>,
+    dump: Arc<Mutex<ProgramsDump>>
+}
+
+#[derive(Clone)]
+pub struct SetlistWindow {
+    window: gtk::Window,
+    /// Device window with the setlist step actions
+    actions: gtk::ApplicationWindow,
+    share: SetlistShare,
+    app_event_tx: broadcast::Sender<AppEvent>,
+    device: Rc<RefCell<Option<Device>>>,
+
+    name_combo: gtk::ComboBoxText,
+    song_label: gtk::Label,
+    next_label: gtk::Label,
+    list: gtk::ListBox,
+    song_entry: gtk::Entry,
+    program_combo: gtk::ComboBoxText
+}
+
+fn cc_combo(selected: Option<u8>) -> gtk::ComboBoxText {
+    let combo = gtk::ComboBoxText::new();
+    combo.append(Some("off"), "Off");
+    for cc in 0u8 ..= 127 {
+        combo.append(Some(&cc.to_string()), &cc.to_string());
+    }
+    let id = selected.map(|cc| cc.to_string()).unwrap_or("off".into());
+    combo.set_active_id(Some(&id));
+    combo
+}
+
+fn cc_from_combo(combo: &gtk::ComboBoxText) -> Option<u8> {
+    combo.active_id().and_then(|id| id.parse::<u8>().ok())
+}
+
+impl SetlistWindow {
+    pub fn new(parent: &gtk::ApplicationWindow, share: SetlistShare,
+               triggers: SetlistTriggersShare, app_event_tx: broadcast::Sender<AppEvent>) -> Self {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title("Setlist");
+        window.set_transient_for(Some(parent));
+        window.set_destroy_with_parent(true);
+        // the step buttons activate the actions of the device window
+        window.insert_action_group("win", Some(parent));
+        window.set_default_size(420, 560);
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 8);
+        vbox.set_border_width(12);
+
+        // setlist selection
+        let name_combo = gtk::ComboBoxText::with_entry();
+        let new_button = gtk::Button::with_label("New");
+        let delete_button = gtk::Button::from_icon_name(Some("edit-delete-symbolic"), gtk::IconSize::Button);
+        delete_button.set_tooltip_text(Some("Delete setlist"));
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        hbox.pack_start(&name_combo, true, true, 0);
+        hbox.pack_start(&new_button, false, false, 0);
+        hbox.pack_start(&delete_button, false, false, 0);
+        vbox.pack_start(&hbox, false, false, 0);
+
+        // current song & stepping
+        let song_label = gtk::Label::new(None);
+        let next_label = gtk::Label::new(None);
+        vbox.pack_start(&song_label, false, false, 0);
+        vbox.pack_start(&next_label, false, false, 0);
+
+        let prev_button = gtk::Button::with_label("◀ Previous");
+        let next_button = gtk::Button::with_label("Next ▶");
+        prev_button.set_action_name(Some("win.setlist-prev"));
+        next_button.set_action_name(Some("win.setlist-next"));
+        prev_button.set_tooltip_text(Some("Page Up"));
+        next_button.set_tooltip_text(Some("Page Down"));
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        hbox.set_homogeneous(true);
+        hbox.set_size_request(-1, 64);
+        hbox.pack_start(&prev_button, true, true, 0);
+        hbox.pack_start(&next_button, true, true, 0);
+        vbox.pack_start(&hbox, false, false, 0);
+
+        // entries
+        let list = gtk::ListBox::new();
+        list.set_selection_mode(gtk::SelectionMode::Single);
+        let scrolled = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .vscrollbar_policy(gtk::PolicyType::Automatic)
+            .build();
+        scrolled.add(&list);
+        vbox.pack_start(&scrolled, true, true, 0);
+
+        let up_button = gtk::Button::from_icon_name(Some("go-up-symbolic"), gtk::IconSize::Button);
+        let down_button = gtk::Button::from_icon_name(Some("go-down-symbolic"), gtk::IconSize::Button);
+        let remove_button = gtk::Button::from_icon_name(Some("list-remove-symbolic"), gtk::IconSize::Button);
+        up_button.set_tooltip_text(Some("Move up"));
+        down_button.set_tooltip_text(Some("Move down"));
+        remove_button.set_tooltip_text(Some("Remove entry"));
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        hbox.pack_start(&up_button, false, false, 0);
+        hbox.pack_start(&down_button, false, false, 0);
+        hbox.pack_end(&remove_button, false, false, 0);
+        vbox.pack_start(&hbox, false, false, 0);
+
+        // adding entries
+        let song_entry = gtk::Entry::new();
+        song_entry.set_placeholder_text(Some("Song"));
+        let program_combo = gtk::ComboBoxText::new();
+        let add_button = gtk::Button::with_label("Add");
+        add_button.set_tooltip_text(Some("Add the selected program"));
+        let add_patch_button = gtk::Button::with_label("Add edit buffer");
+        add_patch_button.set_tooltip_text(Some("Add a copy of the edit buffer as a patch kept in the setlist"));
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        hbox.pack_start(&song_entry, true, true, 0);
+        hbox.pack_start(&program_combo, false, false, 0);
+        hbox.pack_start(&add_button, false, false, 0);
+        hbox.pack_start(&add_patch_button, false, false, 0);
+        vbox.pack_start(&hbox, false, false, 0);
+
+        // MIDI triggers
+        let (next_cc, prev_cc) = {
+            let triggers = triggers.lock().unwrap();
+            (cc_combo(triggers.next_cc), cc_combo(triggers.prev_cc))
+        };
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        hbox.pack_start(&gtk::Label::new(Some("Controller CC next:")), false, false, 0);
+        hbox.pack_start(&next_cc, false, false, 0);
+        hbox.pack_start(&gtk::Label::new(Some("previous:")), false, false, 0);
+        hbox.pack_start(&prev_cc, false, false, 0);
+        vbox.pack_start(&hbox, false, false, 0);
+
+        window.add(&vbox);
+
+        let w = SetlistWindow {
+            window, actions: parent.clone(), share, app_event_tx,
+            device: Rc::new(RefCell::new(None)),
+            name_combo, song_label, next_label, list, song_entry, program_combo
+        };
+
+        // closing the window ends setlist mode
+        w.window.connect_delete_event({
+            let w = w.clone();
+            move |window, _| {
+                window.hide();
+                w.share.lock().unwrap().take();
+                w.set_actions_enabled(false);
+                Inhibit(true)
+            }
+        });
+
+        w.name_combo.connect_changed({
+            let w = w.clone();
+            move |combo| {
+                let Some(name) = combo.active_id() else { return };
+                match Setlist::load_named(&name) {
+                    Ok(setlist) => {
+                        w.share.lock().unwrap().replace(setlist);
+                    }
+                    Err(err) => w.notify(format!("Failed to load setlist {:?}: {}", name, err))
+                }
+                w.refresh();
+            }
+        });
+
+        new_button.connect_clicked({
+            let w = w.clone();
+            move |_| {
+                let name = w.name_combo.active_text()
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_default();
+                let config = w.device.borrow().as_ref().map(|d| d.config);
+                let Some(config) = config else {
+                    w.notify("No device to create a setlist for".into());
+                    return;
+                };
+                if name.is_empty() {
+                    w.notify("Enter a setlist name".into());
+                    return;
+                }
+                let setlist = Setlist::new(&name, config);
+                if let Err(err) = setlist.save() {
+                    w.notify(format!("Failed to save setlist {:?}: {}", name, err));
+                    return;
+                }
+                w.populate_names();
+                w.name_combo.set_active_id(Some(&name));
+            }
+        });
+
+        delete_button.connect_clicked({
+            let w = w.clone();
+            move |_| {
+                let name = w.share.lock().unwrap().take().map(|s| s.name);
+                let Some(name) = name else { return };
+                Setlist::delete(&name)
+                    .unwrap_or_else(|err| w.notify(format!("Failed to delete setlist {:?}: {}", name, err)));
+                w.populate_names();
+                w.refresh();
+            }
+        });
+
+        w.list.connect_row_activated({
+            let w = w.clone();
+            move |_, row| {
+                let e = SetlistEvent::Select(row.index() as usize);
+                w.app_event_tx.send_or_warn(AppEvent::Setlist(e));
+            }
+        });
+
+        up_button.connect_clicked({
+            let w = w.clone();
+            move |_| w.move_selected(-1)
+        });
+        down_button.connect_clicked({
+            let w = w.clone();
+            move |_| w.move_selected(1)
+        });
+        remove_button.connect_clicked({
+            let w = w.clone();
+            move |_| {
+                let Some(index) = w.selected() else { return };
+                w.edit(|setlist| {
+                    setlist.entries.remove(index);
+                    setlist.position = None;
+                });
+            }
+        });
+
+        add_button.connect_clicked({
+            let w = w.clone();
+            move |_| {
+                let program = w.program_combo.active_id()
+                    .and_then(|id| id.parse::<u16>().ok())
+                    .map(Program::from);
+                let Some(program) = program else { return };
+                let song = w.song_entry.text().to_string();
+                w.add(SetlistEntry { song, item: SetlistItem::Program(program) });
+            }
+        });
+
+        add_patch_button.connect_clicked({
+            let w = w.clone();
+            move |_| {
+                let patch = w.device.borrow().as_ref().map(|d| {
+                    let edit = d.edit.lock().unwrap();
+                    (edit.name(), program::store_patch_dump_ctrl(&edit))
+                });
+                let Some((name, data)) = patch else { return };
+                let song = w.song_entry.text().to_string();
+                let song = if song.is_empty() { name.trim().to_string() } else { song };
+                w.add(SetlistEntry { song, item: SetlistItem::Patch { name, data } });
+            }
+        });
+
+        let update_triggers = {
+            let next_cc = next_cc.clone();
+            let prev_cc = prev_cc.clone();
+            move |_: &gtk::ComboBoxText| {
+                let mut triggers = triggers.lock().unwrap();
+                triggers.next_cc = cc_from_combo(&next_cc);
+                triggers.prev_cc = cc_from_combo(&prev_cc);
+                triggers.save()
+                    .unwrap_or_else(|err| error!("Failed to save setlist triggers: {}", err));
+            }
+        };
+        next_cc.connect_changed(update_triggers.clone());
+        prev_cc.connect_changed(update_triggers);
+
+        w
+    }
+
+    /// Update the device the setlist entries get added for
+    pub fn set_device(&self, config: &'static Config, edit: Arc<Mutex<EditBuffer>>,
+                      dump: Arc<Mutex<ProgramsDump>>) {
+        self.device.replace(Some(Device { config, edit, dump }));
+        self.populate_programs();
+        self.refresh();
+    }
+
+    /// Open the setlist window, which turns the setlist mode on
+    pub fn show(&self) {
+        self.populate_names();
+        self.populate_programs();
+        self.refresh();
+        self.set_actions_enabled(true);
+        self.window.show_all();
+        self.window.present();
+    }
+
+    /// Update the current song after a setlist step
+    pub fn set_position(&self, _position: Option<usize>) {
+        self.refresh();
+    }
+
+    /// Enable or disable the setlist step actions, which also controls
+    /// their keyboard shortcuts
+    pub fn set_actions_enabled(&self, enabled: bool) {
+        for name in SETLIST_ACTIONS {
+            if let Some(action) = self.actions.lookup_action(name)
+                .and_then(|a| a.downcast::<gio::SimpleAction>().ok()) {
+                action.set_enabled(enabled);
+            }
+        }
+    }
+
+    fn notify(&self, msg: String) {
+        warn!("{}", msg);
+        self.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+    }
+
+    fn populate_names(&self) {
+        let names = Setlist::list()
+            .map_err(|err| error!("Failed to list setlists: {}", err))
+            .unwrap_or_default();
+        self.name_combo.remove_all();
+        for name in names.iter() {
+            self.name_combo.append(Some(name), name);
+        }
+        let current = self.share.lock().unwrap().as_ref().map(|s| s.name.clone());
+        match current {
+            Some(name) => { self.name_combo.set_active_id(Some(&name)); }
+            None => {
+                if let Some(entry) = self.name_combo.child()
+                    .and_then(|w| w.dynamic_cast::<gtk::Entry>().ok()) {
+                    entry.set_text("");
+                }
+            }
+        }
+    }
+
+    fn populate_programs(&self) {
+        self.program_combo.remove_all();
+        let Some(device) = self.device.borrow().clone() else { return };
+        let dump = device.dump.lock().unwrap();
+        for p in 0 .. device.config.program_num {
+            let name = dump.name(p).unwrap_or_default();
+            let label = format!("{} {}", program_id_string(p), name.trim());
+            self.program_combo.append(Some(&p.to_string()), &label);
+        }
+        if let Some(p) = device.config.pc_manual_mode.map(|_| Program::ManualMode) {
+            let id: u16 = p.into();
+            self.program_combo.append(Some(&id.to_string()), "Manual mode");
+        }
+        if let Some(p) = device.config.pc_tuner.map(|_| Program::Tuner) {
+            let id: u16 = p.into();
+            self.program_combo.append(Some(&id.to_string()), "Tuner");
+        }
+        self.program_combo.set_active(Some(0));
+    }
+
+    fn entry_label(&self, entry: &SetlistEntry) -> String {
+        let item = match &entry.item {
+            SetlistItem::Program(Program::ManualMode) => "Manual mode".into(),
+            SetlistItem::Program(Program::Tuner) => "Tuner".into(),
+            SetlistItem::Program(Program::Program(p)) => {
+                let name = self.device.borrow().as_ref()
+                    .and_then(|d| d.dump.lock().unwrap().name(*p as usize))
+                    .unwrap_or_default();
+                format!("{} {}", program_id_string(*p as usize), name.trim())
+            }
+            SetlistItem::Patch { name, .. } => format!("Patch: {}", name.trim())
+        };
+        format!("{} — {}", entry.song, item)
+    }
+
+    /// Rebuild the list of entries and the current song labels
+    fn refresh(&self) {
+        let setlist = self.share.lock().unwrap().clone();
+        let selected = self.selected();
+
+        self.list.foreach(|w| self.list.remove(w));
+        let Some(setlist) = setlist else {
+            self.song_label.set_text("");
+            self.next_label.set_text("");
+            return;
+        };
+
+        for (i, entry) in setlist.entries.iter().enumerate() {
+            let label = gtk::Label::new(None);
+            let text = glib::markup_escape_text(&format!("{}. {}", i + 1, self.entry_label(entry)));
+            if setlist.position == Some(i) {
+                label.set_markup(&format!("<b>▶ {}</b>", text));
+            } else {
+                label.set_markup(&text);
+            }
+            label.set_halign(gtk::Align::Start);
+            self.list.add(&label);
+        }
+        self.list.show_all();
+        if let Some(row) = selected.and_then(|i| self.list.row_at_index(i as i32)) {
+            self.list.select_row(Some(&row));
+        }
+
+        let song = setlist.current()
+            .map(|e| format!("<span size=\"xx-large\"><b>{}</b></span>", glib::markup_escape_text(&e.song)))
+            .unwrap_or_default();
+        self.song_label.set_markup(&song);
+        let next = setlist.position.map(|p| p + 1).unwrap_or(0);
+        let next = setlist.entries.get(next)
+            .map(|e| format!("Next: {}", e.song))
+            .unwrap_or_default();
+        self.next_label.set_text(&next);
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.list.selected_row().map(|row| row.index() as usize)
+    }
+
+    /// Modify the setlist and save it
+    fn edit<F: FnOnce(&mut Setlist)>(&self, f: F) {
+        {
+            let mut share = self.share.lock().unwrap();
+            let Some(setlist) = share.as_mut() else { return };
+            f(setlist);
+            setlist.save()
+                .unwrap_or_else(|err| error!("Failed to save setlist {:?}: {}", setlist.name, err));
+        }
+        self.refresh();
+    }
+
+    fn add(&self, entry: SetlistEntry) {
+        if self.share.lock().unwrap().is_none() {
+            self.notify("Create or select a setlist first".into());
+            return;
+        }
+        let index = self.selected().map(|i| i + 1);
+        self.edit(|setlist| {
+            let index = index.unwrap_or(setlist.entries.len());
+            setlist.entries.insert(index, entry);
+            setlist.position = None;
+        });
+        self.song_entry.set_text("");
+    }
+
+    fn move_selected(&self, offset: isize) {
+        let Some(index) = self.selected() else { return };
+        let len = self.share.lock().unwrap().as_ref().map(|s| s.entries.len()).unwrap_or(0);
+        let to = index as isize + offset;
+        if to < 0 || to as usize >= len {
+            return;
+        }
+        self.edit(|setlist| {
+            setlist.entries.swap(index, to as usize);
+            setlist.position = None;
+        });
+        if let Some(row) = self.list.row_at_index(to as i32) {
+            self.list.select_row(Some(&row));
+        }
+    }
+}
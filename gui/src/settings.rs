@@ -3,11 +3,13 @@ use anyhow::anyhow;
 use pod_core::midi_io::*;
 use pod_gtk::prelude::*;
 use gtk::{IconSize, ResponseType};
-use crate::{gtk, midi_in_out_start, midi_in_out_stop, set_midi_in_out, State};
+use pod_core::engine::{midi_in_out_start, midi_in_out_stop, set_ext_midi_in, State};
+use crate::{gtk, set_midi_in_out};
 
 use log::*;
 use pod_core::config::configs;
 use pod_core::midi::Channel;
+use pod_core::profile::{Profile, Profiles};
 
 #[derive(Clone)]
 struct SettingsDialog {
@@ -16,6 +18,10 @@ struct SettingsDialog {
     midi_out_combo: gtk::ComboBoxText,
     midi_channel_combo: gtk::ComboBoxText,
     model_combo: gtk::ComboBoxText,
+    controller_in_combo: gtk::ComboBoxText,
+    profile_combo: gtk::ComboBoxText,
+    profile_save_button: gtk::Button,
+    profile_delete_button: gtk::Button,
     autodetect_button: gtk::Button,
     test_button: gtk::Button,
     message_label: gtk::Label,
@@ -32,6 +38,10 @@ impl SettingsDialog {
             midi_out_combo: ui.object("settings_midi_out_combo").unwrap(),
             midi_channel_combo: ui.object("settings_midi_channel_combo").unwrap(),
             model_combo: ui.object("settings_model_combo").unwrap(),
+            controller_in_combo: ui.object("settings_controller_in_combo").unwrap(),
+            profile_combo: ui.object("settings_profile_combo").unwrap(),
+            profile_save_button: ui.object("settings_profile_save_button").unwrap(),
+            profile_delete_button: ui.object("settings_profile_delete_button").unwrap(),
             autodetect_button: ui.object("settings_autodetect_button").unwrap(),
             test_button: ui.object("settings_test_button").unwrap(),
             message_label: ui.object("settings_message_label").unwrap(),
@@ -46,6 +56,10 @@ impl SettingsDialog {
         self.midi_out_combo.set_sensitive(sensitive);
         self.midi_channel_combo.set_sensitive(sensitive);
         self.model_combo.set_sensitive(sensitive);
+        self.controller_in_combo.set_sensitive(sensitive);
+        self.profile_combo.set_sensitive(sensitive);
+        self.profile_save_button.set_sensitive(sensitive);
+        self.profile_delete_button.set_sensitive(sensitive);
         self.autodetect_button.set_sensitive(sensitive);
         self.test_button.set_sensitive(sensitive);
     }
@@ -159,6 +173,127 @@ fn populate_model_combo(settings: &SettingsDialog, selected: &Option<String>) {
     settings.model_combo.set_active(selected);
 }
 
+const CONTROLLER_IN_NONE: &str = "none";
+
+fn populate_controller_in_combo(settings: &SettingsDialog, selected: &Option<String>) {
+    settings.controller_in_combo.remove_all();
+    settings.controller_in_combo.append(Some(CONTROLLER_IN_NONE), "None");
+    let ports = MidiIn::ports().ok().unwrap_or_default();
+    for port in ports.iter() {
+        settings.controller_in_combo.append(Some(port), port);
+    }
+
+    let selected = selected.as_deref()
+        .filter(|name| ports.iter().any(|p| p == name))
+        .unwrap_or(CONTROLLER_IN_NONE);
+    settings.controller_in_combo.set_active_id(Some(selected));
+}
+
+fn controller_in_from_dialog(settings: &SettingsDialog) -> Option<String> {
+    settings.controller_in_combo.active_id()
+        .filter(|id| id != CONTROLLER_IN_NONE)
+        .map(|id| id.to_string())
+}
+
+/// The connection currently selected in the dialog as a profile
+fn profile_from_dialog(settings: &SettingsDialog, name: &str) -> Profile {
+    Profile {
+        name: name.to_string(),
+        midi_in: settings.midi_in_combo.active_text().map(|s| s.to_string()),
+        midi_out: settings.midi_out_combo.active_text().map(|s| s.to_string()),
+        midi_channel: Some(midi_channel_from_combo_index(settings.midi_channel_combo.active())),
+        model: settings.model_combo.active_text().map(|s| s.to_string()),
+        controller_in: controller_in_from_dialog(settings)
+    }
+}
+
+fn populate_profile_combo(settings: &SettingsDialog) {
+    let profiles = Profiles::load()
+        .map_err(|err| error!("Failed to load profiles: {}", err))
+        .unwrap_or_default();
+
+    settings.profile_combo.remove_all();
+    for name in profiles.names() {
+        settings.profile_combo.append(Some(&name), &name);
+    }
+    if let Some(entry) = settings.profile_combo.child()
+        .and_then(|w| w.dynamic_cast::<gtk::Entry>().ok()) {
+        entry.set_text("");
+    }
+}
+
+fn wire_profile_controls(settings: &SettingsDialog) {
+    // picking a profile fills in the connection settings
+    settings.profile_combo.connect_changed({
+        let settings = settings.clone();
+        move |combo| {
+            let Some(name) = combo.active_id() else { return };
+            let profiles = Profiles::load().unwrap_or_default();
+            let Some(profile) = profiles.get(&name) else { return };
+
+            populate_midi_combos(&settings, &profile.midi_in, &profile.midi_out);
+            if let Some(channel) = profile.midi_channel {
+                settings.midi_channel_combo.set_active(midi_channel_to_combo_index(channel));
+            }
+            populate_model_combo(&settings, &profile.model);
+            populate_controller_in_combo(&settings, &profile.controller_in);
+            settings.clear_message();
+        }
+    });
+
+    settings.profile_save_button.connect_clicked({
+        let settings = settings.clone();
+        move |_| {
+            let name = settings.profile_combo.active_text()
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default();
+            if name.is_empty() {
+                settings.set_message("dialog-warning", "Enter a profile name");
+                return;
+            }
+            let res = Profiles::load().and_then(|mut profiles| {
+                profiles.set(profile_from_dialog(&settings, &name));
+                profiles.save()
+            });
+            match res {
+                Ok(_) => {
+                    populate_profile_combo(&settings);
+                    settings.profile_combo.set_active_id(Some(&name));
+                    settings.set_message("dialog-ok", &format!("Profile {:?} saved", name));
+                }
+                Err(err) => {
+                    error!("Failed to save profile: {}", err);
+                    settings.set_message("dialog-error", &format!("Failed to save profile:\n\n{}", err));
+                }
+            }
+        }
+    });
+
+    settings.profile_delete_button.connect_clicked({
+        let settings = settings.clone();
+        move |_| {
+            let Some(name) = settings.profile_combo.active_id() else {
+                settings.set_message("dialog-warning", "Select a saved profile to delete");
+                return;
+            };
+            let res = Profiles::load().and_then(|mut profiles| {
+                profiles.remove(&name);
+                profiles.save()
+            });
+            match res {
+                Ok(_) => {
+                    populate_profile_combo(&settings);
+                    settings.set_message("dialog-ok", &format!("Profile {:?} deleted", name));
+                }
+                Err(err) => {
+                    error!("Failed to delete profile: {}", err);
+                    settings.set_message("dialog-error", &format!("Failed to delete profile:\n\n{}", err));
+                }
+            }
+        }
+    });
+}
+
 fn wire_autodetect_button(settings: &SettingsDialog) {
     let settings = settings.clone();
     settings.autodetect_button.clone().connect_clicked(move |button| {
@@ -250,21 +385,20 @@ fn wire_test_button(settings: &SettingsDialog) {
     });
 }
 
-pub fn create_settings_action(state: Arc<Mutex<State>>, ui: &gtk::Builder) -> gio::ActionEntry<gtk::Application> {
+/// The "preferences" action of a device window. With `remember`, the
+/// connection picked is saved as the last one used.
+pub fn create_settings_action(state: Arc<Mutex<State>>, ui: &gtk::Builder,
+                              remember: bool) -> gio::ActionEntry<gtk::ApplicationWindow> {
     let settings = SettingsDialog::new(ui);
 
     populate_midi_channel_combo(&settings);
+    wire_profile_controls(&settings);
     wire_autodetect_button(&settings);
     wire_test_button(&settings);
 
-    gio::ActionEntry::builder("preferences").activate(move |app: &gtk::Application, _, _| {
-        settings.dialog.set_application(Some(app));
-        let window = app.windows().iter()
-            .find(|w| w.downcast_ref::<gtk::ApplicationWindow>().is_some())
-            .cloned();
-        if let Some(window) = window {
-            settings.dialog.set_transient_for(Some(&window));
-        }
+    gio::ActionEntry::builder("preferences").activate(move |window: &gtk::ApplicationWindow, _, _| {
+        settings.dialog.set_application(window.application().as_ref());
+        settings.dialog.set_transient_for(Some(window));
 
         // reset the dialog
         settings.set_interactive(true);
@@ -281,6 +415,8 @@ pub fn create_settings_action(state: Arc<Mutex<State>>, ui: &gtk::Builder) -> gi
 
             let config_name = state.config.map(|c| c.name.clone());
             populate_model_combo(&settings, &config_name);
+            populate_controller_in_combo(&settings, &state.ext_in_name);
+            populate_profile_combo(&settings);
 
             // stop the midi thread during test
             midi_in_out_stop(&mut state)
@@ -351,7 +487,18 @@ pub fn create_settings_action(state: Arc<Mutex<State>>, ui: &gtk::Builder) -> gi
 
                 let midi_channel = settings.midi_channel_combo.active();
                 let midi_channel = midi_channel_from_combo_index(midi_channel);
-                set_midi_in_out(&mut state.lock().unwrap(), midi_in, midi_out, midi_channel, config);
+
+                let mut state = state.lock().unwrap();
+                let controller_in = controller_in_from_dialog(&settings);
+                if controller_in != state.ext_in_name {
+                    let ext_in = controller_in.and_then(|name| {
+                        MidiIn::new_for_name(&name)
+                            .map_err(|err| error!("Failed to open controller MIDI input {:?}: {}", name, err))
+                            .ok()
+                    });
+                    set_ext_midi_in(&mut state, ext_in);
+                }
+                set_midi_in_out(&mut state, midi_in, midi_out, midi_channel, config, remember);
             }
             _ => {
                 let mut state = state.lock().unwrap();
@@ -0,0 +1,248 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use pod_core::controller::*;
+use pod_core::dump::ProgramsDump;
+use pod_core::event::Program;
+use pod_core::model::Config;
+use pod_core::program_id_string;
+use pod_gtk::prelude::*;
+use gtk::gdk;
+
+const NOTES: &[&str] = &["B", "C", "D♭", "D", "E♭", "E", "F", "G♭", "G", "A♭", "A", "B♭"];
+/// "no note" and "no offset" values of the tuner controls
+const TUNER_NO_NOTE: u16 = 0xfffe;
+const TUNER_NO_OFFSET: u16 = 97;
+
+#[derive(Default)]
+struct StageState {
+    config: Option<&'static Config>,
+    program: Option<Program>,
+    names: Vec<String>,
+    modified: Vec<bool>,
+    tuner_note: Option<usize>,
+    tuner_offset: Option<i16>
+}
+
+/// Full-screen view for live use: current program id, name and modified
+/// state in huge text, large next/previous targets and the tuner readout
+#[derive(Clone)]
+pub struct StageView {
+    window: gtk::Window,
+    /// Device window with the setlist step actions
+    actions: gtk::ApplicationWindow,
+    ui_controller: Arc<Mutex<Controller>>,
+    state: Rc<RefCell<StageState>>,
+
+    program_label: gtk::Label,
+    name_label: gtk::Label,
+    modified_label: gtk::Label,
+    tuner_label: gtk::Label
+}
+
+impl StageView {
+    pub fn new(parent: &gtk::ApplicationWindow, ui_controller: Arc<Mutex<Controller>>) -> Self {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title("Stage view");
+        window.set_widget_name("stage");
+        window.set_transient_for(Some(parent));
+        window.set_destroy_with_parent(true);
+
+        let program_label = gtk::Label::new(None);
+        let name_label = gtk::Label::new(None);
+        name_label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+        let modified_label = gtk::Label::new(None);
+        let tuner_label = gtk::Label::new(None);
+        tuner_label.set_no_show_all(true);
+
+        let prev_button = gtk::Button::with_label("◀");
+        let next_button = gtk::Button::with_label("▶");
+        for b in [&prev_button, &next_button] {
+            b.set_size_request(160, -1);
+            b.style_context().add_class("stage-step");
+        }
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        vbox.set_valign(gtk::Align::Center);
+        vbox.pack_start(&program_label, false, false, 0);
+        vbox.pack_start(&name_label, false, false, 0);
+        vbox.pack_start(&modified_label, false, false, 0);
+        vbox.pack_start(&tuner_label, false, false, 0);
+
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        hbox.set_border_width(12);
+        hbox.pack_start(&prev_button, false, true, 0);
+        hbox.pack_start(&vbox, true, true, 0);
+        hbox.pack_start(&next_button, false, true, 0);
+        window.add(&hbox);
+
+        let view = StageView {
+            window, actions: parent.clone(), ui_controller,
+            state: Rc::new(RefCell::new(StageState::default())),
+            program_label, name_label, modified_label, tuner_label
+        };
+
+        prev_button.connect_clicked({
+            let view = view.clone();
+            move |_| view.step(-1)
+        });
+        next_button.connect_clicked({
+            let view = view.clone();
+            move |_| view.step(1)
+        });
+
+        view.window.connect_key_press_event(|window, event| {
+            match event.keyval() {
+                gdk::keys::constants::Escape | gdk::keys::constants::F11 => {
+                    window.close();
+                    Inhibit(true)
+                }
+                _ => Inhibit(false)
+            }
+        });
+        view.window.connect_delete_event(|window, _| {
+            window.unfullscreen();
+            window.hide();
+            Inhibit(true)
+        });
+
+        view
+    }
+
+    pub fn show(&self) {
+        self.window.show_all();
+        self.window.fullscreen();
+        self.window.present();
+        self.update();
+    }
+
+    /// Update the device the view shows, reading program names and
+    /// modified state from the programs dump
+    pub fn set_device(&self, config: &'static Config, dump: &ProgramsDump) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.config = Some(config);
+            state.names = (0 .. dump.program_num())
+                .map(|p| dump.name(p).unwrap_or_default())
+                .collect();
+            state.modified = (0 .. dump.program_num())
+                .map(|p| dump.modified(p))
+                .collect();
+            state.tuner_note = None;
+            state.tuner_offset = None;
+        }
+        let has_tuner = config.controls.contains_key("tuner_note");
+        self.tuner_label.set_visible(has_tuner);
+        self.update();
+    }
+
+    pub fn set_program(&self, value: u16) {
+        self.state.borrow_mut().program = Some(value.into());
+        self.update();
+    }
+
+    pub fn set_name(&self, program: usize, name: &str) {
+        if let Some(n) = self.state.borrow_mut().names.get_mut(program) {
+            *n = name.to_string();
+        }
+        self.update();
+    }
+
+    pub fn set_modified(&self, program: usize, modified: bool) {
+        if let Some(m) = self.state.borrow_mut().modified.get_mut(program) {
+            *m = modified;
+        }
+        self.update();
+    }
+
+    /// Update the tuner readout from the edit buffer controls
+    pub fn set_control(&self, name: &str, value: u16) {
+        {
+            let mut state = self.state.borrow_mut();
+            match name {
+                "tuner_note" => {
+                    state.tuner_note = Some(value).filter(|v| *v != TUNER_NO_NOTE).map(|v| v as usize);
+                }
+                "tuner_offset" => {
+                    state.tuner_offset = Some(value).filter(|v| *v != TUNER_NO_OFFSET)
+                        .map(|v| (v as i16).clamp(-50, 50));
+                }
+                _ => return
+            }
+        }
+        self.update_tuner();
+    }
+
+    /// Select the previous/next program. In setlist mode, step through
+    /// the setlist instead.
+    fn step(&self, offset: i32) {
+        let action = if offset > 0 { "setlist-next" } else { "setlist-prev" };
+        if self.actions.lookup_action(action).map(|a| a.is_enabled()).unwrap_or(false) {
+            self.actions.activate_action(action, None);
+            return;
+        }
+
+        let state = self.state.borrow();
+        let Some(config) = state.config else { return };
+        let current = match state.program {
+            Some(Program::Program(p)) => p as i32,
+            _ => if offset > 0 { -1 } else { config.program_num as i32 }
+        };
+        let program = current + offset;
+        if program < 0 || program >= config.program_num as i32 {
+            return;
+        }
+        let program: u16 = Program::Program(program as u16).into();
+        self.ui_controller.set("program", program, StoreOrigin::UI);
+    }
+
+    fn update(&self) {
+        if !self.window.is_visible() {
+            return;
+        }
+        let state = self.state.borrow();
+        let (id, name, modified) = match &state.program {
+            Some(Program::Program(p)) => {
+                let p = *p as usize;
+                (program_id_string(p),
+                 state.names.get(p).cloned().unwrap_or_default(),
+                 state.modified.get(p).cloned().unwrap_or_default())
+            }
+            Some(Program::ManualMode) => ("Manual".into(), String::new(), false),
+            Some(Program::Tuner) => ("Tuner".into(), String::new(), false),
+            None => ("—".into(), String::new(), false)
+        };
+
+        self.program_label.set_markup(&format!(
+            "<span size=\"120000\" weight=\"bold\">{}</span>", glib::markup_escape_text(&id)
+        ));
+        self.name_label.set_markup(&format!(
+            "<span size=\"60000\">{}</span>", glib::markup_escape_text(name.trim())
+        ));
+        let modified = if modified { "MODIFIED" } else { "" };
+        self.modified_label.set_markup(&format!(
+            "<span size=\"30000\" foreground=\"#e01b24\" weight=\"bold\">{}</span>", modified
+        ));
+        drop(state);
+        self.update_tuner();
+    }
+
+    fn update_tuner(&self) {
+        if !self.window.is_visible() || !self.tuner_label.is_visible() {
+            return;
+        }
+        let state = self.state.borrow();
+        let note = state.tuner_note
+            .map(|v| format!("{}{}", NOTES[v % 12], v / 12 + 1))
+            .unwrap_or("—".into());
+        let offset = match state.tuner_offset {
+            None => String::new(),
+            Some(v) if v.abs() <= 2 => "●".into(),
+            Some(v) if v < 0 => format!("{} ◀", v),
+            Some(v) => format!("▶ +{}", v)
+        };
+        self.tuner_label.set_markup(&format!(
+            "<span size=\"50000\">{}   {}</span>", note, offset
+        ));
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+use log::*;
+use pod_core::thru::{MidiThru, ThruChannel};
+use pod_gtk::prelude::*;
+use gtk::ResponseType;
+
+/// MIDI thru settings shared with the app event thread
+pub type MidiThruShare = Arc<Mutex<MidiThru>>;
+
+fn channel_to_id(channel: ThruChannel) -> String {
+    match channel {
+        ThruChannel::Keep => "keep".into(),
+        ThruChannel::Device => "device".into(),
+        ThruChannel::Channel(c) => (c + 1).to_string()
+    }
+}
+
+fn channel_from_id(id: &str) -> ThruChannel {
+    match id {
+        "device" => ThruChannel::Device,
+        id => id.parse::<u8>().ok()
+            .map(|c| ThruChannel::Channel(c - 1))
+            .unwrap_or(ThruChannel::Keep)
+    }
+}
+
+/// Show the MIDI thru settings: forwarding of the external controller
+/// input to the device, message type filters and channel remapping
+pub fn show_thru_dialog(window: &gtk::Window, share: MidiThruShare) {
+    let thru = share.lock().unwrap().clone();
+
+    let dialog = gtk::Dialog::with_buttons(
+        Some("MIDI thru"),
+        Some(window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT | gtk::DialogFlags::MODAL,
+        &[("Cancel", ResponseType::Cancel), ("OK", ResponseType::Ok)]
+    );
+    dialog.set_default_response(ResponseType::Ok);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(4);
+    grid.set_column_spacing(12);
+    grid.set_border_width(12);
+
+    let enabled = gtk::CheckButton::with_label("Forward controller MIDI input to the device");
+    enabled.set_active(thru.enabled);
+    grid.attach(&enabled, 0, 0, 2, 1);
+
+    let label = gtk::Label::new(None);
+    label.set_markup("<b>Message types</b>");
+    label.set_halign(gtk::Align::Start);
+    grid.attach(&label, 0, 1, 2, 1);
+
+    let checks = [
+        ("Control change", thru.cc),
+        ("Program change", thru.pc),
+        ("SysEx", thru.sysex),
+        ("Clock & real-time", thru.realtime),
+        ("Notes & other", thru.other),
+    ].iter().enumerate().map(|(i, (title, active))| {
+        let check = gtk::CheckButton::with_label(title);
+        check.set_active(*active);
+        grid.attach(&check, 0, i as i32 + 2, 2, 1);
+        check
+    }).collect::<Vec<_>>();
+
+    let label = gtk::Label::new(Some("Channel:"));
+    label.set_halign(gtk::Align::End);
+    let channel = gtk::ComboBoxText::new();
+    channel.append(Some("keep"), "Keep");
+    channel.append(Some("device"), "Device channel");
+    for c in 1 ..= 16 {
+        channel.append(Some(&c.to_string()), &c.to_string());
+    }
+    channel.set_active_id(Some(&channel_to_id(thru.channel)));
+    grid.attach(&label, 0, 7, 1, 1);
+    grid.attach(&channel, 1, 7, 1, 1);
+
+    dialog.content_area().add(&grid);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Ok {
+            let thru = MidiThru {
+                enabled: enabled.is_active(),
+                cc: checks[0].is_active(),
+                pc: checks[1].is_active(),
+                sysex: checks[2].is_active(),
+                realtime: checks[3].is_active(),
+                other: checks[4].is_active(),
+                channel: channel.active_id()
+                    .map(|id| channel_from_id(id.as_str()))
+                    .unwrap_or_default()
+            };
+            thru.save()
+                .unwrap_or_else(|err| error!("Failed to save MIDI thru settings: {}", err));
+            *share.lock().unwrap() = thru;
+        }
+        dialog.close();
+    });
+    dialog.show_all();
+}
@@ -1,5 +1,4 @@
 use std::fmt::Debug;
-use std::sync::atomic;
 use log::warn;
 use pod_gtk::prelude::glib;
 
@@ -16,14 +15,6 @@ impl <T> ToSome for T {
     }
 }
 
-/// A virtual thread id to show in logs to be able to trace thread start/stop.
-/// This is just a running number with no connection to the real thread id.
-
-static THREAD_ID_COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
-pub fn next_thread_id() -> usize {
-    THREAD_ID_COUNTER.fetch_add(1, atomic::Ordering::SeqCst)
-}
-
 // Implement SenderExt for glib::Sender<T> by duplicating `pod_core::event::SenderExt<T>`
 // for a rather bogus reason IMO of E0210 :( ...
 
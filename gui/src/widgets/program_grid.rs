@@ -1,4 +1,6 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
 use std::time::Duration;
 use log::warn;
 use maplit::hashmap;
@@ -25,9 +27,20 @@ pub enum ProgramGridAction {
     LoadUnmodified { program: usize },
     Store { program: usize },
     LoadDevice { program: usize },
-    StoreDevice { program: usize }
+    StoreDevice { program: usize },
+    Swap { program: usize, with: usize },
+    Move { from: usize, to: usize },
+    Insert { program: usize },
+    LoadDeviceMany { programs: Vec<usize> },
+    StoreDeviceMany { programs: Vec<usize> },
+    Export { programs: Vec<usize>, path: PathBuf },
+    Reset { programs: Vec<usize> },
+    CopyTo { from: Vec<usize>, to: usize }
 }
 
+/// Drag-and-drop target for moving programs within the grid
+const DND_TARGET: &str = "application/x-pod-ui-program";
+
 #[derive(Clone, Debug)]
 struct Widgets {
     size_group: gtk::SizeGroup,
@@ -38,6 +51,7 @@ struct Widgets {
     left: Option<gtk::Button>,
     right: Option<gtk::Button>,
     right_click_menu: gtk::Menu,
+    search: gtk::SearchEntry,
 }
 
 pub struct ProgramGridPriv {
@@ -45,6 +59,15 @@ pub struct ProgramGridPriv {
     num_pages: Cell<usize>,
     is_open: Cell<bool>,
     right_click_target: Cell<i32>,
+    /// Multi-selection of programs (ctrl+click, shift+click)
+    selection: RefCell<BTreeSet<usize>>,
+    selection_anchor: Cell<i32>,
+    /// Programs copied with "copy", to be pasted elsewhere
+    copied: RefCell<Vec<usize>>,
+    /// Programs matching the search text and the last one jumped to,
+    /// `None` until Enter is pressed
+    search_matches: RefCell<Vec<usize>>,
+    search_pos: Cell<Option<usize>>,
     widgets: OnceCell<Widgets>
 }
 
@@ -190,6 +213,69 @@ impl ProgramGridPriv {
     fn set_program_name(&self, program_idx: usize, name: &str) {
         self.program_button(program_idx)
             .map(|p| p.set_program_name(name));
+
+        // keep the search results up to date as the names are loaded
+        if let Some(w) = self.widgets.get() {
+            if !w.search.text().is_empty() {
+                self.search(&w.search.text(), false);
+            }
+        }
+    }
+
+    /// Highlight programs whose name contains `text` (case-insensitive),
+    /// dim the others and, if `jump` is set, show the page with the first
+    /// match. An empty `text` clears the search.
+    fn search(&self, text: &str, jump: bool) {
+        let Some(w) = self.widgets.get() else { return };
+        let text = text.trim().to_lowercase();
+
+        let mut matches = vec![];
+        for (i, button) in w.buttons.iter().enumerate().take(self.num_buttons()) {
+            let ctx = button.style_context();
+            if text.is_empty() {
+                ctx.remove_class("search-match");
+                ctx.remove_class("search-dim");
+                continue;
+            }
+            let name = self.program_name(i).map(|n| n.to_lowercase()).unwrap_or_default();
+            if name.contains(&text) {
+                ctx.add_class("search-match");
+                ctx.remove_class("search-dim");
+                matches.push(i);
+            } else {
+                ctx.add_class("search-dim");
+                ctx.remove_class("search-match");
+            }
+        }
+
+        if jump {
+            // the first Enter goes to the first match
+            self.search_pos.set(None);
+            if let Some(first) = matches.first() {
+                self.show_page(*first / NUM_BUTTONS_PER_PAGE);
+            }
+        }
+        self.search_matches.replace(matches);
+    }
+
+    /// Jump to the next program matching the search
+    fn search_next(&self) {
+        let Some(w) = self.widgets.get() else { return };
+        let matches = self.search_matches.borrow();
+        if matches.is_empty() {
+            return;
+        }
+        let pos = match self.search_pos.get() {
+            Some(pos) => (pos + 1) % matches.len(),
+            None => 0
+        };
+        self.search_pos.set(Some(pos));
+
+        let idx = matches[pos];
+        self.show_page(idx / NUM_BUTTONS_PER_PAGE);
+        if let Some(b) = w.buttons.get(idx) {
+            b.grab_focus();
+        }
     }
 
     fn program_name(&self, program_idx: usize) -> Option<glib::GString> {
@@ -197,25 +283,117 @@ impl ProgramGridPriv {
             .map(|p| p.program_name())
     }
 
+    fn selected_program(&self) -> Option<usize> {
+        self.widgets.get()
+            .and_then(|w| w.buttons.iter().position(|b| b.is_active()))
+            .filter(|idx| *idx < self.num_buttons())
+    }
+
+    fn set_selected(&self, program_idx: usize, selected: bool) {
+        let Some(button) = self.widgets.get().and_then(|w| w.buttons.get(program_idx)) else {
+            return;
+        };
+        let ctx = button.style_context();
+        if selected {
+            ctx.add_class("multi-selected");
+            self.selection.borrow_mut().insert(program_idx);
+        } else {
+            ctx.remove_class("multi-selected");
+            self.selection.borrow_mut().remove(&program_idx);
+        }
+    }
+
+    fn toggle_selected(&self, program_idx: usize) {
+        let selected = self.selection.borrow().contains(&program_idx);
+        self.set_selected(program_idx, !selected);
+        self.selection_anchor.set(program_idx as i32);
+    }
+
+    fn select_range(&self, program_idx: usize) {
+        let anchor = self.selection_anchor.get();
+        let anchor = if anchor < 0 { program_idx } else { anchor as usize };
+        let (from, to) = (anchor.min(program_idx), anchor.max(program_idx));
+        for i in from ..= to {
+            self.set_selected(i, true);
+        }
+    }
+
+    fn clear_selection(&self) {
+        let selection = self.selection.borrow().clone();
+        for i in selection {
+            self.set_selected(i, false);
+        }
+    }
+
+    fn button_clicked(&self, program_idx: usize, event: &gdk::EventButton) -> bool {
+        let state = event.state();
+        if state.contains(gdk::ModifierType::CONTROL_MASK) {
+            self.toggle_selected(program_idx);
+            return true;
+        }
+        if state.contains(gdk::ModifierType::SHIFT_MASK) {
+            self.select_range(program_idx);
+            return true;
+        }
+        self.clear_selection();
+        self.selection_anchor.set(program_idx as i32);
+        false
+    }
+
+    /// Programs targeted by a right-click menu action: the multi-selection
+    /// if the right-clicked program is part of it, otherwise just the
+    /// right-clicked program
+    fn targets(&self, program_idx: usize) -> Vec<usize> {
+        let selection = self.selection.borrow();
+        if selection.contains(&program_idx) {
+            selection.iter().cloned().collect()
+        } else {
+            vec![program_idx]
+        }
+    }
+
     fn show_right_click_menu<T: IsA<gtk::Widget>>(&self, program_idx: usize, widget: &T, event: &gdk::Event, program_id: &str) {
         if let Some(w) = self.widgets.get() {
             w.right_click_menu.set_attach_widget(Some(widget));
             w.right_click_menu.show_all();
 
             let modified = self.program_modified(program_idx).unwrap_or(false);
-            let show_if_modified = |widget:  &gtk::Widget| {
+            let selected = self.selected_program().filter(|idx| *idx != program_idx);
+            let count = self.targets(program_idx).len();
+            let copied = self.copied.borrow().len();
+            let show_if = |widget:  &gtk::Widget| {
                 let style_context = widget.style_context();
                 let classes = style_context.list_classes();
                 if classes.iter().any(|c| c == "show_if_modified") {
                     if modified { widget.show() } else { widget.hide() }
                 }
+                if classes.iter().any(|c| c == "show_if_other_selected") {
+                    if selected.is_some() && count == 1 { widget.show() } else { widget.hide() }
+                }
+                if classes.iter().any(|c| c == "show_if_multi") {
+                    if count > 1 { widget.show() } else { widget.hide() }
+                }
+                if classes.iter().any(|c| c == "hide_if_multi") && count > 1 {
+                    widget.hide()
+                }
+                if classes.iter().any(|c| c == "show_if_copied") {
+                    if copied > 0 { widget.show() } else { widget.hide() }
+                }
             };
 
-            let h = hashmap! { "program_id" => program_id };
+            let selected_id = selected.map(program_id_string).unwrap_or_default();
+            let count = count.to_string();
+            let copied = copied.to_string();
+            let h = hashmap! {
+                "program_id" => program_id,
+                "selected_id" => selected_id.as_str(),
+                "count" => count.as_str(),
+                "copied" => copied.as_str()
+            };
             ObjectList::from_widget(&w.right_click_menu)
                 .objects_by_type::<gtk::MenuItem>()
                 .for_each(|item| {
-                    show_if_modified(item.as_ref());
+                    show_if(item.as_ref());
                     item.render_template(&h);
                 });
 
@@ -237,6 +415,26 @@ impl ProgramGridPriv {
             "store" => ProgramGridAction::Store { program },
             "load-device" => ProgramGridAction::LoadDevice { program },
             "store-device" => ProgramGridAction::StoreDevice { program },
+            "swap" => {
+                let Some(with) = self.selected_program() else { return };
+                ProgramGridAction::Swap { program, with }
+            }
+            "insert" => ProgramGridAction::Insert { program },
+            "load-device-many" => ProgramGridAction::LoadDeviceMany { programs: self.targets(program) },
+            "store-device-many" => ProgramGridAction::StoreDeviceMany { programs: self.targets(program) },
+            "reset" => ProgramGridAction::Reset { programs: self.targets(program) },
+            "copy" => {
+                self.copied.replace(self.targets(program));
+                return;
+            }
+            "paste" => {
+                let from = self.copied.borrow().clone();
+                ProgramGridAction::CopyTo { from, to: program }
+            }
+            "export" => {
+                self.export(self.targets(program));
+                return;
+            }
             _ => {
                 warn!("Unknown right-click menu action: {}", action);
                 return;
@@ -245,6 +443,38 @@ impl ProgramGridPriv {
 
         self.instance().emit_by_name::<()>("action", &[&action]);
     }
+
+    fn export(&self, programs: Vec<usize>) {
+        let window = self.instance().toplevel()
+            .and_then(|w| w.dynamic_cast::<gtk::Window>().ok());
+        let dialog = gtk::FileChooserDialog::with_buttons(
+            Some("Export programs"),
+            window.as_ref(),
+            gtk::FileChooserAction::Save,
+            &[("_Cancel", gtk::ResponseType::Cancel), ("_Export", gtk::ResponseType::Accept)]
+        );
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name("programs.ini");
+
+        dialog.connect_response(glib::clone!(@weak self as p => move |dialog, response| {
+            if response == gtk::ResponseType::Accept {
+                if let Some(path) = dialog.file().and_then(|f| f.path()) {
+                    let action = ProgramGridAction::Export { programs: programs.clone(), path };
+                    p.instance().emit_by_name::<()>("action", &[&action]);
+                }
+            }
+            dialog.close();
+        }));
+        dialog.show();
+    }
+
+    fn drop_program(&self, from: usize, to: usize) {
+        if from == to || from >= self.num_buttons() || to >= self.num_buttons() {
+            return;
+        }
+        let action = ProgramGridAction::Move { from, to };
+        self.instance().emit_by_name::<()>("action", &[&action]);
+    }
 }
 
 #[glib::object_subclass]
@@ -263,6 +493,11 @@ impl ObjectSubclass for ProgramGridPriv {
             num_pages: Cell::new(NUM_PAGES_DEFAULT),
             is_open: Cell::new(false),
             right_click_target: Cell::new(-1),
+            selection: RefCell::new(BTreeSet::new()),
+            selection_anchor: Cell::new(-1),
+            copied: RefCell::new(vec![]),
+            search_matches: RefCell::new(vec![]),
+            search_pos: Cell::new(None),
             widgets: OnceCell::new()
         }
     }
@@ -391,6 +626,9 @@ impl ObjectImpl for ProgramGridPriv {
                 }));
                 b.connect_button_press_event(glib::clone!(@weak self as p =>
                     @default-return Inhibit(false),move |button, event| {
+                        if event.button() == 1 {
+                            return Inhibit(p.button_clicked(i, event));
+                        }
                         if event.button() != 3 { return Inhibit(false) }
 
                         p.show_right_click_menu(i, button, event, program_id.as_str());
@@ -398,6 +636,22 @@ impl ObjectImpl for ProgramGridPriv {
                    })
                 );
 
+                // drag a program onto another slot to move it there
+                let targets = [gtk::TargetEntry::new(DND_TARGET, gtk::TargetFlags::SAME_APP, 0)];
+                b.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &targets, gdk::DragAction::MOVE);
+                b.drag_dest_set(gtk::DestDefaults::ALL, &targets, gdk::DragAction::MOVE);
+                b.connect_drag_data_get(move |_, _, data, _, _| {
+                    data.set(&gdk::Atom::intern(DND_TARGET), 8, i.to_string().as_bytes());
+                });
+                b.connect_drag_data_received(glib::clone!(@weak self as p => move |_, _, _, _, data, _, _| {
+                    let from = String::from_utf8(data.data()).ok()
+                        .and_then(|s| s.parse::<usize>().ok());
+                    match from {
+                        Some(from) => p.drop_program(from, i),
+                        None => warn!("Unexpected program drag-and-drop data")
+                    }
+                }));
+
                 b
             } else {
                 // spacer
@@ -443,6 +697,18 @@ impl ObjectImpl for ProgramGridPriv {
             (Some(left), Some(right))
         };
 
+        // program name search
+        let search = gtk::SearchEntry::new();
+        search.set_placeholder_text(Some("Search programs"));
+        search.set_tooltip_text(Some("Find programs by name, press Enter to go to the next match"));
+        search.connect_search_changed(glib::clone!(@weak self as p => move |entry| {
+            p.search(&entry.text(), true);
+        }));
+        search.connect_activate(glib::clone!(@weak self as p => move |_| {
+            p.search_next();
+        }));
+        grid.attach(&search, 0, 2, 2, 1);
+
         let menu_ui = gtk::Builder::from_string(include_str!("program_grid_menu.glade"));
         let menu: gtk::Menu = menu_ui.object("toplevel").unwrap();
 
@@ -462,6 +728,7 @@ impl ObjectImpl for ProgramGridPriv {
             grid,
             adj: adj.clone(),
             right_click_menu: menu,
+            search,
             left, right
         }).expect("Setting widgets failed");
 
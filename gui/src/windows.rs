@@ -0,0 +1,166 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use pod_core::bank::{is_compatible, Bank};
+use pod_core::controller::*;
+use pod_core::convert::convert_patch;
+use pod_core::dump::ProgramsDump;
+use pod_core::edit::EditBuffer;
+use pod_core::engine::{Services, State};
+use pod_core::event::*;
+use pod_core::model::Config;
+use pod_core::program_id_string;
+use pod_gtk::prelude::*;
+use crate::api::ApiServerShare;
+
+/// The device edited in a window
+#[derive(Clone)]
+pub struct WindowDevice {
+    pub config: &'static Config,
+    pub edit: Arc<Mutex<EditBuffer>>,
+    pub dump: Arc<Mutex<ProgramsDump>>
+}
+
+/// A device window: an engine with its own device context, MIDI ports
+/// and channel, sharing the services of the app with the other windows.
+/// The first window is the primary one: it gets the controller MIDI
+/// input, the OSC and HTTP API servers and its connection is remembered.
+#[derive(Clone)]
+pub struct DeviceWindow {
+    pub window: gtk::ApplicationWindow,
+    pub state: Arc<Mutex<State>>,
+    pub app_event_tx: broadcast::Sender<AppEvent>,
+    pub ui_controller: Arc<Mutex<Controller>>,
+    pub device: Rc<RefCell<Option<WindowDevice>>>,
+    pub primary: bool
+}
+
+impl DeviceWindow {
+    fn notify(&self, msg: String) {
+        self.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+    }
+
+    /// The selected program, if any
+    fn program(&self, config: &Config) -> Option<usize> {
+        let program: Program = self.ui_controller.get("program").unwrap_or(1000).into();
+        match program {
+            Program::Program(p) if (p as usize) < config.program_num => Some(p as usize),
+            _ => None
+        }
+    }
+}
+
+/// A program copied from one of the windows, to be pasted into another
+struct Clipboard {
+    config: &'static Config,
+    name: String,
+    data: Vec<u8>
+}
+
+/// The device windows of the app
+#[derive(Clone)]
+pub struct DeviceWindows {
+    windows: Rc<RefCell<Vec<DeviceWindow>>>,
+    clipboard: Rc<RefCell<Option<Clipboard>>>,
+    /// Shared by the engines of all windows, so that the settings are
+    /// loaded and saved in one place
+    pub services: Services,
+    /// Started by the primary window, shared so that the HTTP API dialog
+    /// of any window controls the same server
+    pub api_server: ApiServerShare
+}
+
+impl DeviceWindows {
+    pub fn new(services: Services) -> Self {
+        DeviceWindows {
+            windows: Rc::new(RefCell::new(vec![])),
+            clipboard: Rc::new(RefCell::new(None)),
+            services,
+            api_server: Arc::new(Mutex::new(None))
+        }
+    }
+
+    pub fn add(&self, window: DeviceWindow) {
+        self.windows.borrow_mut().push(window);
+    }
+
+    /// Remove a closed window. Returns `true` if it was the last one.
+    pub fn remove(&self, window: &gtk::ApplicationWindow) -> bool {
+        let mut windows = self.windows.borrow_mut();
+        windows.retain(|w| w.window != *window);
+        windows.is_empty()
+    }
+
+    pub fn all(&self) -> Vec<DeviceWindow> {
+        self.windows.borrow().clone()
+    }
+
+    pub fn primary(&self) -> Option<DeviceWindow> {
+        self.windows.borrow().iter().find(|w| w.primary).cloned()
+    }
+
+    /// MIDI input ports of the devices connected in the windows
+    pub fn ports_in_use(&self) -> Vec<String> {
+        self.windows.borrow().iter()
+            .flat_map(|w| w.state.lock().unwrap().midi_in_name.clone())
+            .collect()
+    }
+
+    /// Copy the selected program of `window`, with the edit buffer
+    /// changes, to be pasted into another window
+    pub fn copy_program(&self, window: &DeviceWindow) {
+        let Some(device) = window.device.borrow().clone() else { return };
+        let Some(program) = window.program(device.config) else {
+            window.notify("Select a program to copy".into());
+            return;
+        };
+        let current: Program = (program as u16).into();
+        let bank = Bank::capture(device.config, current, &device.edit.lock().unwrap(),
+                                 &device.dump.lock().unwrap(), &[program]);
+        let Some((_, name, data)) = bank.programs.into_iter().next() else { return };
+
+        window.notify(format!("Copied program {} \"{}\" of {}",
+                              program_id_string(program), name.trim(), device.config.name));
+        self.clipboard.replace(Some(Clipboard { config: device.config, name, data }));
+    }
+
+    /// Paste the copied program into the selected program of `window`.
+    /// Like other program changes, it needs to be stored to the device.
+    pub fn paste_program(&self, window: &DeviceWindow) {
+        let Some(device) = window.device.borrow().clone() else { return };
+        let clipboard = self.clipboard.borrow();
+        let Some(clipboard) = clipboard.as_ref() else {
+            window.notify("Nothing to paste, copy a program first".into());
+            return;
+        };
+        let Some(program) = window.program(device.config) else {
+            window.notify("Select a program to paste into".into());
+            return;
+        };
+        // programs of other devices are converted, as far as they can be
+        let data = if is_compatible(clipboard.config, device.config) {
+            clipboard.data.clone()
+        } else {
+            match convert_patch(clipboard.config, &clipboard.data, device.config) {
+                Ok((data, report)) => {
+                    if !report.is_lossless() {
+                        window.notify(format!("Converted from {}. {}",
+                                              clipboard.config.name, report.to_string().trim()));
+                    }
+                    data
+                }
+                Err(err) => {
+                    window.notify(format!("Programs of {} cannot be pasted into {}: {}",
+                                          clipboard.config.name, device.config.name, err));
+                    return;
+                }
+            }
+        };
+
+        let e = ReorderEvent::Import { programs: vec![(program, data)] };
+        window.app_event_tx.send_or_warn(AppEvent::Reorder(e));
+        window.notify(format!("Pasted \"{}\" into program {}, store it to the device to keep it",
+                              clipboard.name.trim(), program_id_string(program)));
+    }
+}
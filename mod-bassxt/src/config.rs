@@ -557,6 +557,8 @@ pub static BASS_PODXT_CONFIG: Lazy<Config> = Lazy::new(|| {
 
         flags: DeviceFlags::MANUAL_MODE,
         midi_quirks: MidiQuirks::empty(),
+        firmware_variants: vec![],
+        tested_firmware: vec![],
     }
 });
 
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use pod_core::device::{BoxedDevice, Device};
+use pod_core::handler::BoxedHandler;
+use pod_core::model::Config;
+use pod_mod_xt::handler::PodXtHandler;
+use pod_mod_xt::model_labels;
+
+use crate::config;
+
+pub struct BassPodXtDevice;
+
+impl Device for BassPodXtDevice {
+    fn config(&self) -> Box<[Config]> {
+        vec![
+            config::BASS_PODXT_CONFIG.clone(),
+            config::BASS_PODXT_PRO_CONFIG.clone(),
+            config::BASS_PODXT_LIVE_CONFIG.clone(),
+        ].into_boxed_slice()
+    }
+
+    fn handler(&self, config: &'static Config) -> BoxedHandler {
+        Box::new(PodXtHandler::new(config, false, crate::rules::rules))
+    }
+
+    fn select_labels(&self, _config: &Config) -> HashMap<String, Vec<String>> {
+        model_labels(&config::STOMP_CONFIG, &config::MOD_CONFIG, &config::DELAY_CONFIG)
+    }
+}
+
+pub fn device() -> BoxedDevice {
+    Box::new(BassPodXtDevice)
+}
+
+#[cfg(test)]
+mod tests {
+    use pod_core::config::configs;
+    use pod_core::controller::*;
+    use pod_core::convert::convert_patch;
+    use pod_core::device::register_device;
+    use pod_core::program::init_program;
+    use crate::device::*;
+
+    fn config(name: &str) -> &'static Config {
+        configs().iter().find(|c| c.name == name).unwrap()
+    }
+
+    /// A PODxt patch with the given control values
+    fn xt_patch(xt: &'static Config, values: &[(&str, u16)]) -> Vec<u8> {
+        let handler = pod_mod_xt::device().handler(xt);
+        let mut data = init_program(xt, handler.as_ref());
+        let mut controller = Controller::new(xt.controls.clone());
+        for (name, value) in values {
+            controller.set(name, *value, StoreOrigin::NONE);
+            handler.control_value_to_buffer(&controller, name, &mut data);
+        }
+        data
+    }
+
+    fn value(config: &'static Config, data: &[u8], name: &str) -> Option<u16> {
+        let handler = device().handler(config);
+        let mut controller = Controller::new(config.controls.clone());
+        handler.control_value_from_buffer(&mut controller, name, data);
+        controller.get(name)
+    }
+
+    #[test]
+    fn convert_from_podxt() {
+        register_device(pod_mod_xt::device()).unwrap();
+        register_device(device()).unwrap();
+        let xt = config("PODxt");
+        let bass = config("Bass PODxt (experimental)");
+
+        // Fuzz Pi, FX-Hi-Talk, Tube Echo
+        let data = xt_patch(xt, &[("stomp_select", 1), ("mod_select", 18), ("delay_select", 2)]);
+        let (out, report) = convert_patch(xt, &data, bass).unwrap();
+        assert_eq!(value(bass, &out, "stomp_select"), Some(4));
+        assert_eq!(value(bass, &out, "mod_select"), Some(9));
+        assert_eq!(value(bass, &out, "delay_select"), Some(2));
+        assert_eq!(report.unmapped, vec![]);
+        assert!(report.dropped.contains(&"reverb_select".to_string()));
+        assert!(report.dropped.contains(&"wah_select".to_string()));
+
+        // Auto Swell, Sine Chorus and Ping Pong are not on the Bass PODxt
+        // and are left at the initial values
+        let data = xt_patch(xt, &[("stomp_select", 8), ("mod_select", 0), ("delay_select", 7)]);
+        let (out, report) = convert_patch(xt, &data, bass).unwrap();
+        assert_eq!(report.unmapped, vec![
+            ("delay_select".to_string(), "Ping Pong".to_string()),
+            ("mod_select".to_string(), "Sine Chorus".to_string()),
+            ("stomp_select".to_string(), "Auto Swell".to_string()),
+        ]);
+        let init = init_program(bass, device().handler(bass).as_ref());
+        for name in ["stomp_select", "mod_select", "delay_select"] {
+            assert_eq!(value(bass, &out, name), value(bass, &init, name));
+        }
+        assert!(!report.is_lossless());
+    }
+}
@@ -1,5 +1,9 @@
 mod config;
+mod device;
+#[cfg(feature = "gtk")]
 mod module;
-mod wiring;
+mod rules;
 
+pub use device::*;
+#[cfg(feature = "gtk")]
 pub use module::*;
@@ -4,34 +4,24 @@ use pod_core::model::Config;
 use pod_core::store::{Signal, StoreSetIm};
 use pod_gtk::prelude::*;
 use gtk::{Builder, Widget};
-use pod_core::handler::BoxedHandler;
+use pod_core::device::BoxedDevice;
 use pod_core::store::Origin::MIDI;
 use pod_mod_pod2::wiring::*;
-use pod_mod_xt::handler::PodXtHandler;
 use pod_mod_xt::widgets::Tuner;
 use pod_mod_xt::wiring::{*, init_combo};
 
 use crate::config;
-use crate::wiring::*;
 
 struct BassPodXtModule;
 
 impl Module for BassPodXtModule {
-    fn config(&self) -> Box<[Config]> {
-        vec![
-            config::BASS_PODXT_CONFIG.clone(),
-            config::BASS_PODXT_PRO_CONFIG.clone(),
-            config::BASS_PODXT_LIVE_CONFIG.clone(),
-        ].into_boxed_slice()
+    fn device(&self) -> BoxedDevice {
+        crate::device()
     }
 
     fn init(&self, config: &'static Config) -> Box<dyn Interface> {
         Box::new(BassPodXtInterface::new(config))
     }
-
-    fn handler(&self, config: &'static Config) -> BoxedHandler {
-        Box::new(PodXtHandler::new(config, false))
-    }
 }
 
 struct BassPodXtInterface {
@@ -97,15 +87,7 @@ impl Interface for BassPodXtInterface {
                         controller.clone(), &self.objects, callbacks)?;
         wire_delay_select(&config::DELAY_CONFIG,
                           controller.clone(), &self.objects, callbacks)?;
-        wire_14bit(controller.clone(), &self.objects, callbacks,
-                   "mod_speed", "mod_speed:msb", "mod_speed:lsb",
-                   true)?;
-        wire_14bit(controller.clone(), &self.objects, callbacks,
-                   "delay_time", "delay_time:msb", "delay_time:lsb",
-                   true)?;
-        wire_tempo(controller.clone(), &self.objects, callbacks)?;
-        wire_delay_controls_show(controller.clone(), &self.objects, callbacks)?;
-        wire_pedal_assign(controller.clone(), &self.objects, callbacks)?;
+        wire_tempo_tap(controller.clone(), &self.objects)?;
         wire_name_change(edit, config, &self.objects, callbacks)?;
 
         let tuner_box = self.objects.ref_by_name::<gtk::Box>("tuner_box").unwrap();
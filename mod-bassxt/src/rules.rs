@@ -0,0 +1,24 @@
+use pod_core::controller::*;
+use pod_core::controller::StoreOrigin::NONE;
+use pod_core::model::Config;
+use pod_core::rules::Rules;
+use pod_mod_xt::rules::rules_common;
+
+use crate::config;
+
+pub fn rules_delay_controls_show(rules: &mut Rules) {
+    rules
+        .on("delay_select")
+        .run(move |value, controller, _| {
+            let show = value <= 6;
+            controller.set("delay_controls:show", show as u16, NONE);
+        });
+}
+
+/// Parameter rules of the Bass PODxt family
+pub fn rules(_config: &'static Config) -> Rules {
+    let mut rules = Rules::new();
+    rules_common(&mut rules, &config::STOMP_CONFIG, &config::MOD_CONFIG, &config::DELAY_CONFIG);
+    rules_delay_controls_show(&mut rules);
+    rules
+}
@@ -0,0 +1,22 @@
+use pod_core::device::{BoxedDevice, Device};
+use pod_core::handler::BoxedHandler;
+use pod_core::model::Config;
+use pod_mod_pod2::Pod2Handler;
+
+use crate::config;
+
+pub struct PocketPodDevice;
+
+impl Device for PocketPodDevice {
+    fn config(&self) -> Box<[Config]> {
+        vec![config::CONFIG.clone()].into_boxed_slice()
+    }
+
+    fn handler(&self, _config: &'static Config) -> BoxedHandler {
+        Box::new(Pod2Handler)
+    }
+}
+
+pub fn device() -> BoxedDevice {
+    Box::new(PocketPodDevice)
+}
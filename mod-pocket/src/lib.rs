@@ -1,4 +1,8 @@
 mod config;
+mod device;
+#[cfg(feature = "gtk")]
 mod module;
 
+pub use device::*;
+#[cfg(feature = "gtk")]
 pub use module::*;
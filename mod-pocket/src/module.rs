@@ -4,27 +4,21 @@ use pod_core::model::Config;
 use pod_core::store::{Signal, StoreSetIm};
 use pod_gtk::prelude::*;
 use gtk::{Builder, Widget};
-use pod_core::handler::BoxedHandler;
+use pod_core::device::BoxedDevice;
 use pod_core::store::Origin::MIDI;
-use pod_mod_pod2::Pod2Handler;
 use pod_mod_pod2::wiring::*;
 
-use crate::config;
 
 pub struct PocketPodModule;
 
 impl Module for PocketPodModule {
-    fn config(&self) -> Box<[Config]> {
-        vec![config::CONFIG.clone()].into_boxed_slice()
+    fn device(&self) -> BoxedDevice {
+        crate::device()
     }
 
     fn init(&self, config: &'static Config) -> Box<dyn Interface> {
         Box::new(PocketPodInterface::new(config))
     }
-
-    fn handler(&self, _config: &'static Config) -> BoxedHandler {
-        Box::new(Pod2Handler)
-    }
 }
 
 struct PocketPodInterface {
@@ -72,10 +66,6 @@ impl Interface for PocketPodInterface {
         wire(controller.clone(), &self.objects, callbacks)?;
 
         wire_amp_select(controller.clone(), config, &self.objects, callbacks)?;
-        wire_14bit(controller.clone(), &self.objects, callbacks,
-                   "delay_time", "delay_time:msb", "delay_time:lsb",
-                   false)?;
-        wire_effect_select(config, controller, callbacks)?;
         wire_name_change(edit, config, &self.objects, callbacks)?;
         //todo!()
         Ok(())
@@ -358,7 +358,10 @@ pub static POD2_CONFIG: Lazy<Config> = Lazy::new(|| {
         program_name_length: 16,
 
         flags: DeviceFlags::MANUAL_MODE | DeviceFlags::ALL_PROGRAMS_DUMP,
-        midi_quirks: MidiQuirks::empty()
+        midi_quirks: MidiQuirks::empty(),
+        firmware_variants: vec![],
+        // device inquiry reply of a POD 2.0, see testing/two-devices.py
+        tested_firmware: vec![ FirmwareVersion::new(2, 30) ]
     }
 });
 
@@ -382,6 +385,7 @@ pub static PODPRO_CONFIG: Lazy<Config> = Lazy::new(|| {
         member: 0x0400,
 
         controls,
+        tested_firmware: vec![],
 
         ..pod2_config
     }
@@ -407,6 +411,7 @@ pub static POD_CONFIG: Lazy<Config> = Lazy::new(|| {
         member: 0x0100,
 
         amp_models,
+        tested_firmware: vec![],
 
         ..pod2_config
     }
@@ -0,0 +1,22 @@
+use pod_core::device::{BoxedDevice, Device};
+use pod_core::handler::BoxedHandler;
+use pod_core::model::Config;
+
+use crate::config::*;
+pub use crate::handler::Pod2Handler;
+
+pub struct Pod2Device;
+
+impl Device for Pod2Device {
+    fn config(&self) -> Box<[Config]> {
+        vec![POD2_CONFIG.clone(), PODPRO_CONFIG.clone(), POD_CONFIG.clone()].into_boxed_slice()
+    }
+
+    fn handler(&self, _config: &'static Config) -> BoxedHandler {
+        Box::new(Pod2Handler)
+    }
+}
+
+pub fn device() -> BoxedDevice {
+    Box::new(Pod2Device)
+}
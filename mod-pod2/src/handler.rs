@@ -6,7 +6,8 @@ use pod_core::event::Origin::MIDI;
 use pod_core::generic;
 use pod_core::handler::Handler;
 use pod_core::midi::MidiMessage;
-use pod_core::model::AbstractControl;
+use pod_core::model::{AbstractControl, Config};
+use pod_core::rules::Rules;
 
 pub struct Pod2Handler;
 
@@ -29,6 +30,10 @@ fn midi_in_buffer_handler(ctx: &Ctx, buffer: Buffer, n_programs: usize, ver: &u8
 }
 
 impl Handler for Pod2Handler {
+    fn rules(&self, config: &'static Config) -> Rules {
+        crate::rules::rules(config)
+    }
+
     fn midi_in_handler(&self, ctx: &Ctx, midi_message: &MidiMessage) {
         generic::midi_in_handler(ctx, midi_message);
 
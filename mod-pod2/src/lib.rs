@@ -1,6 +1,12 @@
 mod config;
+mod device;
+#[cfg(feature = "gtk")]
 mod module;
+#[cfg(feature = "gtk")]
 pub mod wiring;
 pub mod handler;
+pub mod rules;
 
+pub use device::*;
+#[cfg(feature = "gtk")]
 pub use module::*;
@@ -5,26 +5,21 @@ use pod_core::store::{Signal, StoreSetIm};
 use pod_core::store::Origin::MIDI;
 use pod_gtk::prelude::*;
 use gtk::{Builder, Widget};
-use pod_core::handler::BoxedHandler;
+use pod_core::device::BoxedDevice;
 
 use crate::wiring::*;
 use crate::config::*;
-pub use crate::handler::Pod2Handler;
 
 pub struct Pod2Module;
 
 impl Module for Pod2Module {
-    fn config(&self) -> Box<[Config]> {
-        vec![POD2_CONFIG.clone(), PODPRO_CONFIG.clone(), POD_CONFIG.clone()].into_boxed_slice()
+    fn device(&self) -> BoxedDevice {
+        crate::device()
     }
 
     fn init(&self, config: &'static Config) -> Box<dyn Interface> {
         Box::new(Pod2Interface::new(config))
     }
-
-    fn handler(&self, _config: &'static Config) -> BoxedHandler {
-        Box::new(Pod2Handler)
-    }
 }
 
 
@@ -76,10 +71,6 @@ impl Interface for Pod2Interface {
         wire_toggles("toggles", &config.toggles,
                      controller.clone(), &self.objects, callbacks)?;
         wire_amp_select(controller.clone(), config, &self.objects, callbacks)?;
-        wire_14bit(controller.clone(), &self.objects, callbacks,
-                   "delay_time", "delay_time:msb", "delay_time:lsb",
-                   false)?;
-        wire_effect_select(config, controller, callbacks)?;
         wire_name_change(edit, config, &self.objects, callbacks)?;
 
         Ok(())
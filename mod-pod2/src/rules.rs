@@ -0,0 +1,182 @@
+use log::*;
+use pod_core::controller::*;
+use pod_core::controller::StoreOrigin::UI;
+use pod_core::model::*;
+use pod_core::rules::{rules_14bit, Rules};
+use pod_core::store::Origin;
+
+fn effect_entry_for_value(config: &Config, value: u16) -> Option<(&EffectEntry, bool, usize)> {
+    config.effects.iter()
+        .enumerate()
+        .flat_map(|(idx, effect)| {
+            let delay = effect.delay.as_ref()
+                .filter(|e| value == e.id as u16)
+                .map(|e| (e, true, idx));
+            let clean = effect.clean.as_ref()
+                .filter(|e| value == e.id as u16)
+                .map(|e| (e, false, idx));
+            delay.or(clean)
+        })
+        .next()
+        .or_else(|| {
+            warn!("Effect select mapping for value {} not found!", value);
+            None
+        })
+
+}
+
+fn effect_select_from_midi(config: &Config, controller: &mut Controller, origin: Origin) -> Option<EffectEntry> {
+
+    let value = controller.get("effect_select:raw").unwrap();
+    let (entry, delay, index) = effect_entry_for_value(config, value)?;
+
+    controller.set("delay_enable", delay as u16, origin);
+    controller.set("effect_select", index as u16, origin);
+
+    Some(entry.clone())
+}
+
+fn effect_select_from_gui(config: &Config, controller: &mut Controller) -> Option<EffectEntry> {
+
+    let value = controller.get("effect_select").unwrap();
+    let effect = &config.effects[value as usize];
+    let delay_enable = controller.get("delay_enable").unwrap() != 0;
+
+    let (delay, clean) = (effect.delay.as_ref(), effect.clean.as_ref());
+
+    // if delay_enabled is set, try to set an effect with delay (fallback to clean),
+    // otherwise try to set clean effect (fallback to effect with delay)
+    let entry =
+        (if delay_enable { delay.or(clean) } else { clean.or(delay) })
+            .unwrap().clone();
+
+    controller.set("effect_select:raw", entry.id as u16, UI);
+
+    Some(entry)
+}
+/*
+fn effect_select_send_controls(controller: &mut Controller, effect: &EffectEntry) {
+    for name in &effect.controls {
+        if let Some(v) = controller.get(name) {
+            controller.set_full(name, v, UI, Signal::Force);
+        }
+    }
+}
+*/
+/// Effect select, delay enable and effect tweak rules of POD 2.0/Pocket POD
+pub fn rules_effect_select(rules: &mut Rules, config: &'static Config) {
+    rules
+        // effect_select: raw -> controller
+        .on("effect_select:raw")
+        .run(move |_, controller, origin| {
+            effect_select_from_midi(config, controller, origin);
+        })
+        // effect_select: controller -> raw
+        .on("effect_select")
+        .run(move |_, controller, _| {
+            if let Some(_e) = effect_select_from_gui(config, controller) {
+                /*
+                // POD sends controls after effect select
+                // Line6 Edit requests an edit buffer dump from the device
+                effect_select_send_controls(&mut controller, &e);
+                */
+            }
+        })
+        // delay_enable: controller -> raw
+        .on("delay_enable").from(UI)
+        .run(move |value, controller, origin| {
+            if value != 0 {
+                let effect_select = controller.get("effect_select:raw").unwrap();
+                let Some((_, delay, idx)) =
+                    effect_entry_for_value(config, effect_select) else { return };
+                if !delay {
+                    // if `delay_enable` was switched on in the UI and if coming from
+                    // an effect which didn't have delay to begin with, check if it can
+                    // have a delay at all (POD 2.0 rotary cannot). If not, then switch
+                    // to plain "delay" effect.
+                    let need_reset = config.effects.get(idx)
+                        .map(|e| e.delay.is_none()).unwrap_or(false);
+                    if need_reset {
+                        controller.set("effect_select", 0u16, origin);
+                    }
+                }
+            }
+        })
+        // effect_tweak: any origin, UI edits need to reach the tweaked control too
+        .on("effect_tweak")
+        .run(move |value, controller, origin| {
+            let effect_select = controller.get("effect_select:raw").unwrap();
+            let Some((entry, _, _)) =
+                effect_entry_for_value(config, effect_select) else { return };
+            let control_name = &entry.effect_tweak;
+            if control_name.is_empty() { return }
+
+            // HACK: as if everything's coming straight from MIDI
+            let config = controller.get_config("effect_tweak").unwrap();
+            let val = config.value_to_midi(value);
+
+            let config = controller.get_config(control_name).unwrap();
+            let val = config.value_from_midi(val);
+            controller.set(control_name, val, origin);
+        });
+}
+
+/// Parameter rules of POD 2.0 and Pocket POD
+pub fn rules(config: &'static Config) -> Rules {
+    let mut rules = Rules::new();
+    rules_14bit(&mut rules, "delay_time", "delay_time:msb", "delay_time:lsb", false);
+    rules_effect_select(&mut rules, config);
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use pod_core::controller::StoreOrigin::{MIDI, UI};
+    use crate::config::POD2_CONFIG;
+    use crate::rules::*;
+
+    fn controller() -> Controller {
+        let mut controller = Controller::new(POD2_CONFIG.controls.clone());
+        controller.set_rules(rules(&POD2_CONFIG));
+        controller
+    }
+
+    #[test]
+    fn effect_select() {
+        let mut controller = controller();
+        // Compressor with delay
+        controller.set("effect_select:raw", 7, MIDI);
+        assert_eq!(controller.get("effect_select"), Some(1));
+        assert_eq!(controller.get("delay_enable"), Some(1));
+        // Rotary, no delay
+        controller.set("effect_select:raw", 2, MIDI);
+        assert_eq!(controller.get("effect_select"), Some(8));
+        assert_eq!(controller.get("delay_enable"), Some(0));
+
+        // Chorus 1 without delay, Auto Swell only comes with delay
+        controller.set("effect_select", 3, UI);
+        assert_eq!(controller.get_origin("effect_select:raw"), Some((8, UI)));
+        controller.set("effect_select", 2, UI);
+        assert_eq!(controller.get("effect_select:raw"), Some(14));
+
+        // Rotary cannot have delay, switching delay on resets it to plain delay
+        controller.set("effect_select", 8, UI);
+        assert_eq!(controller.get("effect_select:raw"), Some(2));
+        controller.set("delay_enable", 1, UI);
+        assert_eq!(controller.get("effect_select"), Some(0));
+        assert_eq!(controller.get("effect_select:raw"), Some(6));
+    }
+
+    #[test]
+    fn effect_tweak() {
+        let mut controller = controller();
+        // Compressor (clean), tweaks the compression ratio
+        controller.set("effect_select:raw", 11, MIDI);
+
+        controller.set("effect_tweak", 63, UI);
+        let max = POD2_CONFIG.controls["compression_ratio"].value_from_midi(127);
+        assert_eq!(controller.get_origin("compression_ratio"), Some((max, UI)));
+        controller.set("effect_tweak", 0, MIDI);
+        assert_eq!(controller.get_origin("compression_ratio"), Some((0, MIDI)));
+    }
+}
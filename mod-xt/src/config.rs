@@ -4,8 +4,7 @@ use once_cell::sync::Lazy;
 use pod_core::builders::shorthand::*;
 use pod_core::def;
 use pod_core::model::*;
-use pod_gtk::prelude::*;
-use glib::bitflags::bitflags;
+use bitflags::bitflags;
 
 use pod_mod_pod2::{short, long, steps, fmt_percent};
 use crate::model::*;
@@ -693,6 +692,8 @@ pub static PODXT_CONFIG: Lazy<Config> = Lazy::new(|| {
 
         flags: DeviceFlags::empty(),
         midi_quirks: MidiQuirks::empty(),
+        firmware_variants: vec![],
+        tested_firmware: vec![],
     }
 });
 
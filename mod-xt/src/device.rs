@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use pod_core::device::{BoxedDevice, Device};
+use pod_core::handler::BoxedHandler;
+use pod_core::model::Config;
+
+use crate::config;
+use crate::handler::PodXtHandler;
+use crate::model::*;
+
+pub struct PodXtDevice;
+
+impl Device for PodXtDevice {
+    fn config(&self) -> Box<[Config]> {
+        vec![
+            config::PODXT_CONFIG.clone(),
+            config::PODXT_PRO_CONFIG.clone(),
+            config::PODXT_LIVE_CONFIG.clone(),
+        ].into_boxed_slice()
+    }
+
+    fn handler(&self, config: &'static Config) -> BoxedHandler {
+        Box::new(PodXtHandler::new(config, true, crate::rules::rules))
+    }
+
+    fn select_labels(&self, _config: &Config) -> HashMap<String, Vec<String>> {
+        model_labels(&config::STOMP_CONFIG, &config::MOD_CONFIG, &config::DELAY_CONFIG)
+    }
+}
+
+/// Stomp, mod and delay model names by select control. The "FX-" prefix
+/// only marks the models of the FX pack, so it is left out for the names
+/// to match the ones of other devices.
+pub fn model_labels(stomp: &[StompConfig], modc: &[ModConfig], delay: &[DelayConfig]) -> HashMap<String, Vec<String>> {
+    fn names<T: ConfigAccess>(configs: &[T]) -> Vec<String> {
+        configs.iter()
+            .map(|c| c.name().strip_prefix("FX-").unwrap_or(c.name()).to_string())
+            .collect()
+    }
+
+    let mut labels = HashMap::new();
+    labels.insert("stomp_select".to_string(), names(stomp));
+    labels.insert("mod_select".to_string(), names(modc));
+    labels.insert("delay_select".to_string(), names(delay));
+    labels
+}
+
+pub fn device() -> BoxedDevice {
+    Box::new(PodXtDevice)
+}
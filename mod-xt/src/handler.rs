@@ -15,6 +15,7 @@ use pod_core::handler::Handler;
 use pod_core::midi::MidiMessage;
 use pod_core::model::{AbstractControl, Config};
 use pod_core::names::ProgramNames;
+use pod_core::rules::Rules;
 use crate::tuner::Tuner;
 
 /// A marker to send MidiMessage::XtPatchDumpEnd
@@ -44,11 +45,12 @@ struct Inner {
 
 pub struct PodXtHandler {
     has_xt_packs: bool,
+    rules: fn(&'static Config) -> Rules,
     inner: RefCell<Inner>
 }
 
 impl PodXtHandler {
-    pub fn new(config: &Config, has_xt_packs: bool) -> Self {
+    pub fn new(config: &Config, has_xt_packs: bool, rules: fn(&'static Config) -> Rules) -> Self {
         let inner = Inner {
             midi_out_queue: VecDeque::new(),
             need_store_ack: false,
@@ -58,7 +60,7 @@ impl PodXtHandler {
             effects: ProgramNames::new_with_size(config, 64),
             reported_program_number: None,
         };
-        Self { has_xt_packs, inner: RefCell::new(inner) }
+        Self { has_xt_packs, rules, inner: RefCell::new(inner) }
     }
 
     pub fn queue_send(&self, ctx: &Ctx) -> bool {
@@ -521,6 +523,10 @@ impl Handler for PodXtHandler {
     }
 
 
+    fn rules(&self, config: &'static Config) -> Rules {
+        (self.rules)(config)
+    }
+
     fn control_value_from_buffer(&self, controller: &mut Controller, name: &str, buffer: &[u8]) {
         let Some(control) = controller.get_config(name) else {
             return;
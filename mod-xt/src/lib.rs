@@ -1,10 +1,17 @@
 pub mod config;
+mod device;
+#[cfg(feature = "gtk")]
 mod module;
+#[cfg(feature = "gtk")]
 pub mod wiring;
 pub mod model;
 pub mod builders;
 pub mod handler;
+#[cfg(feature = "gtk")]
 pub mod widgets;
 pub mod tuner;
+pub mod rules;
 
+pub use device::*;
+#[cfg(feature = "gtk")]
 pub use module::*;
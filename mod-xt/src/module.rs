@@ -4,33 +4,24 @@ use pod_core::model::Config;
 use pod_core::store::{Signal, StoreSetIm};
 use pod_gtk::prelude::*;
 use gtk::{Builder, Widget};
-use pod_core::handler::BoxedHandler;
+use pod_core::device::BoxedDevice;
 use pod_core::store::Origin::MIDI;
 use pod_mod_pod2::wiring::*;
 
 use crate::config;
-use crate::handler::PodXtHandler;
 use crate::widgets::Tuner;
 use crate::wiring::{*, init_combo};
 
 struct PodXtModule;
 
 impl Module for PodXtModule {
-    fn config(&self) -> Box<[Config]> {
-        vec![
-            config::PODXT_CONFIG.clone(),
-            config::PODXT_PRO_CONFIG.clone(),
-            config::PODXT_LIVE_CONFIG.clone(),
-        ].into_boxed_slice()
+    fn device(&self) -> BoxedDevice {
+        crate::device()
     }
 
     fn init(&self, config: &'static Config) -> Box<dyn Interface> {
         Box::new(PodXtInterface::new(config))
     }
-
-    fn handler(&self, config: &'static Config) -> BoxedHandler {
-        Box::new(PodXtHandler::new(config, true))
-    }
 }
 
 struct PodXtInterface {
@@ -100,17 +91,9 @@ impl Interface for PodXtInterface {
                         controller.clone(), &self.objects, callbacks)?;
         wire_delay_select(&config::DELAY_CONFIG,
                           controller.clone(), &self.objects, callbacks)?;
-        wire_14bit(controller.clone(), &self.objects, callbacks,
-                   "mod_speed", "mod_speed:msb", "mod_speed:lsb",
-                   true)?;
-        wire_14bit(controller.clone(), &self.objects, callbacks,
-                   "delay_time", "delay_time:msb", "delay_time:lsb",
-                   true)?;
-        wire_tempo(controller.clone(), &self.objects, callbacks)?;
-        wire_di_show(controller.clone(), config, &self.objects, callbacks)?;
+        wire_tempo_tap(controller.clone(), &self.objects)?;
         wire_xt_packs(controller.clone(), &self.objects, callbacks)?;
         wire_mics_update(controller.clone(), config, &self.objects, callbacks)?;
-        wire_pedal_assign(controller.clone(), &self.objects, callbacks)?;
         wire_name_change(edit, config, &self.objects, callbacks)?;
 
         let tuner_box = self.objects.ref_by_name::<gtk::Box>("tuner_box").unwrap();
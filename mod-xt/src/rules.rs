@@ -0,0 +1,305 @@
+use std::collections::HashSet;
+use log::*;
+use multimap::MultiMap;
+use regex::Regex;
+use pod_core::controller::*;
+use pod_core::controller::StoreOrigin::*;
+use pod_core::model::{AbstractControl, Config};
+use pod_core::rules::{rules_14bit, Rules};
+use pod_core::store::Origin;
+use crate::config;
+use crate::config::NOTE_DURATION;
+use crate::model::ConfigAccess;
+
+pub fn rules_di_show(rules: &mut Rules, config: &'static Config) {
+    rules
+        // wire `amp_select` for `di:show`
+        .on("amp_select")
+        .run(move |value, controller, origin| {
+            let amp = config.amp_models.get(value as usize);
+            if let Some(amp) = amp {
+                let show = amp.name.starts_with("BX-") as u16;
+                controller.set("di:show", show, origin);
+            }
+        });
+}
+
+pub fn rules_dynamic_params<T: ConfigAccess>(rules: &mut Rules, configs: &'static [T]) {
+    // NOTE: param_to_variant()/variant_to_param() assume the param control is 1:1 with
+    // midi values and does not do value_from_midi()/value_to_midi() on the data read
+    // written to the controller for that control (only for control variants)
+
+    fn param_to_variant(variant: &str, value: u16, controller: &mut Controller, origin: Origin) {
+        let control = controller.get_config(variant).unwrap();
+        let midi = control.value_from_midi(value as u8);
+        // When a control param value comes from MIDI, it should be forwarded to
+        // the variants as MIDI. If it comes from UI (parameter main control or
+        // via a "variant to parameter" wiring, the value should be forwarded to
+        // the variants as "no action should be taken for it".
+        let origin = match origin {
+            MIDI => MIDI,
+            _ => NONE
+        };
+        controller.set(variant, midi, origin);
+    }
+
+    fn variant_to_param(variant: &str, param: &str, value: u16, controller: &mut Controller, origin: Origin) {
+        let control = controller.get_config(variant).unwrap();
+        let midi = control.value_to_midi(value);
+        controller.set(param, midi as u16, origin);
+    }
+
+    let param_names = configs.iter()
+        .flat_map(|c| c.labels().keys())
+        .collect::<HashSet<_>>();
+
+    let mut param_mapping = MultiMap::<String, String>::new();
+    let param_regex = Regex::new(r"(.*_param\d)_.*").unwrap();
+    for name in param_names.iter() {
+        if let Some(caps) = param_regex.captures(name) {
+            param_mapping.insert(
+                caps.get(1).unwrap().as_str().into(),
+                caps.get(0).unwrap().as_str().into()
+            )
+        }
+    }
+
+    for (param, variants) in param_mapping.iter_all() {
+        debug!("wiring dynamic controls: {:?} <-> {:?}", param, variants);
+        rules
+            // any change on the `XXX_paramX` will show up on the virtual
+            // controls as a value coming from MIDI, GUI changes from virtual
+            // controls will show up on `XXX_paramX` as a value coming from GUI
+            .on(param)
+            .run({
+                let variants = variants.clone();
+                move |value, controller, origin| {
+                    for variant in variants.iter() {
+                        param_to_variant(variant, value, controller, origin)
+                    }
+                }
+            });
+
+        for variant in variants.iter() {
+            rules
+                .on(variant).from(UI)
+                .run({
+                    let variant = variant.clone();
+                    let param = param.clone();
+                    move |value, controller, origin| {
+                        variant_to_param(&variant, &param, value, controller, origin)
+                    }
+                });
+        }
+    }
+}
+
+pub fn rules_pedal_assign(rules: &mut Rules) {
+    // Pedal assign is really a range control, but for the sake of showing it
+    // as a select we do this Select <-> VirtualSelect mapping
+    rules
+        .on("pedal_assign")
+        .run(move |value, controller, origin| {
+            let value: u16 = match value {
+                0 ..= 41 => 0,
+                42 ..= 85 => 1,
+                _ => 2
+            };
+            controller.set("pedal_assign_select", value, origin);
+        })
+        .on("pedal_assign_select").from(UI)
+        .run(move |value, controller, origin| {
+            let value: u16 = match value {
+                0 => 0,
+                1 => 64,
+                _ => 127
+            };
+            controller.set("pedal_assign", value, origin);
+        });
+}
+
+pub fn rules_tempo(rules: &mut Rules) {
+
+    fn mod_note_to_mod_speed(mod_note: u16, tempo: u16, controller: &mut Controller) {
+        if mod_note == 0 { return }
+        if !(300 ..= 2400).contains(&tempo) { return }
+
+        // convert tempo & mod_note to Hz
+        let v = &NOTE_DURATION.get(mod_note as usize).unwrap_or(&0.0);
+        let hz = (tempo as f32/2400.0 * *v).clamp(0.1, 15.0);
+
+        // convert Hz to mod_speed value
+        let (k, b) = (14.9/16383.0, 0.1);
+        let mod_speed = (hz - b) / k;
+
+        controller.set("mod_speed", mod_speed as u16, NONE);
+    }
+
+    fn reset_mod_note(controller: &mut Controller) {
+        controller.set("mod_note_select", 0u16, MIDI);
+    }
+
+    fn delay_note_to_delay_time(delay_note: u16, tempo: u16, controller: &mut Controller) {
+        if delay_note == 0 { return }
+        if !(300 ..= 2400).contains(&tempo) { return }
+
+        // convert tempo & delay_note to ms
+        let v = &NOTE_DURATION.get(delay_note as usize).unwrap_or(&0.0);
+        let ms = (1000.0 * 2400.0/(tempo as f32 * *v)).clamp(20.0, 2000.0);
+
+        // convert ms to delay_time value
+        let (k, b) = (1980.0/16383.0, 20.0);
+        let mod_speed = (ms - b) / k;
+
+        controller.set("delay_time", mod_speed as u16, NONE);
+    }
+
+    fn reset_delay_note(controller: &mut Controller) {
+        controller.set("delay_note_select", 0u16, MIDI);
+    }
+
+    rules_14bit(rules, "tempo", "tempo:msb", "tempo:lsb", true);
+
+    rules
+        .on("tempo")
+        .run(move |value, controller, _| {
+            let mod_note = controller.get("mod_note_select").unwrap();
+            mod_note_to_mod_speed(mod_note, value, controller);
+
+            let delay_note = controller.get("delay_note_select").unwrap();
+            delay_note_to_delay_time(delay_note, value, controller);
+        })
+        .on("mod_note_select")
+        .run(move |value, controller, _| {
+            let tempo = controller.get("tempo").unwrap();
+            mod_note_to_mod_speed(value, tempo, controller);
+        })
+        .on("mod_speed").from(MIDI).from(UI)
+        .run(move |_, controller, _| {
+            reset_mod_note(controller);
+        })
+        .on("delay_note_select")
+        .run(move |value, controller, _| {
+            let tempo = controller.get("tempo").unwrap();
+            delay_note_to_delay_time(value, tempo, controller);
+        })
+        .on("delay_time").from(MIDI).from(UI)
+        .run(move |_, controller, _| {
+            reset_delay_note(controller);
+        });
+}
+
+/// Parameter rules shared by the PODxt and Bass PODxt families
+pub fn rules_common<S, M, D>(rules: &mut Rules,
+                             stomp_config: &'static [S],
+                             mod_config: &'static [M],
+                             delay_config: &'static [D])
+    where S: ConfigAccess, M: ConfigAccess, D: ConfigAccess
+{
+    rules_dynamic_params(rules, stomp_config);
+    rules_dynamic_params(rules, mod_config);
+    rules_dynamic_params(rules, delay_config);
+    rules_14bit(rules, "mod_speed", "mod_speed:msb", "mod_speed:lsb", true);
+    rules_14bit(rules, "delay_time", "delay_time:msb", "delay_time:lsb", true);
+    rules_tempo(rules);
+    rules_pedal_assign(rules);
+}
+
+/// Parameter rules of the PODxt family
+pub fn rules(config: &'static Config) -> Rules {
+    let mut rules = Rules::new();
+    rules_common(&mut rules, &config::STOMP_CONFIG, &config::MOD_CONFIG, &config::DELAY_CONFIG);
+    rules_di_show(&mut rules, config);
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use pod_core::edit::EditBuffer;
+    use pod_core::handler::Handler;
+    use pod_core::program::load_patch_dump_ctrl;
+    use crate::config::PODXT_CONFIG;
+    use crate::handler::PodXtHandler;
+    use crate::rules::*;
+
+    fn controller() -> Controller {
+        let mut controller = Controller::new(PODXT_CONFIG.controls.clone());
+        controller.set_rules(rules(&PODXT_CONFIG));
+        controller
+    }
+
+    #[test]
+    fn tempo() {
+        let mut controller = controller();
+        // quarter notes at 120 bpm: 2 Hz, 500 ms
+        controller.set("mod_note_select", 6, UI);
+        controller.set("delay_note_select", 6, UI);
+        controller.set("tempo", 1200, UI);
+        assert_eq!(controller.get("tempo:msb"), Some(1200 >> 7));
+        assert_eq!(controller.get("tempo:lsb"), Some(1200 & 0x7f));
+        assert_eq!(controller.get("mod_speed"), Some(((2.0 - 0.1) / (14.9 / 16383.0)) as u16));
+        assert_eq!(controller.get("delay_time"), Some(((500.0 - 20.0) / (1980.0 / 16383.0)) as u16));
+
+        // setting the speed or time by hand drops the note sync
+        controller.set("mod_speed", 1000, UI);
+        assert_eq!(controller.get("mod_note_select"), Some(0));
+        controller.set("delay_time", 1000, MIDI);
+        assert_eq!(controller.get("delay_note_select"), Some(0));
+        controller.set("tempo", 600, UI);
+        assert_eq!(controller.get("mod_speed"), Some(1000));
+        assert_eq!(controller.get("delay_time"), Some(1000));
+    }
+
+    #[test]
+    fn pedal_assign() {
+        let mut controller = controller();
+        controller.set("pedal_assign", 100, MIDI);
+        assert_eq!(controller.get("pedal_assign_select"), Some(2));
+        controller.set("pedal_assign", 42, MIDI);
+        assert_eq!(controller.get("pedal_assign_select"), Some(1));
+
+        controller.set("pedal_assign_select", 0, UI);
+        assert_eq!(controller.get_origin("pedal_assign"), Some((0, UI)));
+    }
+
+    #[test]
+    fn dynamic_params() {
+        let mut controller = controller();
+        controller.set("stomp_param2", 32, MIDI);
+        assert_eq!(controller.get_origin("stomp_param2_wave"), Some((2, MIDI)));
+        // UI values of the parameter are not sent again by the variants
+        controller.set("stomp_param2", 48, UI);
+        assert_eq!(controller.get_origin("stomp_param2_wave"), Some((3, NONE)));
+
+        controller.set("stomp_param2_wave", 5, UI);
+        assert_eq!(controller.get_origin("stomp_param2"), Some((80, UI)));
+    }
+
+    #[test]
+    fn rules_after_load() {
+        let handler = PodXtHandler::new(&PODXT_CONFIG, true, rules);
+        let mut edit = EditBuffer::new(&PODXT_CONFIG);
+        edit.controller_locked().set_rules(rules(&PODXT_CONFIG));
+        {
+            // left over from the previous program
+            let mut controller = edit.controller_locked();
+            controller.set("mod_note_select", 6, NONE);
+            controller.set("tempo", 600, NONE);
+        }
+
+        // 120 bpm, mod speed 1000 without note sync, pedal assigned to tweak
+        let mut data = vec![0u8; PODXT_CONFIG.program_size];
+        data[32 + 89] = (1200 >> 7) as u8;
+        data[32 + 90] = (1200 & 0x7f) as u8;
+        data[32 + 29] = (1000 >> 7) as u8;
+        data[32 + 61] = (1000 & 0x7f) as u8;
+        data[32 + 65] = 127;
+        load_patch_dump_ctrl(&mut edit, &data, |c, n, b| handler.control_value_from_buffer(c, n, b));
+
+        let controller = edit.controller_locked();
+        assert_eq!(controller.get("tempo"), Some(1200));
+        assert_eq!(controller.get("mod_note_select"), Some(0));
+        assert_eq!(controller.get("mod_speed"), Some(1000));
+        assert_eq!(controller.get("pedal_assign_select"), Some(2));
+    }
+}
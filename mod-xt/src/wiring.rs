@@ -3,18 +3,15 @@ use std::collections::HashSet;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use pod_core::controller::*;
-use pod_core::model::{AbstractControl, Config};
+use pod_core::model::Config;
 use pod_gtk::prelude::*;
 use anyhow::*;
 use log::*;
-use multimap::MultiMap;
-use regex::Regex;
 use tokio::time::Instant;
 use pod_core::controller::StoreOrigin::*;
 use pod_gtk::logic::LogicBuilder;
-use pod_mod_pod2::wiring::wire_14bit;
 use crate::config;
-use crate::config::{NOTE_DURATION, XtPacks};
+use crate::config::XtPacks;
 use crate::model::{ConfigAccess, DelayConfig, ModConfig, StompConfig};
 use crate::widgets::*;
 
@@ -89,22 +86,6 @@ fn update_combo<F>(objs: &ObjectList, name: &str, update: F) -> Result<()>
     Ok(())
 }
 
-pub fn wire_di_show(controller: Arc<Mutex<Controller>>, config: &'static Config, objs: &ObjectList, callbacks: &mut Callbacks) -> Result<()> {
-    let mut builder = LogicBuilder::new(controller, objs.clone(), callbacks);
-    builder
-        // wire `amp_select` for `di:show`
-        .on("amp_select")
-        .run(move |value, controller, origin| {
-            let amp = config.amp_models.get(value as usize);
-            if let Some(amp) = amp {
-                let show = amp.name.starts_with("BX-") as u16;
-                controller.set("di:show", show, origin);
-            }
-        });
-
-    Ok(())
-}
-
 pub fn wire_dynamic_select<T: ConfigAccess>(select_name: &str, configs: &'static [T],
                                             controller: Arc<Mutex<Controller>>, objs: &ObjectList, callbacks: &mut Callbacks) -> Result<()> {
 
@@ -140,88 +121,10 @@ pub fn wire_dynamic_select<T: ConfigAccess>(select_name: &str, configs: &'static
     Ok(())
 }
 
-pub fn wire_dynamic_params<T: ConfigAccess>(configs: &'static [T],
-                                            controller: Arc<Mutex<Controller>>, objs: &ObjectList, callbacks: &mut Callbacks) -> Result<()> {
-    use pod_core::store::Origin;
-
-    // NOTE: param_to_variant()/variant_to_param() assume the param control is 1:1 with
-    // midi values and does not do value_from_midi()/value_to_midi() on the data read
-    // written to the controller for that control (only for control variants)
-
-    fn param_to_variant(variant: &String, value: u16, controller: &mut Controller, origin: Origin) {
-        let control = controller.get_config(variant).unwrap();
-        let midi = control.value_from_midi(value as u8);
-        // When a control param value comes from MIDI, it should be forwarded to
-        // the variants as MIDI. If it comes from UI (parameter main control or
-        // via a "variant to parameter" wiring, the value should be forwarded to
-        // the variants as "no action should be taken for it".
-        let origin = match origin {
-            MIDI => MIDI,
-            _ => NONE
-        };
-        controller.set(variant, midi, origin);
-    }
-
-    fn variant_to_param(variant: &String, param: &String, value: u16, controller: &mut Controller, origin: Origin) {
-        let control = controller.get_config(variant).unwrap();
-        let midi = control.value_to_midi(value);
-        controller.set(param, midi as u16, origin);
-    }
-
-    let param_names = configs.iter()
-        .flat_map(|c| c.labels().keys())
-        .collect::<HashSet<_>>();
-
-    let mut param_mapping = MultiMap::<String, String>::new();
-    let param_regex = Regex::new(r"(.*_param\d)_.*").unwrap();
-    for name in param_names.iter() {
-        if let Some(caps) = param_regex.captures(name) {
-            param_mapping.insert(
-                caps.get(1).unwrap().as_str().into(),
-                caps.get(0).unwrap().as_str().into()
-            )
-        }
-    }
-
-    let mut builder = LogicBuilder::new(controller, objs.clone(), callbacks);
-    let mut builder = builder.on("xyz"); // convert a LogicBuilder to a LogicOnBuilder
-    for (param, variants) in param_mapping.iter_all() {
-        debug!("wiring dynamic controls: {:?} <-> {:?}", param, variants);
-        builder
-            // any change on the `XXX_paramX` will show up on the virtual
-            // controls as a value coming from MIDI, GUI changes from virtual
-            // controls will show up on `XXX_paramX` as a value coming from GUI
-            .on(&param)
-            .run({
-                let variants = variants.clone();
-                move |value, controller, origin| {
-                    for variant in variants.iter() {
-                        param_to_variant(variant, value, controller, origin)
-                    }
-                }
-            });
-
-        for variant in variants.iter() {
-            builder
-                .on(variant).from(UI)
-                .run({
-                    let variant = variant.clone();
-                    let param = param.clone();
-                    move |value, controller, origin| {
-                        variant_to_param(&variant, &param, value, controller, origin)
-                    }
-                });
-        }
-    }
-
-    Ok(())
-}
-
 pub fn wire_stomp_select(stomp_config: &'static [StompConfig],
                          controller: Arc<Mutex<Controller>>, objs: &ObjectList, callbacks: &mut Callbacks) -> Result<()> {
     wire_dynamic_select("stomp_select", stomp_config,
-                        controller.clone(), objs, callbacks)?;
-    wire_dynamic_params(stomp_config, controller, objs, callbacks)?;
+                        controller, objs, callbacks)?;
 
     Ok(())
 }
@@ -229,8 +132,7 @@ pub fn wire_stomp_select(stomp_config: &'static [StompConfig],
 pub fn wire_mod_select(mod_config: &'static [ModConfig],
                        controller: Arc<Mutex<Controller>>, objs: &ObjectList, callbacks: &mut Callbacks) -> Result<()> {
     wire_dynamic_select("mod_select", mod_config,
-                        controller.clone(), objs, callbacks)?;
-    wire_dynamic_params(mod_config, controller, objs, callbacks)?;
+                        controller, objs, callbacks)?;
 
     Ok(())
 }
@@ -238,8 +140,7 @@ pub fn wire_mod_select(mod_config: &'static [ModConfig],
 pub fn wire_delay_select(delay_config: &'static [DelayConfig],
                          controller: Arc<Mutex<Controller>>, objs: &ObjectList, callbacks: &mut Callbacks) -> Result<()> {
     wire_dynamic_select("delay_select", delay_config,
-                        controller.clone(), objs, callbacks)?;
-    wire_dynamic_params(delay_config, controller, objs, callbacks)?;
+                        controller, objs, callbacks)?;
 
     Ok(())
 }
@@ -289,33 +190,6 @@ pub fn wire_mics_update(controller: Arc<Mutex<Controller>>, config: &'static Con
     Ok(())
 }
 
-pub fn wire_pedal_assign(controller: Arc<Mutex<Controller>>, objs: &ObjectList, callbacks: &mut Callbacks) -> Result<()> {
-    // Pedal assign is really a range control, but for the sake of showing it
-    // as a select we do this Select <-> VirtualSelect mapping
-    let mut builder = LogicBuilder::new(controller, objs.clone(), callbacks);
-    builder
-        .on("pedal_assign")
-        .run(move |value, controller, origin| {
-            let value: u16 = match value {
-                0 ..= 41 => 0,
-                42 ..= 85 => 1,
-                _ => 2
-            };
-            controller.set("pedal_assign_select", value, origin);
-        })
-        .on("pedal_assign_select").from(UI)
-        .run(move |value, controller, origin| {
-            let value: u16 = match value {
-                0 => 0,
-                1 => 64,
-                _ => 127
-            };
-            controller.set("pedal_assign", value, origin);
-        });
-
-    Ok(())
-}
-
 pub fn resolve_footswitch_mode_show(objs: &ObjectList, show: bool) -> Result<()> {
     if show { return Ok(()); }
 
@@ -359,85 +233,7 @@ pub fn wire_tuner(tuner: Tuner,
     Ok(())
 }
 
-pub fn wire_tempo(controller: Arc<Mutex<Controller>>, objs: &ObjectList, callbacks: &mut Callbacks) -> Result<()> {
-
-    fn mod_note_to_mod_speed(mod_note: u16, tempo: u16, controller: &mut Controller) {
-        if mod_note == 0 { return }
-        if tempo < 300 || tempo > 2400 { return }
-
-        // convert tempo & mod_note to Hz
-        let v = &NOTE_DURATION.get(mod_note as usize).unwrap_or(&0.0);
-        let hz = (tempo as f32/2400.0 * *v).max(0.1).min(15.0);
-
-        // convert Hz to mod_speed value
-        let (k, b) = (14.9/16383.0, 0.1);
-        let mod_speed = (hz - b) / k;
-
-        controller.set("mod_speed", mod_speed as u16, NONE);
-    }
-
-    fn reset_mod_note(controller: &mut Controller) {
-        controller.set("mod_note_select", 0u16, MIDI);
-    }
-
-    fn delay_note_to_delay_time(delay_note: u16, tempo: u16, controller: &mut Controller) {
-        if delay_note == 0 { return }
-        if tempo < 300 || tempo > 2400 { return }
-
-        // convert tempo & delay_note to ms
-        let v = &NOTE_DURATION.get(delay_note as usize).unwrap_or(&0.0);
-        let ms = (1000.0 * 2400.0/(tempo as f32 * *v)).max(20.0).min(2000.0);
-
-        // convert ms to delay_time value
-        let (k, b) = (1980.0/16383.0, 20.0);
-        let mod_speed = (ms - b) / k;
-
-        controller.set("delay_time", mod_speed as u16, NONE);
-    }
-
-    fn reset_delay_note(controller: &mut Controller) {
-        controller.set("delay_note_select", 0u16, MIDI);
-    }
-
-    wire_14bit(controller.clone(), objs, callbacks,
-               "tempo", "tempo:msb", "tempo:lsb",
-               true)?;
-
-    wire_tempo_tap(controller.clone(), objs)?;
-
-    let mut builder = LogicBuilder::new(controller, objs.clone(), callbacks);
-    builder
-        .on("tempo")
-        .run(move |value, controller, _| {
-            let mod_note = controller.get("mod_note_select").unwrap();
-            mod_note_to_mod_speed(mod_note, value, controller);
-
-            let delay_note = controller.get("delay_note_select").unwrap();
-            delay_note_to_delay_time(delay_note, value, controller);
-        })
-        .on("mod_note_select")
-        .run(move |value, controller, _| {
-            let tempo = controller.get("tempo").unwrap();
-            mod_note_to_mod_speed(value, tempo, controller);
-        })
-        .on("mod_speed").from(MIDI).from(UI)
-        .run(move |_, controller, _| {
-            reset_mod_note(controller);
-        })
-        .on("delay_note_select")
-        .run(move |value, controller, _| {
-            let tempo = controller.get("tempo").unwrap();
-            delay_note_to_delay_time(value, tempo, controller);
-        })
-        .on("delay_time").from(MIDI).from(UI)
-        .run(move |_, controller, _| {
-            reset_delay_note(controller);
-        });
-
-    Ok(())
-}
-
-fn wire_tempo_tap(controller: Arc<Mutex<Controller>>, objs: &ObjectList) -> Result<()> {
+pub fn wire_tempo_tap(controller: Arc<Mutex<Controller>>, objs: &ObjectList) -> Result<()> {
 
     let button = objs.ref_by_name::<gtk::Button>("tempo_tap_button")?;
     let last_click = Rc::new(RefCell::new(Instant::now()));
@@ -0,0 +1,710 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crossterm::event::{Event as InputEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use log::*;
+use once_cell::sync::Lazy;
+use tokio::sync::{broadcast, mpsc};
+use tokio::sync::broadcast::error::RecvError;
+use pod_core::bank::{is_compatible, Bank};
+use pod_core::config::configs;
+use pod_core::context::Ctx;
+use pod_core::controller::*;
+use pod_core::convert::{convert_patch, select_labels};
+use pod_core::device::{init_device, init_device_controls, InitializedDevice};
+use pod_core::dispatch::BufferReroute;
+use pod_core::dump::ProgramsDump;
+use pod_core::edit::EditBuffer;
+use pod_core::engine::{Engine, EngineEvent, Services};
+use pod_core::event::*;
+use pod_core::midi::Channel;
+use pod_core::midi_io::{MidiIn, MidiOut, MidiPorts};
+use pod_core::model::{Config, Control, DeviceFlags, VirtualSelect};
+use pod_core::{next_thread_id, program_id_string};
+use pod_core::offline::{apply_sync_action, SyncAction, SyncSlot};
+use crate::connect::{connect_detected, reconnect, redetect, Connection, Detected};
+use crate::params::{self, Param};
+
+const CONTROLLER_CHANNEL_CAPACITY: usize = 512;
+
+static UI_CONTROLS: Lazy<HashMap<String, Control>> = Lazy::new(|| {
+    [
+        "midi_channel",
+        "program",
+        "program:prev",
+        // Set if device config contains DeviceFlags::MANUAL_MODE
+        "manual_mode_present",
+        "tuner_present",
+        // Set when there is no MIDI device connected
+        "offline"
+    ].into_iter()
+        .map(|name| (name.to_string(), VirtualSelect::default().into()))
+        .collect()
+});
+
+pub enum TuiEvent {
+    Input(InputEvent),
+    /// An engine event of the session with the given index
+    Engine(usize, EngineEvent),
+    /// Another device found by autodetect, to be opened in a new session
+    Detected(Detected),
+    /// A control value changed, the screen needs to be redrawn
+    Changed,
+    /// The engine of the session with the given index has stopped
+    Stopped(usize)
+}
+
+pub type TuiEventSender = mpsc::UnboundedSender<TuiEvent>;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Focus {
+    Programs,
+    Params
+}
+
+/// The device being edited
+pub struct Device {
+    pub config: &'static Config,
+    pub controller: Arc<Mutex<Controller>>,
+    pub edit: Arc<Mutex<EditBuffer>>,
+    pub dump: Arc<Mutex<ProgramsDump>>,
+    pub params: Vec<Param>,
+    pub labels: HashMap<String, Vec<String>>
+}
+
+/// Connection panel fields, in Tab order
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConnectField {
+    MidiIn,
+    MidiOut,
+    Model,
+    Channel
+}
+
+pub struct ConnectPanel {
+    /// Session to connect, `None` for a new session
+    pub session: Option<usize>,
+    /// Port names, `None` for editing offline
+    pub midi_in: Vec<Option<String>>,
+    pub midi_out: Vec<Option<String>>,
+    pub midi_in_sel: usize,
+    pub midi_out_sel: usize,
+    pub model_sel: usize,
+    pub midi_channel: u8,
+    pub focus: ConnectField
+}
+
+impl ConnectPanel {
+    /// Connection panel for session `session` (with its engine) or,
+    /// with `None`, for a new session
+    fn new(session: Option<(usize, &Engine)>) -> Self {
+        let ports = |names: anyhow::Result<Vec<String>>| {
+            let names = names
+                .map_err(|err| error!("Failed to list MIDI ports: {}", err))
+                .unwrap_or_default();
+            std::iter::once(None).chain(names.into_iter().map(Some)).collect::<Vec<_>>()
+        };
+        let midi_in = ports(MidiIn::ports());
+        let midi_out = ports(MidiOut::ports());
+
+        let mut panel = ConnectPanel {
+            session: session.map(|(id, _)| id),
+            midi_in_sel: 0,
+            midi_out_sel: 0,
+            model_sel: 0,
+            midi_channel: Channel::all(),
+            focus: ConnectField::MidiIn,
+            midi_in,
+            midi_out
+        };
+        if let Some((_, engine)) = session {
+            let state = engine.state();
+            let state = state.lock().unwrap();
+            let position = |ports: &Vec<Option<String>>, name: &Option<String>| {
+                ports.iter().position(|p| p == name).unwrap_or(0)
+            };
+            panel.midi_in_sel = position(&panel.midi_in, &state.midi_in_name);
+            panel.midi_out_sel = position(&panel.midi_out, &state.midi_out_name);
+            panel.model_sel = state.config
+                .and_then(|config| configs().iter().position(|c| c == config))
+                .unwrap_or(0);
+            panel.midi_channel = state.midi_channel_num;
+        }
+        panel
+    }
+
+    fn connection(&self) -> Option<Connection> {
+        Some(Connection {
+            midi_in: self.midi_in.get(self.midi_in_sel)?.clone(),
+            midi_out: self.midi_out.get(self.midi_out_sel)?.clone(),
+            midi_channel: self.midi_channel,
+            config: configs().get(self.model_sel)?
+        })
+    }
+
+    fn move_selection(&mut self, delta: i32) {
+        let (sel, len) = match self.focus {
+            ConnectField::MidiIn => (&mut self.midi_in_sel, self.midi_in.len()),
+            ConnectField::MidiOut => (&mut self.midi_out_sel, self.midi_out.len()),
+            ConnectField::Model => (&mut self.model_sel, configs().len()),
+            ConnectField::Channel => {
+                // channels 0..15, then "omni"
+                let channel = if self.midi_channel == Channel::all() { 16 } else { self.midi_channel as i32 };
+                let channel = (channel + delta).clamp(0, 16);
+                self.midi_channel = if channel == 16 { Channel::all() } else { channel as u8 };
+                return;
+            }
+        };
+        *sel = move_index(*sel, len, delta);
+    }
+
+    fn next_field(&mut self) {
+        self.focus = match self.focus {
+            ConnectField::MidiIn => ConnectField::MidiOut,
+            ConnectField::MidiOut => ConnectField::Model,
+            ConnectField::Model => ConnectField::Channel,
+            ConnectField::Channel => ConnectField::MidiIn
+        };
+    }
+}
+
+fn move_index(index: usize, len: usize, delta: i32) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    (index as i32 + delta).max(0).min(len as i32 - 1) as usize
+}
+
+/// Forward controller changes to `f`, asking for a redraw on every change
+fn start_controller_rx<F>(controller: &Arc<Mutex<Controller>>, tx: TuiEventSender, f: F)
+    where F: Fn(String, u16, StoreOrigin) + Send + 'static
+{
+    let (controller_tx, mut rx) = broadcast::channel::<Event<String,u16>>(CONTROLLER_CHANNEL_CAPACITY);
+    controller.broadcast(Some(controller_tx));
+
+    tokio::spawn(async move {
+        let id = next_thread_id();
+        info!("Controller RX thread {:?} start", id);
+        loop {
+            match rx.recv().await {
+                Ok(Event { key, value, origin, .. }) => f(key, value, origin),
+                Err(RecvError::Closed) => break,
+                Err(RecvError::Lagged(_)) => continue
+            }
+            if tx.send(TuiEvent::Changed).is_err() {
+                break;
+            }
+        }
+        info!("Controller RX thread {:?} stop", id);
+    });
+}
+
+/// Forward the engine events of session `id`
+fn start_engine_rx(id: usize, engine: &Engine, tx: TuiEventSender) {
+    let mut engine_event_rx = engine.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let event = match engine_event_rx.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Closed) => {
+                    info!("Engine event bus closed");
+                    return;
+                }
+                Err(RecvError::Lagged(n)) => {
+                    error!("Engine event bus lagged: {}", n);
+                    continue;
+                }
+            };
+            if tx.send(TuiEvent::Engine(id, event)).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// A device session: an engine with its own device context, MIDI ports
+/// and channel. The first session is the primary one: it gets the
+/// controller MIDI input and its connection is remembered.
+pub struct Session {
+    pub engine: Engine,
+    pub ui_controller: Arc<Mutex<Controller>>,
+    pub device: Option<Device>,
+    pub title: String,
+    pub program_sel: usize,
+    pub param_sel: usize,
+    /// Offline edits that differ from the device, waiting for the user
+    /// to choose what to do with them
+    pub sync: Option<Vec<SyncSlot>>,
+    stopping: bool
+}
+
+impl Session {
+    fn new(id: usize, services: &Services, tx: &TuiEventSender) -> Self {
+        let engine = Engine::new(services.clone());
+        let ui_controller = Arc::new(Mutex::new(Controller::new((*UI_CONTROLS).clone())));
+        ui_controller.set("program", Program::ManualMode.into(), StoreOrigin::NONE);
+        ui_controller.set("program:prev", Program::ManualMode.into(), StoreOrigin::NONE);
+
+        // program selection, as done by the program buttons in the GUI
+        start_controller_rx(&ui_controller, tx.clone(), {
+            let app_event_tx = engine.sender();
+            move |name, value, origin| {
+                if name != "program" || value >= 1000 { return }
+                let Ok(origin) = Origin::try_from(origin) else { return };
+                let e = ProgramChangeEvent { program: value.into(), origin };
+                app_event_tx.send_or_warn(AppEvent::ProgramChange(e));
+            }
+        });
+
+        start_engine_rx(id, &engine, tx.clone());
+        engine.start(vec![]);
+
+        Session {
+            engine,
+            ui_controller,
+            device: None,
+            title: "Connecting...".into(),
+            program_sel: 0,
+            param_sel: 0,
+            sync: None,
+            stopping: false
+        }
+    }
+
+    /// Short name for the session tab
+    pub fn name(&self) -> String {
+        self.device.as_ref()
+            .map(|device| device.config.name.clone())
+            .unwrap_or_else(|| "...".into())
+    }
+
+    fn new_config(&mut self, tx: &TuiEventSender) {
+        let config = self.engine.state().lock().unwrap().config.unwrap();
+        info!("Initiating device for config {:?}", &config.name);
+        let InitializedDevice { handler, edit_buffer, dump } = match init_device(config) {
+            Ok(device) => device,
+            Err(err) => {
+                error!("Failed to initialize config {:?}: {}", config.name, err);
+                return;
+            }
+        };
+
+        if let Some(ctx) = self.engine.take_ctx() {
+            // close channels
+            ctx.controller.broadcast(None);
+        }
+
+        let controller = edit_buffer.lock().unwrap().controller();
+        start_controller_rx(&controller, tx.clone(), {
+            let app_event_tx = self.engine.sender();
+            move |name, value, origin| {
+                let e = ControlChangeEvent { name, value, origin };
+                app_event_tx.send_or_warn(AppEvent::ControlChange(e));
+            }
+        });
+
+        let ctx = Ctx {
+            config,
+            controller: controller.clone(),
+            handler,
+            edit: edit_buffer.clone(),
+            dump: dump.clone(),
+            reroute: BufferReroute::default(),
+            ui_controller: self.ui_controller.clone(),
+            app_event_tx: self.engine.sender()
+        };
+        self.engine.set_ctx(ctx);
+
+        init_device_controls(config, &edit_buffer.lock().unwrap())
+            .unwrap_or_else(|err| error!("{}", err));
+
+        self.ui_controller.set_full("manual_mode_present",
+                                    config.flags.contains(DeviceFlags::MANUAL_MODE) as u16,
+                                    StoreOrigin::NONE, Signal::Force);
+        self.ui_controller.set_full("tuner_present", config.has_tuner() as u16,
+                                    StoreOrigin::NONE, Signal::Force);
+        // no program selected
+        self.ui_controller.set_full("program", 1000, StoreOrigin::NONE, Signal::Force);
+
+        self.device.replace(Device {
+            config,
+            controller,
+            edit: edit_buffer,
+            dump,
+            params: params::params(config),
+            labels: select_labels(config)
+        });
+        self.program_sel = 0;
+        self.param_sel = 0;
+    }
+
+    fn update_title(&mut self) {
+        let state = self.engine.state();
+        let state = state.lock().unwrap();
+
+        let config_name = state.config.map(|c| c.name.clone()).unwrap_or_default();
+        let name = match &state.detected {
+            _ if config_name.is_empty() => String::new(),
+            None => config_name,
+            Some(d) if d.name == config_name => format!("{} {}", d.name, d.version),
+            Some(d) => format!("{} {} as {}", d.name, d.version, config_name)
+        };
+        let connection = match (&state.midi_in_name, &state.midi_out_name) {
+            (None, _) | (_, None) => "offline, no device connected".to_string(),
+            (Some(a), Some(b)) if a == b => a.clone(),
+            (Some(a), Some(b)) => format!("i: {} / o: {}", a, b)
+        };
+        self.title = if name.is_empty() {
+            connection
+        } else {
+            format!("{} @ {}", name, connection)
+        };
+    }
+}
+
+/// A program copied from one of the sessions, to be pasted into another
+struct Clipboard {
+    config: &'static Config,
+    name: String,
+    data: Vec<u8>
+}
+
+pub struct App {
+    tx: TuiEventSender,
+    /// Shared by the engines of all sessions
+    services: Services,
+    pub sessions: Vec<Session>,
+    pub current: usize,
+    pub focus: Focus,
+    pub connect: Option<ConnectPanel>,
+    pub status: Option<String>,
+    clipboard: Option<Clipboard>,
+    shutting_down: bool,
+    /// Number of sessions stopped on shutdown
+    stopped: usize,
+    pub quit: bool
+}
+
+impl App {
+    /// Create the app with the primary session
+    pub fn new(tx: TuiEventSender) -> Self {
+        let services = Services::load();
+        let session = Session::new(0, &services, &tx);
+        App {
+            tx,
+            services,
+            sessions: vec![session],
+            current: 0,
+            focus: Focus::Programs,
+            connect: None,
+            status: None,
+            clipboard: None,
+            shutting_down: false,
+            stopped: 0,
+            quit: false
+        }
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.sessions[self.current]
+    }
+
+    fn add_session(&mut self) -> usize {
+        let id = self.sessions.len();
+        self.sessions.push(Session::new(id, &self.services, &self.tx));
+        id
+    }
+
+    pub fn handle(&mut self, event: TuiEvent) {
+        match event {
+            TuiEvent::Input(InputEvent::Key(key)) if key.kind != KeyEventKind::Release => {
+                self.input(key);
+            }
+            TuiEvent::Input(_) | TuiEvent::Changed => {}
+            TuiEvent::Engine(id, event) => self.engine_event(id, event),
+            TuiEvent::Detected(_) if self.shutting_down => {}
+            TuiEvent::Detected(detected) => {
+                let id = self.add_session();
+                connect_detected(&self.sessions[id].engine.state(), detected);
+            }
+            TuiEvent::Stopped(id) => {
+                debug!("Session {} stopped", id + 1);
+                self.stopped += 1;
+                self.quit = self.stopped == self.sessions.len();
+            }
+        }
+    }
+
+    fn send(&self, event: AppEvent) {
+        self.session().engine.send(event);
+    }
+
+    fn engine_event(&mut self, id: usize, event: EngineEvent) {
+        let Some(session) = self.sessions.get_mut(id) else { return };
+        // messages from the other sessions are marked with the session number
+        let status = |msg: String| if id == self.current { msg } else { format!("[{}] {}", id + 1, msg) };
+        match event {
+            EngineEvent::DeviceDetected(event) => {
+                session.engine.state().lock().unwrap().detected.replace(event);
+                session.update_title();
+            }
+            EngineEvent::NewMidiConnection => {
+                session.update_title();
+            }
+            EngineEvent::NewConfig => {
+                session.new_config(&self.tx);
+                session.update_title();
+            }
+            EngineEvent::Sync(slots) => {
+                self.status = Some(status(format!("Offline edits of {} program(s) differ from the device",
+                                                  slots.len())));
+                session.sync.get_or_insert_with(Vec::new).extend(slots);
+            }
+            EngineEvent::Notification(msg, _) => {
+                self.status = Some(status(msg));
+            }
+            EngineEvent::Shutdown if !session.stopping => {
+                session.stopping = true;
+                session.title = "Shutting down...".into();
+
+                let handle = session.engine.stop();
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    handle.await;
+                    tx.send(TuiEvent::Stopped(id)).unwrap_or_default();
+                });
+            }
+            EngineEvent::MidiTx | EngineEvent::MidiRx | EngineEvent::Modified(_, _) |
+            EngineEvent::Setlist(_) | EngineEvent::Shutdown => {}
+        }
+    }
+
+    fn shutdown(&mut self) {
+        if self.shutting_down {
+            return;
+        }
+        self.shutting_down = true;
+        for session in self.sessions.iter() {
+            session.engine.send(AppEvent::Shutdown);
+        }
+    }
+
+    fn input(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.shutdown();
+            return;
+        }
+        if self.connect.is_some() {
+            self.connect_input(key);
+            return;
+        }
+        if self.session().sync.is_some() {
+            self.sync_input(key);
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => self.shutdown(),
+            KeyCode::Char('c') if self.session().device.is_some() => {
+                let panel = ConnectPanel::new(Some((self.current, &self.session().engine)));
+                self.connect.replace(panel);
+            }
+            KeyCode::Char('n') => {
+                self.connect.replace(ConnectPanel::new(None));
+            }
+            KeyCode::Char(c @ '1' ..= '9') => {
+                let id = c as usize - '1' as usize;
+                if id < self.sessions.len() {
+                    self.current = id;
+                }
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Focus::Programs => Focus::Params,
+                    Focus::Params => Focus::Programs
+                };
+            }
+            _ => self.device_input(key)
+        }
+    }
+
+    /// Apply the chosen action to the offline edits of the current session
+    fn sync_input(&mut self, key: KeyEvent) {
+        let (action, desc) = match key.code {
+            KeyCode::Char('u') => (SyncAction::Push, "sent to the device"),
+            KeyCode::Char('d') => (SyncAction::Pull, "discarded, keeping the device programs"),
+            KeyCode::Char('k') => (SyncAction::KeepLocal, "kept as modified"),
+            _ => return
+        };
+        let session = &mut self.sessions[self.current];
+        let Some(slots) = session.sync.take() else { return };
+        for slot in slots.iter() {
+            apply_sync_action(slot, action, &session.engine.sender());
+        }
+        let msg = format!("Offline edits of {} program(s) {}", slots.len(), desc);
+        info!("{}", msg);
+        self.status = Some(msg);
+    }
+
+    fn device_input(&mut self, key: KeyEvent) {
+        let focus = self.focus;
+        let session = &mut self.sessions[self.current];
+        let Some(device) = &session.device else { return };
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
+        let (len, sel) = match focus {
+            Focus::Programs => (device.config.program_num, &mut session.program_sel),
+            Focus::Params => (device.params.len(), &mut session.param_sel)
+        };
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => *sel = move_index(*sel, len, -1),
+            KeyCode::Down | KeyCode::Char('j') => *sel = move_index(*sel, len, 1),
+            KeyCode::PageUp => *sel = move_index(*sel, len, -10),
+            KeyCode::PageDown => *sel = move_index(*sel, len, 10),
+            KeyCode::Home => *sel = 0,
+            KeyCode::End => *sel = len.saturating_sub(1),
+            _ => {}
+        }
+
+        let session = self.session();
+        let Some(device) = &session.device else { return };
+        let program = session.program_sel;
+        let load = |buffer| AppEvent::Load(BufferLoadEvent { buffer, origin: Origin::UI });
+        let store = |buffer| AppEvent::Store(BufferStoreEvent { buffer, origin: Origin::UI });
+        match (self.focus, key.code) {
+            // programs
+            (Focus::Programs, KeyCode::Enter) => {
+                session.ui_controller.set("program", program as u16, StoreOrigin::UI);
+            }
+            (Focus::Programs, KeyCode::Char('l')) => self.send(load(Buffer::Program(program))),
+            (Focus::Programs, KeyCode::Char('s')) => self.send(store(Buffer::Program(program))),
+            (Focus::Programs, KeyCode::Char('w')) => {
+                let e = BufferCopyEvent { from: Buffer::EditBuffer, to: Buffer::Program(program) };
+                self.send(AppEvent::Copy(e));
+            }
+            (Focus::Programs, KeyCode::Char('y')) => self.copy_program(program),
+            (Focus::Programs, KeyCode::Char('p')) => self.paste_program(program),
+            // parameters
+            (Focus::Params, KeyCode::Left | KeyCode::Right | KeyCode::Char('-') |
+                            KeyCode::Char('+') | KeyCode::Char('=') |
+                            KeyCode::Char('[') | KeyCode::Char(']')) => {
+                let Some(param) = device.params.get(session.param_sel) else { return };
+                let step = match key.code {
+                    KeyCode::Char('[') | KeyCode::Char(']') => params::coarse_step(device.config, &param.name),
+                    _ if shift => params::coarse_step(device.config, &param.name),
+                    _ => 1
+                };
+                let delta = match key.code {
+                    KeyCode::Left | KeyCode::Char('-') | KeyCode::Char('[') => -step,
+                    _ => step
+                };
+                let value = device.controller.get(&param.name).unwrap_or(0);
+                if let Some(value) = params::step(device.config, &param.name, value, delta) {
+                    device.controller.set(&param.name, value, StoreOrigin::UI);
+                }
+            }
+            (Focus::Params, KeyCode::Char(' ')) => {
+                let Some(param) = device.params.get(session.param_sel) else { return };
+                if !params::is_switch(device.config, &param.name) { return }
+                let value = device.controller.get(&param.name).unwrap_or(0);
+                device.controller.set(&param.name, (value == 0) as u16, StoreOrigin::UI);
+            }
+            (Focus::Params, KeyCode::Char('l')) => self.send(load(Buffer::Current)),
+            (Focus::Params, KeyCode::Char('s')) => self.send(store(Buffer::Current)),
+            // everywhere
+            (_, KeyCode::Char('e')) => self.send(load(Buffer::EditBuffer)),
+            (_, KeyCode::Char('E')) => self.send(store(Buffer::EditBuffer)),
+            (_, KeyCode::Char('L')) => self.send(load(Buffer::All)),
+            (_, KeyCode::Char('S')) => self.send(store(Buffer::All)),
+            (_, KeyCode::Char('m')) if device.config.flags.contains(DeviceFlags::MANUAL_MODE) => {
+                session.ui_controller.set("program", Program::ManualMode.into(), StoreOrigin::UI);
+            }
+            _ => {}
+        }
+    }
+
+    /// Copy `program` of the current session, with the edit buffer changes
+    /// if it is the current program
+    fn copy_program(&mut self, program: usize) {
+        let session = self.session();
+        let Some(device) = &session.device else { return };
+        let current: Program = session.ui_controller.get("program").unwrap_or(1000).into();
+        let bank = Bank::capture(device.config, current, &device.edit.lock().unwrap(),
+                                 &device.dump.lock().unwrap(), &[program]);
+        let Some((_, name, data)) = bank.programs.into_iter().next() else { return };
+
+        let config = device.config;
+        self.status = Some(format!("Copied program {} \"{}\" of {}",
+                                   program_id_string(program), name.trim(), config.name));
+        self.clipboard.replace(Clipboard { config, name, data });
+    }
+
+    /// Paste the copied program into `program` of the current session.
+    /// Like other program changes, it needs to be stored to the device.
+    fn paste_program(&mut self, program: usize) {
+        let Some(device) = &self.session().device else { return };
+        let Some(clipboard) = &self.clipboard else {
+            self.status = Some("Nothing to paste, copy a program first".into());
+            return;
+        };
+        // programs of other devices are converted, as far as they can be
+        let (data, report) = if is_compatible(clipboard.config, device.config) {
+            (clipboard.data.clone(), None)
+        } else {
+            match convert_patch(clipboard.config, &clipboard.data, device.config) {
+                Ok((data, report)) => (data, Some(report).filter(|r| !r.is_lossless())),
+                Err(err) => {
+                    self.status = Some(format!("Programs of {} cannot be pasted into {}: {}",
+                                               clipboard.config.name, device.config.name, err));
+                    return;
+                }
+            }
+        };
+
+        let e = ReorderEvent::Import { programs: vec![(program, data)] };
+        self.status = Some(match report {
+            Some(report) => format!("Pasted \"{}\" into program {}, converted from {}. {}",
+                                    clipboard.name.trim(), program_id_string(program),
+                                    clipboard.config.name, report.to_string().trim().replace('\n', "; ")),
+            None => format!("Pasted \"{}\" into program {}, press \"s\" to store it to the device",
+                            clipboard.name.trim(), program_id_string(program))
+        });
+        self.send(AppEvent::Reorder(e));
+    }
+
+    /// MIDI input ports used by the sessions other than `session`
+    fn ports_in_use(&self, session: Option<usize>) -> Vec<String> {
+        self.sessions.iter().enumerate()
+            .filter(|(id, _)| Some(*id) != session)
+            .flat_map(|(_, s)| s.engine.state().lock().unwrap().midi_in_name.clone())
+            .collect()
+    }
+
+    fn connect_input(&mut self, key: KeyEvent) {
+        let Some(panel) = &mut self.connect else { return };
+        match key.code {
+            KeyCode::Esc => { self.connect.take(); }
+            KeyCode::Tab | KeyCode::BackTab => panel.next_field(),
+            KeyCode::Up | KeyCode::Char('k') | KeyCode::Left => panel.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Right => panel.move_selection(1),
+            KeyCode::Enter | KeyCode::Char('a') => {
+                let Some(panel) = self.connect.take() else { return };
+                let Some(connection) = panel.connection() else { return };
+                let in_use = self.ports_in_use(panel.session);
+                let id = match panel.session {
+                    Some(id) => id,
+                    None => self.add_session()
+                };
+                self.current = id;
+                let state = self.sessions[id].engine.state();
+                // only the primary session connection is remembered
+                let remember = id == 0;
+                if key.code == KeyCode::Enter {
+                    reconnect(state, connection, remember);
+                } else {
+                    redetect(state, in_use, connection, remember);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::sync::{Arc, Mutex};
+use anyhow::*;
+use log::*;
+use pod_core::config::{config_for_str, configs};
+use pod_core::engine::{midi_in_out_stop, set_ext_midi_in, set_midi_in_out, State};
+use pod_core::event::{AppEvent, NotificationEvent, SenderExt};
+use pod_core::midi::Channel;
+use pod_core::midi_io::*;
+use pod_core::model::Config;
+use pod_core::profile::{save_last_connection, Profile, Profiles};
+use crate::app::{TuiEvent, TuiEventSender};
+use crate::opts::Opts;
+
+/// An autodetected device: its MIDI ports, channel and config
+pub type Detected = (MidiIn, MidiOut, u8, &'static Config);
+
+/// MIDI connection settings picked in the connection panel
+#[derive(Clone, Debug)]
+pub struct Connection {
+    pub midi_in: Option<String>,
+    pub midi_out: Option<String>,
+    pub midi_channel: u8,
+    pub config: &'static Config
+}
+
+fn notify(state: &Arc<Mutex<State>>, msg: String) {
+    let state = state.lock().unwrap();
+    state.app_event_tx.send_or_warn(AppEvent::Notification(NotificationEvent::msg(msg)));
+}
+
+/// Install `config` & start the device MIDI connection. The connection
+/// of the primary session is remembered as the last one used.
+fn connect(state: &Arc<Mutex<State>>, midi_in: Option<MidiIn>, midi_out: Option<MidiOut>,
+           midi_channel: u8, config: Option<&'static Config>, remember: bool) {
+    let mut state = state.lock().unwrap();
+    set_midi_in_out(&mut state, midi_in, midi_out, midi_channel, config);
+    if let Some(last) = state.connection().filter(|_| remember) {
+        save_last_connection(last);
+    }
+}
+
+/// Start a session with a device found by autodetect
+pub fn connect_detected(state: &Arc<Mutex<State>>, detected: Detected) {
+    let (midi_in, midi_out, midi_channel, config) = detected;
+    connect(state, Some(midi_in), Some(midi_out), midi_channel, Some(config), false);
+}
+
+/// Edit `config` offline after the device could not be found
+fn connect_offline(state: &Arc<Mutex<State>>, midi_channel: u8, config: Option<&'static Config>) {
+    connect(state, None, None, midi_channel, config, true);
+    if let Some(config) = config {
+        let msg = format!("No device found, editing offline as {}. \
+                           Press \"c\" to connect a device.", config.name);
+        notify(state, msg);
+    }
+}
+
+/// The saved connection to use when no ports are given on the command
+/// line: the `--profile` one or the one used last
+fn saved_profile(opts: &Opts) -> Option<Profile> {
+    let profiles = Profiles::load()
+        .map_err(|err| warn!("Failed to load saved settings: {}", err))
+        .ok()?;
+    match &opts.profile {
+        Some(name) => {
+            let profile = profiles.get(name).cloned();
+            if profile.is_none() {
+                warn!("Profile {:?} not found, available profiles: {:?}", name, profiles.names());
+            }
+            profile
+        }
+        None => profiles.last
+    }
+}
+
+/// Open the device connection of the primary session on start: the ports
+/// given on the command line, the saved connection or the autodetected
+/// device. If no device is found, the device model is edited offline.
+/// Any other autodetected devices are sent to `tx` to be opened in
+/// sessions of their own.
+pub fn detect(state: Arc<Mutex<State>>, opts: &Opts, tx: TuiEventSender) -> Result<()> {
+    let midi_channel = match opts.channel {
+        None => None,
+        Some(0) => Some(Channel::all()),
+        Some(x) if (1u8 ..= 16).contains(&x) => Some(x - 1),
+        Some(x) => {
+            bail!("Midi channel {} out of bounds (0, 1..16)", x);
+        }
+    };
+    let model = opts.model.as_ref().map(|m| config_for_str(m)).transpose()?;
+
+    let saved = match (&opts.input, &opts.output) {
+        (None, None) => saved_profile(opts),
+        _ => None
+    };
+    let saved_model = saved.as_ref()
+        .and_then(|p| p.model.as_ref())
+        .and_then(|name| configs().iter().find(|c| &c.name == name));
+
+    let (ports, config, midi_channel) = match (&opts.input, &opts.output) {
+        (Some(i), Some(o)) => {
+            let ports = (MidiIn::new_for_address(i)?, MidiOut::new_for_address(o)?);
+            (Some(ports), model, midi_channel)
+        }
+        (None, None) => {
+            let ports = saved.as_ref().and_then(|p| p.open_ports());
+            let midi_channel = match &ports {
+                Some(_) => midi_channel.or_else(|| saved.as_ref().and_then(|p| p.midi_channel)),
+                None => midi_channel
+            };
+            let config = ports.as_ref().and(model.or(saved_model));
+            (ports, config, midi_channel)
+        }
+        _ => {
+            bail!("Both input and output port need to be set on command line to skip autodetect!")
+        }
+    };
+
+    // external controller input (MIDI learn)
+    if let Some(name) = saved.as_ref().and_then(|p| p.controller_in.as_ref()) {
+        match MidiIn::new_for_name(name) {
+            std::result::Result::Ok(midi_in) => set_ext_midi_in(&mut state.lock().unwrap(), Some(midi_in)),
+            Err(err) => warn!("Failed to open controller MIDI input {:?}: {}", name, err)
+        }
+    }
+
+    // model to edit offline if the device is not found
+    let fallback = model.or(saved_model).or_else(|| configs().iter().next());
+
+    tokio::spawn(async move {
+        let res = match (ports, config) {
+            (Some((midi_in, midi_out)), Some(config)) => {
+                // manually configured device
+                Ok(vec![(midi_in, midi_out, midi_channel.unwrap_or(Channel::all()), config)])
+            }
+            (Some((midi_in, midi_out)), None) => {
+                // autodetect device on provided ports
+                autodetect_with_ports(vec![midi_in], vec![midi_out], midi_channel).await
+                    .map(|detected| vec![detected])
+            }
+            (None, _) => {
+                autodetect_all(midi_channel).await
+            }
+        };
+        match res {
+            std::result::Result::Ok(detected) => {
+                let mut detected = detected.into_iter();
+                if let Some((midi_in, midi_out, midi_channel, config)) = detected.next() {
+                    connect(&state, Some(midi_in), Some(midi_out), midi_channel, Some(config), true);
+                }
+                for detected in detected {
+                    tx.send(TuiEvent::Detected(detected)).unwrap_or_default();
+                }
+            }
+            Err(err) => {
+                error!("MIDI autodetect failed: {}", err);
+                connect_offline(&state, midi_channel.unwrap_or(Channel::all()), fallback);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Stop the device MIDI connection, returning what is needed to restart it
+async fn stop(state: &Arc<Mutex<State>>) -> Option<Connection> {
+    let (handle, connection) = {
+        let mut state = state.lock().unwrap();
+        let connection = state.config.map(|config| Connection {
+            midi_in: state.midi_in_name.clone(),
+            midi_out: state.midi_out_name.clone(),
+            midi_channel: state.midi_channel_num,
+            config
+        });
+        (midi_in_out_stop(&mut state), connection)
+    };
+    handle.await;
+    connection
+}
+
+fn open_ports(connection: &Connection) -> Result<(Option<MidiIn>, Option<MidiOut>)> {
+    let midi_in = connection.midi_in.as_ref()
+        .map(|name| MidiIn::new_for_name(name)).transpose()?;
+    let midi_out = connection.midi_out.as_ref()
+        .map(|name| MidiOut::new_for_name(name)).transpose()?;
+    Ok((midi_in, midi_out))
+}
+
+/// Restart the previous device MIDI connection after a failed attempt
+/// to change it
+fn restart(state: &Arc<Mutex<State>>, previous: Connection, remember: bool) {
+    let (midi_in, midi_out) = open_ports(&previous)
+        .map_err(|err| error!("Unable to restart MIDI connection: {}", err))
+        .unwrap_or((None, None));
+    connect(state, midi_in, midi_out, previous.midi_channel, Some(previous.config), remember);
+}
+
+/// Connect to the device with the given settings. Without MIDI ports,
+/// the device is edited offline. `remember` saves the connection as
+/// the last one used.
+pub fn reconnect(state: Arc<Mutex<State>>, connection: Connection, remember: bool) {
+    tokio::spawn(async move {
+        let previous = stop(&state).await;
+        match open_ports(&connection) {
+            std::result::Result::Ok((midi_in, midi_out)) => {
+                connect(&state, midi_in, midi_out, connection.midi_channel, Some(connection.config), remember);
+            }
+            Err(err) => {
+                error!("Failed to open MIDI ports: {}", err);
+                notify(&state, format!("Failed to open MIDI ports: {}", err));
+                // a new session is edited offline instead
+                let offline = Connection { midi_in: None, midi_out: None, ..connection };
+                restart(&state, previous.unwrap_or(offline), remember);
+            }
+        }
+    });
+}
+
+/// Autodetect a device that is not connected to another session (on its
+/// MIDI input port, one of `in_use`), keeping the current connection if
+/// none is found. A new session falls back to `fallback`.
+pub fn redetect(state: Arc<Mutex<State>>, in_use: Vec<String>, fallback: Connection, remember: bool) {
+    tokio::spawn(async move {
+        let previous = stop(&state).await.unwrap_or(fallback);
+        notify(&state, "Detecting device...".into());
+        let detected = autodetect_all(None).await
+            .and_then(|detected| {
+                detected.into_iter()
+                    .find(|(midi_in, _, _, _)| !in_use.contains(&midi_in.name))
+                    .ok_or_else(|| anyhow!("All detected devices are already connected"))
+            });
+        match detected {
+            std::result::Result::Ok((midi_in, midi_out, midi_channel, config)) => {
+                connect(&state, Some(midi_in), Some(midi_out), midi_channel, Some(config), remember);
+            }
+            Err(err) => {
+                error!("MIDI autodetect failed: {}", err);
+                notify(&state, format!("Autodetect failed: {}", err));
+                restart(&state, previous, remember);
+            }
+        }
+    });
+}
@@ -0,0 +1,100 @@
+mod app;
+mod connect;
+mod opts;
+mod params;
+mod ui;
+
+use std::io;
+use anyhow::Result;
+use clap::Parser;
+use crossterm::event;
+use crossterm::execute;
+use crossterm::tty::IsTty;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use log::*;
+use tokio::sync::mpsc;
+use tui::backend::CrosstermBackend;
+use tui::Terminal;
+use pod_core::device::register_device;
+use crate::app::{App, TuiEvent, TuiEventSender};
+use crate::opts::Opts;
+
+fn restore_terminal() {
+    disable_raw_mode().unwrap_or_default();
+    execute!(io::stdout(), LeaveAlternateScreen).unwrap_or_default();
+}
+
+/// Read terminal input in a separate thread, since crossterm input is blocking
+fn start_input_thread(tx: TuiEventSender) {
+    std::thread::spawn(move || {
+        loop {
+            let event = match event::read() {
+                Ok(event) => event,
+                Err(err) => {
+                    error!("Terminal input error: {}", err);
+                    break;
+                }
+            };
+            if tx.send(TuiEvent::Input(event)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let opts = Opts::parse();
+    // the log would garble the UI, so it is only written when stderr
+    // is redirected
+    if !io::stderr().is_tty() {
+        simple_logger::SimpleLogger::new()
+            .with_level(LevelFilter::Info)
+            .with_colors(false)
+            .env()
+            .init()?;
+    }
+    info!("Starting POD UI (text mode)");
+
+    register_device(pod_mod_pod2::device())?;
+    register_device(pod_mod_pocket::device())?;
+    register_device(pod_mod_xt::device())?;
+    register_device(pod_mod_bassxt::device())?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<TuiEvent>();
+    let mut app = App::new(tx.clone());
+    connect::detect(app.session().engine.state(), &opts, tx.clone())?;
+
+    // put the terminal back in order, should anything go wrong
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    start_input_thread(tx);
+
+    let res = async {
+        loop {
+            terminal.draw(|f| ui::draw(f, &app))?;
+
+            let Some(event) = rx.recv().await else { break };
+            app.handle(event);
+            // handle everything that is queued up before redrawing
+            while let Ok(event) = rx.try_recv() {
+                app.handle(event);
+            }
+            if app.quit {
+                break;
+            }
+        }
+        Ok(())
+    }.await;
+
+    restore_terminal();
+    terminal.show_cursor()?;
+    res
+}
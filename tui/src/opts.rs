@@ -0,0 +1,38 @@
+use clap::Parser;
+
+#[derive(Parser, Clone)]
+#[clap(name = "pod-tui", about = "Text-mode editor for Line6 POD devices",
+        after_help = "The log is written to stderr when it is redirected: `pod-tui 2> pod-tui.log`. \
+                      The log level is set with the RUST_LOG environment variable, \"info\" by default.")]
+pub struct Opts {
+    #[clap(short, long)]
+    /// Select the MIDI port to be connected as input. <INPUT> must be an
+    /// integer index of a MIDI input port present on this system. On Linux,
+    /// this can also be an ALSA <client>:<port> pair, such as "20:0".
+    /// To select ports manually, both `-i` and `-o` options must be provided.
+    pub input: Option<String>,
+
+    #[clap(short, long)]
+    /// Select the MIDI port to be connected as output. <OUTPUT> must be an
+    /// integer index of a MIDI output port present on this system. On Linux,
+    /// this can also be an ALSA <client>:<port> pair, such as "20:0".
+    pub output: Option<String>,
+
+    #[clap(short, long)]
+    /// Select the MIDI channel the POD is configured on. 0 means "omni" mode,
+    /// values 1 - 16 configure specific channel.
+    pub channel: Option<u8>,
+
+    #[clap(short, long)]
+    /// Select the model of the device. <MODEL> must be either an
+    /// integer index of a supported device model or a string name
+    /// of the model in question. If the device is not detected, the
+    /// model is edited offline.
+    pub model: Option<String>,
+
+    #[clap(long)]
+    /// Use the MIDI ports, channel and model saved in the named connection
+    /// profile. Ignored if `-i` and `-o` are given. Without this option,
+    /// the connection used last is restored.
+    pub profile: Option<String>,
+}
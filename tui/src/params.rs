@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use pod_core::convert::{control_bounds, format_value};
+use pod_core::model::{AbstractControl, Config, Control};
+
+/// Parameter list sections, matched by control name prefix in order.
+/// Controls that match none go into the "Other" section.
+const SECTIONS: &[(&str, &[&str])] = &[
+    ("Amp", &["amp_", "distortion_", "drive", "bright_", "bass", "mid", "lo_mid", "hi_mid",
+              "treble", "presence", "chan_volume", "bypass_volume"]),
+    ("Cabinet", &["cab_", "mic_", "room", "air"]),
+    ("Noise gate", &["noise_gate_", "gate_"]),
+    ("Compressor", &["compressor_", "compression_"]),
+    ("Wah", &["wah_"]),
+    ("Stomp", &["stomp_"]),
+    ("Effect", &["effect_", "chorus_flanger_", "rotary_", "trem_", "volume_swell_"]),
+    ("Modulation", &["mod_"]),
+    ("Delay", &["delay_"]),
+    ("Reverb", &["reverb_"]),
+    ("EQ", &["eq_"]),
+    ("Volume pedal", &["vol_", "pedal_"]),
+    ("D.I.", &["di_"]),
+];
+
+const OTHER: &str = "Other";
+
+pub struct Param {
+    pub name: String,
+    pub section: &'static str
+}
+
+pub fn section_for(name: &str) -> (usize, &'static str) {
+    SECTIONS.iter().enumerate()
+        .find(|(_, (_, prefixes))| prefixes.iter().any(|p| name.starts_with(p)))
+        .map(|(i, (section, _))| (i, *section))
+        .unwrap_or((SECTIONS.len(), OTHER))
+}
+
+/// Buffer address to sort a control by. Controls that are not stored in
+/// the buffer (such as the dynamic parameter variants) sort next to the
+/// control their name is derived from.
+fn sort_addr(config: &Config, name: &str) -> usize {
+    let mut name = name;
+    loop {
+        let addr = config.controls.get(name).and_then(|c| c.get_addr());
+        if let Some((addr, _)) = addr {
+            return addr as usize;
+        }
+        match name.rfind('_') {
+            Some(pos) => name = &name[.. pos],
+            None => return usize::MAX
+        }
+    }
+}
+
+/// Controls shown in the parameter list: everything with a value range
+/// except buttons and the internal helper controls (names with ":")
+fn is_param(config: &Config, name: &str, control: &Control) -> bool {
+    !name.contains(':') &&
+        !matches!(control, Control::Button(_)) &&
+        control_bounds(config, name).is_some()
+}
+
+/// Editable parameters of a device, grouped by section
+pub fn params(config: &Config) -> Vec<Param> {
+    let mut params = config.controls.iter()
+        .filter(|(name, control)| is_param(config, name, control))
+        .map(|(name, _)| {
+            let (index, section) = section_for(name);
+            (index, sort_addr(config, name), name, section)
+        })
+        .collect::<Vec<_>>();
+    params.sort();
+
+    params.into_iter()
+        .map(|(_, _, name, section)| Param { name: name.clone(), section })
+        .collect()
+}
+
+/// Parameter value formatted for display
+pub fn display(config: &Config, labels: &HashMap<String, Vec<String>>, name: &str, value: u16) -> String {
+    let Some(control) = config.controls.get(name) else {
+        return String::new();
+    };
+    match control {
+        Control::SwitchControl(_) | Control::MidiSwitchControl(_) => {
+            if value != 0 { "on" } else { "off" }.into()
+        }
+        _ => format_value(control, labels.get(name), value)
+            .unwrap_or_else(|| value.to_string())
+    }
+}
+
+pub fn is_switch(config: &Config, name: &str) -> bool {
+    matches!(config.controls.get(name), Some(Control::SwitchControl(_) | Control::MidiSwitchControl(_)))
+}
+
+/// Parameter value changed by `delta`, kept within the control bounds
+pub fn step(config: &Config, name: &str, value: u16, delta: i32) -> Option<u16> {
+    let (from, to) = control_bounds(config, name)?;
+    Some((value as i32 + delta).max(from as i32).min(to as i32) as u16)
+}
+
+/// A "large" step for a parameter: a tenth of its range
+pub fn coarse_step(config: &Config, name: &str) -> i32 {
+    control_bounds(config, name)
+        .map(|(from, to)| ((to - from) / 10).max(1) as i32)
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::params::*;
+
+    #[test]
+    fn sections() {
+        assert_eq!(section_for("drive2"), (0, "Amp"));
+        assert_eq!(section_for("mid"), (0, "Amp"));
+        assert_eq!(section_for("mic_select"), (1, "Cabinet"));
+        assert_eq!(section_for("noise_gate_enable").1, "Noise gate");
+        assert_eq!(section_for("delay_param3_heads").1, "Delay");
+        assert_eq!(section_for("pedal_assign_select").1, "Volume pedal");
+        assert_eq!(section_for("tempo").1, "Other");
+    }
+}
@@ -0,0 +1,218 @@
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs, Wrap};
+use pod_core::config::configs;
+use pod_core::controller::*;
+use pod_core::event::Program;
+use pod_core::midi::Channel;
+use pod_core::program_id_string;
+use crate::app::{App, ConnectField, ConnectPanel, Device, Focus, Session};
+use crate::params;
+
+const PROGRAMS_WIDTH: u16 = 30;
+
+fn block(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    Block::default()
+        .title(Span::styled(format!(" {} ", title), style))
+        .borders(Borders::ALL)
+        .border_style(style)
+}
+
+fn highlight() -> Style {
+    Style::default().add_modifier(Modifier::REVERSED)
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Min(3),
+            Constraint::Length(1), Constraint::Length(1)])
+        .split(f.size());
+
+    // device sessions
+    let titles = app.sessions.iter().enumerate()
+        .map(|(i, s)| Spans::from(format!("{}: {}", i + 1, s.name())))
+        .collect::<Vec<_>>();
+    let tabs = Tabs::new(titles)
+        .select(app.current)
+        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+    f.render_widget(tabs, rows[0]);
+
+    let session = app.session();
+    let title = Paragraph::new(Spans::from(vec![
+        Span::styled("POD UI", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(format!("  {}", session.title))
+    ]));
+    f.render_widget(title, rows[1]);
+
+    match &session.device {
+        Some(device) => {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(PROGRAMS_WIDTH), Constraint::Min(20)])
+                .split(rows[2]);
+            draw_programs(f, app, session, device, columns[0]);
+            draw_params(f, app, session, device, columns[1]);
+        }
+        None => {
+            let text = Paragraph::new("Looking for a device...")
+                .block(Block::default().borders(Borders::ALL));
+            f.render_widget(text, rows[2]);
+        }
+    }
+
+    let status = Paragraph::new(app.status.clone().unwrap_or_default())
+        .style(Style::default().fg(Color::Cyan));
+    f.render_widget(status, rows[3]);
+    f.render_widget(Paragraph::new(help(app)).style(Style::default().fg(Color::DarkGray)), rows[4]);
+
+    if let Some(panel) = &app.connect {
+        draw_connect(f, panel);
+    }
+}
+
+fn help(app: &App) -> &'static str {
+    match (&app.connect, app.focus) {
+        (Some(_), _) => "Tab: next field  ↑↓: select  Enter: connect  a: autodetect  Esc: cancel",
+        (None, _) if app.session().sync.is_some() =>
+            "Offline edits differ from the device  u: push to device  d: pull from device  \
+             k: keep local, store later  Ctrl-C: quit",
+        (None, Focus::Programs) => "Tab: parameters  Enter: select  l/s: load/store program  \
+                                    w: write edit buffer  y/p: copy/paste  L/S: load/store all  \
+                                    c: connection  n: new device  1-9: device  q: quit",
+        (None, Focus::Params) => "Tab: programs  ←→ [ ]: change  Space: toggle  l/s: load/store program  \
+                                  e/E: load/store edit buffer  c: connection  q: quit"
+    }
+}
+
+fn draw_programs<B: Backend>(f: &mut Frame<B>, app: &App, session: &Session, device: &Device, area: Rect) {
+    let current: Program = session.ui_controller.get("program").unwrap_or(1000).into();
+    let dump = device.dump.lock().unwrap();
+
+    let items = (0 .. device.config.program_num)
+        .map(|p| {
+            let selected = current == Program::Program(p as u16);
+            let name = dump.name(p).map(|n| n.trim().to_string()).unwrap_or_default();
+            let modified = if dump.modified(p) { "*" } else { " " };
+            let style = if selected {
+                Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            let marker = if selected { ">" } else { " " };
+            ListItem::new(Spans::from(vec![
+                Span::styled(format!("{}{:>4} ", marker, program_id_string(p)), style),
+                Span::styled(name, style),
+                Span::styled(modified, Style::default().fg(Color::Red))
+            ]))
+        })
+        .collect::<Vec<_>>();
+    drop(dump);
+
+    let title = match current {
+        Program::ManualMode => "Programs (manual mode)",
+        Program::Tuner => "Programs (tuner)",
+        Program::Program(_) => "Programs"
+    };
+    let list = List::new(items)
+        .block(block(title, app.focus == Focus::Programs))
+        .highlight_style(highlight());
+    let mut state = ListState::default();
+    state.select(Some(session.program_sel));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_params<B: Backend>(f: &mut Frame<B>, app: &App, session: &Session, device: &Device, area: Rect) {
+    let name_width = device.params.iter().map(|p| p.name.len()).max().unwrap_or(0);
+
+    let mut items = vec![];
+    let mut selected = None;
+    let mut section = "";
+    for (i, param) in device.params.iter().enumerate() {
+        if param.section != section {
+            section = param.section;
+            items.push(ListItem::new(Span::styled(
+                section, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            )));
+        }
+        if i == session.param_sel {
+            selected = Some(items.len());
+        }
+        let value = device.controller.get(&param.name)
+            .map(|v| params::display(device.config, &device.labels, &param.name, v))
+            .unwrap_or_default();
+        items.push(ListItem::new(Spans::from(vec![
+            Span::raw(format!("  {:width$}  ", param.name, width = name_width)),
+            Span::styled(value, Style::default().add_modifier(Modifier::BOLD))
+        ])));
+    }
+
+    let list = List::new(items)
+        .block(block("Parameters", app.focus == Focus::Params))
+        .highlight_style(highlight());
+    let mut state = ListState::default();
+    state.select(selected);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// A rectangle of the given size centered in `area`
+fn centered(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height)
+}
+
+fn draw_connect<B: Backend>(f: &mut Frame<B>, panel: &ConnectPanel) {
+    let area = centered(100, 20, f.size());
+    f.render_widget(Clear, area);
+    let title = match panel.session {
+        Some(_) => " Connection ",
+        None => " New device session "
+    };
+    let outer = Block::default().title(title).borders(Borders::ALL);
+    let inner = outer.inner(area);
+    f.render_widget(outer, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(inner);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(2, 5), Constraint::Ratio(2, 5), Constraint::Ratio(1, 5)])
+        .split(rows[0]);
+
+    let port_name = |name: &Option<String>| name.clone().unwrap_or_else(|| "(none, offline)".into());
+    let lists = [
+        ("MIDI in", ConnectField::MidiIn, panel.midi_in.iter().map(port_name).collect::<Vec<_>>(), panel.midi_in_sel),
+        ("MIDI out", ConnectField::MidiOut, panel.midi_out.iter().map(port_name).collect(), panel.midi_out_sel),
+        ("Model", ConnectField::Model, configs().iter().map(|c| c.name.clone()).collect(), panel.model_sel)
+    ];
+    for ((title, field, names, sel), area) in lists.into_iter().zip(columns.iter()) {
+        let items = names.into_iter().map(ListItem::new).collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(block(title, panel.focus == field))
+            .highlight_style(highlight());
+        let mut state = ListState::default();
+        state.select(Some(sel));
+        f.render_stateful_widget(list, *area, &mut state);
+    }
+
+    let channel = if panel.midi_channel == Channel::all() {
+        "omni".to_string()
+    } else {
+        (panel.midi_channel + 1).to_string()
+    };
+    let channel = Paragraph::new(format!("< {} >", channel))
+        .block(block("MIDI channel", panel.focus == ConnectField::Channel))
+        .wrap(Wrap { trim: true });
+    f.render_widget(channel, rows[1]);
+}